@@ -64,10 +64,13 @@ impl_nop! {
 
     // First-party types
     anchor_client::Cluster,
+    anchor_client::TransactionEncoding,
 
     // Third-party types
     crucible_fuzz_cli::Cli,
     clap_complete::Shell,
     solana_commitment_config::CommitmentLevel,
+    solana_derivation_path::DerivationPath,
     solana_pubkey::Pubkey,
+    solana_signature::Signature,
 }
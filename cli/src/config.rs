@@ -1,7 +1,9 @@
 use {
-    crate::{get_keypair, is_hidden, keys_sync, target_dir, AbsolutePath, DEFAULT_RPC_PORT},
+    crate::{
+        get_keypair, is_hidden, keys_sync, signer, target_dir, AbsolutePath, DEFAULT_RPC_PORT,
+    },
     anchor_client::Cluster,
-    anchor_lang_idl::types::Idl,
+    anchor_lang_idl::types::{Idl, IdlType, IdlTypeDefTy},
     anyhow::{anyhow, bail, Context, Error, Result},
     clap::{Parser, ValueEnum},
     dirs::home_dir,
@@ -329,7 +331,7 @@ impl WithPath<Config> {
 
 impl WalletPath {
     fn resolve_relative_to(self, base: &Path) -> Self {
-        if self.0.is_relative() {
+        if self.0.is_relative() && !self.is_signer_uri() {
             Self(base.join(self.0))
         } else {
             self
@@ -360,6 +362,8 @@ pub struct Config {
     pub hooks: HooksConfig,
     pub workspace: WorkspaceConfig,
     pub clients: ClientsConfig,
+    pub idl: IdlConfig,
+    pub deploy: DeployPlansConfig,
     // Separate entry next to test_config because
     // "anchor localnet" only has access to the Anchor.toml,
     // not the Test.toml files
@@ -428,6 +432,9 @@ pub struct FeaturesConfig {
     /// Disable safety comment checks
     #[serde(default, rename = "skip-lint")]
     pub skip_lint: bool,
+    /// Generate the IDL by parsing the program's source code instead of compiling it
+    #[serde(default, rename = "static-idl")]
+    pub static_idl: bool,
 }
 
 impl FeaturesConfig {
@@ -441,6 +448,7 @@ impl Default for FeaturesConfig {
         Self {
             resolution: Self::get_default_resolution(),
             skip_lint: false,
+            static_idl: false,
         }
     }
 }
@@ -472,6 +480,40 @@ pub struct HooksConfig {
     post_deploy: Option<Hook>,
 }
 
+impl HooksConfig {
+    /// Commands configured for `hook_type`.
+    pub fn commands(&self, hook_type: HookType) -> &[String] {
+        let hooks = match hook_type {
+            HookType::PreBuild => &self.pre_build,
+            HookType::PostBuild => &self.post_build,
+            HookType::PreTest => &self.pre_test,
+            HookType::PostTest => &self.post_test,
+            HookType::PreDeploy => &self.pre_deploy,
+            HookType::PostDeploy => &self.post_deploy,
+        };
+        hooks.as_ref().map(Hook::hooks).unwrap_or_default()
+    }
+
+    /// Run the commands configured for `hook_type` with the given extra environment variables.
+    pub fn run(&self, hook_type: HookType, envs: &[(&str, &str)]) -> Result<()> {
+        for cmd in self.commands(hook_type) {
+            let status = Command::new("bash")
+                .arg("-c")
+                .arg(cmd)
+                .envs(envs.iter().copied())
+                .status()
+                .with_context(|| format!("failed to execute `{cmd}`"))?;
+            if !status.success() {
+                match status.code() {
+                    Some(code) => bail!("`{cmd}` failed with exit code {code}"),
+                    None => bail!("`{cmd}` killed by signal"),
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(untagged)]
 enum Hook {
@@ -498,6 +540,90 @@ pub enum HookType {
     PostDeploy,
 }
 
+/// `[deploy]` section of `Anchor.toml`, mapping plan names to deployment plans.
+pub type DeployPlansConfig = BTreeMap<String, DeployPlan>;
+
+/// Deployment plan run by `anchor deploy --plan <name>`.
+///
+/// Programs are deployed in the order they are listed. Only the `pre-deploy` and `post-deploy`
+/// hooks of the plan are run, with `ANCHOR_PROVIDER_URL` and `ANCHOR_WALLET` set to the cluster
+/// and wallet of the plan.
+///
+/// TOML shape:
+///
+/// ```toml
+/// [deploy.mainnet]
+/// cluster = "mainnet"
+/// wallet = "~/.config/solana/deployer.json"
+/// hooks = { post-deploy = "yarn run ts-node scripts/initialize.ts" }
+///
+/// [[deploy.mainnet.programs]]
+/// name = "registry"
+/// mode = "upgrade"
+///
+/// [[deploy.mainnet.programs]]
+/// name = "vault"
+/// idl = false
+/// max-len = 400000
+/// extend-headroom = 20
+/// upgrade-authority = "7bYpk1JQH5pQGYjL3Ts1aJcEpD6ow2BNF3RYpYZoSgFt"
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct DeployPlan {
+    #[serde(serialize_with = "ser_cluster", deserialize_with = "des_cluster")]
+    pub cluster: Cluster,
+    /// Wallet paying for the deployment, defaults to the provider wallet.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wallet: Option<String>,
+    #[serde(default)]
+    pub hooks: HooksConfig,
+    #[serde(default)]
+    pub programs: Vec<DeployPlanProgram>,
+}
+
+/// Program entry of a deployment plan.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct DeployPlanProgram {
+    /// Name of the program in the workspace.
+    pub name: String,
+    #[serde(default)]
+    pub mode: DeployMode,
+    /// Upload the IDL after deploying the program.
+    #[serde(default = "DeployPlanProgram::default_idl")]
+    pub idl: bool,
+    /// Maximum length of the program data, only used when the program is first deployed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_len: Option<usize>,
+    /// Percentage of the program size to extend the program data by beyond what an upgrade
+    /// needs, when the new program doesn't fit.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extend_headroom: Option<u16>,
+    /// Upgrade authority to hand the program over to after it's deployed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub upgrade_authority: Option<String>,
+}
+
+impl DeployPlanProgram {
+    fn default_idl() -> bool {
+        true
+    }
+}
+
+/// Whether a program of a deployment plan is expected to exist on the cluster.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DeployMode {
+    /// Deploy the program if it doesn't exist, upgrade it otherwise.
+    #[default]
+    Auto,
+    /// Deploy a new program, failing if it already exists.
+    Init,
+    /// Upgrade an existing program, failing if it doesn't exist.
+    Upgrade,
+}
+
 /// `[clients]` section of `Anchor.toml`.
 ///
 /// Declares which Codama-generated client SDKs the workspace ships, where
@@ -620,6 +746,53 @@ fn is_false(b: &bool) -> bool {
     !*b
 }
 
+/// `[idl]` section of `Anchor.toml`.
+///
+/// TOML shape:
+///
+/// ```toml
+/// [idl.types]
+/// "pyth_sdk::Price" = { kind = "struct", fields = [
+///   { name = "mantissa", type = "i64" },
+///   { name = "expo", type = "i32" },
+/// ] }
+/// "foo::Opaque" = "bytes"
+/// ```
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct IdlConfig {
+    /// IDL definitions of the types from other crates that don't implement `IdlBuild`, keyed by
+    /// their paths
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub types: BTreeMap<String, IdlExternalType>,
+}
+
+/// IDL definition of an external type. Accepts either an IDL type (`"bytes"`), which makes the
+/// external type an alias of it, or a type definition (`{ kind = "struct", fields = [...] }`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum IdlExternalType {
+    Alias(IdlType),
+    Definition(IdlTypeDefTy),
+}
+
+impl IdlConfig {
+    /// Get the type definitions of the external types.
+    pub fn external_types(&self) -> BTreeMap<String, IdlTypeDefTy> {
+        self.types
+            .iter()
+            .map(|(path, ty)| {
+                let ty = match ty {
+                    IdlExternalType::Alias(alias) => IdlTypeDefTy::Type {
+                        alias: alias.clone(),
+                    },
+                    IdlExternalType::Definition(ty) => ty.clone(),
+                };
+                (path.clone(), ty)
+            })
+            .collect()
+    }
+}
+
 #[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct WorkspaceConfig {
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
@@ -724,34 +897,17 @@ impl Config {
             .parse::<Self>()
     }
 
-    pub fn wallet_kp(&self) -> Result<Keypair> {
-        get_keypair(Path::new(&self.provider.wallet.0))
+    pub fn wallet_signer(&self) -> Result<Box<dyn Signer>> {
+        signer::signer_from_path(&self.provider.wallet.to_string(), "wallet")
+    }
+
+    /// Pubkey of the wallet, without unlocking it if it's a keystore.
+    pub fn wallet_pubkey(&self) -> Result<Pubkey> {
+        signer::pubkey_from_path(&self.provider.wallet.to_string(), "wallet")
     }
 
     pub fn run_hooks(&self, hook_type: HookType) -> Result<()> {
-        let hooks = match hook_type {
-            HookType::PreBuild => &self.hooks.pre_build,
-            HookType::PostBuild => &self.hooks.post_build,
-            HookType::PreTest => &self.hooks.pre_test,
-            HookType::PostTest => &self.hooks.post_test,
-            HookType::PreDeploy => &self.hooks.pre_deploy,
-            HookType::PostDeploy => &self.hooks.post_deploy,
-        };
-        let cmds = hooks.as_ref().map(Hook::hooks).unwrap_or_default();
-        for cmd in cmds {
-            let status = Command::new("bash")
-                .arg("-c")
-                .arg(cmd)
-                .status()
-                .with_context(|| format!("failed to execute `{cmd}`"))?;
-            if !status.success() {
-                match status.code() {
-                    Some(code) => bail!("`{cmd}` failed with exit code {code}"),
-                    None => bail!("`{cmd}` killed by signal"),
-                }
-            }
-        }
-        Ok(())
+        self.hooks.run(hook_type, &[])
     }
 }
 
@@ -770,6 +926,10 @@ struct _Config {
     skip_local_validator: Option<bool>,
     #[serde(skip_serializing_if = "Option::is_none")]
     clients: Option<ClientsConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    idl: Option<IdlConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    deploy: Option<DeployPlansConfig>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -881,6 +1041,8 @@ impl fmt::Display for Config {
                     && clients.go.is_none();
                 (!empty).then(|| clients.clone())
             },
+            idl: (!self.idl.types.is_empty()).then(|| self.idl.clone()),
+            deploy: (!self.deploy.is_empty()).then(|| self.deploy.clone()),
         };
 
         let cfg = toml::to_string(&cfg).expect("Must be well formed");
@@ -911,6 +1073,8 @@ impl FromStr for Config {
             surfpool_config: cfg.surfpool.map(Into::into),
             skip_local_validator: cfg.skip_local_validator,
             clients: cfg.clients.unwrap_or_default(),
+            idl: cfg.idl.unwrap_or_default(),
+            deploy: cfg.deploy.unwrap_or_default(),
         })
     }
 }
@@ -1886,7 +2050,7 @@ pub struct RunbookExecution {
 #[macro_export]
 macro_rules! home_path {
     ($my_struct:ident, $path:literal) => {
-        #[derive(Clone, Debug, AbsolutePath)]
+        #[derive(Clone, Debug)]
         pub struct $my_struct(::std::path::PathBuf);
 
         impl Default for $my_struct {
@@ -1922,6 +2086,23 @@ macro_rules! home_path {
 
 home_path!(WalletPath, ".config/solana/id.json");
 
+impl WalletPath {
+    /// Whether this is a signer URI such as `usb://ledger` rather than a keypair file.
+    pub fn is_signer_uri(&self) -> bool {
+        self.0.to_str().is_some_and(signer::is_signer_uri)
+    }
+}
+
+impl AbsolutePath for WalletPath {
+    fn absolute(self) -> Self {
+        if self.is_signer_uri() {
+            self
+        } else {
+            Self(self.0.absolute())
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -2153,6 +2334,80 @@ go = { enable = true, path = "go-client" }
         );
     }
 
+    #[test]
+    fn parse_idl_types_section() {
+        let toml = BASE_CONFIG.to_owned()
+            + r#"
+[idl.types]
+"pyth_sdk::Price" = { kind = "struct", fields = [
+  { name = "mantissa", type = "i64" },
+  { name = "expo", type = "i32" },
+] }
+"foo::Opaque" = "bytes"
+"#;
+        let config = Config::from_str(&toml).unwrap();
+        let types = config.idl.external_types();
+        assert_eq!(
+            types["foo::Opaque"],
+            IdlTypeDefTy::Type {
+                alias: IdlType::Bytes
+            }
+        );
+        assert!(matches!(
+            &types["pyth_sdk::Price"],
+            IdlTypeDefTy::Struct {
+                fields: Some(anchor_lang_idl::types::IdlDefinedFields::Named(fields))
+            } if fields.len() == 2 && fields[1].ty == IdlType::I32
+        ));
+    }
+
+    #[test]
+    fn parse_deploy_plans() {
+        let toml = BASE_CONFIG.to_owned()
+            + r#"
+[deploy.mainnet]
+cluster = "mainnet"
+wallet = "deployer.json"
+hooks = { post-deploy = ["yarn run init", "yarn run seed"] }
+
+[[deploy.mainnet.programs]]
+name = "registry"
+mode = "upgrade"
+
+[[deploy.mainnet.programs]]
+name = "vault"
+idl = false
+max-len = 400000
+extend-headroom = 20
+upgrade-authority = "7bYpk1JQH5pQGYjL3Ts1aJcEpD6ow2BNF3RYpYZoSgFt"
+"#;
+        let config = Config::from_str(&toml).unwrap();
+        let plan = &config.deploy["mainnet"];
+        assert_eq!(plan.cluster, Cluster::Mainnet);
+        assert_eq!(plan.wallet.as_deref(), Some("deployer.json"));
+        assert_eq!(
+            plan.hooks.commands(HookType::PostDeploy),
+            ["yarn run init", "yarn run seed"]
+        );
+        assert!(plan.hooks.commands(HookType::PreDeploy).is_empty());
+
+        let [registry, vault] = plan.programs.as_slice() else {
+            panic!("expected two programs");
+        };
+        assert_eq!(registry.name, "registry");
+        assert_eq!(registry.mode, DeployMode::Upgrade);
+        assert!(registry.idl);
+        assert_eq!(vault.mode, DeployMode::Auto);
+        assert!(!vault.idl);
+        assert_eq!(vault.max_len, Some(400000));
+        assert_eq!(vault.extend_headroom, Some(20));
+        assert_eq!(registry.extend_headroom, None);
+
+        // The plans survive a round trip through `Anchor.toml`
+        let config = Config::from_str(&config.to_string()).unwrap();
+        assert_eq!(config.deploy["mainnet"].programs.len(), 2);
+    }
+
     #[test]
     fn clients_custom_paths_resolve_from_workspace_root() {
         let workspace_dir = Path::new("workspace-root");
@@ -2345,4 +2600,27 @@ ledger = "ledgers/local"
 
         assert_eq!(validator.ledger, "ledgers/local");
     }
+
+    #[test]
+    fn signer_uri_wallets_are_kept_as_is() {
+        let uri = "usb://ledger?key=0/1";
+        let wallet: WalletPath = uri.parse().unwrap();
+        assert!(wallet.is_signer_uri());
+        assert_eq!(wallet.clone().absolute().to_string(), uri);
+        assert_eq!(
+            wallet
+                .resolve_relative_to(Path::new("/workspace"))
+                .to_string(),
+            uri
+        );
+
+        let wallet: WalletPath = "keys/id.json".parse().unwrap();
+        assert!(!wallet.is_signer_uri());
+        assert_eq!(
+            wallet
+                .resolve_relative_to(Path::new("/workspace"))
+                .to_string(),
+            "/workspace/keys/id.json"
+        );
+    }
 }
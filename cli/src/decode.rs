@@ -0,0 +1,747 @@
+//! Decoding of transactions, instructions and program logs with program IDLs.
+//!
+//! IDLs are looked up in the following order: `--idl` files, the IDLs of the workspace programs,
+//! the JSON files in the `idls` directory and finally the IDL stored in the program's on-chain
+//! metadata account.
+
+use {
+    crate::{
+        abs_path::AbsolutePath,
+        config::{Config, ConfigOverride},
+        create_client,
+        fetch::fetch_pmp_idl,
+        get_cluster_and_wallet,
+        idl_diff::flatten_accounts,
+    },
+    anchor_cli_macros::AbsolutePath,
+    anchor_client::TransactionEncoding,
+    anchor_lang::event::EVENT_IX_TAG_LE,
+    anchor_lang_idl::{codec::IdlCodec, convert::convert_idl, types::Idl},
+    anyhow::{anyhow, Context, Result},
+    base64::{engine::general_purpose::STANDARD, Engine},
+    clap::Parser,
+    solana_commitment_config::CommitmentConfig,
+    solana_pubkey::Pubkey,
+    solana_rpc_client::rpc_client::RpcClient,
+    solana_rpc_client_api::config::RpcTransactionConfig,
+    solana_signature::Signature,
+    solana_transaction_status_client_types::{
+        option_serializer::OptionSerializer, UiInstruction, UiTransactionEncoding,
+    },
+    std::{
+        collections::HashMap,
+        fs,
+        io::Read,
+        path::{Path, PathBuf},
+        str::FromStr,
+    },
+};
+
+/// Programs that never have an IDL, so there is no point in fetching one.
+const BUILTIN_PROGRAMS: &[Pubkey] = &[
+    solana_sdk_ids::address_lookup_table::ID,
+    solana_sdk_ids::bpf_loader::ID,
+    solana_sdk_ids::bpf_loader_deprecated::ID,
+    solana_sdk_ids::bpf_loader_upgradeable::ID,
+    solana_sdk_ids::compute_budget::ID,
+    solana_sdk_ids::config::ID,
+    solana_sdk_ids::ed25519_program::ID,
+    solana_sdk_ids::loader_v4::ID,
+    solana_sdk_ids::native_loader::ID,
+    solana_sdk_ids::secp256k1_program::ID,
+    solana_sdk_ids::secp256r1_program::ID,
+    solana_sdk_ids::stake::ID,
+    solana_sdk_ids::system_program::ID,
+    solana_sdk_ids::vote::ID,
+];
+
+#[derive(Debug, Parser, AbsolutePath)]
+pub enum DecodeCommand {
+    /// Decode the instructions, events, errors and CPIs of a confirmed transaction
+    #[clap(alias = "tx")]
+    Transaction {
+        /// Signature of the transaction
+        signature: Signature,
+    },
+    /// Decode raw instruction data
+    #[clap(alias = "ix")]
+    Instruction {
+        /// Program the instruction is sent to
+        program_id: Pubkey,
+        /// Instruction data (`-` to read from stdin)
+        data: String,
+        /// Accounts of the instruction in order, can be repeated
+        #[clap(long = "account")]
+        accounts: Vec<Pubkey>,
+        /// Encoding of the instruction data. Detected from the data if not specified, using the
+        /// instruction discriminators of the IDL when the data is valid in both encodings.
+        #[clap(long)]
+        encoding: Option<TransactionEncoding>,
+    },
+    /// Decode the events and errors in program logs, one log message per line
+    Logs {
+        /// File to read the logs from (defaults to stdin)
+        file: Option<PathBuf>,
+    },
+}
+
+pub fn decode(cfg_override: &ConfigOverride, idls: Vec<PathBuf>, cmd: DecodeCommand) -> Result<()> {
+    let mut resolver = IdlResolver::new(cfg_override, &idls)?;
+    match cmd {
+        DecodeCommand::Transaction { signature } => {
+            let (cluster_url, _) = get_cluster_and_wallet(cfg_override)?;
+            decode_transaction(&mut resolver, &create_client(cluster_url), &signature)
+        }
+        DecodeCommand::Instruction {
+            program_id,
+            data,
+            accounts,
+            encoding,
+        } => {
+            let data = if data == "-" {
+                let mut buf = String::new();
+                std::io::stdin().read_to_string(&mut buf)?;
+                buf
+            } else {
+                data
+            };
+            resolver.resolve([program_id]);
+            let node = InstructionNode {
+                program_id,
+                accounts,
+                data: decode_instruction_data(&data, encoding, resolver.get(&program_id))?,
+                logs: None,
+                inner: vec![],
+            };
+
+            Printer::new(&resolver).instruction(&node, "1", 0);
+            Ok(())
+        }
+        DecodeCommand::Logs { file } => {
+            let logs = match file {
+                Some(file) => fs::read_to_string(&file)
+                    .with_context(|| format!("Failed to read `{}`", file.display()))?,
+                None => {
+                    let mut buf = String::new();
+                    std::io::stdin().read_to_string(&mut buf)?;
+                    buf
+                }
+            };
+            let invocations = parse_logs(&logs.lines().collect::<Vec<_>>());
+
+            resolver.resolve(invocations.iter().flat_map(Invocation::program_ids));
+            let printer = Printer::new(&resolver);
+            for (i, invocation) in invocations.iter().enumerate() {
+                printer.invocation(invocation, &(i + 1).to_string(), 0);
+            }
+            Ok(())
+        }
+    }
+}
+
+fn decode_transaction(
+    resolver: &mut IdlResolver,
+    client: &RpcClient,
+    signature: &Signature,
+) -> Result<()> {
+    let tx = client
+        .get_transaction_with_config(
+            signature,
+            RpcTransactionConfig {
+                encoding: Some(UiTransactionEncoding::Base64),
+                commitment: Some(CommitmentConfig::confirmed()),
+                max_supported_transaction_version: Some(0),
+            },
+        )
+        .with_context(|| format!("Failed to fetch transaction {signature}"))?;
+    let meta = tx
+        .transaction
+        .meta
+        .ok_or_else(|| anyhow!("Transaction {signature} has no status metadata"))?;
+    let versioned_tx = tx
+        .transaction
+        .transaction
+        .decode()
+        .ok_or_else(|| anyhow!("Failed to decode transaction {signature}"))?;
+
+    let mut keys = versioned_tx.message.static_account_keys().to_vec();
+    if let OptionSerializer::Some(loaded) = &meta.loaded_addresses {
+        for key in loaded.writable.iter().chain(&loaded.readonly) {
+            keys.push(Pubkey::from_str(key)?);
+        }
+    }
+    let key = |index: u8| {
+        keys.get(index as usize)
+            .copied()
+            .ok_or_else(|| anyhow!("Account index {index} is out of bounds"))
+    };
+
+    let mut nodes = versioned_tx
+        .message
+        .instructions()
+        .iter()
+        .map(|ix| {
+            Ok(InstructionNode {
+                program_id: key(ix.program_id_index)?,
+                accounts: ix.accounts.iter().map(|i| key(*i)).collect::<Result<_>>()?,
+                data: ix.data.clone(),
+                logs: None,
+                inner: vec![],
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    if let OptionSerializer::Some(inner_instructions) = &meta.inner_instructions {
+        for inner in inner_instructions {
+            let Some(parent) = nodes.get_mut(inner.index as usize) else {
+                continue;
+            };
+            for ix in &inner.instructions {
+                let UiInstruction::Compiled(ix) = ix else {
+                    continue;
+                };
+                let node = InstructionNode {
+                    program_id: key(ix.program_id_index)?,
+                    accounts: ix.accounts.iter().map(|i| key(*i)).collect::<Result<_>>()?,
+                    data: bs58::decode(&ix.data).into_vec()?,
+                    logs: None,
+                    inner: vec![],
+                };
+                // Stack height is 1 for the top-level instructions, and missing in old transactions
+                let depth = ix.stack_height.map_or(1, |height| height.saturating_sub(1));
+                parent.insert(depth as usize, node);
+            }
+        }
+    }
+    if let OptionSerializer::Some(logs) = &meta.log_messages {
+        attach_logs(&mut nodes, parse_logs(logs));
+    }
+
+    println!("Signature: {signature}");
+    println!("Slot: {}", tx.slot);
+    match &meta.err {
+        Some(err) => println!("Status: Failed ({err})"),
+        None => println!("Status: Success"),
+    }
+
+    resolver.resolve(nodes.iter().flat_map(InstructionNode::program_ids));
+    let printer = Printer::new(resolver);
+    for (i, node) in nodes.iter().enumerate() {
+        println!();
+        printer.instruction(node, &(i + 1).to_string(), 0);
+    }
+
+    Ok(())
+}
+
+/// Decodes instruction data given in `encoding`, or in the encoding detected from the data.
+///
+/// Data that is valid in both encodings is decoded with the one that starts with an instruction
+/// discriminator of the IDL, as the alphabet of base58 is a subset of the one of base64.
+fn decode_instruction_data(
+    data: &str,
+    encoding: Option<TransactionEncoding>,
+    idl: Option<&Idl>,
+) -> Result<Vec<u8>> {
+    let data = data.trim();
+    let decode = |encoding| match encoding {
+        TransactionEncoding::Base58 => bs58::decode(data).into_vec().map_err(anyhow::Error::from),
+        TransactionEncoding::Base64 => STANDARD.decode(data).map_err(anyhow::Error::from),
+    };
+    if let Some(encoding) = encoding {
+        return decode(encoding).with_context(|| format!("Invalid {encoding} instruction data"));
+    }
+
+    match (
+        decode(TransactionEncoding::Base58),
+        decode(TransactionEncoding::Base64),
+    ) {
+        (Ok(data), Err(_)) | (Err(_), Ok(data)) => Ok(data),
+        (Err(_), Err(_)) => Err(anyhow!(
+            "Invalid instruction data, expected base58 or base64"
+        )),
+        (Ok(base58), Ok(base64)) => {
+            let has_discriminator = |data: &[u8]| {
+                idl.is_some_and(|idl| {
+                    idl.instructions
+                        .iter()
+                        .any(|ix| data.starts_with(&ix.discriminator))
+                })
+            };
+            match (has_discriminator(&base58), has_discriminator(&base64)) {
+                (true, false) => Ok(base58),
+                (false, true) => Ok(base64),
+                _ => Err(anyhow!(
+                    "Instruction data is valid base58 and base64, specify its `--encoding`"
+                )),
+            }
+        }
+    }
+}
+
+/// Resolves and caches the IDLs of programs.
+struct IdlResolver {
+    idls: HashMap<Pubkey, Option<Idl>>,
+    client: Option<RpcClient>,
+}
+
+impl IdlResolver {
+    /// Loads the local IDLs, i.e. the given files, the workspace IDLs and the `idls` directory.
+    fn new(cfg_override: &ConfigOverride, paths: &[PathBuf]) -> Result<Self> {
+        let mut idls = HashMap::new();
+        let mut add = |idl: Idl| {
+            if let Ok(address) = Pubkey::from_str(&idl.address) {
+                idls.entry(address).or_insert(Some(idl));
+            }
+        };
+
+        for path in paths {
+            add(read_idl(path)?);
+        }
+
+        let cfg = Config::discover(cfg_override).ok().flatten();
+        if let Some(cfg) = &cfg {
+            for program in cfg.read_all_programs().unwrap_or_default() {
+                if let Some(idl) = program.idl {
+                    add(idl);
+                }
+            }
+        }
+
+        let idls_dir = match &cfg {
+            Some(cfg) => cfg.path().parent().unwrap().join("idls"),
+            None => PathBuf::from("idls"),
+        };
+        if let Ok(entries) = fs::read_dir(idls_dir) {
+            for path in entries.filter_map(|entry| entry.ok().map(|entry| entry.path())) {
+                if path.extension().is_some_and(|ext| ext == "json") {
+                    if let Ok(idl) = read_idl(&path) {
+                        add(idl);
+                    }
+                }
+            }
+        }
+
+        let client = get_cluster_and_wallet(cfg_override)
+            .ok()
+            .map(|(cluster_url, _)| create_client(cluster_url));
+        Ok(Self { idls, client })
+    }
+
+    /// Fetches the on-chain IDLs of the given programs that don't have a local IDL.
+    fn resolve(&mut self, program_ids: impl IntoIterator<Item = Pubkey>) {
+        for program_id in program_ids {
+            if self.idls.contains_key(&program_id) {
+                continue;
+            }
+
+            let idl = match (&self.client, BUILTIN_PROGRAMS.contains(&program_id)) {
+                (Some(client), false) => fetch_pmp_idl(client, &program_id)
+                    .and_then(|idl| idl.map(|idl| convert_idl(&idl)).transpose())
+                    .unwrap_or_else(|e| {
+                        eprintln!("Failed to fetch the IDL of {program_id}: {e}");
+                        None
+                    }),
+                _ => None,
+            };
+            self.idls.insert(program_id, idl);
+        }
+    }
+
+    fn get(&self, program_id: &Pubkey) -> Option<&Idl> {
+        self.idls.get(program_id).and_then(Option::as_ref)
+    }
+}
+
+fn read_idl(path: &Path) -> Result<Idl> {
+    let idl = fs::read(path).with_context(|| format!("Failed to read `{}`", path.display()))?;
+    convert_idl(&idl).with_context(|| format!("Failed to parse IDL `{}`", path.display()))
+}
+
+/// Program invocation reconstructed from program logs.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Invocation {
+    program_id: Pubkey,
+    /// Data logged with `sol_log_data`, e.g. events emitted with `emit!`
+    data: Vec<Vec<u8>>,
+    /// `AnchorError` log messages
+    errors: Vec<String>,
+    /// Reason of the failure if the invocation failed
+    failure: Option<String>,
+    /// Invocations made by this program
+    inner: Vec<Invocation>,
+}
+
+impl Invocation {
+    fn program_ids(&self) -> Vec<Pubkey> {
+        std::iter::once(self.program_id)
+            .chain(self.inner.iter().flat_map(Invocation::program_ids))
+            .collect()
+    }
+}
+
+/// Parses program logs into a tree of invocations.
+///
+/// Logs of truncated or incomplete invocations are kept as is.
+fn parse_logs<S: AsRef<str>>(logs: &[S]) -> Vec<Invocation> {
+    fn finish(stack: &mut Vec<Invocation>, invocations: &mut Vec<Invocation>) {
+        if let Some(invocation) = stack.pop() {
+            match stack.last_mut() {
+                Some(parent) => parent.inner.push(invocation),
+                None => invocations.push(invocation),
+            }
+        }
+    }
+
+    let mut invocations = vec![];
+    let mut stack: Vec<Invocation> = vec![];
+    for log in logs.iter().map(AsRef::as_ref) {
+        if let Some(data) = log.strip_prefix("Program data: ") {
+            if let Some(current) = stack.last_mut() {
+                let data = data
+                    .split_whitespace()
+                    .filter_map(|chunk| STANDARD.decode(chunk).ok())
+                    .flatten()
+                    .collect();
+                current.data.push(data);
+            }
+        } else if let Some(error) = log.strip_prefix("Program log: AnchorError") {
+            if let Some(current) = stack.last_mut() {
+                current.errors.push(format!("AnchorError{error}"));
+            }
+        } else if let Some((program_id, rest)) = log
+            .strip_prefix("Program ")
+            .and_then(|log| log.split_once(' '))
+        {
+            let Ok(program_id) = Pubkey::from_str(program_id) else {
+                continue;
+            };
+            if rest.starts_with("invoke [") {
+                stack.push(Invocation {
+                    program_id,
+                    ..Default::default()
+                });
+            } else if rest == "success" {
+                finish(&mut stack, &mut invocations);
+            } else if let Some(reason) = rest.strip_prefix("failed: ") {
+                if let Some(current) = stack.last_mut() {
+                    current.failure = Some(reason.to_owned());
+                }
+                finish(&mut stack, &mut invocations);
+            }
+        }
+    }
+    while !stack.is_empty() {
+        finish(&mut stack, &mut invocations);
+    }
+
+    invocations
+}
+
+/// Instruction along with the instructions it invoked.
+#[derive(Debug)]
+struct InstructionNode {
+    program_id: Pubkey,
+    accounts: Vec<Pubkey>,
+    data: Vec<u8>,
+    logs: Option<Invocation>,
+    inner: Vec<InstructionNode>,
+}
+
+impl InstructionNode {
+    /// Inserts an instruction invoked at `depth`, where 1 is a direct CPI of this instruction.
+    fn insert(&mut self, depth: usize, node: InstructionNode) {
+        match self.inner.last_mut() {
+            Some(last) if depth > 1 => last.insert(depth - 1, node),
+            _ => self.inner.push(node),
+        }
+    }
+
+    fn program_ids(&self) -> Vec<Pubkey> {
+        std::iter::once(self.program_id)
+            .chain(self.inner.iter().flat_map(InstructionNode::program_ids))
+            .collect()
+    }
+
+    /// Returns the event data if this is a self-CPI made by `emit_cpi!`.
+    fn cpi_event(&self) -> Option<&[u8]> {
+        self.data.strip_prefix(EVENT_IX_TAG_LE)
+    }
+}
+
+/// Attaches the invocations to the instructions that made them, in order.
+///
+/// Instructions that weren't executed, e.g. the ones after a failed instruction, have no logs.
+fn attach_logs(nodes: &mut [InstructionNode], invocations: Vec<Invocation>) {
+    let mut invocations = invocations.into_iter().peekable();
+    for node in nodes {
+        if let Some(mut invocation) =
+            invocations.next_if(|invocation| invocation.program_id == node.program_id)
+        {
+            attach_logs(&mut node.inner, std::mem::take(&mut invocation.inner));
+            node.logs = Some(invocation);
+        }
+    }
+}
+
+struct Printer<'a> {
+    resolver: &'a IdlResolver,
+}
+
+impl<'a> Printer<'a> {
+    fn new(resolver: &'a IdlResolver) -> Self {
+        Self { resolver }
+    }
+
+    fn instruction(&self, node: &InstructionNode, label: &str, indent: usize) {
+        let pad = "  ".repeat(indent);
+        let idl = self.resolver.get(&node.program_id);
+        let decoded = idl.map(|idl| IdlCodec::new(idl).decode_instruction(&node.data));
+        let name = match &decoded {
+            Some(Ok((ix, _))) => format!("{}::{}", program_name(idl, &node.program_id), ix.name),
+            _ => program_name(idl, &node.program_id),
+        };
+        println!("{pad}Instruction #{label}: {name}");
+        println!("{pad}  Program: {}", node.program_id);
+
+        match decoded {
+            Some(Ok((ix, args))) => {
+                let accounts = flatten_accounts(&ix.accounts);
+                if !node.accounts.is_empty() {
+                    println!("{pad}  Accounts:");
+                }
+                for (i, pubkey) in node.accounts.iter().enumerate() {
+                    match accounts.get(i) {
+                        Some((name, acc)) => {
+                            let flags = [(acc.writable, "writable"), (acc.signer, "signer")]
+                                .into_iter()
+                                .filter_map(|(set, flag)| set.then_some(flag))
+                                .collect::<Vec<_>>();
+                            if flags.is_empty() {
+                                println!("{pad}    {name}: {pubkey}");
+                            } else {
+                                println!("{pad}    {name} ({}): {pubkey}", flags.join(", "));
+                            }
+                        }
+                        None => println!(
+                            "{pad}    remaining_accounts[{}]: {pubkey}",
+                            i - accounts.len()
+                        ),
+                    }
+                }
+                print_json(&format!("{pad}  Args"), &args);
+            }
+            Some(Err(e)) => {
+                println!("{pad}  Failed to decode instruction: {e}");
+                println!("{pad}  Data: {}", bs58::encode(&node.data).into_string());
+            }
+            None => {
+                if !node.accounts.is_empty() {
+                    println!("{pad}  Accounts:");
+                }
+                for (i, pubkey) in node.accounts.iter().enumerate() {
+                    println!("{pad}    #{}: {pubkey}", i + 1);
+                }
+                println!("{pad}  Data: {}", bs58::encode(&node.data).into_string());
+            }
+        }
+
+        // `emit_cpi!` events are shown as events of the emitting instruction
+        let cpi_events = node
+            .inner
+            .iter()
+            .filter(|inner| inner.program_id == node.program_id)
+            .filter_map(InstructionNode::cpi_event)
+            .collect::<Vec<_>>();
+        match &node.logs {
+            Some(logs) => self.logs(logs, &cpi_events, indent + 1),
+            None => self.events(&node.program_id, &cpi_events, indent + 1),
+        }
+
+        let mut i = 0;
+        for inner in &node.inner {
+            if inner.program_id == node.program_id && inner.cpi_event().is_some() {
+                continue;
+            }
+            i += 1;
+            self.instruction(inner, &format!("{label}.{i}"), indent + 1);
+        }
+    }
+
+    fn invocation(&self, invocation: &Invocation, label: &str, indent: usize) {
+        let pad = "  ".repeat(indent);
+        let idl = self.resolver.get(&invocation.program_id);
+        println!(
+            "{pad}Invocation #{label}: {}",
+            program_name(idl, &invocation.program_id)
+        );
+        println!("{pad}  Program: {}", invocation.program_id);
+        self.logs(invocation, &[], indent + 1);
+
+        for (i, inner) in invocation.inner.iter().enumerate() {
+            self.invocation(inner, &format!("{label}.{}", i + 1), indent + 1);
+        }
+    }
+
+    /// Prints the events and errors of an invocation.
+    fn logs(&self, invocation: &Invocation, cpi_events: &[&[u8]], indent: usize) {
+        let pad = "  ".repeat(indent);
+        let events = invocation
+            .data
+            .iter()
+            .map(Vec::as_slice)
+            .chain(cpi_events.iter().copied())
+            .collect::<Vec<_>>();
+        self.events(&invocation.program_id, &events, indent);
+
+        for error in &invocation.errors {
+            println!("{pad}Error: {error}");
+        }
+        if let Some(failure) = &invocation.failure {
+            let idl = self.resolver.get(&invocation.program_id);
+            println!("{pad}Failed: {}", describe_failure(failure, idl));
+        }
+    }
+
+    fn events(&self, program_id: &Pubkey, events: &[&[u8]], indent: usize) {
+        if events.is_empty() {
+            return;
+        }
+
+        let pad = "  ".repeat(indent);
+        let idl = self.resolver.get(program_id);
+        println!("{pad}Events:");
+        for data in events {
+            match idl.map(|idl| IdlCodec::new(idl).decode_event(data)) {
+                Some(Ok((event, value))) => print_json(&format!("{pad}  {}", event.name), &value),
+                _ => println!("{pad}  Unknown event: {}", STANDARD.encode(data)),
+            }
+        }
+    }
+}
+
+fn program_name(idl: Option<&Idl>, program_id: &Pubkey) -> String {
+    idl.map(|idl| idl.metadata.name.clone())
+        .unwrap_or_else(|| program_id.to_string())
+}
+
+/// Adds the name and message of the IDL error to custom program errors.
+fn describe_failure(failure: &str, idl: Option<&Idl>) -> String {
+    let error = failure
+        .strip_prefix("custom program error: 0x")
+        .and_then(|code| u32::from_str_radix(code, 16).ok())
+        .and_then(|code| idl?.errors.iter().find(|err| err.code == code));
+    match error {
+        Some(error) => match &error.msg {
+            Some(msg) => format!("{failure} ({}: {msg})", error.name),
+            None => format!("{failure} ({})", error.name),
+        },
+        None => failure.to_owned(),
+    }
+}
+
+fn print_json(label: &str, value: &serde_json::Value) {
+    let indent = label.len() - label.trim_start().len();
+    let json = serde_json::to_string_pretty(value).expect("JSON values are serializable");
+    let json = json.replace('\n', &format!("\n{}", " ".repeat(indent)));
+    println!("{label}: {json}");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_logs_into_invocations() {
+        let program = Pubkey::new_unique();
+        let logs = [
+            format!("Program {program} invoke [1]"),
+            "Program log: Instruction: Initialize".into(),
+            format!("Program {} invoke [2]", solana_sdk_ids::system_program::ID),
+            format!("Program {} success", solana_sdk_ids::system_program::ID),
+            "Program data: AQID BA==".into(),
+            format!("Program {program} consumed 5000 of 200000 compute units"),
+            format!("Program {program} success"),
+            format!("Program {program} invoke [1]"),
+            "Program log: AnchorError occurred. Error Code: InvalidAmount. Error Number: 6000. \
+             Error Message: Invalid amount."
+                .into(),
+            format!("Program {program} failed: custom program error: 0x1770"),
+        ];
+
+        let invocations = parse_logs(&logs);
+        assert_eq!(
+            invocations,
+            vec![
+                Invocation {
+                    program_id: program,
+                    data: vec![vec![1, 2, 3, 4]],
+                    inner: vec![Invocation {
+                        program_id: solana_sdk_ids::system_program::ID,
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                },
+                Invocation {
+                    program_id: program,
+                    errors: vec!["AnchorError occurred. Error Code: InvalidAmount. \
+                                  Error Number: 6000. Error Message: Invalid amount."
+                        .into()],
+                    failure: Some("custom program error: 0x1770".into()),
+                    ..Default::default()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn detect_instruction_data_encoding() {
+        let data = [0xaf, 0xaf, 0x6d, 0x1f, 0x0d, 0x98, 0x9b, 0xed, 0x01];
+        let base58 = bs58::encode(data).into_string();
+        let base64 = STANDARD.encode(data);
+
+        assert_eq!(decode_instruction_data(&base58, None, None).unwrap(), data);
+        assert_eq!(decode_instruction_data(&base64, None, None).unwrap(), data);
+        assert_eq!(
+            decode_instruction_data(&base58, Some(TransactionEncoding::Base58), None).unwrap(),
+            data
+        );
+        assert!(decode_instruction_data("0OIl", Some(TransactionEncoding::Base58), None).is_err());
+        assert!(decode_instruction_data("0OIl!", None, None).is_err());
+    }
+
+    #[test]
+    fn detect_ambiguous_instruction_data_encoding_with_discriminators() {
+        // Valid in both encodings
+        let data = "abcdefghijkmnopq";
+        let base58 = bs58::decode(data).into_vec().unwrap();
+        let base64 = STANDARD.decode(data).unwrap();
+        let idl = |discriminator: &[u8]| -> Idl {
+            serde_json::from_value(serde_json::json!({
+                "address": Pubkey::new_unique().to_string(),
+                "metadata": { "name": "example", "version": "0.1.0", "spec": "0.1.0" },
+                "instructions": [{
+                    "name": "initialize",
+                    "discriminator": discriminator,
+                    "accounts": [],
+                    "args": [],
+                }],
+            }))
+            .unwrap()
+        };
+
+        let err = decode_instruction_data(data, None, None).unwrap_err();
+        assert!(err.to_string().contains("`--encoding`"));
+        assert_eq!(
+            decode_instruction_data(data, None, Some(&idl(&base58[..8]))).unwrap(),
+            base58
+        );
+        assert_eq!(
+            decode_instruction_data(data, None, Some(&idl(&base64[..8]))).unwrap(),
+            base64
+        );
+        assert_eq!(
+            decode_instruction_data(data, Some(TransactionEncoding::Base64), None).unwrap(),
+            base64
+        );
+    }
+}
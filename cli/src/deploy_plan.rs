@@ -0,0 +1,282 @@
+//! Deployment plans declared in the `[deploy.<plan>]` sections of `Anchor.toml`.
+//!
+//! A plan deploys or upgrades its programs in order on a single cluster, uploads their IDLs,
+//! hands the upgrade authority over and runs the `pre-deploy`/`post-deploy` hooks of the plan.
+//! The balance of the payer is checked against the cost of the whole plan before anything is
+//! sent. With `--dry-run`, every transaction is printed along with that cost instead.
+
+use {
+    crate::{
+        config::{ConfigOverride, DeployMode, DeployPlan, DeployPlanProgram, HookType},
+        create_client, format_sol, get_cluster_and_wallet,
+        program::{self, DeployCost, EstimateTotals},
+        signer::pubkey_from_path,
+        strip_workspace_prefix, target_dir, with_workspace,
+    },
+    anyhow::{anyhow, bail, Context, Result},
+    solana_keypair::Keypair,
+    solana_pubkey::Pubkey,
+    solana_rpc_client::rpc_client::RpcClient,
+    solana_signer::{EncodableKey, Signer},
+    std::{fs, path::PathBuf},
+};
+
+/// Program of a plan resolved against the workspace and the cluster.
+struct PlannedProgram {
+    entry: DeployPlanProgram,
+    program_id: Pubkey,
+    binary_path: PathBuf,
+    keypair_path: PathBuf,
+    idl_path: PathBuf,
+    upgrade_authority: Option<Pubkey>,
+}
+
+pub fn deploy_plan(
+    cfg_override: &ConfigOverride,
+    name: &str,
+    verifiable: bool,
+    dry_run: bool,
+    solana_args: Vec<String>,
+) -> Result<()> {
+    with_workspace(cfg_override, |cfg| -> Result<()> {
+        let plan = cfg
+            .deploy
+            .get(name)
+            .cloned()
+            .ok_or_else(|| anyhow!("Deployment plan `{name}` not found in Anchor.toml"))?;
+        if plan.programs.is_empty() {
+            bail!("Deployment plan `{name}` has no programs");
+        }
+
+        let plan_override = ConfigOverride {
+            cluster: Some(plan.cluster.clone()),
+            wallet: match plan.wallet.as_deref() {
+                Some(wallet) => Some(shellexpand::tilde(wallet).parse()?),
+                None => cfg_override.wallet.clone(),
+            },
+            commitment: cfg_override.commitment,
+        };
+        let (url, wallet) = get_cluster_and_wallet(&plan_override)?;
+        let payer = pubkey_from_path(&wallet, "wallet")?;
+        let client = create_client(&url);
+
+        // Resolve every program before sending anything so a misconfigured plan fails early
+        let programs = plan
+            .programs
+            .iter()
+            .map(|entry| {
+                let program = cfg.get_programs(Some(entry.name.clone()))?.remove(0);
+                let keypair_path = program.keypair_file()?.path().clone();
+                let program_id = Keypair::read_from_file(&keypair_path)
+                    .map_err(|e| {
+                        anyhow!(
+                            "Failed to read program keypair from {}: {e}",
+                            keypair_path.display()
+                        )
+                    })?
+                    .pubkey();
+                let upgrade_authority = entry
+                    .upgrade_authority
+                    .as_deref()
+                    .map(|authority| {
+                        authority.parse().with_context(|| {
+                            format!("Invalid upgrade authority of `{}`: {authority}", entry.name)
+                        })
+                    })
+                    .transpose()?;
+                Ok(PlannedProgram {
+                    entry: entry.clone(),
+                    program_id,
+                    binary_path: program.binary_path(verifiable)?,
+                    keypair_path,
+                    idl_path: target_dir()?
+                        .join("idl")
+                        .join(&program.lib_name)
+                        .with_extension("json"),
+                    upgrade_authority,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        for program in &programs {
+            let exists = client
+                .get_account_with_commitment(&program.program_id, client.commitment())?
+                .value
+                .is_some();
+            match (program.entry.mode, exists) {
+                (DeployMode::Init, true) => bail!(
+                    "Program `{}` ({}) is already deployed, but the plan initializes it",
+                    program.entry.name,
+                    program.program_id
+                ),
+                (DeployMode::Upgrade, false) => bail!(
+                    "Program `{}` ({}) is not deployed, but the plan upgrades it",
+                    program.entry.name,
+                    program.program_id
+                ),
+                _ => {}
+            }
+        }
+
+        let costs = estimate_plan(&programs, &client, &payer, &solana_args)?;
+        let totals = plan_totals(&programs, &costs);
+        if dry_run {
+            print_plan(name, &plan, &programs, &costs, &totals, &url, &payer);
+            let balance = program::ensure_payer_balance(&client, &payer, totals.required())?;
+            println!("Balance:      {}", format_sol(balance));
+            return Ok(());
+        }
+
+        // Fail before the first program rather than partway through the plan
+        program::ensure_payer_balance(&client, &payer, totals.required())?;
+
+        println!("Deploying plan `{name}` to cluster: {url}");
+        let payer_pubkey = payer.to_string();
+        let envs = [
+            ("ANCHOR_PROVIDER_URL", url.as_str()),
+            ("ANCHOR_WALLET", wallet.as_str()),
+            ("ANCHOR_WALLET_PUBKEY", payer_pubkey.as_str()),
+        ];
+        plan.hooks.run(HookType::PreDeploy, &envs)?;
+
+        for program in &programs {
+            println!("Deploying program {:?}...", program.entry.name);
+            program::program_deploy(
+                &plan_override,
+                Some(strip_workspace_prefix(program.binary_path.clone())),
+                None,
+                Some(strip_workspace_prefix(program.keypair_path.clone())),
+                None, // upgrade_authority - uses the wallet of the plan
+                None, // program_id - derived from program_keypair
+                None, // buffer
+                program.entry.max_len,
+                false, // use_rpc
+                !program.entry.idl,
+                false, // make_final
+                program.entry.extend_headroom.unwrap_or_default(),
+                false, // estimate
+                solana_args.clone(),
+            )?;
+
+            if let Some(authority) = program.upgrade_authority {
+                program::program_set_upgrade_authority(
+                    &plan_override,
+                    program.program_id,
+                    Some(authority),
+                    None,
+                    true, // the new authority is usually a multisig that can't sign
+                    false,
+                    None,
+                )?;
+            }
+        }
+
+        println!("Deploy success");
+        plan.hooks.run(HookType::PostDeploy, &envs)
+    })?
+}
+
+/// Estimate the cost of deploying each program of the plan.
+fn estimate_plan(
+    programs: &[PlannedProgram],
+    client: &RpcClient,
+    payer: &Pubkey,
+    solana_args: &[String],
+) -> Result<Vec<DeployCost>> {
+    let priority_fee = program::parse_priority_fee_from_args(solana_args);
+    programs
+        .iter()
+        .map(|program| {
+            let program_len = fs::metadata(&program.binary_path)
+                .with_context(|| {
+                    format!(
+                        "Failed to read program binary `{}`, run `anchor build` first",
+                        program.binary_path.display()
+                    )
+                })?
+                .len() as usize;
+            program::estimate_deploy_cost(
+                client,
+                &program.program_id,
+                program_len,
+                program.entry.max_len,
+                payer,
+                priority_fee,
+                program.entry.idl.then(|| program.idl_path.clone()),
+                program.entry.extend_headroom.unwrap_or_default(),
+            )
+        })
+        .collect()
+}
+
+/// Totals of the plan, including the authority handoffs.
+fn plan_totals(programs: &[PlannedProgram], costs: &[DeployCost]) -> EstimateTotals {
+    let mut totals = EstimateTotals::default();
+    for (program, cost) in programs.iter().zip(costs) {
+        totals.add(cost);
+        if program.upgrade_authority.is_some() {
+            totals.transactions += 1;
+            totals.fees += program::LAMPORTS_PER_SIGNATURE;
+        }
+    }
+    totals
+}
+
+/// Print the hooks and transactions of the plan along with the expected rent and fees.
+fn print_plan(
+    name: &str,
+    plan: &DeployPlan,
+    programs: &[PlannedProgram],
+    costs: &[DeployCost],
+    totals: &EstimateTotals,
+    url: &str,
+    payer: &Pubkey,
+) {
+    println!("Deployment plan `{name}` (dry run)");
+    println!("Cluster: {url}");
+    println!("Payer:   {payer}");
+    print_hooks(plan, HookType::PreDeploy);
+
+    for (index, (program, cost)) in programs.iter().zip(costs).enumerate() {
+        println!();
+        println!(
+            "{}. {} ({}): {}",
+            index + 1,
+            program.entry.name,
+            program.program_id,
+            if cost.loader.is_upgrade {
+                "upgrade"
+            } else {
+                "deploy"
+            }
+        );
+        cost.print();
+        if let Some(authority) = program.upgrade_authority {
+            program::print_estimate_step(
+                "SetAuthority",
+                format!("upgrade authority to {authority}"),
+            );
+        }
+    }
+
+    print_hooks(plan, HookType::PostDeploy);
+
+    println!();
+    totals.print();
+}
+
+fn print_hooks(plan: &DeployPlan, hook_type: HookType) {
+    let commands = plan.hooks.commands(hook_type);
+    if commands.is_empty() {
+        return;
+    }
+
+    println!();
+    match hook_type {
+        HookType::PreDeploy => println!("Pre-deploy hooks:"),
+        _ => println!("Post-deploy hooks:"),
+    }
+    for command in commands {
+        println!("   $ {command}");
+    }
+}
@@ -29,6 +29,8 @@ use self::{
     rpc::{create_rpc_client, fetch_idl_signatures, fetch_pmp_idl_signatures},
 };
 
+pub use self::pmp::{fetch_pmp_idl, pmp_metadata_address, PMP_METADATA_HEADER_SIZE};
+
 const DEFAULT_MAX_RETRIES: u32 = 5;
 const DEFAULT_RETRY_BACKOFF_MS: u64 = 500;
 const PROGRESS_TICK_INTERVAL_MS: u64 = 80;
@@ -28,6 +28,10 @@ const METADATA_SEED_PADDING: usize = 14;
 // Buffer accounts include a fixed header before the staged metadata payload bytes begin.
 const PMP_BUFFER_HEADER_SIZE: usize = 1 + 32 + 32 + 1 + SEED_SIZE + METADATA_SEED_PADDING;
 
+// Metadata accounts share the buffer header size, with the data header and length stored in the
+// space buffers leave as padding.
+pub const PMP_METADATA_HEADER_SIZE: usize = PMP_BUFFER_HEADER_SIZE;
+
 // Mirrors the Program Metadata instruction discriminators so historical fetch can decode the raw
 // compiled instructions it replays from transaction history.
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
@@ -297,6 +301,58 @@ pub fn pmp_metadata_address(program_id: &Pubkey, authority: Option<&Pubkey>) ->
     .0
 }
 
+// Reads the IDL currently stored in the canonical PMP metadata account of a program. Returns
+// `None` when the program has no metadata account.
+pub fn fetch_pmp_idl(client: &RpcClient, program_id: &Pubkey) -> Result<Option<Vec<u8>>> {
+    let metadata_address = pmp_metadata_address(program_id, None);
+    let Some(account) = client
+        .get_account_with_commitment(&metadata_address, client.commitment())?
+        .value
+    else {
+        return Ok(None);
+    };
+
+    let (header, payload) =
+        parse_metadata_account(&account.data).map_err(|err| anyhow!(err.detail))?;
+    header
+        .decode_direct(payload)
+        .map(Some)
+        .map_err(|err| anyhow!(err.detail))
+}
+
+// Parses the header and payload bytes of an initialized PMP metadata account.
+fn parse_metadata_account(
+    data: &[u8],
+) -> std::result::Result<(PmpMetadataHeader, &[u8]), PmpFetchError> {
+    // The decoding header follows the discriminator, program, authority, mutable and canonical
+    // flags, and the seed.
+    const HEADER_OFFSET: usize = 1 + 32 + 32 + 1 + 1 + SEED_SIZE;
+    const DATA_LENGTH_OFFSET: usize = HEADER_OFFSET + 4;
+    if data.len() < PMP_METADATA_HEADER_SIZE {
+        return Err(PmpFetchError::invalid_transaction(
+            "invalid PMP metadata account",
+        ));
+    }
+
+    let header = PmpMetadataHeader {
+        encoding: MetadataEncoding::try_from(data[HEADER_OFFSET])?,
+        compression: MetadataCompression::try_from(data[HEADER_OFFSET + 1])?,
+        format: MetadataFormat::try_from(data[HEADER_OFFSET + 2])?,
+        data_source: MetadataDataSource::try_from(data[HEADER_OFFSET + 3])?,
+    };
+    let data_length = u32::from_le_bytes(
+        data[DATA_LENGTH_OFFSET..DATA_LENGTH_OFFSET + 4]
+            .try_into()
+            .unwrap(),
+    ) as usize;
+    let payload = data[PMP_METADATA_HEADER_SIZE..]
+        .get(..data_length)
+        .ok_or_else(|| {
+            PmpFetchError::invalid_transaction("PMP metadata data length exceeds account size")
+        })?;
+    Ok((header, payload))
+}
+
 // Replays the transaction history of a PMP metadata account and recovers every historical IDL
 // version that can be decoded from it.
 pub fn fetch_pmp_historical_idls(
@@ -1014,6 +1070,28 @@ mod tests {
         assert_eq!(decoded, input);
     }
 
+    #[test]
+    fn parse_metadata_account_payload() {
+        let payload = br#"{"name":"example"}"#;
+        let mut account = vec![0u8; PMP_METADATA_HEADER_SIZE];
+        account[83..87].copy_from_slice(&[
+            MetadataEncoding::Utf8 as u8,
+            MetadataCompression::None as u8,
+            MetadataFormat::Json as u8,
+            MetadataDataSource::Direct as u8,
+        ]);
+        account[87..91].copy_from_slice(&(payload.len() as u32).to_le_bytes());
+        account.extend_from_slice(payload);
+        // Trailing bytes of a metadata account that was shrunk in place
+        account.extend_from_slice(&[0; 8]);
+
+        let (header, data) = parse_metadata_account(&account).unwrap();
+        assert_eq!(header.decode_direct(data).unwrap(), payload);
+
+        account.truncate(PMP_METADATA_HEADER_SIZE + 4);
+        assert!(parse_metadata_account(&account).is_err());
+    }
+
     #[test]
     fn parse_inline_set_data() {
         let instruction = [
@@ -0,0 +1,813 @@
+//! Compatibility check between two versions of a program's IDL.
+//!
+//! Changes are classified as breaking when clients built against the old IDL would no longer be
+//! able to interact with the program, e.g. because an instruction's discriminator or an account's
+//! layout has changed. Everything else, such as new instructions or changed docs, is non-breaking.
+
+use {
+    crate::{config::ConfigOverride, idl_fetch},
+    anchor_lang_idl::{
+        convert::convert_idl,
+        types::{
+            Idl, IdlDefinedFields, IdlField, IdlGenericArg, IdlInstructionAccount,
+            IdlInstructionAccountItem, IdlType, IdlTypeDef, IdlTypeDefTy,
+        },
+    },
+    anyhow::{bail, Context, Result},
+    solana_pubkey::Pubkey,
+    std::{collections::HashSet, fmt, fs, path::Path},
+};
+
+/// Severity of an IDL change.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// Existing clients are no longer compatible with the program.
+    Breaking,
+    /// Existing clients keep working.
+    NonBreaking,
+}
+
+/// A single difference between two IDLs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IdlChange {
+    pub severity: Severity,
+    /// Dot-separated path of the changed item, e.g. `instructions.initialize.args.amount`.
+    pub path: String,
+    pub message: String,
+}
+
+impl fmt::Display for IdlChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+pub fn idl_diff(
+    cfg_override: &ConfigOverride,
+    old: String,
+    new: std::path::PathBuf,
+    fail_on_breaking: bool,
+) -> Result<()> {
+    let old = load_old_idl(cfg_override, &old)?;
+    let new = read_idl(&new)?;
+
+    let changes = diff_idls(&old, &new);
+    if changes.is_empty() {
+        println!("No changes found");
+        return Ok(());
+    }
+
+    let (breaking, non_breaking): (Vec<_>, Vec<_>) = changes
+        .iter()
+        .partition(|change| change.severity == Severity::Breaking);
+    for (title, changes) in [
+        ("Breaking changes", &breaking),
+        ("Non-breaking changes", &non_breaking),
+    ] {
+        if !changes.is_empty() {
+            println!("{title} ({}):", changes.len());
+            for change in changes {
+                println!("  - {change}");
+            }
+        }
+    }
+
+    if fail_on_breaking && !breaking.is_empty() {
+        bail!("Found {} breaking change(s)", breaking.len());
+    }
+
+    Ok(())
+}
+
+/// Loads the old IDL either from a file, or from the cluster if a program id is given.
+fn load_old_idl(cfg_override: &ConfigOverride, old: &str) -> Result<Idl> {
+    let path = Path::new(old);
+    if !path.exists() {
+        if let Ok(program_id) = old.parse::<Pubkey>() {
+            let dir = tempfile::tempdir()?;
+            let out = dir.path().join("idl.json");
+            idl_fetch(
+                cfg_override,
+                program_id,
+                Some(out.display().to_string()),
+                false,
+            )?;
+            return read_idl(&out);
+        }
+    }
+
+    read_idl(path)
+}
+
+fn read_idl(path: &Path) -> Result<Idl> {
+    let bytes =
+        fs::read(path).with_context(|| format!("Failed to read IDL `{}`", path.display()))?;
+    convert_idl(&bytes).with_context(|| format!("Failed to parse IDL `{}`", path.display()))
+}
+
+/// Returns all differences between the `old` and the `new` IDL.
+pub fn diff_idls(old: &Idl, new: &Idl) -> Vec<IdlChange> {
+    let mut differ = Differ {
+        old,
+        new,
+        changes: vec![],
+        visited: HashSet::new(),
+    };
+    differ.instructions();
+    differ.accounts();
+    differ.events();
+    differ.errors();
+    differ.constants();
+    differ.changes
+}
+
+struct Differ<'a> {
+    old: &'a Idl,
+    new: &'a Idl,
+    changes: Vec<IdlChange>,
+    /// Pairs of type definitions already compared for the current item, to handle recursive types.
+    visited: HashSet<(String, String)>,
+}
+
+impl Differ<'_> {
+    fn breaking(&mut self, path: impl Into<String>, message: impl Into<String>) {
+        self.push(Severity::Breaking, path, message)
+    }
+
+    fn non_breaking(&mut self, path: impl Into<String>, message: impl Into<String>) {
+        self.push(Severity::NonBreaking, path, message)
+    }
+
+    fn push(&mut self, severity: Severity, path: impl Into<String>, message: impl Into<String>) {
+        self.changes.push(IdlChange {
+            severity,
+            path: path.into(),
+            message: message.into(),
+        })
+    }
+
+    fn instructions(&mut self) {
+        for old_ix in &self.old.instructions {
+            self.visited.clear();
+
+            let path = format!("instructions.{}", old_ix.name);
+            let Some(new_ix) = self
+                .new
+                .instructions
+                .iter()
+                .find(|ix| ix.name == old_ix.name)
+            else {
+                self.breaking(path, "instruction removed");
+                continue;
+            };
+
+            if old_ix.discriminator != new_ix.discriminator {
+                self.breaking(
+                    &path,
+                    format!(
+                        "discriminator changed from {:?} to {:?}",
+                        old_ix.discriminator, new_ix.discriminator
+                    ),
+                );
+            }
+            self.instruction_accounts(
+                &format!("{path}.accounts"),
+                &flatten_accounts(&old_ix.accounts),
+                &flatten_accounts(&new_ix.accounts),
+            );
+            self.fields(&format!("{path}.args"), &old_ix.args, &new_ix.args);
+            match (&old_ix.returns, &new_ix.returns) {
+                (Some(old_ty), Some(new_ty)) => self.ty(&format!("{path}.returns"), old_ty, new_ty),
+                (None, None) => {}
+                (old_ty, new_ty) => self.breaking(
+                    format!("{path}.returns"),
+                    format!(
+                        "return type changed from `{}` to `{}`",
+                        old_ty.as_ref().map(type_name).as_deref().unwrap_or("()"),
+                        new_ty.as_ref().map(type_name).as_deref().unwrap_or("()"),
+                    ),
+                ),
+            }
+        }
+
+        for new_ix in &self.new.instructions {
+            if !self
+                .old
+                .instructions
+                .iter()
+                .any(|ix| ix.name == new_ix.name)
+            {
+                self.non_breaking(format!("instructions.{}", new_ix.name), "instruction added");
+            }
+        }
+    }
+
+    fn instruction_accounts(
+        &mut self,
+        path: &str,
+        old: &[(String, &IdlInstructionAccount)],
+        new: &[(String, &IdlInstructionAccount)],
+    ) {
+        for (i, (name, old_acc)) in old.iter().enumerate() {
+            let path = format!("{path}.{name}");
+            let Some((new_name, new_acc)) = new.get(i) else {
+                self.breaking(path, "account removed");
+                continue;
+            };
+            if new_name != name {
+                match new.iter().position(|(new_name, _)| new_name == name) {
+                    Some(j) => self.breaking(path, format!("account moved from {i} to {j}")),
+                    None => self.breaking(path, format!("account replaced with `{new_name}`")),
+                }
+                continue;
+            }
+
+            match (old_acc.signer, new_acc.signer) {
+                (false, true) => self.breaking(&path, "account is now a signer"),
+                (true, false) => self.non_breaking(&path, "account is no longer a signer"),
+                _ => {}
+            }
+            match (old_acc.writable, new_acc.writable) {
+                (false, true) => self.breaking(&path, "account is now writable"),
+                (true, false) => self.non_breaking(&path, "account is no longer writable"),
+                _ => {}
+            }
+            match (old_acc.optional, new_acc.optional) {
+                (true, false) => self.breaking(&path, "account is no longer optional"),
+                (false, true) => self.non_breaking(&path, "account is now optional"),
+                _ => {}
+            }
+            if old_acc.address != new_acc.address {
+                self.breaking(&path, "account address changed");
+            }
+        }
+
+        for (name, _) in new.iter().skip(old.len()) {
+            if !old.iter().any(|(old_name, _)| old_name == name) {
+                self.breaking(format!("{path}.{name}"), "account added");
+            }
+        }
+    }
+
+    fn accounts(&mut self) {
+        for old_acc in &self.old.accounts {
+            self.visited.clear();
+
+            let path = format!("accounts.{}", old_acc.name);
+            let Some(new_acc) = self
+                .new
+                .accounts
+                .iter()
+                .find(|acc| acc.name == old_acc.name)
+            else {
+                self.breaking(path, "account removed");
+                continue;
+            };
+
+            if old_acc.discriminator != new_acc.discriminator {
+                self.breaking(
+                    &path,
+                    format!(
+                        "discriminator changed from {:?} to {:?}",
+                        old_acc.discriminator, new_acc.discriminator
+                    ),
+                );
+            }
+            self.type_def(&path, &old_acc.name, &new_acc.name);
+        }
+
+        for new_acc in &self.new.accounts {
+            if !self.old.accounts.iter().any(|acc| acc.name == new_acc.name) {
+                self.non_breaking(format!("accounts.{}", new_acc.name), "account added");
+            }
+        }
+    }
+
+    fn events(&mut self) {
+        for old_ev in &self.old.events {
+            self.visited.clear();
+
+            let path = format!("events.{}", old_ev.name);
+            let Some(new_ev) = self.new.events.iter().find(|ev| ev.name == old_ev.name) else {
+                self.breaking(path, "event removed");
+                continue;
+            };
+
+            if old_ev.discriminator != new_ev.discriminator {
+                self.breaking(
+                    &path,
+                    format!(
+                        "discriminator changed from {:?} to {:?}",
+                        old_ev.discriminator, new_ev.discriminator
+                    ),
+                );
+            }
+            self.type_def(&path, &old_ev.name, &new_ev.name);
+        }
+
+        for new_ev in &self.new.events {
+            if !self.old.events.iter().any(|ev| ev.name == new_ev.name) {
+                self.non_breaking(format!("events.{}", new_ev.name), "event added");
+            }
+        }
+    }
+
+    fn errors(&mut self) {
+        for old_err in &self.old.errors {
+            let path = format!("errors.{}", old_err.name);
+            let Some(new_err) = self.new.errors.iter().find(|err| err.code == old_err.code) else {
+                self.breaking(path, format!("error code {} removed", old_err.code));
+                continue;
+            };
+
+            if new_err.name != old_err.name {
+                self.breaking(
+                    &path,
+                    format!("error code {} renamed to `{}`", old_err.code, new_err.name),
+                );
+            }
+            if new_err.msg != old_err.msg {
+                self.non_breaking(&path, "error message changed");
+            }
+        }
+
+        for new_err in &self.new.errors {
+            if !self.old.errors.iter().any(|err| err.code == new_err.code) {
+                self.non_breaking(
+                    format!("errors.{}", new_err.name),
+                    format!("error code {} added", new_err.code),
+                );
+            }
+        }
+    }
+
+    fn constants(&mut self) {
+        for old_const in &self.old.constants {
+            self.visited.clear();
+
+            let path = format!("constants.{}", old_const.name);
+            let Some(new_const) = self.new.constants.iter().find(|c| c.name == old_const.name)
+            else {
+                self.breaking(path, "constant removed");
+                continue;
+            };
+
+            self.ty(&path, &old_const.ty, &new_const.ty);
+            if old_const.value != new_const.value {
+                self.non_breaking(
+                    &path,
+                    format!(
+                        "value changed from `{}` to `{}`",
+                        old_const.value, new_const.value
+                    ),
+                );
+            }
+        }
+
+        for new_const in &self.new.constants {
+            if !self.old.constants.iter().any(|c| c.name == new_const.name) {
+                self.non_breaking(format!("constants.{}", new_const.name), "constant added");
+            }
+        }
+    }
+
+    /// Compares named fields by position, as their order determines the serialized layout.
+    fn fields(&mut self, path: &str, old: &[IdlField], new: &[IdlField]) {
+        for (i, old_field) in old.iter().enumerate() {
+            let path = format!("{path}.{}", old_field.name);
+            let Some(new_field) = new.get(i) else {
+                self.breaking(path, "field removed");
+                continue;
+            };
+            if new_field.name != old_field.name {
+                match new.iter().position(|f| f.name == old_field.name) {
+                    Some(j) => {
+                        self.breaking(path, format!("field moved from {i} to {j}"));
+                        continue;
+                    }
+                    None => self.breaking(&path, format!("field renamed to `{}`", new_field.name)),
+                }
+            }
+
+            self.ty(&path, &old_field.ty, &new_field.ty);
+        }
+
+        for new_field in new.iter().skip(old.len()) {
+            if !old.iter().any(|f| f.name == new_field.name) {
+                self.breaking(format!("{path}.{}", new_field.name), "field added");
+            }
+        }
+    }
+
+    fn defined_fields(
+        &mut self,
+        path: &str,
+        old: &Option<IdlDefinedFields>,
+        new: &Option<IdlDefinedFields>,
+    ) {
+        match (old, new) {
+            (None, None) => {}
+            (Some(IdlDefinedFields::Named(old)), Some(IdlDefinedFields::Named(new))) => {
+                self.fields(path, old, new)
+            }
+            (Some(IdlDefinedFields::Tuple(old)), Some(IdlDefinedFields::Tuple(new))) => {
+                for i in 0..old.len().max(new.len()) {
+                    let path = format!("{path}.{i}");
+                    match (old.get(i), new.get(i)) {
+                        (Some(old_ty), Some(new_ty)) => self.ty(&path, old_ty, new_ty),
+                        (Some(_), None) => self.breaking(path, "field removed"),
+                        (None, Some(_)) => self.breaking(path, "field added"),
+                        (None, None) => unreachable!(),
+                    }
+                }
+            }
+            _ => self.breaking(path, "fields changed"),
+        }
+    }
+
+    fn ty(&mut self, path: &str, old: &IdlType, new: &IdlType) {
+        let old = resolve_alias(self.old, old);
+        let new = resolve_alias(self.new, new);
+        match (old, new) {
+            (IdlType::Option(old_ty), IdlType::Option(new_ty))
+            | (IdlType::Vec(old_ty), IdlType::Vec(new_ty)) => self.ty(path, old_ty, new_ty),
+            (IdlType::Array(old_ty, old_len), IdlType::Array(new_ty, new_len)) => {
+                if old_len != new_len {
+                    self.breaking(
+                        path,
+                        format!(
+                            "type changed from `{}` to `{}`",
+                            type_name(old),
+                            type_name(new)
+                        ),
+                    );
+                } else {
+                    self.ty(path, old_ty, new_ty);
+                }
+            }
+            (
+                IdlType::Defined {
+                    name: old_name,
+                    generics: old_generics,
+                },
+                IdlType::Defined {
+                    name: new_name,
+                    generics: new_generics,
+                },
+            ) => {
+                if old_generics.len() != new_generics.len() {
+                    self.breaking(
+                        path,
+                        format!(
+                            "type changed from `{}` to `{}`",
+                            type_name(old),
+                            type_name(new)
+                        ),
+                    );
+                    return;
+                }
+                for (old_arg, new_arg) in old_generics.iter().zip(new_generics) {
+                    match (old_arg, new_arg) {
+                        (
+                            IdlGenericArg::Type { ty: old_ty },
+                            IdlGenericArg::Type { ty: new_ty },
+                        ) => self.ty(path, old_ty, new_ty),
+                        (old_arg, new_arg) if old_arg == new_arg => {}
+                        _ => self.breaking(
+                            path,
+                            format!(
+                                "type changed from `{}` to `{}`",
+                                type_name(old),
+                                type_name(new)
+                            ),
+                        ),
+                    }
+                }
+                self.type_def(path, old_name, new_name);
+            }
+            (old, new) if old == new => {}
+            (old, new) => self.breaking(
+                path,
+                format!(
+                    "type changed from `{}` to `{}`",
+                    type_name(old),
+                    type_name(new)
+                ),
+            ),
+        }
+    }
+
+    fn type_def(&mut self, path: &str, old_name: &str, new_name: &str) {
+        if !self
+            .visited
+            .insert((old_name.to_owned(), new_name.to_owned()))
+        {
+            return;
+        }
+
+        let (Some(old), Some(new)) = (find_type(self.old, old_name), find_type(self.new, new_name))
+        else {
+            if old_name != new_name {
+                self.breaking(
+                    path,
+                    format!("type changed from `{old_name}` to `{new_name}`"),
+                );
+            }
+            return;
+        };
+
+        if old.serialization != new.serialization {
+            self.breaking(path, "serialization changed");
+        }
+        if old.repr != new.repr {
+            self.breaking(path, "representation changed");
+        }
+        if old.generics != new.generics {
+            self.breaking(path, "generics changed");
+        }
+
+        match (&old.ty, &new.ty) {
+            (
+                IdlTypeDefTy::Struct { fields: old_fields },
+                IdlTypeDefTy::Struct { fields: new_fields },
+            ) => self.defined_fields(path, old_fields, new_fields),
+            (
+                IdlTypeDefTy::Enum {
+                    variants: old_variants,
+                },
+                IdlTypeDefTy::Enum {
+                    variants: new_variants,
+                },
+            ) => {
+                for (i, old_variant) in old_variants.iter().enumerate() {
+                    let path = format!("{path}.{}", old_variant.name);
+                    let Some(new_variant) = new_variants.get(i) else {
+                        self.breaking(path, "variant removed");
+                        continue;
+                    };
+                    if new_variant.name != old_variant.name {
+                        match new_variants.iter().position(|v| v.name == old_variant.name) {
+                            Some(j) => {
+                                self.breaking(path, format!("variant moved from {i} to {j}"));
+                                continue;
+                            }
+                            None => self.breaking(
+                                &path,
+                                format!("variant renamed to `{}`", new_variant.name),
+                            ),
+                        }
+                    }
+
+                    self.defined_fields(&path, &old_variant.fields, &new_variant.fields);
+                }
+
+                for new_variant in new_variants.iter().skip(old_variants.len()) {
+                    if !old_variants.iter().any(|v| v.name == new_variant.name) {
+                        self.non_breaking(format!("{path}.{}", new_variant.name), "variant added");
+                    }
+                }
+            }
+            (IdlTypeDefTy::Type { alias: old_alias }, IdlTypeDefTy::Type { alias: new_alias }) => {
+                self.ty(path, old_alias, new_alias)
+            }
+            _ => self.breaking(path, "type kind changed"),
+        }
+    }
+}
+
+/// Flattens composite accounts into `(path, account)` pairs in instruction order.
+pub(crate) fn flatten_accounts(
+    items: &[IdlInstructionAccountItem],
+) -> Vec<(String, &IdlInstructionAccount)> {
+    items
+        .iter()
+        .flat_map(|item| match item {
+            IdlInstructionAccountItem::Single(acc) => vec![(acc.name.clone(), acc)],
+            IdlInstructionAccountItem::Composite(accs) => flatten_accounts(&accs.accounts)
+                .into_iter()
+                .map(|(name, acc)| (format!("{}.{name}", accs.name), acc))
+                .collect(),
+        })
+        .collect()
+}
+
+fn find_type<'a>(idl: &'a Idl, name: &str) -> Option<&'a IdlTypeDef> {
+    idl.types.iter().find(|ty| ty.name == name)
+}
+
+/// Resolves non-generic type aliases, as they don't affect the serialized layout.
+fn resolve_alias<'a>(idl: &'a Idl, mut ty: &'a IdlType) -> &'a IdlType {
+    let mut seen = HashSet::new();
+    while let IdlType::Defined { name, generics } = ty {
+        if !generics.is_empty() || !seen.insert(name) {
+            break;
+        }
+        match find_type(idl, name) {
+            Some(IdlTypeDef {
+                ty: IdlTypeDefTy::Type { alias },
+                generics,
+                ..
+            }) if generics.is_empty() => ty = alias,
+            _ => break,
+        }
+    }
+
+    ty
+}
+
+fn type_name(ty: &IdlType) -> String {
+    match ty {
+        IdlType::Option(ty) => format!("Option<{}>", type_name(ty)),
+        IdlType::Vec(ty) => format!("Vec<{}>", type_name(ty)),
+        IdlType::Array(ty, len) => {
+            let len = match len {
+                anchor_lang_idl::types::IdlArrayLen::Generic(name) => name.to_owned(),
+                anchor_lang_idl::types::IdlArrayLen::Value(len) => len.to_string(),
+            };
+            format!("[{}; {len}]", type_name(ty))
+        }
+        IdlType::Defined { name, generics } if generics.is_empty() => name.to_owned(),
+        IdlType::Defined { name, generics } => {
+            let generics = generics
+                .iter()
+                .map(|arg| match arg {
+                    IdlGenericArg::Type { ty } => type_name(ty),
+                    IdlGenericArg::Const { value } => value.to_owned(),
+                })
+                .collect::<Vec<_>>()
+                .join(", ");
+            format!("{name}<{generics}>")
+        }
+        IdlType::Generic(name) => name.to_owned(),
+        ty => match serde_json::to_value(ty) {
+            Ok(serde_json::Value::String(name)) => name,
+            _ => format!("{ty:?}"),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, serde_json::json};
+
+    fn idl(value: serde_json::Value) -> Idl {
+        let mut idl = json!({
+            "address": "Fg6PaFpoGXkYsidMpWTK6W2BeZ7FEfcYkg476zPFsLnS",
+            "metadata": { "name": "test", "version": "0.1.0", "spec": "0.1.0" },
+            "instructions": [],
+        });
+        idl.as_object_mut()
+            .unwrap()
+            .extend(value.as_object().unwrap().clone());
+        serde_json::from_value(idl).unwrap()
+    }
+
+    fn base() -> serde_json::Value {
+        json!({
+            "instructions": [{
+                "name": "initialize",
+                "discriminator": [1, 2, 3, 4, 5, 6, 7, 8],
+                "accounts": [
+                    { "name": "payer", "writable": true, "signer": true },
+                    { "name": "data", "writable": true }
+                ],
+                "args": [{ "name": "amount", "type": "u64" }]
+            }],
+            "accounts": [{ "name": "Data", "discriminator": [8, 7, 6, 5, 4, 3, 2, 1] }],
+            "errors": [{ "code": 6000, "name": "Overflow", "msg": "Overflow" }],
+            "types": [
+                {
+                    "name": "Data",
+                    "type": {
+                        "kind": "struct",
+                        "fields": [
+                            { "name": "amount", "type": "u64" },
+                            { "name": "kind", "type": { "defined": { "name": "Kind" } } }
+                        ]
+                    }
+                },
+                {
+                    "name": "Kind",
+                    "type": { "kind": "enum", "variants": [{ "name": "A" }, { "name": "B" }] }
+                }
+            ]
+        })
+    }
+
+    fn changes(old: serde_json::Value, new: serde_json::Value) -> Vec<(Severity, String)> {
+        diff_idls(&idl(old), &idl(new))
+            .into_iter()
+            .map(|change| (change.severity, change.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn identical_idls_have_no_changes() {
+        assert!(changes(base(), base()).is_empty());
+    }
+
+    #[test]
+    fn detects_breaking_changes() {
+        let mut new = base();
+        new["instructions"][0]["discriminator"] = json!([0, 0, 0, 0, 0, 0, 0, 0]);
+        new["instructions"][0]["args"][0]["type"] = json!("u32");
+        new["instructions"][0]["accounts"] = json!([
+            { "name": "data", "writable": true },
+            { "name": "payer", "writable": true, "signer": true }
+        ]);
+        new["types"][0]["type"]["fields"] = json!([
+            { "name": "kind", "type": { "defined": { "name": "Kind" } } },
+            { "name": "amount", "type": "u64" }
+        ]);
+        new["errors"] = json!([]);
+
+        assert_eq!(
+            changes(base(), new),
+            [
+                "instructions.initialize: discriminator changed from [1, 2, 3, 4, 5, 6, 7, 8] to [0, 0, 0, 0, 0, 0, 0, 0]",
+                "instructions.initialize.accounts.payer: account moved from 0 to 1",
+                "instructions.initialize.accounts.data: account moved from 1 to 0",
+                "instructions.initialize.args.amount: type changed from `u64` to `u32`",
+                "accounts.Data.amount: field moved from 0 to 1",
+                "accounts.Data.kind: field moved from 1 to 0",
+                "errors.Overflow: error code 6000 removed",
+            ]
+            .map(|msg| (Severity::Breaking, msg.to_owned()))
+        );
+    }
+
+    #[test]
+    fn detects_nested_type_changes() {
+        let mut new = base();
+        new["types"][1]["type"]["variants"] = json!([{ "name": "A" }]);
+        new["accounts"][0]["discriminator"] = json!([0, 0, 0, 0, 0, 0, 0, 0]);
+
+        assert_eq!(
+            changes(base(), new),
+            [
+                "accounts.Data: discriminator changed from [8, 7, 6, 5, 4, 3, 2, 1] to [0, 0, 0, 0, 0, 0, 0, 0]",
+                "accounts.Data.kind.B: variant removed",
+            ]
+            .map(|msg| (Severity::Breaking, msg.to_owned()))
+        );
+    }
+
+    #[test]
+    fn detects_non_breaking_changes() {
+        let mut new = base();
+        new["instructions"][0]["accounts"][1]["writable"] = json!(false);
+        new["types"][1]["type"]["variants"] =
+            json!([{ "name": "A" }, { "name": "B" }, { "name": "C" }]);
+        new["errors"] = json!([
+            { "code": 6000, "name": "Overflow", "msg": "Math overflow" },
+            { "code": 6001, "name": "Underflow" }
+        ]);
+        new["events"] = json!([{ "name": "Data", "discriminator": [1, 1, 1, 1, 1, 1, 1, 1] }]);
+
+        assert_eq!(
+            changes(base(), new),
+            [
+                "instructions.initialize.accounts.data: account is no longer writable",
+                "accounts.Data.kind.C: variant added",
+                "events.Data: event added",
+                "errors.Overflow: error message changed",
+                "errors.Underflow: error code 6001 added",
+            ]
+            .map(|msg| (Severity::NonBreaking, msg.to_owned()))
+        );
+    }
+
+    #[test]
+    fn appending_fields_is_breaking() {
+        let mut new = base();
+        new["types"][0]["type"]["fields"]
+            .as_array_mut()
+            .unwrap()
+            .push(json!({ "name": "extra", "type": "bool" }));
+        new["instructions"][0]["accounts"]
+            .as_array_mut()
+            .unwrap()
+            .push(json!({ "name": "system_program" }));
+
+        assert_eq!(
+            changes(base(), new),
+            [
+                "instructions.initialize.accounts.system_program: account added",
+                "accounts.Data.extra: field added",
+            ]
+            .map(|msg| (Severity::Breaking, msg.to_owned()))
+        );
+    }
+
+    #[test]
+    fn resolves_type_aliases() {
+        let mut new = base();
+        new["types"]
+            .as_array_mut()
+            .unwrap()
+            .push(json!({ "name": "Amount", "type": { "kind": "type", "alias": "u64" } }));
+        new["types"][0]["type"]["fields"][0]["type"] = json!({ "defined": { "name": "Amount" } });
+
+        assert!(changes(base(), new).is_empty());
+    }
+}
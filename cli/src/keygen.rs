@@ -1,11 +1,18 @@
 use {
-    crate::{config::ConfigOverride, get_keypair, KeygenCommand},
+    crate::{
+        config::ConfigOverride,
+        get_keypair,
+        keystore::{self, Keystore},
+        signer::signer_from_path,
+        KeygenCommand,
+    },
     anyhow::{anyhow, bail, Result},
     bip39::{Language, Mnemonic, MnemonicType, Seed},
     console::{Key, Term},
     dirs::home_dir,
+    solana_derivation_path::DerivationPath,
     solana_instruction::{AccountMeta, Instruction},
-    solana_keypair::Keypair,
+    solana_keypair::{seed_derivable::keypair_from_seed_and_derivation_path, Keypair},
     solana_pubkey::Pubkey,
     solana_signer::{EncodableKey, Signer},
     solana_transaction::Message,
@@ -13,12 +20,24 @@ use {
         fs,
         io::{self, Write},
         path::{Path, PathBuf},
+        sync::{
+            atomic::{AtomicU64, AtomicUsize, Ordering},
+            mpsc::{self, RecvTimeoutError, Sender},
+        },
+        thread,
+        time::Duration,
     },
 };
 
+/// Characters of base58 encoded pubkeys
+const BASE58_ALPHABET: &str = "123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+/// Number of keypairs each grind thread generates between progress updates
+const GRIND_BATCH_SIZE: u64 = 1024;
+
 /// Secure password input with asterisk visual feedback
 /// - show_spaces: if true, spaces are visible (for seed phrases); if false, all characters are asterisks (for passphrases)
-fn secure_input(prompt: &str, show_spaces: bool) -> Result<String> {
+pub(crate) fn secure_input(prompt: &str, show_spaces: bool) -> Result<String> {
     print!("{}", prompt);
     io::stdout().flush()?;
 
@@ -64,6 +83,216 @@ fn print_step(step: &str) {
     println!("✓ {}", step);
 }
 
+/// Default keypair path of the Solana CLI
+fn default_keypair_path() -> PathBuf {
+    let mut path = home_dir().expect("home directory");
+    path.push(".config");
+    path.push("solana");
+    path.push("id.json");
+    path
+}
+
+/// Prompt for the password of the keystores to write, if `encrypt` is set
+fn keystore_password(encrypt: bool) -> Result<Option<String>> {
+    if !encrypt {
+        return Ok(None);
+    }
+
+    println!("\n🔒 Keystore password");
+    keystore::new_password().map(Some)
+}
+
+/// Write the keypair, as a password-encrypted keystore if a `password` is given
+fn write_keypair(keypair: &Keypair, outfile_path: &Path, password: Option<&str>) -> Result<()> {
+    if let Some(outdir) = outfile_path.parent() {
+        fs::create_dir_all(outdir)?;
+    }
+
+    if let Some(password) = password {
+        print_step("Encrypting keypair");
+        return Keystore::encrypt(keypair, password)?.write(outfile_path);
+    }
+
+    keypair.write_to_file(outfile_path).map_err(|e| {
+        anyhow!(
+            "Failed to write keypair to {}: {}",
+            outfile_path.display(),
+            e
+        )
+    })?;
+
+    // Set restrictive permissions (owner read/write only)
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mut perms = fs::metadata(outfile_path)?.permissions();
+        perms.set_mode(0o600);
+        fs::set_permissions(outfile_path, perms)?;
+    }
+
+    Ok(())
+}
+
+/// Write a converted keypair, replacing the input unless `outfile` is given. The new file is
+/// written next to the output first so that an interrupted write never loses the key.
+fn convert_keypair(
+    input: &Path,
+    outfile: Option<PathBuf>,
+    force: bool,
+    keypair: &Keypair,
+    password: Option<&str>,
+) -> Result<PathBuf> {
+    let outfile_path = outfile.unwrap_or_else(|| input.to_owned());
+    if outfile_path != input && outfile_path.exists() && !force {
+        bail!(
+            "Refusing to overwrite {} without --force flag",
+            outfile_path.display()
+        );
+    }
+
+    let mut tmp_path = outfile_path.clone().into_os_string();
+    tmp_path.push(".tmp");
+    let tmp_path = PathBuf::from(tmp_path);
+    write_keypair(keypair, &tmp_path, password)?;
+    fs::rename(&tmp_path, &outfile_path)?;
+    Ok(outfile_path)
+}
+
+/// Parse an absolute derivation path, e.g. `m/44'/501'/0'/0'`. Every index is hardened, as
+/// ed25519 only supports hardened derivation.
+pub(crate) fn parse_derivation_path(path: &str) -> Result<DerivationPath> {
+    DerivationPath::from_absolute_path_str(path).map_err(|e| anyhow!("{e}"))
+}
+
+/// Derivation paths of the keypairs to derive from a seed, along with their output files.
+/// `None` derives the keypair from the seed directly, like `keygen` always did.
+fn keypair_outfiles(
+    outfile_path: PathBuf,
+    derivation_path: Option<DerivationPath>,
+    accounts: Option<u32>,
+) -> Result<Vec<(Option<DerivationPath>, PathBuf)>> {
+    let Some(accounts) = accounts else {
+        return Ok(vec![(derivation_path, outfile_path)]);
+    };
+    if accounts == 0 {
+        bail!("--accounts must be at least 1");
+    }
+
+    // Increment the account index the way wallets do, e.g. `m/44'/501'/<index>'/0'`
+    let derivation_path =
+        derivation_path.unwrap_or_else(|| DerivationPath::new_bip44(Some(0), Some(0)));
+    let indexes = derivation_path
+        .path()
+        .iter()
+        .map(|index| index.to_u32())
+        .collect::<Vec<_>>();
+    let (first_account, change) = match indexes[..] {
+        [44, 501, account] => (account, None),
+        [44, 501, account, change] => (account, Some(change)),
+        _ => bail!(
+            "--accounts requires a derivation path of the form m/44'/501'/<account>'/<change>', \
+            got {derivation_path:?}"
+        ),
+    };
+
+    (0..accounts)
+        .map(|i| {
+            let account = first_account
+                .checked_add(i)
+                .ok_or_else(|| anyhow!("Account index overflow"))?;
+
+            let stem = outfile_path
+                .file_stem()
+                .unwrap_or_default()
+                .to_string_lossy();
+            let file_name = match outfile_path.extension() {
+                Some(ext) => format!("{stem}-{account}.{}", ext.to_string_lossy()),
+                None => format!("{stem}-{account}"),
+            };
+            Ok((
+                Some(DerivationPath::new_bip44(Some(account), change)),
+                outfile_path.with_file_name(file_name),
+            ))
+        })
+        .collect()
+}
+
+/// Refuse to overwrite any of the output files unless `force` is set
+fn check_overwrite(outfiles: &[(Option<DerivationPath>, PathBuf)], force: bool) -> Result<()> {
+    for (_, outfile_path) in outfiles {
+        if outfile_path.exists() {
+            if !force {
+                bail!(
+                    "Refusing to overwrite {} without --force flag",
+                    outfile_path.display()
+                );
+            }
+            println!(
+                "⚠️  Warning: Overwriting existing keypair at {}",
+                outfile_path.display()
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Derive a keypair from a 64-byte BIP39 seed
+fn derive_keypair(seed: &[u8], derivation_path: Option<DerivationPath>) -> Result<Keypair> {
+    match derivation_path {
+        // Ed25519 keypair derivation: use the first 32 bytes of the seed as the secret key
+        None => {
+            let secret_key_bytes: [u8; 32] = seed[..32].try_into().unwrap();
+            Ok(Keypair::new_from_array(secret_key_bytes))
+        }
+        // SLIP-0010 derivation, as used by `solana-keygen` and most wallets
+        Some(derivation_path) => keypair_from_seed_and_derivation_path(seed, Some(derivation_path))
+            .map_err(|e| anyhow!("Failed to derive keypair: {e}")),
+    }
+}
+
+/// Derive and write the keypairs of `outfiles`
+fn write_derived_keypairs(
+    seed: &[u8],
+    outfiles: Vec<(Option<DerivationPath>, PathBuf)>,
+    encrypt: bool,
+) -> Result<Vec<(Option<DerivationPath>, Keypair)>> {
+    if outfiles.len() > 1 {
+        print_step(&format!("Deriving {} keypairs from seed", outfiles.len()));
+    } else {
+        print_step("Deriving keypair from seed");
+    }
+    let keypairs = outfiles
+        .into_iter()
+        .map(|(derivation_path, outfile_path)| {
+            let keypair = derive_keypair(seed, derivation_path.clone())?;
+            Ok((derivation_path, keypair, outfile_path))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let password = keystore_password(encrypt)?;
+    keypairs
+        .into_iter()
+        .map(|(derivation_path, keypair, outfile_path)| {
+            write_keypair(&keypair, &outfile_path, password.as_deref())?;
+            print_step(&format!("Keypair saved to {}", outfile_path.display()));
+            Ok((derivation_path, keypair))
+        })
+        .collect()
+}
+
+/// Print the pubkeys of derived keypairs, along with their derivation paths
+fn print_pubkeys(keypairs: &[(Option<DerivationPath>, Keypair)]) {
+    for (derivation_path, keypair) in keypairs {
+        match derivation_path {
+            Some(derivation_path) => {
+                println!("📋 Public Key: {} ({derivation_path:?})", keypair.pubkey())
+            }
+            None => println!("📋 Public Key: {}", keypair.pubkey()),
+        }
+    }
+}
+
 pub fn keygen(_cfg_override: &ConfigOverride, cmd: KeygenCommand) -> Result<()> {
     match cmd {
         KeygenCommand::New {
@@ -72,47 +301,85 @@ pub fn keygen(_cfg_override: &ConfigOverride, cmd: KeygenCommand) -> Result<()>
             no_passphrase,
             silent,
             word_count,
-        } => keygen_new(outfile, force, no_passphrase, silent, word_count),
+            derivation_path,
+            accounts,
+            encrypt,
+        } => keygen_new(
+            outfile,
+            force,
+            no_passphrase,
+            silent,
+            word_count,
+            derivation_path,
+            accounts,
+            encrypt,
+        ),
         KeygenCommand::Pubkey { keypair } => keygen_pubkey(keypair),
         KeygenCommand::Recover {
             outfile,
             force,
             skip_seed_phrase_validation,
             no_passphrase,
-        } => keygen_recover(outfile, force, skip_seed_phrase_validation, no_passphrase),
+            derivation_path,
+            accounts,
+            encrypt,
+        } => keygen_recover(
+            outfile,
+            force,
+            skip_seed_phrase_validation,
+            no_passphrase,
+            derivation_path,
+            accounts,
+            encrypt,
+        ),
+        KeygenCommand::Encrypt {
+            keypair,
+            outfile,
+            force,
+        } => keygen_encrypt(keypair, outfile, force),
+        KeygenCommand::Decrypt {
+            keystore,
+            outfile,
+            force,
+        } => keygen_decrypt(keystore, outfile, force),
+        KeygenCommand::Grind {
+            starts_with,
+            ends_with,
+            ignore_case,
+            count,
+            threads,
+            outdir,
+            encrypt,
+        } => keygen_grind(
+            starts_with,
+            ends_with,
+            ignore_case,
+            count,
+            threads,
+            outdir,
+            encrypt,
+        ),
         KeygenCommand::Verify { pubkey, keypair } => keygen_verify(pubkey, keypair),
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn keygen_new(
     outfile: Option<PathBuf>,
     force: bool,
     no_passphrase: bool,
     silent: bool,
     word_count: usize,
+    derivation_path: Option<DerivationPath>,
+    accounts: Option<u32>,
+    encrypt: bool,
 ) -> Result<()> {
-    // Determine output file path
-    let outfile_path = outfile.unwrap_or_else(|| {
-        let mut path = home_dir().expect("home directory");
-        path.push(".config");
-        path.push("solana");
-        path.push("id.json");
-        path
-    });
+    // Determine output file paths
+    let outfile_path = outfile.unwrap_or_else(default_keypair_path);
+    let outfiles = keypair_outfiles(outfile_path, derivation_path, accounts)?;
 
     // Check for overwrite
-    if Path::new(&outfile_path).exists() {
-        if !force {
-            bail!(
-                "Refusing to overwrite {} without --force flag",
-                outfile_path.display()
-            );
-        }
-        println!(
-            "⚠️  Warning: Overwriting existing keypair at {}",
-            outfile_path.display()
-        );
-    }
+    check_overwrite(&outfiles, force)?;
 
     println!("\n🔑 Generating a new keypair");
     println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
@@ -147,37 +414,9 @@ fn keygen_new(
         pass
     };
 
-    // Generate seed from mnemonic and passphrase
-    print_step("Deriving keypair from seed");
+    // Generate seed from mnemonic and passphrase, and derive the keypairs from it
     let seed = Seed::new(&mnemonic, &passphrase);
-
-    // Create keypair from seed (use first 32 bytes as secret key)
-    // Ed25519 keypair derivation: use the first 32 bytes of the seed as the secret key
-    let secret_key_bytes: [u8; 32] = seed.as_bytes()[0..32].try_into().unwrap();
-    let keypair = Keypair::new_from_array(secret_key_bytes);
-
-    // Write keypair to file
-    if let Some(outdir) = Path::new(&outfile_path).parent() {
-        fs::create_dir_all(outdir)?;
-    }
-    keypair.write_to_file(&outfile_path).map_err(|e| {
-        anyhow!(
-            "Failed to write keypair to {}: {}",
-            outfile_path.display(),
-            e
-        )
-    })?;
-
-    // Set restrictive permissions (owner read/write only)
-    #[cfg(unix)]
-    {
-        use std::os::unix::fs::PermissionsExt;
-        let mut perms = fs::metadata(&outfile_path)?.permissions();
-        perms.set_mode(0o600);
-        fs::set_permissions(&outfile_path, perms)?;
-    }
-
-    print_step(&format!("Keypair saved to {}", outfile_path.display()));
+    let keypairs = write_derived_keypairs(seed.as_bytes(), outfiles, encrypt)?;
 
     let phrase: &str = mnemonic.phrase();
     let divider = "━".repeat(phrase.len().max(60));
@@ -190,7 +429,7 @@ fn keygen_new(
     // Always show the seed phrase - it's critical for recovery
     println!("\n{}", divider);
     if !silent {
-        println!("📋 Public Key: {}", keypair.pubkey());
+        print_pubkeys(&keypairs);
         println!("{}", divider);
     }
     println!(
@@ -204,16 +443,62 @@ fn keygen_new(
 }
 
 fn keygen_pubkey(keypair_path: Option<PathBuf>) -> Result<()> {
-    let path = keypair_path.unwrap_or_else(|| {
-        let mut p = home_dir().expect("home directory");
-        p.push(".config");
-        p.push("solana");
-        p.push("id.json");
-        p
-    });
+    let path = keypair_path.unwrap_or_else(default_keypair_path);
 
+    // Keystores store their pubkey in plaintext, no need to unlock them
+    println!("{}", keystore::read_pubkey(&path)?);
+    Ok(())
+}
+
+fn keygen_encrypt(
+    keypair_path: Option<PathBuf>,
+    outfile: Option<PathBuf>,
+    force: bool,
+) -> Result<()> {
+    let path = keypair_path.unwrap_or_else(default_keypair_path);
+    if keystore::is_keystore(&path) {
+        bail!("{} is already encrypted", path.display());
+    }
+
+    println!("\n🔒 Encrypt keypair");
+    println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
     let keypair = get_keypair(&path)?;
-    println!("{}", keypair.pubkey());
+    print_step(&format!("Loaded keypair {}", keypair.pubkey()));
+
+    let password = keystore_password(true)?;
+    let outfile_path = convert_keypair(&path, outfile, force, &keypair, password.as_deref())?;
+    print_step(&format!("Keystore saved to {}", outfile_path.display()));
+    if outfile_path != path {
+        println!(
+            "\n⚠️  The plaintext keypair at {} was left in place",
+            path.display()
+        );
+    }
+
+    Ok(())
+}
+
+fn keygen_decrypt(
+    keystore_path: Option<PathBuf>,
+    outfile: Option<PathBuf>,
+    force: bool,
+) -> Result<()> {
+    let path = keystore_path.unwrap_or_else(default_keypair_path);
+    let Some(keystore) = Keystore::read(&path)? else {
+        bail!("{} is not an encrypted keystore", path.display());
+    };
+
+    println!("\n🔓 Decrypt keystore");
+    println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
+    let keypair = keystore::unlock(&path, &keystore)?;
+    print_step(&format!("Unlocked keypair {}", keypair.pubkey()));
+
+    let outfile_path = convert_keypair(&path, outfile, force, &keypair, None)?;
+    print_step(&format!(
+        "Plaintext keypair saved to {}",
+        outfile_path.display()
+    ));
+
     Ok(())
 }
 
@@ -222,32 +507,19 @@ fn keygen_recover(
     force: bool,
     skip_seed_phrase_validation: bool,
     no_passphrase: bool,
+    derivation_path: Option<DerivationPath>,
+    accounts: Option<u32>,
+    encrypt: bool,
 ) -> Result<()> {
     println!("\n🔓 Recover keypair from seed phrase");
     println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
 
-    // Determine output file path
-    let outfile_path = outfile.unwrap_or_else(|| {
-        let mut path = home_dir().expect("home directory");
-        path.push(".config");
-        path.push("solana");
-        path.push("id.json");
-        path
-    });
+    // Determine output file paths
+    let outfile_path = outfile.unwrap_or_else(default_keypair_path);
+    let outfiles = keypair_outfiles(outfile_path, derivation_path, accounts)?;
 
     // Check for overwrite
-    if Path::new(&outfile_path).exists() {
-        if !force {
-            bail!(
-                "Refusing to overwrite {} without --force flag",
-                outfile_path.display()
-            );
-        }
-        println!(
-            "⚠️  Warning: Overwriting existing keypair at {}",
-            outfile_path.display()
-        );
-    }
+    check_overwrite(&outfiles, force)?;
 
     // Prompt for seed phrase (secure input with spaces visible)
     println!("\n🌱 Enter Recovery Seed Phrase");
@@ -274,7 +546,6 @@ fn keygen_recover(
     //     checksum entirely and run PBKDF2 directly on the raw phrase bytes.
     //     Needed for phrases produced outside the BIP-39 spec (e.g. some
     //     Ledger recovery words) and for test fixtures.
-    print_step("Deriving keypair from seed");
     let mut seed_bytes = [0u8; 64];
     if skip_seed_phrase_validation {
         let salt = format!("mnemonic{passphrase}");
@@ -291,54 +562,191 @@ fn keygen_recover(
         seed_bytes.copy_from_slice(Seed::new(&mnemonic, &passphrase).as_bytes());
     }
 
-    // First 32 bytes of the PBKDF2 output become the ed25519 secret key.
-    let secret_key_bytes: [u8; 32] = seed_bytes[..32].try_into().unwrap();
-    let keypair = Keypair::new_from_array(secret_key_bytes);
+    // Without a derivation path, the first 32 bytes of the PBKDF2 output become the ed25519
+    // secret key.
+    let keypairs = write_derived_keypairs(&seed_bytes, outfiles, encrypt)?;
 
-    // Write keypair to file
-    if let Some(outdir) = Path::new(&outfile_path).parent() {
-        fs::create_dir_all(outdir)?;
+    println!("\n━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
+    print_pubkeys(&keypairs);
+    println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━\n");
+
+    Ok(())
+}
+
+/// Prefix and/or suffix to grind pubkeys for
+struct GrindPattern {
+    starts_with: String,
+    ends_with: String,
+    ignore_case: bool,
+}
+
+impl GrindPattern {
+    fn new(
+        starts_with: Option<String>,
+        ends_with: Option<String>,
+        ignore_case: bool,
+    ) -> Result<Self> {
+        let starts_with = starts_with.unwrap_or_default();
+        let ends_with = ends_with.unwrap_or_default();
+        if starts_with.is_empty() && ends_with.is_empty() {
+            bail!("Provide --starts-with and/or --ends-with");
+        }
+
+        let is_base58 = |c: char| {
+            BASE58_ALPHABET.contains(c)
+                || (ignore_case
+                    && (BASE58_ALPHABET.contains(c.to_ascii_lowercase())
+                        || BASE58_ALPHABET.contains(c.to_ascii_uppercase())))
+        };
+        for (flag, affix) in [("--starts-with", &starts_with), ("--ends-with", &ends_with)] {
+            if let Some(c) = affix.chars().find(|c| !is_base58(*c)) {
+                bail!("{flag} contains `{c}`, which never appears in base58 pubkeys");
+            }
+        }
+
+        let normalize = |affix: String| {
+            if ignore_case {
+                affix.to_lowercase()
+            } else {
+                affix
+            }
+        };
+        Ok(Self {
+            starts_with: normalize(starts_with),
+            ends_with: normalize(ends_with),
+            ignore_case,
+        })
     }
-    keypair.write_to_file(&outfile_path).map_err(|e| {
-        anyhow!(
-            "Failed to write keypair to {}: {}",
-            outfile_path.display(),
-            e
-        )
-    })?;
 
-    // Set restrictive permissions (owner read/write only)
-    #[cfg(unix)]
-    {
-        use std::os::unix::fs::PermissionsExt;
-        let mut perms = fs::metadata(&outfile_path)?.permissions();
-        perms.set_mode(0o600);
-        fs::set_permissions(&outfile_path, perms)?;
+    fn matches(&self, pubkey: &Pubkey) -> bool {
+        let mut pubkey = pubkey.to_string();
+        if self.ignore_case {
+            pubkey.make_ascii_lowercase();
+        }
+        pubkey.starts_with(&self.starts_with) && pubkey.ends_with(&self.ends_with)
     }
 
-    print_step(&format!("Keypair recovered to {}", outfile_path.display()));
+    fn describe(&self) -> String {
+        let mut description = match (self.starts_with.is_empty(), self.ends_with.is_empty()) {
+            (false, true) => format!("starting with `{}`", self.starts_with),
+            (true, false) => format!("ending with `{}`", self.ends_with),
+            _ => format!(
+                "starting with `{}` and ending with `{}`",
+                self.starts_with, self.ends_with
+            ),
+        };
+        if self.ignore_case {
+            description.push_str(" (ignoring case)");
+        }
+        description
+    }
+}
 
-    println!("\n━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
-    println!("📋 Public Key: {}", keypair.pubkey());
-    println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━\n");
+/// Generate keypairs until `count` matches are found in total, sending the matches to `tx`
+fn grind_keypairs(
+    pattern: &GrindPattern,
+    count: usize,
+    found: &AtomicUsize,
+    attempts: &AtomicU64,
+    tx: Sender<Keypair>,
+) {
+    while found.load(Ordering::Relaxed) < count {
+        for _ in 0..GRIND_BATCH_SIZE {
+            let keypair = Keypair::new();
+            if pattern.matches(&keypair.pubkey())
+                && found.fetch_add(1, Ordering::Relaxed) < count
+                && tx.send(keypair).is_err()
+            {
+                return;
+            }
+        }
+        attempts.fetch_add(GRIND_BATCH_SIZE, Ordering::Relaxed);
+    }
+}
 
-    Ok(())
+fn keygen_grind(
+    starts_with: Option<String>,
+    ends_with: Option<String>,
+    ignore_case: bool,
+    count: usize,
+    threads: Option<usize>,
+    outdir: Option<PathBuf>,
+    encrypt: bool,
+) -> Result<()> {
+    let pattern = GrindPattern::new(starts_with, ends_with, ignore_case)?;
+    if count == 0 {
+        bail!("--count must be at least 1");
+    }
+    let threads = match threads {
+        Some(0) => bail!("--threads must be at least 1"),
+        Some(threads) => threads,
+        None => thread::available_parallelism().map_or(1, |threads| threads.get()),
+    };
+    let outdir = match outdir {
+        Some(outdir) => outdir,
+        None => std::env::current_dir()?,
+    };
+    fs::create_dir_all(&outdir)?;
+
+    println!("\n⛏️  Grinding keypairs");
+    println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
+    print_step(&format!(
+        "Searching for {count} pubkey(s) {} with {threads} thread(s)",
+        pattern.describe()
+    ));
+
+    // Ask for the password up front, rather than in the middle of the search
+    let password = keystore_password(encrypt)?;
+
+    let found = AtomicUsize::new(0);
+    let attempts = AtomicU64::new(0);
+    let (tx, rx) = mpsc::channel();
+    thread::scope(|scope| {
+        for _ in 0..threads {
+            let (pattern, found, attempts, tx) = (&pattern, &found, &attempts, tx.clone());
+            scope.spawn(move || grind_keypairs(pattern, count, found, attempts, tx));
+        }
+        drop(tx);
+
+        let write_matches = || -> Result<()> {
+            let mut written = 0;
+            while written < count {
+                let keypair = match rx.recv_timeout(Duration::from_secs(10)) {
+                    Ok(keypair) => keypair,
+                    Err(RecvTimeoutError::Timeout) => {
+                        println!("  Searched {} keypairs", attempts.load(Ordering::Relaxed));
+                        continue;
+                    }
+                    Err(RecvTimeoutError::Disconnected) => bail!("Grind threads exited"),
+                };
+
+                let outfile_path = outdir.join(format!("{}.json", keypair.pubkey()));
+                write_keypair(&keypair, &outfile_path, password.as_deref())?;
+                print_step(&format!(
+                    "Found {}, saved to {}",
+                    keypair.pubkey(),
+                    outfile_path.display()
+                ));
+                written += 1;
+            }
+            Ok(())
+        };
+        let result = write_matches();
+
+        // Stop the remaining threads, also when writing a match failed
+        found.store(count, Ordering::Relaxed);
+        result
+    })
 }
 
 fn keygen_verify(pubkey: Pubkey, keypair_path: Option<PathBuf>) -> Result<()> {
     println!("\n🔍 Verifying keypair");
     println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
 
-    let path = keypair_path.unwrap_or_else(|| {
-        let mut p = home_dir().expect("home directory");
-        p.push(".config");
-        p.push("solana");
-        p.push("id.json");
-        p
-    });
+    let path = keypair_path.unwrap_or_else(default_keypair_path);
 
     print_step(&format!("Loading keypair from {}", path.display()));
-    let keypair = get_keypair(&path)?;
+    let signer = signer_from_path(&path.to_string_lossy(), "keypair")?;
 
     // Create a simple message to sign
     print_step("Creating test message");
@@ -346,14 +754,14 @@ fn keygen_verify(pubkey: Pubkey, keypair_path: Option<PathBuf>) -> Result<()> {
         &[Instruction::new_with_bincode(
             Pubkey::default(),
             &0,
-            vec![AccountMeta::new(keypair.pubkey(), true)],
+            vec![AccountMeta::new(signer.pubkey(), true)],
         )],
-        Some(&keypair.pubkey()),
+        Some(&signer.pubkey()),
     );
 
     // Sign the message
     print_step("Signing message with keypair");
-    let signature = keypair.sign_message(message.serialize().as_slice());
+    let signature = signer.try_sign_message(message.serialize().as_slice())?;
 
     // Verify the signature
     print_step("Verifying signature");
@@ -392,7 +800,17 @@ mod tests {
         let outfile_path = tmp_outfile_path(&outfile_dir, "test-keypair.json");
 
         // Test: successful keypair generation with default word count (12)
-        keygen_new(Some(outfile_path.clone()), false, true, true, 12).unwrap();
+        keygen_new(
+            Some(outfile_path.clone()),
+            false,
+            true,
+            true,
+            12,
+            None,
+            None,
+            false,
+        )
+        .unwrap();
 
         // Verify the keypair file was created
         assert!(Path::new(&outfile_path).exists());
@@ -402,7 +820,16 @@ mod tests {
         assert_ne!(keypair.pubkey(), Pubkey::default());
 
         // Test: refuse to overwrite without --force
-        let result = keygen_new(Some(outfile_path.clone()), false, true, true, 12);
+        let result = keygen_new(
+            Some(outfile_path.clone()),
+            false,
+            true,
+            true,
+            12,
+            None,
+            None,
+            false,
+        );
         assert!(result.is_err());
         assert!(result
             .unwrap_err()
@@ -410,7 +837,17 @@ mod tests {
             .contains("Refusing to overwrite"));
 
         // Test: overwrite with --force flag
-        keygen_new(Some(outfile_path.clone()), true, true, true, 12).unwrap();
+        keygen_new(
+            Some(outfile_path.clone()),
+            true,
+            true,
+            true,
+            12,
+            None,
+            None,
+            false,
+        )
+        .unwrap();
         assert!(Path::new(&outfile_path).exists());
     }
 
@@ -429,6 +866,52 @@ mod tests {
         assert!(result.is_ok());
     }
 
+    #[test]
+    fn test_convert_keypair() {
+        let keypair_dir = tempdir().unwrap();
+        let keypair_path = tmp_outfile_path(&keypair_dir, "test-keypair.json");
+        let outfile_path = tmp_outfile_path(&keypair_dir, "converted.json");
+        let keypair = Keypair::new();
+        keypair.write_to_file(&keypair_path).unwrap();
+
+        // Test: writing to a new file leaves the input in place
+        let path = convert_keypair(
+            &keypair_path,
+            Some(outfile_path.clone()),
+            false,
+            &keypair,
+            None,
+        )
+        .unwrap();
+        assert_eq!(path, outfile_path);
+        assert_eq!(
+            read_keypair_file(&outfile_path).unwrap().pubkey(),
+            keypair.pubkey()
+        );
+        assert!(keypair_path.exists());
+
+        // Test: refuse to overwrite another file without --force
+        let result = convert_keypair(
+            &keypair_path,
+            Some(outfile_path.clone()),
+            false,
+            &keypair,
+            None,
+        );
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("Refusing to overwrite"));
+
+        // Test: replacing the input doesn't need --force or leave a temporary file behind
+        convert_keypair(&keypair_path, None, false, &keypair, None).unwrap();
+        assert_eq!(
+            read_keypair_file(&keypair_path).unwrap().pubkey(),
+            keypair.pubkey()
+        );
+        assert_eq!(fs::read_dir(keypair_dir.path()).unwrap().count(), 2);
+    }
+
     #[test]
     fn test_keygen_verify() {
         let keypair_dir = tempdir().unwrap();
@@ -453,6 +936,19 @@ mod tests {
         )));
     }
 
+    #[test]
+    fn test_keygen_verify_keystore() {
+        let keypair_dir = tempdir().unwrap();
+        let keystore_path = tmp_outfile_path(&keypair_dir, "test-keystore.json");
+        let keypair = Keypair::new();
+        write_keypair(&keypair, &keystore_path, Some("password")).unwrap();
+        assert!(keystore::is_keystore(&keystore_path));
+        keystore::remember_unlocked(&keystore_path, &keypair);
+
+        assert!(keygen_verify(keypair.pubkey(), Some(keystore_path.clone())).is_ok());
+        assert!(keygen_verify(Pubkey::new_unique(), Some(keystore_path)).is_err());
+    }
+
     #[test]
     fn test_keypair_from_seed_consistency() {
         // Test that the same seed phrase produces the same keypair
@@ -509,7 +1005,16 @@ mod tests {
                 tmp_outfile_path(&outfile_dir, &format!("test-keypair-{}.json", word_count));
 
             // Test: successful keypair generation with different word counts
-            let result = keygen_new(Some(outfile_path.clone()), false, true, true, word_count);
+            let result = keygen_new(
+                Some(outfile_path.clone()),
+                false,
+                true,
+                true,
+                word_count,
+                None,
+                None,
+                false,
+            );
             assert!(
                 result.is_ok(),
                 "Failed to generate keypair with {} words",
@@ -531,11 +1036,176 @@ mod tests {
         let outfile_path = tmp_outfile_path(&outfile_dir, "test-invalid-wordcount.json");
 
         // Test: invalid word count should fail
-        let result = keygen_new(Some(outfile_path), false, true, true, 9);
+        let result = keygen_new(Some(outfile_path), false, true, true, 9, None, None, false);
         assert!(result.is_err());
         assert!(result
             .unwrap_err()
             .to_string()
             .contains("Invalid word count"));
     }
+
+    #[test]
+    fn test_keypair_outfiles() {
+        let outfile_path = PathBuf::from("keys/wallet.json");
+
+        // Test: a single keypair is written to the outfile, with or without derivation path
+        let outfiles = keypair_outfiles(outfile_path.clone(), None, None).unwrap();
+        assert_eq!(outfiles, vec![(None, outfile_path.clone())]);
+
+        // Test: accounts default to the path used by wallets
+        let outfiles = keypair_outfiles(outfile_path.clone(), None, Some(3)).unwrap();
+        let outfiles = outfiles
+            .iter()
+            .map(|(path, outfile)| (format!("{:?}", path.as_ref().unwrap()), outfile.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            outfiles,
+            vec![
+                ("m/44'/501'/0'/0'".into(), "keys/wallet-0.json".into()),
+                ("m/44'/501'/1'/0'".into(), "keys/wallet-1.json".into()),
+                ("m/44'/501'/2'/0'".into(), "keys/wallet-2.json".into()),
+            ]
+        );
+
+        // Test: accounts start at the account index of the given path
+        let derivation_path = parse_derivation_path("m/44'/501'/5'").unwrap();
+        let outfiles = keypair_outfiles(outfile_path.clone(), Some(derivation_path), Some(2))
+            .unwrap()
+            .into_iter()
+            .map(|(path, _)| format!("{:?}", path.unwrap()))
+            .collect::<Vec<_>>();
+        assert_eq!(outfiles, ["m/44'/501'/5'", "m/44'/501'/6'"]);
+
+        // Test: accounts of non-Solana paths are rejected
+        let derivation_path = parse_derivation_path("m/44'/60'/0'/0'").unwrap();
+        assert!(keypair_outfiles(outfile_path, Some(derivation_path), Some(2)).is_err());
+    }
+
+    #[test]
+    fn test_derive_keypair() {
+        // SLIP-0010 ed25519 test vector 1
+        let seed = (0..16).collect::<Vec<u8>>();
+        let keypair = derive_keypair(&seed, Some(parse_derivation_path("m/0'").unwrap())).unwrap();
+        assert_eq!(
+            keypair
+                .secret_bytes()
+                .iter()
+                .map(|b| format!("{b:02x}"))
+                .collect::<String>(),
+            "68e0fe46dfb67e368c75379acec591dad19df3cde26e63b93a8e704f1dade7a3"
+        );
+
+        // Test: the first account of a mnemonic matches the one of common wallets
+        let mnemonic = Mnemonic::from_phrase(
+            "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon \
+             abandon about",
+            Language::English,
+        )
+        .unwrap();
+        let seed = Seed::new(&mnemonic, "");
+        let derivation_path = parse_derivation_path("m/44'/501'/0'/0'").unwrap();
+        let keypair = derive_keypair(seed.as_bytes(), Some(derivation_path)).unwrap();
+        assert_eq!(
+            keypair.pubkey().to_string(),
+            "HAgk14JpMQLgt6rVgv7cBQFJWFto5Dqxi472uT3DKpqk"
+        );
+
+        // Test: without derivation path, the seed is the secret key
+        let seed = [7; 64];
+        let keypair = derive_keypair(&seed, None).unwrap();
+        assert_eq!(keypair.secret_bytes(), &[7; 32]);
+        let derived = derive_keypair(&seed, Some(DerivationPath::new_bip44(Some(0), Some(0))));
+        assert_ne!(derived.unwrap().pubkey(), keypair.pubkey());
+    }
+
+    #[test]
+    fn test_keygen_new_accounts() {
+        let outfile_dir = tempdir().unwrap();
+        let outfile_path = tmp_outfile_path(&outfile_dir, "wallet.json");
+
+        keygen_new(
+            Some(outfile_path.clone()),
+            false,
+            true,
+            true,
+            12,
+            None,
+            Some(3),
+            false,
+        )
+        .unwrap();
+
+        // Verify a keypair was written per account
+        assert!(!outfile_path.exists());
+        let pubkeys = (0..3)
+            .map(|i| {
+                let path = tmp_outfile_path(&outfile_dir, &format!("wallet-{i}.json"));
+                read_keypair_file(&path).unwrap().pubkey()
+            })
+            .collect::<std::collections::HashSet<_>>();
+        assert_eq!(pubkeys.len(), 3);
+
+        // Test: refuse to overwrite any account without --force
+        let result = keygen_new(
+            Some(outfile_path),
+            false,
+            true,
+            true,
+            12,
+            None,
+            Some(1),
+            false,
+        );
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("Refusing to overwrite"));
+    }
+
+    #[test]
+    fn test_grind_pattern() {
+        // Test: characters that never appear in pubkeys are rejected
+        assert!(GrindPattern::new(None, None, false).is_err());
+        assert!(GrindPattern::new(Some("ab0".into()), None, false).is_err());
+        assert!(GrindPattern::new(None, Some("Il".into()), false).is_err());
+        assert!(GrindPattern::new(None, Some("Il".into()), true).is_ok());
+
+        let pubkey = "AnchorXyz1111111111111111111111111111111111"
+            .parse::<Pubkey>()
+            .unwrap();
+        let pattern = GrindPattern::new(Some("Anchor".into()), Some("111".into()), false).unwrap();
+        assert!(pattern.matches(&pubkey));
+        let pattern = GrindPattern::new(Some("anchor".into()), None, false).unwrap();
+        assert!(!pattern.matches(&pubkey));
+        let pattern = GrindPattern::new(Some("anchorx".into()), None, true).unwrap();
+        assert!(pattern.matches(&pubkey));
+    }
+
+    #[test]
+    fn test_keygen_grind() {
+        let outdir = tempdir().unwrap();
+
+        keygen_grind(
+            Some("A".into()),
+            None,
+            false,
+            2,
+            Some(2),
+            Some(outdir.path().to_owned()),
+            false,
+        )
+        .unwrap();
+
+        // Verify exactly the requested keypairs were written as `<pubkey>.json`
+        let paths = fs::read_dir(outdir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect::<Vec<_>>();
+        assert_eq!(paths.len(), 2);
+        for path in paths {
+            let pubkey = read_keypair_file(&path).unwrap().pubkey().to_string();
+            assert!(pubkey.starts_with('A'));
+            assert_eq!(path.file_name().unwrap(), format!("{pubkey}.json").as_str());
+        }
+    }
 }
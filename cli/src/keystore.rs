@@ -0,0 +1,351 @@
+//! Password-encrypted keystores, written by `anchor keygen --encrypt`.
+//!
+//! A keystore is a JSON file holding the 64 keypair bytes encrypted with AES-256-GCM, under a
+//! key derived from the password with scrypt. The pubkey is stored in plaintext and
+//! authenticated as associated data, so it can be displayed without the password:
+//!
+//! ```json
+//! {
+//!   "version": 1,
+//!   "pubkey": "<base58>",
+//!   "kdf": { "name": "scrypt", "log-n": 17, "r": 8, "p": 1, "salt": "<base64>" },
+//!   "cipher": { "name": "aes-256-gcm", "nonce": "<base64>" },
+//!   "ciphertext": "<base64>"
+//! }
+//! ```
+//!
+//! Everywhere the CLI reads a keypair file to sign, a keystore is unlocked with a password
+//! prompt instead, once per process.
+
+use {
+    crate::keygen::secure_input,
+    aes_gcm::{
+        aead::{rand_core::RngCore, Aead, OsRng, Payload},
+        AeadCore, Aes256Gcm, KeyInit, Nonce,
+    },
+    anyhow::{anyhow, bail, Context, Result},
+    base64::{engine::general_purpose::STANDARD, Engine},
+    serde::{Deserialize, Serialize},
+    solana_keypair::Keypair,
+    solana_pubkey::Pubkey,
+    solana_signer::Signer,
+    std::{
+        collections::BTreeMap,
+        fs,
+        io::Write,
+        path::{Path, PathBuf},
+        sync::Mutex,
+    },
+};
+
+const KEYSTORE_VERSION: u8 = 1;
+const SALT_LEN: usize = 32;
+const PASSWORD_ATTEMPTS: usize = 3;
+
+/// Keystores unlocked by this process, so that each password is only asked once.
+static UNLOCKED: Mutex<BTreeMap<PathBuf, Keypair>> = Mutex::new(BTreeMap::new());
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Keystore {
+    version: u8,
+    pubkey: String,
+    kdf: Kdf,
+    cipher: Cipher,
+    ciphertext: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "name", rename_all = "kebab-case")]
+enum Kdf {
+    #[serde(rename_all = "kebab-case")]
+    Scrypt {
+        log_n: u8,
+        r: u32,
+        p: u32,
+        salt: String,
+    },
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "name")]
+enum Cipher {
+    #[serde(rename = "aes-256-gcm")]
+    Aes256Gcm { nonce: String },
+}
+
+impl Kdf {
+    fn derive_key(&self, password: &str) -> Result<[u8; 32]> {
+        match self {
+            Self::Scrypt { log_n, r, p, salt } => {
+                let params = scrypt::Params::new(*log_n, *r, *p, 32)
+                    .map_err(|e| anyhow!("Invalid scrypt parameters: {e}"))?;
+                let mut key = [0; 32];
+                scrypt::scrypt(
+                    password.as_bytes(),
+                    &STANDARD.decode(salt)?,
+                    &params,
+                    &mut key,
+                )
+                .map_err(|e| anyhow!("Failed to derive the keystore key: {e}"))?;
+                Ok(key)
+            }
+        }
+    }
+}
+
+impl Keystore {
+    /// Encrypt the keypair with the recommended scrypt parameters.
+    pub fn encrypt(keypair: &Keypair, password: &str) -> Result<Self> {
+        Self::encrypt_with(keypair, password, scrypt::Params::RECOMMENDED_LOG_N)
+    }
+
+    fn encrypt_with(keypair: &Keypair, password: &str, log_n: u8) -> Result<Self> {
+        let mut salt = [0; SALT_LEN];
+        OsRng.fill_bytes(&mut salt);
+        let kdf = Kdf::Scrypt {
+            log_n,
+            r: scrypt::Params::RECOMMENDED_R,
+            p: scrypt::Params::RECOMMENDED_P,
+            salt: STANDARD.encode(salt),
+        };
+
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let pubkey = keypair.pubkey();
+        let ciphertext = Aes256Gcm::new(&kdf.derive_key(password)?.into())
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: &keypair.to_bytes(),
+                    aad: pubkey.as_ref(),
+                },
+            )
+            .map_err(|_| anyhow!("Failed to encrypt the keypair"))?;
+
+        Ok(Self {
+            version: KEYSTORE_VERSION,
+            pubkey: pubkey.to_string(),
+            kdf,
+            cipher: Cipher::Aes256Gcm {
+                nonce: STANDARD.encode(nonce),
+            },
+            ciphertext: STANDARD.encode(ciphertext),
+        })
+    }
+
+    pub fn decrypt(&self, password: &str) -> Result<Keypair> {
+        if self.version != KEYSTORE_VERSION {
+            bail!("Unsupported keystore version {}", self.version);
+        }
+
+        let pubkey = self.pubkey()?;
+        let Cipher::Aes256Gcm { nonce } = &self.cipher;
+        let nonce = STANDARD.decode(nonce)?;
+        if nonce.len() != 12 {
+            bail!("Invalid keystore nonce");
+        }
+        let bytes = Aes256Gcm::new(&self.kdf.derive_key(password)?.into())
+            .decrypt(
+                Nonce::from_slice(&nonce),
+                Payload {
+                    msg: &STANDARD.decode(&self.ciphertext)?,
+                    aad: pubkey.as_ref(),
+                },
+            )
+            .map_err(|_| anyhow!("Wrong password"))?;
+
+        let keypair = Keypair::try_from(bytes.as_slice())
+            .map_err(|e| anyhow!("Invalid keypair in keystore: {e}"))?;
+        if keypair.pubkey() != pubkey {
+            bail!("Keystore keypair does not match its pubkey {pubkey}");
+        }
+        Ok(keypair)
+    }
+
+    pub fn pubkey(&self) -> Result<Pubkey> {
+        self.pubkey
+            .parse()
+            .map_err(|e| anyhow!("Invalid keystore pubkey: {e}"))
+    }
+
+    /// Read the keystore at `path`, or `None` if it's not a keystore, e.g. a plaintext keypair
+    /// or a missing file.
+    pub fn read(path: &Path) -> Result<Option<Self>> {
+        let Ok(content) = fs::read_to_string(path) else {
+            return Ok(None);
+        };
+        if !content.trim_start().starts_with('{') {
+            return Ok(None);
+        }
+        serde_json::from_str(&content)
+            .map(Some)
+            .with_context(|| format!("Invalid keystore {}", path.display()))
+    }
+
+    /// Write the keystore readable by the owner only.
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let content = serde_json::to_string_pretty(self)?;
+
+        let mut opts = fs::OpenOptions::new();
+        opts.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            opts.mode(0o600);
+        }
+        let mut file = opts
+            .open(path)
+            .with_context(|| format!("Failed to create keystore {}", path.display()))?;
+        // The mode only applies to new files, an existing one is restricted before writing to it
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            file.set_permissions(fs::Permissions::from_mode(0o600))?;
+        }
+        file.write_all(content.as_bytes())
+            .with_context(|| format!("Failed to write keystore {}", path.display()))?;
+
+        Ok(())
+    }
+}
+
+/// Whether the file at `path` is a keystore rather than a plaintext keypair.
+pub fn is_keystore(path: &Path) -> bool {
+    Keystore::read(path).is_ok_and(|keystore| keystore.is_some())
+}
+
+/// Unlock the keystore at `path`, prompting for its password unless it's already unlocked.
+pub fn unlock(path: &Path, keystore: &Keystore) -> Result<Keypair> {
+    let key = path.canonicalize().unwrap_or_else(|_| path.to_owned());
+    let mut unlocked = UNLOCKED
+        .lock()
+        .map_err(|_| anyhow!("Keystore cache poisoned"))?;
+    if let Some(keypair) = unlocked.get(&key) {
+        return Ok(keypair.insecure_clone());
+    }
+
+    let pubkey = keystore.pubkey()?;
+    let mut attempt = 1;
+    loop {
+        let password = secure_input(
+            &format!("Password for {pubkey} ({}): ", path.display()),
+            false,
+        )?;
+        match keystore.decrypt(&password) {
+            Ok(keypair) => {
+                unlocked.insert(key, keypair.insecure_clone());
+                return Ok(keypair);
+            }
+            Err(e) if attempt < PASSWORD_ATTEMPTS => {
+                eprintln!("{e}, try again");
+                attempt += 1;
+            }
+            Err(e) => return Err(e.context(format!("Failed to unlock {}", path.display()))),
+        }
+    }
+}
+
+/// Mark the keystore at `path` as unlocked, so tests don't prompt for its password.
+#[cfg(test)]
+pub fn remember_unlocked(path: &Path, keypair: &Keypair) {
+    let key = path.canonicalize().unwrap_or_else(|_| path.to_owned());
+    UNLOCKED
+        .lock()
+        .unwrap()
+        .insert(key, keypair.insecure_clone());
+}
+
+/// Prompt for a new keystore password, twice.
+pub fn new_password() -> Result<String> {
+    let password = secure_input("New keystore password: ", false)?;
+    if password.is_empty() {
+        bail!("The keystore password can't be empty");
+    }
+    if secure_input("Confirm keystore password: ", false)? != password {
+        bail!("Passwords don't match");
+    }
+    Ok(password)
+}
+
+/// Pubkey of a keypair file or keystore, without unlocking the keystore.
+pub fn read_pubkey(path: &Path) -> Result<Pubkey> {
+    match Keystore::read(path)? {
+        Some(keystore) => keystore.pubkey(),
+        None => crate::get_keypair(path).map(|keypair| keypair.pubkey()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Fast scrypt parameters, the recommended ones take a while in debug builds
+    const TEST_LOG_N: u8 = 4;
+
+    #[test]
+    fn keystore_roundtrip() {
+        let keypair = Keypair::new();
+        let keystore = Keystore::encrypt_with(&keypair, "hunter2", TEST_LOG_N).unwrap();
+        assert_eq!(keystore.pubkey().unwrap(), keypair.pubkey());
+
+        let json = serde_json::to_string(&keystore).unwrap();
+        assert!(json.contains(r#""name":"scrypt","log-n":4"#));
+        assert!(json.contains(r#""name":"aes-256-gcm""#));
+        let keystore: Keystore = serde_json::from_str(&json).unwrap();
+
+        let decrypted = keystore.decrypt("hunter2").unwrap();
+        assert_eq!(decrypted.to_bytes(), keypair.to_bytes());
+        assert_eq!(
+            keystore.decrypt("hunter3").unwrap_err().to_string(),
+            "Wrong password"
+        );
+    }
+
+    #[test]
+    fn keystore_pubkey_is_authenticated() {
+        let keypair = Keypair::new();
+        let mut keystore = Keystore::encrypt_with(&keypair, "hunter2", TEST_LOG_N).unwrap();
+        keystore.pubkey = Pubkey::new_unique().to_string();
+        assert!(keystore.decrypt("hunter2").is_err());
+    }
+
+    #[test]
+    fn detects_keystores() {
+        let dir = tempfile::tempdir().unwrap();
+        let keypair = Keypair::new();
+
+        let plaintext = dir.path().join("id.json");
+        solana_keypair::write_keypair_file(&keypair, &plaintext).unwrap();
+        assert!(!is_keystore(&plaintext));
+        assert_eq!(read_pubkey(&plaintext).unwrap(), keypair.pubkey());
+
+        let encrypted = dir.path().join("id.keystore.json");
+        Keystore::encrypt_with(&keypair, "hunter2", TEST_LOG_N)
+            .unwrap()
+            .write(&encrypted)
+            .unwrap();
+        assert!(is_keystore(&encrypted));
+        assert_eq!(read_pubkey(&encrypted).unwrap(), keypair.pubkey());
+        assert!(!is_keystore(&dir.path().join("missing.json")));
+    }
+    #[cfg(unix)]
+    #[test]
+    fn keystore_is_owner_only() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let keystore = Keystore::encrypt_with(&Keypair::new(), "hunter2", TEST_LOG_N).unwrap();
+        let mode = |path: &Path| fs::metadata(path).unwrap().permissions().mode() & 0o777;
+
+        let new = dir.path().join("new.json");
+        keystore.write(&new).unwrap();
+        assert_eq!(mode(&new), 0o600);
+
+        let existing = dir.path().join("existing.json");
+        fs::write(&existing, "x".repeat(4096)).unwrap();
+        fs::set_permissions(&existing, fs::Permissions::from_mode(0o644)).unwrap();
+        keystore.write(&existing).unwrap();
+        assert_eq!(mode(&existing), 0o600);
+        assert!(Keystore::read(&existing).unwrap().is_some());
+    }
+}
@@ -284,7 +284,7 @@ fn idl_set_buffer(
     priority_fee: Option<u64>,
 ) -> Result<Pubkey> {
     with_workspace(cfg_override, |cfg| {
-        let keypair = cfg.wallet_kp()?;
+        let keypair = cfg.wallet_signer()?;
         let url = cluster_url(cfg, &cfg.test_validator, &cfg.surfpool_config);
         let client = create_client(url);
 
@@ -435,7 +435,7 @@ fn idl_set_authority(
             None => IdlAccount::address(&program_id),
             Some(addr) => addr,
         };
-        let keypair = cfg.wallet_kp()?;
+        let keypair = cfg.wallet_signer()?;
         let url = cluster_url(cfg, &cfg.test_validator, &cfg.surfpool_config);
         let client = create_client(url);
 
@@ -508,7 +508,7 @@ fn idl_close_account(
     print_only: bool,
     priority_fee: Option<u64>,
 ) -> Result<()> {
-    let keypair = cfg.wallet_kp()?;
+    let keypair = cfg.wallet_signer()?;
     let url = cluster_url(cfg, &cfg.test_validator, &cfg.surfpool_config);
     let client = create_client(url);
 
@@ -560,7 +560,7 @@ fn idl_write(
     idl_address: Pubkey,
     priority_fee: Option<u64>,
 ) -> Result<()> {
-    let keypair = cfg.wallet_kp()?;
+    let keypair = cfg.wallet_signer()?;
     let url = cluster_url(cfg, &cfg.test_validator, &cfg.surfpool_config);
     let client = create_client(url);
 
@@ -628,7 +628,7 @@ fn create_idl_account(
     priority_fee: Option<u64>,
 ) -> Result<Pubkey> {
     let idl_address = IdlAccount::address(program_id);
-    let keypair = cfg.wallet_kp()?;
+    let keypair = cfg.wallet_signer()?;
     let url = cluster_url(cfg, &cfg.test_validator, &cfg.surfpool_config);
     let client = create_client(url);
     let idl_data = serialize_idl(idl)?;
@@ -714,7 +714,7 @@ fn create_idl_buffer(
     idl: &Idl,
     priority_fee: Option<u64>,
 ) -> Result<Pubkey> {
-    let keypair = cfg.wallet_kp()?;
+    let keypair = cfg.wallet_signer()?;
     let url = cluster_url(cfg, &cfg.test_validator, &cfg.surfpool_config);
     let client = create_client(url);
 
@@ -763,7 +763,7 @@ fn create_idl_buffer(
         let tx = Transaction::new_signed_with_payer(
             &instructions,
             Some(&keypair.pubkey()),
-            &[&keypair, &buffer],
+            &[&*keypair, &buffer],
             latest_hash,
         );
         match client.send_and_confirm_transaction_with_spinner(&tx) {
@@ -1,7 +1,7 @@
 use {
     crate::config::{
         get_default_ledger_path, BootstrapMode, BuildConfig, Config, ConfigOverride, HookType,
-        Manifest, PackageManager, ProgramDeployment, ProgramWorkspace, ScriptsConfig,
+        Manifest, PackageManager, Program, ProgramDeployment, ProgramWorkspace, ScriptsConfig,
         SurfnetInfoResponse, SurfpoolConfig, TestValidator, Validator, ValidatorType, WithPath,
         SHUTDOWN_WAIT, STARTUP_WAIT, SURFPOOL_HOST,
     },
@@ -12,6 +12,7 @@ use {
         prelude::UpgradeableLoaderState, solana_program::bpf_loader_upgradeable, AnchorDeserialize,
     },
     anchor_lang_idl::{
+        codec::IdlCodec,
         convert::{convert_idl, convert_idl_to_legacy},
         types::{Idl, IdlArrayLen, IdlDefinedFields, IdlType, IdlTypeDefTy},
     },
@@ -29,6 +30,7 @@ use {
     solana_cli_config::Config as SolanaCliConfig,
     solana_commitment_config::CommitmentConfig,
     solana_compute_budget_interface::ComputeBudgetInstruction,
+    solana_derivation_path::DerivationPath,
     solana_instruction::Instruction,
     solana_keypair::Keypair,
     solana_pubkey::Pubkey,
@@ -39,7 +41,7 @@ use {
         request::RpcRequest,
         response::{Response as RpcResponse, RpcLogsResponse},
     },
-    solana_signer::{EncodableKey, Signer},
+    solana_signer::Signer,
     std::{
         collections::{BTreeMap, HashMap, HashSet},
         ffi::OsString,
@@ -62,16 +64,23 @@ pub mod config;
 pub mod coverage;
 #[cfg(not(windows))]
 pub mod debugger;
+mod decode;
+mod deploy_plan;
 pub mod fetch;
 #[cfg(not(windows))]
 mod flamegraph;
+mod idl_diff;
 mod keygen;
+mod keystore;
 mod legacy_idl;
 mod metadata;
 #[cfg(not(windows))]
 mod profile;
 mod program;
+mod signer;
 pub mod template;
+mod transaction;
+mod verify;
 
 // Version of the docker image.
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
@@ -277,22 +286,35 @@ pub enum Command {
     /// Verifies the on-chain bytecode matches the locally compiled artifact.
     /// Run this command inside a program subdirectory, i.e., in the dir
     /// containing the program's Cargo.toml.
+    ///
+    /// Without `--repo-url` or `--current-dir`, the program is rebuilt with
+    /// `anchor build --verifiable` and compared natively against the on-chain
+    /// program data.
     Verify {
         /// The program ID to verify.
         program_id: Pubkey,
-        /// The URL of the repository to verify against. Conflicts with `--current-dir`.
+        /// The URL of the repository to verify against with `solana-verify`. Conflicts with
+        /// `--current-dir`.
         #[clap(long, conflicts_with = "current_dir")]
         repo_url: Option<String>,
         /// The commit hash to verify against. Requires `--repo-url`.
         #[clap(long, requires = "repo_url")]
         commit_hash: Option<String>,
-        /// Verify against the source code in the current directory. Conflicts with `--repo-url`.
+        /// Verify against the source code in the current directory with `solana-verify`.
+        /// Conflicts with `--repo-url`.
         #[clap(long)]
         current_dir: bool,
         /// Name of the program to run the command on. Defaults to the package name.
-        #[clap(long)]
+        #[clap(short, long)]
         program_name: Option<String>,
-        /// Any additional arguments to pass to `solana-verify`.
+        /// Compare against the existing verifiable build instead of rebuilding the program.
+        #[clap(long, conflicts_with_all = ["repo_url", "current_dir"])]
+        skip_build: bool,
+        /// Also check that the on-chain IDL metadata matches the local IDL.
+        #[clap(long, conflicts_with_all = ["repo_url", "current_dir"])]
+        idl: bool,
+        /// Any additional arguments to pass to `solana-verify`, or to the build when verifying
+        /// natively.
         #[clap(raw = true)]
         args: Vec<String>,
     },
@@ -424,6 +446,20 @@ pub enum Command {
         /// Don't upload IDL during deployment (IDL is uploaded by default)
         #[clap(long)]
         no_idl: bool,
+        /// Run the `[deploy.<plan>]` deployment plan of Anchor.toml
+        #[clap(long, conflicts_with_all = ["program_name", "no_idl"])]
+        plan: Option<String>,
+        /// Print the transactions of the plan and their expected cost without sending them
+        #[clap(long, requires = "plan")]
+        dry_run: bool,
+        /// Print the accounts, transactions and lamports the deployment needs and check the
+        /// balance of the payer without deploying
+        #[clap(long, conflicts_with = "plan")]
+        estimate: bool,
+        /// Extend ProgramData by this percentage of the program size beyond what an upgrade
+        /// needs when the new program doesn't fit
+        #[clap(long, default_value = "0", conflicts_with = "plan")]
+        extend_headroom: u16,
         /// Arguments to pass to the underlying `solana program deploy` command.
         #[clap(required = false, last = true)]
         solana_args: Vec<String>,
@@ -444,6 +480,10 @@ pub enum Command {
         /// Max times to retry on failure.
         #[clap(long, default_value = "0")]
         max_retries: u32,
+        /// Extend ProgramData by this percentage of the program size beyond what the upgrade
+        /// needs when the new program doesn't fit
+        #[clap(long, default_value = "0")]
+        extend_headroom: u16,
         /// Arguments to pass to the underlying `solana program deploy` command.
         #[clap(required = false, last = true)]
         solana_args: Vec<String>,
@@ -518,6 +558,9 @@ pub enum Command {
         /// Path of IDL to use (defaults to workspace IDL)
         #[clap(long)]
         idl: Option<PathBuf>,
+        /// Print the account with the JSON encoding of the IDL codec
+        #[clap(long)]
+        json_codec: bool,
     },
     /// Generates shell completions.
     Completions {
@@ -568,6 +611,26 @@ pub enum Command {
         #[clap(subcommand)]
         subcmd: codama::CodamaCommand,
     },
+    /// Decode transactions, instructions and program logs using the programs' IDLs
+    Decode {
+        /// IDL file to decode with, can be repeated. Takes precedence over the workspace IDLs,
+        /// the `idls` directory and on-chain IDLs.
+        #[clap(long = "idl", global = true)]
+        idls: Vec<PathBuf>,
+        #[clap(subcommand)]
+        subcmd: decode::DecodeCommand,
+    },
+    /// Inspect signer URIs and run a mock external signer
+    Signer {
+        #[clap(subcommand)]
+        subcmd: signer::SignerCommand,
+    },
+    /// Offline and multi-party transaction signing
+    #[clap(alias = "tx")]
+    Transaction {
+        #[clap(subcommand)]
+        subcmd: transaction::TransactionCommand,
+    },
     /// [DEPRECATED] Manage legacy on-chain IDL accounts.
     /// These commands interact with the old Anchor IDL instruction protocol and will be removed
     /// in a future release. Migrate to Program Metadata-based IDL management (`anchor idl`).
@@ -596,6 +659,17 @@ pub enum KeygenCommand {
         /// Number of words in the mnemonic phrase [possible values: 12, 15, 18, 21, 24]
         #[clap(short = 'w', long, default_value = "12")]
         word_count: usize,
+        /// Derive the keypair along this path, e.g. `m/44'/501'/0'/0'`, instead of from the
+        /// seed directly
+        #[clap(long, value_parser = keygen::parse_derivation_path)]
+        derivation_path: Option<DerivationPath>,
+        /// Derive this many accounts, incrementing the account index of the derivation path
+        /// (defaults to `m/44'/501'/0'/0'`). Each keypair is written to `<outfile>-<index>.json`
+        #[clap(long)]
+        accounts: Option<u32>,
+        /// Write a password-encrypted keystore instead of a plaintext keypair
+        #[clap(long)]
+        encrypt: bool,
     },
     /// Display the pubkey for a given keypair
     Pubkey {
@@ -616,6 +690,65 @@ pub enum KeygenCommand {
         /// Do not prompt for a passphrase
         #[clap(long)]
         no_passphrase: bool,
+        /// Derive the keypair along this path, e.g. `m/44'/501'/0'/0'`, instead of from the
+        /// seed directly
+        #[clap(long, value_parser = keygen::parse_derivation_path)]
+        derivation_path: Option<DerivationPath>,
+        /// Derive this many accounts, incrementing the account index of the derivation path
+        /// (defaults to `m/44'/501'/0'/0'`). Each keypair is written to `<outfile>-<index>.json`
+        #[clap(long)]
+        accounts: Option<u32>,
+        /// Write a password-encrypted keystore instead of a plaintext keypair
+        #[clap(long)]
+        encrypt: bool,
+    },
+    /// Encrypt a plaintext keypair into a password-encrypted keystore
+    Encrypt {
+        /// Keypair filepath (defaults to configured wallet)
+        keypair: Option<PathBuf>,
+        /// Path to the keystore (defaults to replacing the keypair)
+        #[clap(short = 'o', long)]
+        outfile: Option<PathBuf>,
+        /// Overwrite the output file if it exists
+        #[clap(short, long)]
+        force: bool,
+    },
+    /// Decrypt a keystore back into a plaintext keypair
+    Decrypt {
+        /// Keystore filepath (defaults to configured wallet)
+        keystore: Option<PathBuf>,
+        /// Path to the plaintext keypair (defaults to replacing the keystore)
+        #[clap(short = 'o', long)]
+        outfile: Option<PathBuf>,
+        /// Overwrite the output file if it exists
+        #[clap(short, long)]
+        force: bool,
+    },
+    /// Grind for keypairs whose pubkey starts and/or ends with the given characters, e.g. for
+    /// vanity addresses and program ids
+    Grind {
+        /// Prefix of the pubkey
+        #[clap(long, required_unless_present = "ends_with")]
+        starts_with: Option<String>,
+        /// Suffix of the pubkey
+        #[clap(long)]
+        ends_with: Option<String>,
+        /// Match the prefix and suffix case-insensitively
+        #[clap(long)]
+        ignore_case: bool,
+        /// Number of keypairs to find
+        #[clap(short = 'n', long, default_value = "1")]
+        count: usize,
+        /// Number of threads to grind with (defaults to the number of CPUs)
+        #[clap(long)]
+        threads: Option<usize>,
+        /// Directory to write the keypairs to, as `<pubkey>.json` (defaults to the current
+        /// directory)
+        #[clap(short = 'o', long)]
+        outdir: Option<PathBuf>,
+        /// Write password-encrypted keystores instead of plaintext keypairs
+        #[clap(long)]
+        encrypt: bool,
     },
     /// Verify a keypair can sign and verify a message
     Verify {
@@ -636,6 +769,15 @@ pub enum KeysCommand {
         #[clap(short, long)]
         program_name: Option<String>,
     },
+    /// Rotate a program's id: generate a new program keypair and replace the old id across the
+    /// workspace.
+    Rotate {
+        /// Name of the program to rotate
+        program_name: String,
+        /// Use this keypair as the new program keypair, e.g. one found with `anchor keygen grind`
+        #[clap(long)]
+        keypair: Option<PathBuf>,
+    },
 }
 
 #[derive(Debug, Parser, AbsolutePath)]
@@ -672,6 +814,14 @@ pub enum ProgramCommand {
         /// Make the program immutable after deployment (cannot be upgraded)
         #[clap(long = "final")]
         make_final: bool,
+        /// Extend ProgramData by this percentage of the program size beyond what an upgrade
+        /// needs when the new program doesn't fit
+        #[clap(long, default_value = "0")]
+        extend_headroom: u16,
+        /// Print the accounts, transactions and lamports the deployment needs and check the
+        /// balance of the payer without deploying
+        #[clap(long)]
+        estimate: bool,
         /// Additional arguments to configure deployment (e.g., --with-compute-unit-price 1000)
         #[clap(required = false, last = true)]
         solana_args: Vec<String>,
@@ -753,6 +903,17 @@ pub enum ProgramCommand {
         /// Upgrade authority (defaults to configured wallet)
         #[clap(long)]
         upgrade_authority: Option<String>,
+        /// Multisig vault that holds the upgrade authority. Instead of upgrading, transfer the
+        /// buffer authority to the vault and print the unsigned upgrade transaction to propose
+        #[clap(long, conflicts_with = "upgrade_authority")]
+        multisig: Option<Pubkey>,
+        /// Recipient of the buffer's lamports after the upgrade (defaults to the multisig vault)
+        #[clap(long, requires = "multisig")]
+        spill: Option<Pubkey>,
+        /// Extend ProgramData by this percentage of the program size beyond what an upgrade
+        /// needs when the new program doesn't fit
+        #[clap(long, default_value = "0")]
+        extend_headroom: u16,
         /// Max times to retry on failure
         #[clap(long, default_value = "0")]
         max_retries: u32,
@@ -788,6 +949,38 @@ pub enum ProgramCommand {
         #[clap(long)]
         bypass_warning: bool,
     },
+    /// List the buffer accounts of an authority and reclaim the ones left by
+    /// deploys in this workspace
+    Buffers {
+        /// Buffer authority keypair (defaults to configured wallet)
+        #[clap(long)]
+        authority: Option<String>,
+        /// Close the superseded buffers recorded in this workspace's deploy
+        /// journals and withdraw their lamports
+        #[clap(long)]
+        close: bool,
+        /// Also close buffers of interrupted deploys that can still be resumed
+        #[clap(long, requires = "close")]
+        include_resumable: bool,
+        /// Close these buffers instead, even if no deploy journal records them
+        #[clap(
+            long = "buffer",
+            value_name = "ADDRESS",
+            requires = "close",
+            conflicts_with = "all"
+        )]
+        buffers: Vec<Pubkey>,
+        /// Close every buffer of the authority, including the ones of other
+        /// workspaces, `program write-buffer` and pending multisig upgrades
+        #[clap(long, requires = "close", conflicts_with = "include_resumable")]
+        all: bool,
+        /// Recipient address for reclaimed lamports (defaults to authority)
+        #[clap(long)]
+        recipient: Option<Pubkey>,
+        /// Bypass warning prompts
+        #[clap(long)]
+        bypass_warning: bool,
+    },
     /// Extend the length of an upgradeable program
     Extend {
         /// Program id to extend.
@@ -923,6 +1116,21 @@ pub enum IdlCommand {
         #[clap(long)]
         to_legacy: bool,
     },
+    /// Compare two IDLs and classify the changes as breaking or non-breaking
+    Diff {
+        /// Path to the old IDL file, or the program id to fetch the old IDL from the cluster
+        old: String,
+        /// Path to the new IDL file
+        new: PathBuf,
+        /// Exit with an error if there are any breaking changes
+        #[clap(long)]
+        fail_on_breaking: bool,
+    },
+    /// Validate an IDL file and report the path of each error
+    Validate {
+        /// Path to the IDL file
+        path: PathBuf,
+    },
     /// Generate TypeScript type for the IDL
     Type {
         /// Path to the IDL file
@@ -1003,10 +1211,25 @@ pub enum ConfigCommand {
 }
 
 fn get_keypair(path: &Path) -> Result<Keypair> {
+    if let Some(keystore) = keystore::Keystore::read(path)? {
+        return keystore::unlock(path, &keystore);
+    }
+
     solana_keypair::read_keypair_file(path)
         .map_err(|_| anyhow!("Unable to read keypair file ({})", path.display()))
 }
 
+/// `ANCHOR_WALLET_PUBKEY` for scripts when the wallet is a signer URI or a keystore, since they
+/// can't read `ANCHOR_WALLET` as a keypair file then.
+fn wallet_pubkey_env(cfg: &Config) -> Result<Option<(&'static str, String)>> {
+    if signer::is_plaintext_keypair(&cfg.provider.wallet.to_string()) {
+        return Ok(None);
+    }
+
+    let pubkey = cfg.wallet_pubkey()?;
+    Ok(Some(("ANCHOR_WALLET_PUBKEY", pubkey.to_string())))
+}
+
 /// Format lamports as SOL with trailing zeros removed
 fn format_sol(lamports: u64) -> String {
     let sol = lamports as f64 / 1_000_000_000.0;
@@ -1063,14 +1286,18 @@ fn get_cluster_and_wallet(cfg_override: &ConfigOverride) -> Result<(String, Stri
             )
         };
 
-    // Apply cluster override if provided
+    // Apply cluster and wallet overrides if provided
     let final_cluster = if let Some(cluster) = &cfg_override.cluster {
         cluster.url().to_string()
     } else {
         cluster_url
     };
+    let final_wallet = match &cfg_override.wallet {
+        Some(wallet) => wallet.to_string(),
+        None => wallet_path,
+    };
 
-    Ok((final_cluster, wallet_path))
+    Ok((final_cluster, final_wallet))
 }
 
 /// Get the recommended priority fee from the RPC client, falling back to 0 if unavailable.
@@ -1437,23 +1664,49 @@ fn process_command(opts: Opts) -> Result<()> {
             commit_hash,
             current_dir,
             program_name,
+            skip_build,
+            idl,
             args,
-        } => verify(
-            program_id,
-            repo_url,
-            commit_hash,
-            current_dir,
-            program_name,
-            args,
-        ),
+        } => {
+            if repo_url.is_none() && !current_dir {
+                verify::verify_native(
+                    &opts.cfg_override,
+                    program_id,
+                    program_name,
+                    skip_build,
+                    idl,
+                    args,
+                )
+            } else {
+                verify(
+                    program_id,
+                    repo_url,
+                    commit_hash,
+                    current_dir,
+                    program_name,
+                    args,
+                )
+            }
+        }
         Command::Clean => clean(&opts.cfg_override),
         #[allow(deprecated)]
+        Command::Deploy {
+            verifiable,
+            plan: Some(plan),
+            dry_run,
+            solana_args,
+            ..
+        } => deploy_plan::deploy_plan(&opts.cfg_override, &plan, verifiable, dry_run, solana_args),
+        #[allow(deprecated)]
         Command::Deploy {
             program_name,
             program_keypair,
             verifiable,
             no_idl,
+            estimate,
+            extend_headroom,
             solana_args,
+            ..
         } => {
             eprintln!(
                 "Warning: 'anchor deploy' is deprecated. Use 'anchor program deploy' instead."
@@ -1464,6 +1717,8 @@ fn process_command(opts: Opts) -> Result<()> {
                 program_keypair,
                 verifiable,
                 no_idl,
+                estimate,
+                extend_headroom,
                 solana_args,
             )
         }
@@ -1477,6 +1732,7 @@ fn process_command(opts: Opts) -> Result<()> {
             program_id,
             program_filepath,
             max_retries,
+            extend_headroom,
             solana_args,
         } => {
             eprintln!(
@@ -1487,6 +1743,7 @@ fn process_command(opts: Opts) -> Result<()> {
                 program_id,
                 program_filepath,
                 max_retries,
+                extend_headroom,
                 solana_args,
             )
         }
@@ -1591,7 +1848,8 @@ fn process_command(opts: Opts) -> Result<()> {
             account_type,
             address,
             idl,
-        } => account(&opts.cfg_override, account_type, address, idl),
+            json_codec,
+        } => account(&opts.cfg_override, account_type, address, idl, json_codec),
         Command::Completions { shell } => {
             clap_complete::generate(
                 shell,
@@ -1613,6 +1871,9 @@ fn process_command(opts: Opts) -> Result<()> {
         Command::Keygen { subcmd } => keygen::keygen(&opts.cfg_override, subcmd),
         Command::Program { subcmd } => program::program(&opts.cfg_override, subcmd),
         Command::Codama { subcmd } => codama::entry(subcmd),
+        Command::Decode { idls, subcmd } => decode::decode(&opts.cfg_override, idls, subcmd),
+        Command::Signer { subcmd } => signer::signer(&opts.cfg_override, subcmd),
+        Command::Transaction { subcmd } => transaction::transaction(&opts.cfg_override, subcmd),
     }
 }
 
@@ -3150,6 +3411,12 @@ fn idl(cfg_override: &ConfigOverride, subcmd: IdlCommand) -> Result<()> {
             program_id,
             to_legacy,
         } => idl_convert(path, out, program_id, to_legacy),
+        IdlCommand::Diff {
+            old,
+            new,
+            fail_on_breaking,
+        } => idl_diff::idl_diff(cfg_override, old, new, fail_on_breaking),
+        IdlCommand::Validate { path } => idl_validate(path),
         IdlCommand::Type { path, out } => idl_type(path, out),
         IdlCommand::Close {
             program_id,
@@ -3205,31 +3472,25 @@ fn idl_init(
     }
 
     let program_id = match program_id {
-        Some(id) => id.to_string(),
+        Some(id) => id,
         _ => {
             let idl = fs::read(&idl_filepath)?;
             let idl = convert_idl(&idl)?;
-            idl.address
+            idl.address.parse()?
         }
     };
 
-    let command = metadata::IdlCommand::funded(
+    metadata::run_funded(
         cluster_url,
         wallet_path,
         priority_fee,
         metadata::FundedIdlSubcommand::Write {
             program_id,
-            idl_filepath: idl_filepath
-                .to_str()
-                .ok_or_else(|| anyhow!("IDL filepath is not valid UTF-8"))?
-                .to_string(),
+            idl_filepath,
             non_canonical,
         },
-    );
-
-    if !command.status()?.success() {
-        return Err(anyhow!("Failed to initialize IDL"));
-    }
+    )
+    .context("Failed to initialize IDL")?;
 
     println!("IDL initialized.");
     Ok(())
@@ -3256,30 +3517,25 @@ fn idl_upgrade(
     }
 
     let program_id = match program_id {
-        Some(id) => id.to_string(),
+        Some(id) => id,
         _ => {
             let idl = fs::read(&idl_filepath)?;
             let idl = convert_idl(&idl)?;
-            idl.address
+            idl.address.parse()?
         }
     };
 
-    let command = metadata::IdlCommand::funded(
+    metadata::run_funded(
         cluster_url,
         wallet_path,
         priority_fee,
         metadata::FundedIdlSubcommand::Write {
             program_id,
-            idl_filepath: idl_filepath
-                .to_str()
-                .ok_or_else(|| anyhow!("IDL filepath is not valid UTF-8"))?
-                .to_string(),
+            idl_filepath,
             non_canonical: false,
         },
-    );
-    if !command.status()?.success() {
-        return Err(anyhow!("Failed to upgrade IDL"));
-    }
+    )
+    .context("Failed to upgrade IDL")?;
 
     println!("IDL upgraded.");
     Ok(())
@@ -3336,12 +3592,17 @@ fn generate_idl(
     no_docs: bool,
     cargo_args: &[String],
 ) -> Result<Idl> {
-    check_idl_build_feature()?;
+    // The `idl-build` feature is only used when the IDL is generated via compilation
+    if !cfg.features.static_idl {
+        check_idl_build_feature()?;
+    }
 
     let idl = anchor_lang_idl::build::IdlBuilder::new()
         .resolution(cfg.features.resolution)
         .skip_lint(cfg.features.skip_lint || skip_lint)
         .no_docs(no_docs)
+        .static_analysis(cfg.features.static_idl)
+        .external_types(cfg.idl.external_types())
         .cargo_args(cargo_args.into())
         .build()?;
 
@@ -3464,6 +3725,22 @@ fn idl_type(path: PathBuf, out: Option<PathBuf>) -> Result<()> {
     Ok(())
 }
 
+fn idl_validate(path: PathBuf) -> Result<()> {
+    let idl = fs::read(&path)?;
+    match anchor_lang_idl::validate::validate_idl_json(&idl) {
+        Ok(_) => {
+            println!("IDL is valid");
+            Ok(())
+        }
+        Err(errors) => {
+            for error in &errors {
+                eprintln!("{error}");
+            }
+            bail!("Found {} error(s) in `{}`", errors.len(), path.display())
+        }
+    }
+}
+
 fn idl_close_metadata(
     cfg_override: &ConfigOverride,
     program_id: Pubkey,
@@ -3471,19 +3748,13 @@ fn idl_close_metadata(
     priority_fee: Option<u64>,
 ) -> Result<()> {
     let (cluster_url, wallet_path) = get_cluster_and_wallet(cfg_override)?;
-    let command = metadata::IdlCommand::funded(
+    metadata::run_funded(
         cluster_url,
         wallet_path,
         priority_fee,
-        metadata::FundedIdlSubcommand::Close {
-            program_id: program_id.to_string(),
-            seed,
-        },
-    );
-
-    if !command.status()?.success() {
-        return Err(anyhow!("Failed to close metadata account"));
-    }
+        metadata::FundedIdlSubcommand::Close { program_id, seed },
+    )
+    .context("Failed to close metadata account")?;
 
     println!("Metadata account closed successfully.");
     Ok(())
@@ -3495,21 +3766,13 @@ fn idl_create_buffer(
     priority_fee: Option<u64>,
 ) -> Result<()> {
     let (cluster_url, wallet_path) = get_cluster_and_wallet(cfg_override)?;
-    let command = metadata::IdlCommand::funded(
+    metadata::run_funded(
         cluster_url,
         wallet_path,
         priority_fee,
-        metadata::FundedIdlSubcommand::CreateBuffer {
-            filepath: filepath
-                .to_str()
-                .ok_or_else(|| anyhow!("IDL filepath is not valid UTF-8"))?
-                .to_string(),
-        },
-    );
-
-    if !command.status()?.success() {
-        return Err(anyhow!("Failed to create buffer"));
-    }
+        metadata::FundedIdlSubcommand::CreateBuffer { filepath },
+    )
+    .context("Failed to create buffer")?;
 
     println!("Buffer created successfully.");
     Ok(())
@@ -3522,19 +3785,16 @@ fn idl_set_buffer_authority(
     priority_fee: Option<u64>,
 ) -> Result<()> {
     let (cluster_url, wallet_path) = get_cluster_and_wallet(cfg_override)?;
-    let command = metadata::IdlCommand::funded(
+    metadata::run_funded(
         cluster_url,
         wallet_path,
         priority_fee,
         metadata::FundedIdlSubcommand::SetBufferAuthority {
-            buffer: buffer.to_string(),
-            new_authority: new_authority.to_string(),
+            buffer,
+            new_authority,
         },
-    );
-
-    if !command.status()?.success() {
-        return Err(anyhow!("Failed to set buffer authority"));
-    }
+    )
+    .context("Failed to set buffer authority")?;
 
     println!("Buffer authority set successfully.");
     Ok(())
@@ -3549,21 +3809,18 @@ fn idl_write_buffer_metadata(
     priority_fee: Option<u64>,
 ) -> Result<()> {
     let (cluster_url, wallet_path) = get_cluster_and_wallet(cfg_override)?;
-    let command = metadata::IdlCommand::funded(
+    metadata::run_funded(
         cluster_url,
         wallet_path,
         priority_fee,
         metadata::FundedIdlSubcommand::WriteBuffer {
-            program_id: program_id.to_string(),
-            buffer: buffer.to_string(),
+            program_id,
+            buffer,
             seed,
             close_buffer,
         },
-    );
-
-    if !command.status()?.success() {
-        return Err(anyhow!("Failed to write metadata using buffer"));
-    }
+    )
+    .context("Failed to write metadata using buffer")?;
 
     println!("Metadata written successfully using buffer.");
     Ok(())
@@ -3646,6 +3903,7 @@ fn account(
     account_type: String,
     address: Pubkey,
     idl_filepath: Option<PathBuf>,
+    json_codec: bool,
 ) -> Result<()> {
     let (program_name, account_type_name) = account_type
         .split_once('.') // Split at first occurrence of dot
@@ -3736,10 +3994,13 @@ fn account(
                 )
             }
         })?;
-    let mut data_view = &data[disc_len..];
 
-    let deserialized_json =
-        deserialize_idl_defined_type_to_json(&idl, account_type_name, &mut data_view)?;
+    let deserialized_json = if json_codec {
+        IdlCodec::new(&idl).decode_account_as(account_type_name, &data)?
+    } else {
+        let mut data_view = &data[disc_len..];
+        deserialize_idl_defined_type_to_json(&idl, account_type_name, &mut data_view)?
+    };
 
     println!(
         "{}",
@@ -4065,7 +4326,7 @@ fn test(
             config_skip_local_validator,
         );
         if validator_plan.predeploy {
-            deploy(cfg_override, None, None, false, true, vec![])?;
+            deploy(cfg_override, None, None, false, true, false, 0, vec![])?;
         }
 
         cfg.run_hooks(HookType::PreTest)?;
@@ -4637,6 +4898,7 @@ fn run_test_suite(
             .arg(script_args)
             .env("ANCHOR_PROVIDER_URL", url)
             .env("ANCHOR_WALLET", cfg.provider.wallet.to_string())
+            .envs(wallet_pubkey_env(cfg)?)
             .env("NODE_OPTIONS", node_options)
             .stdout(Stdio::inherit())
             .stderr(Stdio::inherit())
@@ -5166,7 +5428,7 @@ fn validator_deploy_flags(
             flags.push("--upgradeable-program".to_string());
             flags.push(address.clone());
             flags.push(binary_path);
-            flags.push(cfg.wallet_kp()?.pubkey().to_string());
+            flags.push(cfg.wallet_pubkey()?.to_string());
         } else {
             flags.push("--bpf-program".to_string());
             flags.push(address.clone());
@@ -5200,7 +5462,7 @@ fn validator_deploy_flags(
                     flags.push("--upgradeable-program".to_string());
                     flags.push(entry.address.clone());
                     flags.push(entry.program.clone());
-                    flags.push(cfg.wallet_kp()?.pubkey().to_string());
+                    flags.push(cfg.wallet_pubkey()?.to_string());
                 } else {
                     flags.push("--bpf-program".to_string());
                     flags.push(entry.address.clone());
@@ -5788,7 +6050,7 @@ fn start_solana_test_validator(
         .arg("--ledger")
         .arg(test_ledger_directory)
         .arg("--mint")
-        .arg(cfg.wallet_kp()?.pubkey().to_string())
+        .arg(cfg.wallet_pubkey()?.to_string())
         .args(flags.unwrap_or_default())
         .stdout(test_validator_stdout)
         .stderr(test_validator_stderr)
@@ -5946,12 +6208,15 @@ fn clean(cfg_override: &ConfigOverride) -> Result<()> {
     Ok(())
 }
 
+#[allow(clippy::too_many_arguments)]
 fn deploy(
     cfg_override: &ConfigOverride,
     program_name: Option<String>,
     program_keypair: Option<PathBuf>,
     verifiable: bool,
     no_idl: bool,
+    estimate: bool,
+    extend_headroom: u16,
     solana_args: Vec<String>,
 ) -> Result<()> {
     // Execute the code within the workspace
@@ -5959,6 +6224,28 @@ fn deploy(
         let url = cluster_url(cfg, &cfg.test_validator, &cfg.surfpool_config);
         let keypair = cfg.provider.wallet.to_string();
 
+        if estimate {
+            let programs = cfg
+                .get_programs(program_name)?
+                .into_iter()
+                .map(|program| {
+                    let program_keypair_filepath = match program_keypair.as_ref() {
+                        Some(path) => path.clone(),
+                        None => program.keypair_file()?.path().clone(),
+                    };
+                    Ok((program.binary_path(verifiable)?, program_keypair_filepath))
+                })
+                .collect::<Result<Vec<_>>>()?;
+            return program::estimate_programs_deploy(
+                cfg_override,
+                &programs,
+                None,
+                extend_headroom,
+                no_idl,
+                &solana_args,
+            );
+        }
+
         cfg.run_hooks(HookType::PreDeploy)?;
         // Deploy the programs.
         println!("Deploying cluster: {url}");
@@ -5988,6 +6275,8 @@ fn deploy(
                 false, // use_rpc
                 no_idl,
                 false, // make_final
+                extend_headroom,
+                false, // estimate
                 solana_args.clone(),
             )?;
         }
@@ -6004,6 +6293,7 @@ fn upgrade(
     program_id: Pubkey,
     program_filepath: PathBuf,
     max_retries: u32,
+    extend_headroom: u16,
     solana_args: Vec<String>,
 ) -> Result<()> {
     // Use our native upgrade implementation
@@ -6014,6 +6304,9 @@ fn upgrade(
         None, // program_name - not needed since we have filepath
         None, // buffer
         None, // upgrade_authority - uses wallet from config
+        None, // multisig
+        None, // spill
+        extend_headroom,
         max_retries,
         false, // use_rpc
         solana_args,
@@ -6052,6 +6345,7 @@ fn migrate(cfg_override: &ConfigOverride) -> Result<()> {
                     &fs::canonicalize(deploy_ts)?.to_string_lossy(),
                 ])
                 .env("ANCHOR_WALLET", cfg.provider.wallet.to_string())
+                .envs(wallet_pubkey_env(cfg)?)
                 .stdout(Stdio::inherit())
                 .stderr(Stdio::inherit())
                 .output()?
@@ -6065,6 +6359,7 @@ fn migrate(cfg_override: &ConfigOverride) -> Result<()> {
             std::process::Command::new("node")
                 .arg(&deploy_js)
                 .env("ANCHOR_WALLET", cfg.provider.wallet.to_string())
+                .envs(wallet_pubkey_env(cfg)?)
                 .stdout(Stdio::inherit())
                 .stderr(Stdio::inherit())
                 .output()?
@@ -6156,10 +6451,8 @@ fn airdrop(cfg_override: &ConfigOverride, amount: f64, pubkey: Option<Pubkey>) -
     let recipient_pubkey = if let Some(pubkey) = pubkey {
         pubkey
     } else {
-        // Load keypair from wallet path and get pubkey
-        let keypair = Keypair::read_from_file(&wallet_path)
-            .map_err(|e| anyhow!("Failed to read keypair from {}: {}", wallet_path, e))?;
-        keypair.pubkey()
+        // Load the wallet and get its pubkey
+        signer::pubkey_from_path(&wallet_path, "wallet")?
     };
 
     // Convert SOL to lamports
@@ -6407,6 +6700,7 @@ fn run(cfg_override: &ConfigOverride, script: String, script_args: Vec<String>)
             .arg(&script_with_args)
             .env("ANCHOR_PROVIDER_URL", url)
             .env("ANCHOR_WALLET", cfg.provider.wallet.to_string())
+            .envs(wallet_pubkey_env(cfg)?)
             .stdout(Stdio::inherit())
             .stderr(Stdio::inherit())
             .output()
@@ -6422,6 +6716,10 @@ fn keys(cfg_override: &ConfigOverride, cmd: KeysCommand) -> Result<()> {
     match cmd {
         KeysCommand::List => keys_list(cfg_override),
         KeysCommand::Sync { program_name } => keys_sync(cfg_override, program_name),
+        KeysCommand::Rotate {
+            program_name,
+            keypair,
+        } => keys_rotate(cfg_override, program_name, keypair),
     }
 }
 
@@ -6517,6 +6815,206 @@ fn keys_sync(cfg_override: &ConfigOverride, program_name: Option<String>) -> Res
     })?
 }
 
+/// Generate a new keypair for the program and replace its old id in every file of the workspace
+/// that's known to contain it. Other occurrences, e.g. in tests, are only reported.
+fn keys_rotate(
+    cfg_override: &ConfigOverride,
+    program_name: String,
+    keypair: Option<PathBuf>,
+) -> Result<()> {
+    with_workspace(cfg_override, |cfg| -> Result<()> {
+        let program = cfg.get_program(&program_name)?;
+        if let ProgramIdComparison::Mismatch { lib_name, .. } =
+            check_program_id_mismatch(cfg, Some(program_name))?
+        {
+            bail!(
+                "The `declare_id!` of `{lib_name}` doesn't match its keypair, run `anchor keys \
+                sync` first"
+            );
+        }
+
+        let keypair_path = program.keypair_file()?.path().to_owned();
+        let old_program_id = program.pubkey()?;
+        let new_keypair = match keypair {
+            Some(path) => get_keypair(&path)?,
+            None => Keypair::new(),
+        };
+        let new_program_id = new_keypair.pubkey();
+        if new_program_id == old_program_id {
+            bail!("The new program id is the same as the old one ({old_program_id})");
+        }
+        println!(
+            "Rotating the program id of `{}` from {old_program_id} to {new_program_id}\n",
+            program.lib_name
+        );
+
+        // Keep the old keypair, so that the old id can still be restored
+        let backup_path = keypair_path.with_file_name(format!(
+            "{}-keypair-{old_program_id}.json",
+            program.lib_name
+        ));
+        let files = program_id_files(cfg, &program, target_dir()?);
+        let workspace_dir = cfg.path().parent().unwrap().to_owned();
+        for path in rotate_program_id(
+            files,
+            &keypair_path,
+            &backup_path,
+            old_program_id,
+            &new_keypair,
+        )? {
+            let path = path.strip_prefix(&workspace_dir).unwrap_or(&path);
+            println!("Updated {}", path.display());
+        }
+        println!("Saved the old program keypair to {}", backup_path.display());
+
+        let occurrences = find_program_id(&workspace_dir, old_program_id);
+        if occurrences.is_empty() {
+            println!("\nNo other occurrences of the old program id were found.");
+        } else {
+            println!("\nThe old program id is still hard-coded in:");
+            for (path, line) in occurrences {
+                let path = path.strip_prefix(&workspace_dir).unwrap_or(&path);
+                println!("  {}:{line}", path.display());
+            }
+        }
+        println!("\nPlease rebuild the program to update the generated artifacts.");
+
+        Ok(())
+    })?
+}
+
+/// Every file of the workspace the program id is known to be written to
+fn program_id_files(cfg: &WithPath<Config>, program: &Program, target_dir: &Path) -> Vec<PathBuf> {
+    let workspace_dir = cfg.path().parent().unwrap();
+    let mut files = vec![cfg.path().to_owned()];
+    files.extend(workspace_files(&program.path.join("src")));
+    files.extend(workspace_files(workspace_dir).filter(|path| path.ends_with("Test.toml")));
+    files.push(
+        target_dir
+            .join("idl")
+            .join(&program.lib_name)
+            .with_extension("json"),
+    );
+    files.push(
+        target_dir
+            .join("types")
+            .join(&program.lib_name)
+            .with_extension("ts"),
+    );
+    for idls_dir in ["idls", cfg.workspace.idls.as_str()] {
+        if !idls_dir.is_empty() {
+            files.extend(
+                workspace_files(&workspace_dir.join(idls_dir))
+                    .filter(|path| path.extension().is_some_and(|ext| ext == "json")),
+            );
+        }
+    }
+    for (_, client_dir) in cfg.clients.enabled(workspace_dir) {
+        files.extend(workspace_files(&client_dir));
+    }
+
+    files
+}
+
+/// Replace the old program id in the given files with the id of the new keypair, then back up the
+/// old keypair and write the new one, returning the files that changed.
+///
+/// The keypair is written last, and the files are restored if that fails, so that the keypair
+/// never ends up out of sync with the files.
+fn rotate_program_id(
+    files: Vec<PathBuf>,
+    keypair_path: &Path,
+    backup_path: &Path,
+    old_program_id: Pubkey,
+    new_keypair: &Keypair,
+) -> Result<Vec<PathBuf>> {
+    let replaced = replace_program_id(files, old_program_id, new_keypair.pubkey())?;
+
+    let write_keypair = || -> Result<()> {
+        fs::copy(keypair_path, backup_path)?;
+        solana_keypair::write_keypair_file(new_keypair, keypair_path)
+            .map_err(|e| anyhow!("Failed to write {}: {e}", keypair_path.display()))?;
+        Ok(())
+    };
+    if let Err(e) = write_keypair() {
+        restore_files(&replaced);
+        return Err(e);
+    }
+
+    Ok(replaced.into_iter().map(|(path, _)| path).collect())
+}
+
+/// Files under `dir`, skipping hidden directories, build artifacts and dependencies
+fn workspace_files(dir: &Path) -> impl Iterator<Item = PathBuf> {
+    walkdir::WalkDir::new(dir)
+        .into_iter()
+        .filter_entry(|entry| {
+            entry.depth() == 0 || !(is_hidden(entry) || entry.file_name() == "node_modules")
+        })
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_type().is_file())
+        .map(|entry| entry.into_path())
+}
+
+/// Replace the old program id with the new one in the given text files, returning the files
+/// that changed along with their previous content.
+///
+/// The files are restored if any of them can't be written.
+fn replace_program_id(
+    mut paths: Vec<PathBuf>,
+    old_program_id: Pubkey,
+    new_program_id: Pubkey,
+) -> Result<Vec<(PathBuf, String)>> {
+    let (old_program_id, new_program_id) = (old_program_id.to_string(), new_program_id.to_string());
+    paths.sort();
+    paths.dedup();
+
+    let mut changed = vec![];
+    for path in paths {
+        let Ok(content) = fs::read_to_string(&path) else {
+            continue;
+        };
+        if content.contains(&old_program_id) {
+            if let Err(e) = fs::write(&path, content.replace(&old_program_id, &new_program_id)) {
+                restore_files(&changed);
+                return Err(anyhow!("Failed to write {}: {e}", path.display()));
+            }
+            changed.push((path, content));
+        }
+    }
+
+    Ok(changed)
+}
+
+/// Write back the previous content of the given files
+fn restore_files(files: &[(PathBuf, String)]) {
+    for (path, content) in files {
+        if let Err(e) = fs::write(path, content) {
+            eprintln!("Failed to restore {}: {e}", path.display());
+        }
+    }
+}
+
+/// Lines of the text files in the workspace that contain the program id
+fn find_program_id(workspace_dir: &Path, program_id: Pubkey) -> Vec<(PathBuf, usize)> {
+    let program_id = program_id.to_string();
+    let mut occurrences = vec![];
+    for path in workspace_files(workspace_dir) {
+        let Ok(content) = fs::read_to_string(&path) else {
+            continue;
+        };
+        occurrences.extend(
+            content
+                .lines()
+                .enumerate()
+                .filter(|(_, line)| line.contains(&program_id))
+                .map(|(i, _)| (path.clone(), i + 1)),
+        );
+    }
+
+    occurrences
+}
+
 enum ProgramIdComparison {
     Same,
     Mismatch {
@@ -6872,12 +7370,8 @@ pub(crate) fn create_client<U: ToString>(url: U) -> RpcClient {
 fn address(cfg_override: &ConfigOverride) -> Result<()> {
     let (_cluster_url, wallet_path) = get_cluster_and_wallet(cfg_override)?;
 
-    // Load keypair and get pubkey
-    let keypair = Keypair::read_from_file(&wallet_path)
-        .map_err(|e| anyhow!("Failed to read keypair from {}: {}", wallet_path, e))?;
-
-    // Print the public key
-    println!("{}", keypair.pubkey());
+    // Load the wallet and print its pubkey
+    println!("{}", signer::pubkey_from_path(&wallet_path, "wallet")?);
 
     Ok(())
 }
@@ -6892,10 +7386,8 @@ fn balance(cfg_override: &ConfigOverride, pubkey: Option<Pubkey>, lamports: bool
     let account_pubkey = if let Some(pubkey) = pubkey {
         pubkey
     } else {
-        // Load keypair from wallet path and get pubkey
-        let keypair = Keypair::read_from_file(&wallet_path)
-            .map_err(|e| anyhow!("Failed to read keypair from {}: {}", wallet_path, e))?;
-        keypair.pubkey()
+        // Load the wallet and get its pubkey
+        signer::pubkey_from_path(&wallet_path, "wallet")?
     };
 
     // Get balance
@@ -7116,6 +7608,160 @@ mod tests {
         tempfile::tempdir,
     };
 
+    #[test]
+    fn test_rotate_program_id_in_files() {
+        let dir = tempdir().unwrap();
+        let old_program_id = Pubkey::new_unique();
+        let new_program_id = Pubkey::new_unique();
+        let write = |path: &str, content: String| {
+            let path = dir.path().join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, content).unwrap();
+            path
+        };
+
+        let lib_rs = write(
+            "programs/counter/src/lib.rs",
+            format!("declare_id!(\"{old_program_id}\");\n"),
+        );
+        let idl = write(
+            "idls/counter.json",
+            format!(r#"{{"address":"{old_program_id}"}}"#),
+        );
+        let unrelated = write("idls/other.json", r#"{"address":"other"}"#.into());
+        let test = write(
+            "tests/counter.ts",
+            format!("// Counter\nconst id = \"{old_program_id}\";\n"),
+        );
+        write("node_modules/dep/index.js", old_program_id.to_string());
+        write("target/idl/counter.json", old_program_id.to_string());
+
+        // Test: only the known files that contain the old id are rewritten
+        let changed = replace_program_id(
+            vec![lib_rs.clone(), idl.clone(), unrelated, lib_rs.clone()],
+            old_program_id,
+            new_program_id,
+        )
+        .unwrap()
+        .into_iter()
+        .map(|(path, _)| path)
+        .collect::<Vec<_>>();
+        assert_eq!(changed, vec![idl.clone(), lib_rs.clone()]);
+        assert_eq!(
+            fs::read_to_string(&lib_rs).unwrap(),
+            format!("declare_id!(\"{new_program_id}\");\n")
+        );
+
+        // Test: remaining occurrences are found outside of dependencies and build artifacts
+        assert_eq!(find_program_id(dir.path(), old_program_id), vec![(test, 2)]);
+        assert_eq!(find_program_id(dir.path(), new_program_id).len(), 2);
+    }
+
+    #[test]
+    fn test_rotate_program_id_in_workspace() {
+        let dir = tempdir().unwrap();
+        let old_keypair = Keypair::new();
+        let old_program_id = old_keypair.pubkey();
+        let new_keypair = Keypair::new();
+        let new_program_id = new_keypair.pubkey();
+        let write = |path: &str, content: String| {
+            let path = dir.path().join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, content).unwrap();
+            path
+        };
+
+        let anchor_toml = write(
+            "Anchor.toml",
+            format!(
+                r#"
+[provider]
+cluster = "localnet"
+wallet = "id.json"
+
+[programs.localnet]
+counter = "{old_program_id}"
+
+[clients]
+rust = true
+"#
+            ),
+        );
+        let test_toml = write(
+            "tests/Test.toml",
+            format!(
+                r#"
+[[test.genesis]]
+address = "{old_program_id}"
+program = "counter.so"
+"#
+            ),
+        );
+        let client = write(
+            "clients/rust/src/lib.rs",
+            format!("pub const ID: &str = \"{old_program_id}\";\n"),
+        );
+        let lib_rs = write(
+            "programs/counter/src/lib.rs",
+            format!("declare_id!(\"{old_program_id}\");\n"),
+        );
+        let keypair_path = dir.path().join("target/deploy/counter-keypair.json");
+        let backup_path = dir.path().join(format!(
+            "target/deploy/counter-keypair-{old_program_id}.json"
+        ));
+
+        let cfg = WithPath::new(
+            fs::read_to_string(&anchor_toml).unwrap().parse().unwrap(),
+            anchor_toml.clone(),
+        );
+        let program = Program {
+            lib_name: "counter".into(),
+            path: dir.path().join("programs/counter"),
+            idl: None,
+        };
+        let files = program_id_files(&cfg, &program, &dir.path().join("target"));
+
+        // Test: the files are left untouched when the keypair can't be written
+        assert!(rotate_program_id(
+            files.clone(),
+            &keypair_path,
+            &backup_path,
+            old_program_id,
+            &new_keypair,
+        )
+        .is_err());
+        for path in [&anchor_toml, &test_toml, &client, &lib_rs] {
+            let content = fs::read_to_string(path).unwrap();
+            assert!(content.contains(&old_program_id.to_string()), "{content}");
+        }
+
+        // Test: the config, Test.toml genesis entries, clients and the program are rewritten
+        fs::create_dir_all(keypair_path.parent().unwrap()).unwrap();
+        solana_keypair::write_keypair_file(&old_keypair, &keypair_path).unwrap();
+        let mut changed = rotate_program_id(
+            files,
+            &keypair_path,
+            &backup_path,
+            old_program_id,
+            &new_keypair,
+        )
+        .unwrap();
+        changed.sort();
+        let mut expected = vec![anchor_toml, test_toml, client, lib_rs];
+        expected.sort();
+        assert_eq!(changed, expected);
+        for path in &changed {
+            let content = fs::read_to_string(path).unwrap();
+            assert!(!content.contains(&old_program_id.to_string()), "{content}");
+            assert!(content.contains(&new_program_id.to_string()), "{content}");
+        }
+
+        // Test: the new keypair replaces the old one, which is backed up
+        let read = |path: &Path| solana_keypair::read_keypair_file(path).unwrap().pubkey();
+        assert_eq!(read(&keypair_path), new_program_id);
+        assert_eq!(read(&backup_path), old_program_id);
+    }
+
     #[test]
     fn test_init_accepts_anchor_version() {
         let opts =
@@ -7184,6 +7830,80 @@ mod tests {
         assert_eq!(validator, ValidatorType::Surfpool);
     }
 
+    #[test]
+    #[allow(deprecated)]
+    fn test_deploy_and_upgrade_accept_extend_headroom() {
+        let opts = Opts::try_parse_from(["anchor", "deploy", "--extend-headroom", "20"]).unwrap();
+        let Command::Deploy {
+            extend_headroom, ..
+        } = opts.command
+        else {
+            panic!("expected deploy command");
+        };
+        assert_eq!(extend_headroom, 20);
+
+        let program_id = Pubkey::new_unique().to_string();
+        let opts = Opts::try_parse_from([
+            "anchor",
+            "upgrade",
+            "target/deploy/example.so",
+            "--program-id",
+            &program_id,
+            "--extend-headroom",
+            "20",
+        ])
+        .unwrap();
+        let Command::Upgrade {
+            extend_headroom, ..
+        } = opts.command
+        else {
+            panic!("expected upgrade command");
+        };
+        assert_eq!(extend_headroom, 20);
+
+        assert!(Opts::try_parse_from([
+            "anchor",
+            "deploy",
+            "--plan",
+            "x",
+            "--extend-headroom",
+            "20"
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn test_program_buffers_closes_other_buffers_only_on_request() {
+        let buffer = Pubkey::new_unique();
+        let opts = Opts::try_parse_from([
+            "anchor",
+            "program",
+            "buffers",
+            "--close",
+            "--buffer",
+            &buffer.to_string(),
+        ])
+        .unwrap();
+        let Command::Program {
+            subcmd: ProgramCommand::Buffers { buffers, all, .. },
+        } = opts.command
+        else {
+            panic!("expected program buffers command");
+        };
+        assert_eq!(buffers, [buffer]);
+        assert!(!all);
+
+        for args in [
+            vec!["--all"],
+            vec!["--buffer", &buffer.to_string()],
+            vec!["--close", "--all", "--buffer", &buffer.to_string()],
+            vec!["--close", "--all", "--include-resumable"],
+        ] {
+            let args = ["anchor", "program", "buffers"].into_iter().chain(args);
+            assert!(Opts::try_parse_from(args).is_err());
+        }
+    }
+
     #[test]
     fn test_codama_command_parses() {
         let opts = Opts::try_parse_from([
@@ -1,141 +1,143 @@
 //! Utilities for interacting with the Solana [Program Metadata program](https://github.com/solana-program/program-metadata).
 //! Used for storing program IDLs.
+//!
+//! Commands go through the program-metadata JS client, which reads the keypair file of the wallet
+//! itself. With other wallets, i.e. keystores, Ledger devices and external signers, commands that
+//! write metadata build the program's instructions in-process and sign them with the wallet
+//! signer instead.
 
-use std::{
-    io,
-    process::{Command, ExitStatus},
+use {
+    crate::{
+        create_client,
+        fetch::PMP_METADATA_HEADER_SIZE,
+        prepend_compute_unit_ix,
+        signer::{is_plaintext_keypair, signer_from_path},
+    },
+    anyhow::{anyhow, bail, Context, Result},
+    flate2::{write::ZlibEncoder, Compression},
+    solana_account::Account,
+    solana_instruction::{AccountMeta, Instruction},
+    solana_keypair::Keypair,
+    solana_message::Message,
+    solana_pubkey::{pubkey, Pubkey},
+    solana_rpc_client::rpc_client::RpcClient,
+    solana_signature::Signature,
+    solana_signer::Signer,
+    solana_transaction::Transaction,
+    std::{
+        ffi::OsString,
+        fs,
+        io::{self, Write},
+        path::PathBuf,
+        process::{Command, ExitStatus},
+    },
 };
 
 /// Corresponds to a version of the [program-metadata JS client](https://www.npmjs.com/package/@solana-program/program-metadata).
 const PMP_CLIENT_VERSION: &str = "0.5.1";
 
-pub struct IdlCommand {
-    rpc_url: String,
-    subcommand: IdlSubcommandKind,
-}
+const PROGRAM_METADATA_ID: Pubkey = pubkey!("ProgM6JCCvbYkfKqJYHePx4xxSUSqJp7rh8Lyv7nk7S");
 
-impl IdlCommand {
-    pub fn funded(
-        rpc_url: String,
-        keypair_path: String,
-        priority_fees: Option<u64>,
-        cmd: FundedIdlSubcommand,
-    ) -> Self {
-        let priority_fees_str = priority_fees.map(|f| f.to_string());
-        Self {
-            rpc_url,
-            subcommand: IdlSubcommandKind::Funded {
-                keypair_path,
-                priority_fees_str,
-                cmd,
-            },
-        }
-    }
+/// Seeds are stored zero-padded to this size.
+const SEED_SIZE: usize = 16;
 
-    pub fn unfunded(rpc_url: String, cmd: UnfundedIdlSubcommand) -> Self {
-        Self {
-            rpc_url,
-            subcommand: IdlSubcommandKind::Unfunded(cmd),
-        }
-    }
+/// Buffer and metadata accounts start with a fixed header. Write offsets and extend lengths are
+/// relative to the data that follows it.
+const HEADER_SIZE: usize = PMP_METADATA_HEADER_SIZE;
 
-    pub fn status(self) -> io::Result<ExitStatus> {
-        let mut command = Command::new("npx");
-        // Force on first-time install
-        command.arg("--yes");
-        // Use pinned version
-        command.arg(format!(
-            "--package=@solana-program/program-metadata@{PMP_CLIENT_VERSION}"
-        ));
-        command.arg("--");
-        command.arg("program-metadata");
-        command.args(["--rpc", &self.rpc_url]);
-        command.args(self.subcommand.args());
-        command.status()
-    }
-}
+/// Account discriminators
+const BUFFER_DISCRIMINATOR: u8 = 1;
+const METADATA_DISCRIMINATOR: u8 = 2;
 
-pub enum IdlSubcommandKind {
-    /// IDL commands requiring funding, i.e. those that perform writes
-    Funded {
-        keypair_path: String,
-        priority_fees_str: Option<String>,
-        cmd: FundedIdlSubcommand,
-    },
-    /// IDL commands requiring no funding, i.e. readonly commands
-    Unfunded(UnfundedIdlSubcommand),
-}
+/// Data header of IDLs: UTF-8 encoded JSON, zlib compressed and stored directly in the account,
+/// the same as the program-metadata client writes them.
+const IDL_DATA_HEADER: [u8; 4] = [
+    1, // Encoding: UTF-8
+    2, // Compression: zlib
+    1, // Format: JSON
+    0, // Data source: direct
+];
 
-impl IdlSubcommandKind {
-    fn args(&self) -> Vec<&str> {
-        match self {
-            IdlSubcommandKind::Funded {
-                keypair_path,
-                priority_fees_str,
-                cmd,
-            } => cmd.args(keypair_path, priority_fees_str.as_deref()),
-            IdlSubcommandKind::Unfunded(cmd) => cmd.args(),
-        }
-    }
+/// Bytes per `Write` instruction, which leaves room for a priority fee in the transaction.
+const MAX_WRITE_SIZE: usize = 900;
+
+/// Accounts can grow by at most 10KiB per instruction.
+const MAX_EXTEND_SIZE: usize = 10_240;
+
+/// `Extend` instructions per transaction
+const EXTENDS_PER_TRANSACTION: usize = 8;
+
+const SEND_ATTEMPTS: usize = 5;
+
+#[derive(Clone, Copy)]
+#[repr(u8)]
+enum MetadataInstruction {
+    Write = 0,
+    Initialize = 1,
+    SetAuthority = 2,
+    SetData = 3,
+    Trim = 5,
+    Close = 6,
+    Allocate = 7,
+    Extend = 8,
 }
 
 pub enum FundedIdlSubcommand {
     Write {
-        program_id: String,
-        idl_filepath: String,
+        program_id: Pubkey,
+        idl_filepath: PathBuf,
         non_canonical: bool,
     },
     Close {
-        program_id: String,
+        program_id: Pubkey,
         seed: String,
     },
     CreateBuffer {
-        filepath: String,
+        filepath: PathBuf,
     },
     SetBufferAuthority {
-        buffer: String,
-        new_authority: String,
+        buffer: Pubkey,
+        new_authority: Pubkey,
     },
     WriteBuffer {
-        program_id: String,
-        buffer: String,
+        program_id: Pubkey,
+        buffer: Pubkey,
         seed: String,
         close_buffer: bool,
     },
 }
 
 impl FundedIdlSubcommand {
-    fn args<'a>(&'a self, keypair_path: &'a str, priority_fees: Option<&'a str>) -> Vec<&'a str> {
-        let mut args = vec!["--keypair", keypair_path];
-        if let Some(fees) = priority_fees {
-            args.extend(["--priority-fees", fees]);
-        }
+    /// Arguments of the program-metadata client.
+    fn args(&self) -> Vec<OsString> {
+        let mut args: Vec<OsString> = vec![];
         match self {
             FundedIdlSubcommand::Write {
                 program_id,
-                idl_filepath: filepath,
+                idl_filepath,
                 non_canonical,
             } => {
-                args.extend(["write", "idl", program_id, filepath]);
+                args.extend(["write".into(), "idl".into(), program_id.to_string().into()]);
+                args.push(idl_filepath.into());
                 if *non_canonical {
-                    args.push("--non-canonical");
+                    args.push("--non-canonical".into());
                 }
             }
             FundedIdlSubcommand::Close { program_id, seed } => {
-                args.extend(["close", seed, program_id]);
+                args.extend(["close".into(), seed.into(), program_id.to_string().into()]);
             }
             FundedIdlSubcommand::CreateBuffer { filepath } => {
-                args.extend(["create-buffer", filepath]);
+                args.extend(["create-buffer".into(), filepath.into()]);
             }
             FundedIdlSubcommand::SetBufferAuthority {
                 buffer,
                 new_authority,
             } => {
                 args.extend([
-                    "set-buffer-authority",
-                    buffer,
-                    "--new-authority",
-                    new_authority,
+                    "set-buffer-authority".into(),
+                    buffer.to_string().into(),
+                    "--new-authority".into(),
+                    new_authority.to_string().into(),
                 ]);
             }
             FundedIdlSubcommand::WriteBuffer {
@@ -144,9 +146,15 @@ impl FundedIdlSubcommand {
                 seed,
                 close_buffer,
             } => {
-                args.extend(["write", seed, program_id, "--buffer", buffer]);
+                args.extend([
+                    "write".into(),
+                    seed.into(),
+                    program_id.to_string().into(),
+                    "--buffer".into(),
+                    buffer.to_string().into(),
+                ]);
                 if *close_buffer {
-                    args.push("--close-buffer");
+                    args.push("--close-buffer".into());
                 }
             }
         }
@@ -154,6 +162,645 @@ impl FundedIdlSubcommand {
     }
 }
 
+/// Run the funded IDL command with the wallet.
+///
+/// Plaintext keypair files are passed to the program-metadata client, other wallets sign the
+/// instructions built by [`MetadataClient`].
+pub fn run_funded(
+    rpc_url: String,
+    wallet_path: String,
+    priority_fee: Option<u64>,
+    cmd: FundedIdlSubcommand,
+) -> Result<()> {
+    if !is_plaintext_keypair(&wallet_path) {
+        return MetadataClient::new(rpc_url, &wallet_path, priority_fee)?.run(cmd);
+    }
+
+    let status = IdlCommand::funded(rpc_url, wallet_path, priority_fee, cmd).status()?;
+    if !status.success() {
+        bail!("The program-metadata client failed ({status})");
+    }
+    Ok(())
+}
+
+/// Signs and sends the instructions of the funded IDL commands with the wallet.
+struct MetadataClient {
+    client: RpcClient,
+    wallet: Box<dyn Signer>,
+    priority_fee: Option<u64>,
+}
+
+impl MetadataClient {
+    fn new(rpc_url: String, wallet_path: &str, priority_fee: Option<u64>) -> Result<Self> {
+        Ok(Self {
+            client: create_client(rpc_url),
+            wallet: signer_from_path(wallet_path, "wallet")?,
+            priority_fee,
+        })
+    }
+
+    fn run(&self, cmd: FundedIdlSubcommand) -> Result<()> {
+        match cmd {
+            FundedIdlSubcommand::Write {
+                program_id,
+                idl_filepath,
+                non_canonical,
+            } => {
+                let idl = fs::read(&idl_filepath)
+                    .with_context(|| format!("Failed to read {}", idl_filepath.display()))?;
+                let data = compress(&idl)?;
+                self.write_metadata(&program_id, "idl", !non_canonical, Source::Data(&data))
+            }
+            FundedIdlSubcommand::Close { program_id, seed } => {
+                let metadata = metadata_address(&program_id, None, &seed)?;
+                let accounts = ProgramAccounts::new(program_id, true);
+                self.send(
+                    vec![close_ix(
+                        &metadata,
+                        &self.wallet.pubkey(),
+                        &accounts,
+                        &self.wallet.pubkey(),
+                    )],
+                    &[],
+                )?;
+                Ok(())
+            }
+            FundedIdlSubcommand::CreateBuffer { filepath } => {
+                let data = fs::read(&filepath)
+                    .with_context(|| format!("Failed to read {}", filepath.display()))?;
+                let buffer = self.create_buffer(&compress(&data)?)?;
+                println!("Buffer: {buffer}");
+                Ok(())
+            }
+            FundedIdlSubcommand::SetBufferAuthority {
+                buffer,
+                new_authority,
+            } => {
+                self.send(
+                    vec![set_authority_ix(
+                        &buffer,
+                        &self.wallet.pubkey(),
+                        &ProgramAccounts::none(),
+                        Some(&new_authority),
+                    )],
+                    &[],
+                )?;
+                Ok(())
+            }
+            FundedIdlSubcommand::WriteBuffer {
+                program_id,
+                buffer,
+                seed,
+                close_buffer,
+            } => {
+                let account = self
+                    .get_account(&buffer)?
+                    .filter(|account| account.owner == PROGRAM_METADATA_ID)
+                    .ok_or_else(|| anyhow!("Buffer {buffer} does not exist"))?;
+                if account.data.first() != Some(&BUFFER_DISCRIMINATOR) {
+                    bail!("Account {buffer} is not a metadata buffer");
+                }
+
+                let len = account.data.len().saturating_sub(HEADER_SIZE);
+                self.write_metadata(&program_id, &seed, true, Source::Buffer(buffer, len))?;
+                if close_buffer {
+                    self.close_buffer(&buffer)?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Create or update the metadata account of the program with the given data.
+    fn write_metadata(
+        &self,
+        program_id: &Pubkey,
+        seed: &str,
+        canonical: bool,
+        source: Source,
+    ) -> Result<()> {
+        let authority = self.wallet.pubkey();
+        let metadata = metadata_address(program_id, (!canonical).then_some(&authority), seed)?;
+        let accounts = ProgramAccounts::new(*program_id, canonical);
+        let account = self
+            .get_account(&metadata)?
+            .filter(|account| account.owner == PROGRAM_METADATA_ID);
+
+        match account {
+            Some(account) if account.data.first() == Some(&METADATA_DISCRIMINATOR) => {
+                self.update_metadata(&metadata, &account, &accounts, source)
+            }
+            // Left allocated by an interrupted write, pick up from there
+            account => {
+                let allocated = account.is_some();
+                let capacity = account
+                    .as_ref()
+                    .map_or(0, |account| account.data.len().saturating_sub(HEADER_SIZE));
+                self.initialize_metadata(&metadata, seed, allocated, capacity, &accounts, source)
+            }
+        }
+    }
+
+    /// Allocate the metadata account, write the data into it, and initialize it.
+    fn initialize_metadata(
+        &self,
+        metadata: &Pubkey,
+        seed: &str,
+        allocated: bool,
+        capacity: usize,
+        accounts: &ProgramAccounts,
+        source: Source,
+    ) -> Result<()> {
+        let authority = self.wallet.pubkey();
+        let mut instructions = self.fund_ix(metadata, source.len())?;
+        if !allocated {
+            instructions.push(allocate_ix(metadata, &authority, accounts, Some(seed))?);
+        }
+        self.extend(metadata, capacity, source.len(), accounts, instructions)?;
+
+        match source {
+            Source::Data(data) => self.write_data(metadata, data)?,
+            Source::Buffer(buffer, _) => {
+                self.send(
+                    vec![write_ix(metadata, &authority, Some(&buffer), 0, &[])],
+                    &[],
+                )?;
+            }
+        }
+
+        self.send(
+            vec![initialize_ix(metadata, &authority, accounts, seed)?],
+            &[],
+        )?;
+        Ok(())
+    }
+
+    /// Replace the data of an initialized metadata account through a buffer.
+    fn update_metadata(
+        &self,
+        metadata: &Pubkey,
+        account: &Account,
+        accounts: &ProgramAccounts,
+        source: Source,
+    ) -> Result<()> {
+        let authority = self.wallet.pubkey();
+        let capacity = account.data.len().saturating_sub(HEADER_SIZE);
+        let instructions = self.fund_ix(metadata, source.len())?;
+        self.extend(metadata, capacity, source.len(), accounts, instructions)?;
+
+        let (buffer, temporary) = match source {
+            Source::Data(data) => (self.create_buffer(data)?, true),
+            Source::Buffer(buffer, _) => (buffer, false),
+        };
+
+        let mut instructions = vec![set_data_ix(metadata, &authority, &buffer, accounts)];
+        if source.len() < capacity {
+            instructions.push(trim_ix(metadata, &authority, accounts, &authority));
+        }
+        self.send(instructions, &[])?;
+
+        if temporary {
+            self.close_buffer(&buffer)?;
+        }
+        Ok(())
+    }
+
+    /// Create a buffer account owned by the wallet that holds the given data.
+    fn create_buffer(&self, data: &[u8]) -> Result<Pubkey> {
+        let payer = self.wallet.pubkey();
+        let buffer = Keypair::new();
+        let space = HEADER_SIZE + data.len();
+        let lamports = self.client.get_minimum_balance_for_rent_exemption(space)?;
+
+        // Buffers are allocated with themselves as the authority, which is then handed over to
+        // the wallet so that the buffer keypair isn't needed anymore
+        let none = ProgramAccounts::none();
+        self.send(
+            vec![
+                solana_system_interface::instruction::create_account(
+                    &payer,
+                    &buffer.pubkey(),
+                    lamports,
+                    space as u64,
+                    &PROGRAM_METADATA_ID,
+                ),
+                allocate_ix(&buffer.pubkey(), &buffer.pubkey(), &none, None)?,
+                set_authority_ix(&buffer.pubkey(), &buffer.pubkey(), &none, Some(&payer)),
+            ],
+            &[&buffer],
+        )?;
+
+        self.write_data(&buffer.pubkey(), data)?;
+        Ok(buffer.pubkey())
+    }
+
+    fn close_buffer(&self, buffer: &Pubkey) -> Result<()> {
+        let authority = self.wallet.pubkey();
+        self.send(
+            vec![close_ix(
+                buffer,
+                &authority,
+                &ProgramAccounts::none(),
+                &authority,
+            )],
+            &[],
+        )?;
+        Ok(())
+    }
+
+    /// Transfer the lamports the account needs to stay rent exempt with `len` bytes of data.
+    fn fund_ix(&self, account: &Pubkey, len: usize) -> Result<Vec<Instruction>> {
+        let rent = self
+            .client
+            .get_minimum_balance_for_rent_exemption(HEADER_SIZE + len)?;
+        let balance = self
+            .get_account(account)?
+            .map_or(0, |account| account.lamports);
+        Ok((rent > balance)
+            .then(|| {
+                solana_system_interface::instruction::transfer(
+                    &self.wallet.pubkey(),
+                    account,
+                    rent - balance,
+                )
+            })
+            .into_iter()
+            .collect())
+    }
+
+    /// Grow the account from `capacity` to `len` bytes of data, sending `instructions` along with
+    /// the first extension.
+    fn extend(
+        &self,
+        account: &Pubkey,
+        capacity: usize,
+        len: usize,
+        accounts: &ProgramAccounts,
+        mut instructions: Vec<Instruction>,
+    ) -> Result<()> {
+        let authority = self.wallet.pubkey();
+        let extends = (capacity..len)
+            .step_by(MAX_EXTEND_SIZE)
+            .map(|start| {
+                extend_ix(
+                    account,
+                    &authority,
+                    accounts,
+                    (len - start).min(MAX_EXTEND_SIZE),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let mut chunks = extends.chunks(EXTENDS_PER_TRANSACTION);
+        instructions.extend(chunks.next().unwrap_or_default().iter().cloned());
+        if !instructions.is_empty() {
+            self.send(instructions, &[])?;
+        }
+        for chunk in chunks {
+            self.send(chunk.to_vec(), &[])?;
+        }
+        Ok(())
+    }
+
+    fn write_data(&self, account: &Pubkey, data: &[u8]) -> Result<()> {
+        let authority = self.wallet.pubkey();
+        for (i, chunk) in data.chunks(MAX_WRITE_SIZE).enumerate() {
+            let offset = i * MAX_WRITE_SIZE;
+            println!("Step {offset}/{} ", data.len());
+            self.send(
+                vec![write_ix(account, &authority, None, offset as u32, chunk)],
+                &[],
+            )?;
+        }
+        Ok(())
+    }
+
+    fn get_account(&self, address: &Pubkey) -> Result<Option<Account>> {
+        Ok(self
+            .client
+            .get_account_with_commitment(address, self.client.commitment())?
+            .value)
+    }
+
+    /// Send the instructions with the wallet as the fee payer, retrying with a new blockhash on
+    /// failure.
+    fn send(&self, instructions: Vec<Instruction>, signers: &[&dyn Signer]) -> Result<Signature> {
+        let write_locked = instructions
+            .iter()
+            .flat_map(|ix| &ix.accounts)
+            .filter(|meta| meta.is_writable)
+            .map(|meta| meta.pubkey)
+            .collect::<Vec<_>>();
+        let instructions =
+            prepend_compute_unit_ix(instructions, &self.client, self.priority_fee, &write_locked);
+        let message = Message::new(&instructions, Some(&self.wallet.pubkey()));
+        let mut signers = signers.to_vec();
+        signers.push(&*self.wallet);
+
+        let mut attempt = 1;
+        loop {
+            let blockhash = self.client.get_latest_blockhash()?;
+            let mut tx = Transaction::new_unsigned(message.clone());
+            tx.try_sign(&signers, blockhash)?;
+            match self.client.send_and_confirm_transaction_with_spinner(&tx) {
+                Ok(signature) => return Ok(signature),
+                Err(e) if attempt < SEND_ATTEMPTS => {
+                    println!("Error: {e}. Retrying transaction.");
+                    attempt += 1;
+                }
+                Err(e) => return Err(anyhow!("Error: {e}. Failed to send transaction.")),
+            }
+        }
+    }
+}
+
+/// Where the data of a metadata account comes from.
+#[derive(Clone, Copy)]
+enum Source<'a> {
+    Data(&'a [u8]),
+    /// Buffer account and the length of its data
+    Buffer(Pubkey, usize),
+}
+
+impl Source<'_> {
+    fn len(&self) -> usize {
+        match self {
+            Self::Data(data) => data.len(),
+            Self::Buffer(_, len) => *len,
+        }
+    }
+}
+
+/// Program accounts of metadata instructions. The `ProgramData` account makes the authority's
+/// metadata canonical, and is omitted for non-canonical metadata and buffers.
+struct ProgramAccounts {
+    program: Option<Pubkey>,
+    program_data: Option<Pubkey>,
+}
+
+impl ProgramAccounts {
+    fn new(program_id: Pubkey, canonical: bool) -> Self {
+        let program_data = Pubkey::find_program_address(
+            &[program_id.as_ref()],
+            &solana_sdk_ids::bpf_loader_upgradeable::ID,
+        )
+        .0;
+        Self {
+            program: Some(program_id),
+            program_data: canonical.then_some(program_data),
+        }
+    }
+
+    fn none() -> Self {
+        Self {
+            program: None,
+            program_data: None,
+        }
+    }
+
+    fn metas(&self) -> [AccountMeta; 2] {
+        [optional(self.program), optional(self.program_data)]
+    }
+}
+
+/// Optional accounts that are omitted are passed as the program id.
+fn optional(account: Option<Pubkey>) -> AccountMeta {
+    AccountMeta::new_readonly(account.unwrap_or(PROGRAM_METADATA_ID), false)
+}
+
+/// Metadata accounts are derived from the program, the authority for non-canonical metadata,
+/// and the seed.
+fn metadata_address(program_id: &Pubkey, authority: Option<&Pubkey>, seed: &str) -> Result<Pubkey> {
+    let seed = padded_seed(seed)?;
+    let authority_seed = authority.map(|key| key.as_ref()).unwrap_or_default();
+    Ok(Pubkey::find_program_address(
+        &[program_id.as_ref(), authority_seed, &seed],
+        &PROGRAM_METADATA_ID,
+    )
+    .0)
+}
+
+fn padded_seed(seed: &str) -> Result<[u8; SEED_SIZE]> {
+    if seed.len() > SEED_SIZE {
+        bail!("Metadata seed `{seed}` is longer than {SEED_SIZE} bytes");
+    }
+    let mut padded = [0; SEED_SIZE];
+    padded[..seed.len()].copy_from_slice(seed.as_bytes());
+    Ok(padded)
+}
+
+fn compress(data: &[u8]) -> Result<Vec<u8>> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(data)?;
+    Ok(encoder.finish()?)
+}
+
+fn metadata_ix(
+    kind: MetadataInstruction,
+    accounts: Vec<AccountMeta>,
+    args: &[&[u8]],
+) -> Instruction {
+    let mut data = vec![kind as u8];
+    data.extend(args.iter().copied().flatten());
+    Instruction {
+        program_id: PROGRAM_METADATA_ID,
+        accounts,
+        data,
+    }
+}
+
+fn write_ix(
+    account: &Pubkey,
+    authority: &Pubkey,
+    source: Option<&Pubkey>,
+    offset: u32,
+    bytes: &[u8],
+) -> Instruction {
+    metadata_ix(
+        MetadataInstruction::Write,
+        vec![
+            AccountMeta::new(*account, false),
+            AccountMeta::new_readonly(*authority, true),
+            optional(source.copied()),
+        ],
+        &[&offset.to_le_bytes(), bytes],
+    )
+}
+
+fn allocate_ix(
+    account: &Pubkey,
+    authority: &Pubkey,
+    accounts: &ProgramAccounts,
+    seed: Option<&str>,
+) -> Result<Instruction> {
+    let seed = seed.map(padded_seed).transpose()?;
+    let mut metas = vec![
+        AccountMeta::new(*account, false),
+        AccountMeta::new_readonly(*authority, true),
+    ];
+    metas.extend(accounts.metas());
+    // Only PDAs are allocated by the program, buffers are created beforehand
+    metas.push(optional(
+        seed.is_some().then_some(solana_sdk_ids::system_program::ID),
+    ));
+    Ok(metadata_ix(
+        MetadataInstruction::Allocate,
+        metas,
+        &[seed.as_ref().map_or(&[][..], |seed| &seed[..])],
+    ))
+}
+
+fn extend_ix(
+    account: &Pubkey,
+    authority: &Pubkey,
+    accounts: &ProgramAccounts,
+    len: usize,
+) -> Instruction {
+    let mut metas = vec![
+        AccountMeta::new(*account, false),
+        AccountMeta::new_readonly(*authority, true),
+    ];
+    metas.extend(accounts.metas());
+    metadata_ix(
+        MetadataInstruction::Extend,
+        metas,
+        &[&(len as u16).to_le_bytes()],
+    )
+}
+
+fn initialize_ix(
+    metadata: &Pubkey,
+    authority: &Pubkey,
+    accounts: &ProgramAccounts,
+    seed: &str,
+) -> Result<Instruction> {
+    let mut metas = vec![
+        AccountMeta::new(*metadata, false),
+        AccountMeta::new_readonly(*authority, true),
+    ];
+    metas.extend(accounts.metas());
+    metas.push(AccountMeta::new_readonly(
+        solana_sdk_ids::system_program::ID,
+        false,
+    ));
+    // Without inline data, the data written to the allocated account is used
+    Ok(metadata_ix(
+        MetadataInstruction::Initialize,
+        metas,
+        &[&padded_seed(seed)?, &IDL_DATA_HEADER],
+    ))
+}
+
+fn set_data_ix(
+    metadata: &Pubkey,
+    authority: &Pubkey,
+    buffer: &Pubkey,
+    accounts: &ProgramAccounts,
+) -> Instruction {
+    let mut metas = vec![
+        AccountMeta::new(*metadata, false),
+        AccountMeta::new_readonly(*authority, true),
+        AccountMeta::new_readonly(*buffer, false),
+    ];
+    metas.extend(accounts.metas());
+    metadata_ix(MetadataInstruction::SetData, metas, &[&IDL_DATA_HEADER])
+}
+
+fn set_authority_ix(
+    account: &Pubkey,
+    authority: &Pubkey,
+    accounts: &ProgramAccounts,
+    new_authority: Option<&Pubkey>,
+) -> Instruction {
+    let mut metas = vec![
+        AccountMeta::new(*account, false),
+        AccountMeta::new_readonly(*authority, true),
+    ];
+    metas.extend(accounts.metas());
+    // The default pubkey removes the authority
+    metadata_ix(
+        MetadataInstruction::SetAuthority,
+        metas,
+        &[new_authority.copied().unwrap_or_default().as_ref()],
+    )
+}
+
+fn trim_ix(
+    account: &Pubkey,
+    authority: &Pubkey,
+    accounts: &ProgramAccounts,
+    destination: &Pubkey,
+) -> Instruction {
+    let mut metas = vec![
+        AccountMeta::new(*account, false),
+        AccountMeta::new_readonly(*authority, true),
+    ];
+    metas.extend(accounts.metas());
+    metas.push(AccountMeta::new(*destination, false));
+    metas.push(AccountMeta::new_readonly(
+        solana_sdk_ids::sysvar::rent::ID,
+        false,
+    ));
+    metadata_ix(MetadataInstruction::Trim, metas, &[])
+}
+
+fn close_ix(
+    account: &Pubkey,
+    authority: &Pubkey,
+    accounts: &ProgramAccounts,
+    destination: &Pubkey,
+) -> Instruction {
+    let mut metas = vec![
+        AccountMeta::new(*account, false),
+        AccountMeta::new_readonly(*authority, true),
+    ];
+    metas.extend(accounts.metas());
+    metas.push(AccountMeta::new(*destination, false));
+    metadata_ix(MetadataInstruction::Close, metas, &[])
+}
+
+/// IDL commands run through the program-metadata JS client.
+pub struct IdlCommand {
+    rpc_url: String,
+    args: Vec<OsString>,
+}
+
+impl IdlCommand {
+    fn funded(
+        rpc_url: String,
+        keypair_path: String,
+        priority_fee: Option<u64>,
+        cmd: FundedIdlSubcommand,
+    ) -> Self {
+        let mut args: Vec<OsString> = vec!["--keypair".into(), keypair_path.into()];
+        if let Some(fee) = priority_fee {
+            args.extend(["--priority-fees".into(), fee.to_string().into()]);
+        }
+        args.extend(cmd.args());
+        Self { rpc_url, args }
+    }
+
+    pub fn unfunded(rpc_url: String, cmd: UnfundedIdlSubcommand) -> Self {
+        let args = cmd.args().into_iter().map(Into::into).collect();
+        Self { rpc_url, args }
+    }
+
+    pub fn status(self) -> io::Result<ExitStatus> {
+        let mut command = Command::new("npx");
+        // Force on first-time install
+        command.arg("--yes");
+        // Use pinned version
+        command.arg(format!(
+            "--package=@solana-program/program-metadata@{PMP_CLIENT_VERSION}"
+        ));
+        command.arg("--");
+        command.arg("program-metadata");
+        command.args(["--rpc", &self.rpc_url]);
+        command.args(&self.args);
+        command.status()
+    }
+}
+
 pub enum UnfundedIdlSubcommand {
     Fetch {
         program_id: String,
@@ -183,3 +830,138 @@ impl UnfundedIdlSubcommand {
         args
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::fetch::pmp_metadata_address};
+
+    #[test]
+    fn idl_metadata_address_matches_fetch() {
+        let program_id = Pubkey::new_unique();
+        let authority = Pubkey::new_unique();
+        assert_eq!(
+            metadata_address(&program_id, None, "idl").unwrap(),
+            pmp_metadata_address(&program_id, None)
+        );
+        assert_eq!(
+            metadata_address(&program_id, Some(&authority), "idl").unwrap(),
+            pmp_metadata_address(&program_id, Some(&authority))
+        );
+        assert!(metadata_address(&program_id, None, "a-seed-over-16-bytes").is_err());
+    }
+
+    #[test]
+    fn write_ix_encodes_offset_and_pads_source() {
+        let account = Pubkey::new_unique();
+        let authority = Pubkey::new_unique();
+        let ix = write_ix(&account, &authority, None, 900, b"idl");
+
+        assert_eq!(ix.data, [0, 0x84, 0x03, 0, 0, b'i', b'd', b'l']);
+        assert_eq!(
+            ix.accounts,
+            [
+                AccountMeta::new(account, false),
+                AccountMeta::new_readonly(authority, true),
+                AccountMeta::new_readonly(PROGRAM_METADATA_ID, false),
+            ]
+        );
+    }
+
+    #[test]
+    fn initialize_ix_encodes_seed_and_data_header() {
+        let program_id = Pubkey::new_unique();
+        let metadata = metadata_address(&program_id, None, "idl").unwrap();
+        let authority = Pubkey::new_unique();
+        let accounts = ProgramAccounts::new(program_id, true);
+        let ix = initialize_ix(&metadata, &authority, &accounts, "idl").unwrap();
+
+        let mut data = vec![MetadataInstruction::Initialize as u8];
+        data.extend(b"idl");
+        data.extend([0; SEED_SIZE - 3]);
+        data.extend([1, 2, 1, 0]);
+        assert_eq!(ix.data, data);
+        assert_eq!(ix.accounts[2].pubkey, program_id);
+        assert_ne!(ix.accounts[3].pubkey, PROGRAM_METADATA_ID);
+        assert_eq!(ix.accounts[4].pubkey, solana_sdk_ids::system_program::ID);
+
+        // Non-canonical metadata omits the `ProgramData` account
+        let accounts = ProgramAccounts::new(program_id, false);
+        let ix = initialize_ix(&metadata, &authority, &accounts, "idl").unwrap();
+        assert_eq!(ix.accounts[3].pubkey, PROGRAM_METADATA_ID);
+    }
+
+    #[test]
+    fn set_data_ix_reads_from_buffer() {
+        let metadata = Pubkey::new_unique();
+        let authority = Pubkey::new_unique();
+        let buffer = Pubkey::new_unique();
+        let accounts = ProgramAccounts::new(Pubkey::new_unique(), true);
+        let ix = set_data_ix(&metadata, &authority, &buffer, &accounts);
+
+        assert_eq!(ix.data, [MetadataInstruction::SetData as u8, 1, 2, 1, 0]);
+        assert_eq!(ix.accounts[2], AccountMeta::new_readonly(buffer, false));
+    }
+
+    #[test]
+    fn funded_commands_pass_keypair_and_fees_to_the_client() {
+        let program_id = Pubkey::new_unique();
+        let buffer = Pubkey::new_unique();
+        let command = IdlCommand::funded(
+            "http://localhost:8899".into(),
+            "id.json".into(),
+            Some(100),
+            FundedIdlSubcommand::WriteBuffer {
+                program_id,
+                buffer,
+                seed: "idl".into(),
+                close_buffer: true,
+            },
+        );
+        let expected = [
+            "--keypair".into(),
+            "id.json".into(),
+            "--priority-fees".into(),
+            "100".into(),
+            "write".into(),
+            "idl".into(),
+            program_id.to_string(),
+            "--buffer".into(),
+            buffer.to_string(),
+            "--close-buffer".into(),
+        ];
+        assert_eq!(command.args, expected.map(OsString::from));
+
+        let command = IdlCommand::funded(
+            "http://localhost:8899".into(),
+            "id.json".into(),
+            None,
+            FundedIdlSubcommand::Write {
+                program_id,
+                idl_filepath: "target/idl/example.json".into(),
+                non_canonical: true,
+            },
+        );
+        let expected = [
+            "--keypair".into(),
+            "id.json".into(),
+            "write".into(),
+            "idl".into(),
+            program_id.to_string(),
+            "target/idl/example.json".into(),
+            "--non-canonical".into(),
+        ];
+        assert_eq!(command.args, expected.map(OsString::from));
+    }
+
+    #[test]
+    fn extend_ix_grows_by_len() {
+        let account = Pubkey::new_unique();
+        let ix = extend_ix(
+            &account,
+            &Pubkey::new_unique(),
+            &ProgramAccounts::none(),
+            MAX_EXTEND_SIZE,
+        );
+        assert_eq!(ix.data, [MetadataInstruction::Extend as u8, 0x00, 0x28]);
+    }
+}
@@ -1,11 +1,17 @@
 use {
     crate::{
         config::{Config, Program, WithPath},
+        fetch::{pmp_metadata_address, PMP_METADATA_HEADER_SIZE},
+        signer::signer_from_path,
         target_dir, ConfigOverride, ProgramCommand, DEFAULT_MAX_SIGN_ATTEMPTS,
     },
     anchor_lang_idl::types::Idl,
     anyhow::{anyhow, bail, Result},
+    base64::{engine::general_purpose::STANDARD, Engine},
     cargo_metadata::{Metadata, MetadataCommand, Package, TargetKind},
+    flate2::{write::ZlibEncoder, Compression},
+    serde::{Deserialize, Serialize},
+    sha2::{Digest, Sha256},
     solana_cli_config::Config as SolanaCliConfig,
     solana_client::{
         connection_cache::ConnectionCache,
@@ -27,7 +33,11 @@ use {
     solana_pubkey::Pubkey,
     solana_rpc_client::rpc_client::RpcClient,
     solana_rpc_client_api::{
-        config::{RpcSendTransactionConfig, RpcSimulateTransactionConfig},
+        config::{
+            RpcAccountInfoConfig, RpcProgramAccountsConfig, RpcSendTransactionConfig,
+            RpcSimulateTransactionConfig, UiAccountEncoding, UiDataSliceConfig,
+        },
+        filter::{Memcmp, RpcFilterType},
         response::RpcSimulateTransactionResult,
     },
     solana_sdk_ids::{
@@ -36,6 +46,7 @@ use {
     },
     solana_signature::Signature,
     solana_signer::{EncodableKey, Signer},
+    solana_system_interface::MAX_PERMITTED_DATA_LENGTH,
     solana_transaction::Transaction,
     std::{
         collections::{BTreeMap, HashSet},
@@ -96,7 +107,7 @@ fn ensure_buffer_keypair_arg(mut args: Vec<String>, program_name: &str) -> Resul
 }
 
 /// Parse priority fee from solana args
-fn parse_priority_fee_from_args(args: &[String]) -> Option<u64> {
+pub(crate) fn parse_priority_fee_from_args(args: &[String]) -> Option<u64> {
     args.windows(2)
         .find(|pair| pair[0] == "--with-compute-unit-price")
         .and_then(|pair| pair[1].parse().ok())
@@ -128,7 +139,7 @@ fn read_buffer_keypair_from_args(args: &[String]) -> Result<Option<Keypair>> {
     let Some(path) = parse_buffer_keypair_path_from_args(args) else {
         return Ok(None);
     };
-    let kp = Keypair::read_from_file(&path).map_err(|e| {
+    let kp = crate::get_keypair(&path).map_err(|e| {
         anyhow!(
             "Failed to read buffer keypair from {}: {}",
             path.display(),
@@ -252,9 +263,9 @@ fn wait_for_buffer_stable(
 /// Close an undersized buffer we own so the next attempt re-creates it at the correct size.
 fn close_buffer_for_resize(
     rpc_client: &RpcClient,
-    payer: &Keypair,
+    payer: &dyn Signer,
     buffer_pubkey: &Pubkey,
-    authority: &Keypair,
+    authority: &dyn Signer,
     priority_fee: Option<u64>,
     skip_preflight: bool,
 ) -> Result<()> {
@@ -294,6 +305,278 @@ fn close_buffer_for_resize(
     Ok(())
 }
 
+/// Local record of an in-progress deploy/upgrade, kept at
+/// `target/deploy/{program_name}-deploy-journal.json` until the program is
+/// deployed. It is written before the buffer is created so the buffer address
+/// survives a crash, and refreshed with the chunks confirmed on-chain after
+/// every attempt. Buffers of earlier deploys that were replaced by a new one
+/// stay in the journal until `anchor program buffers --close` reclaims them.
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+struct DeployJournal {
+    program_id: String,
+    /// Buffer of the interrupted deploy, `None` once it has been closed.
+    buffer: Option<String>,
+    /// SHA-256 of the whole program binary.
+    program_hash: String,
+    program_len: usize,
+    chunk_size: usize,
+    /// SHA-256 of each chunk that has been confirmed in the buffer, `None` if
+    /// the chunk still needs to be written.
+    chunks: Vec<Option<String>>,
+    /// Buffers of earlier deploys of this program that have not been closed.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    superseded: Vec<String>,
+}
+
+impl DeployJournal {
+    fn path(program_name: &str) -> Result<PathBuf> {
+        Ok(target_dir()?
+            .join("deploy")
+            .join(format!("{program_name}-deploy-journal.json")))
+    }
+
+    fn new(program_id: &Pubkey, buffer: &Pubkey, program_data: &[u8], chunk_size: usize) -> Self {
+        Self {
+            program_id: program_id.to_string(),
+            buffer: Some(buffer.to_string()),
+            program_hash: sha256_hex(program_data),
+            program_len: program_data.len(),
+            chunk_size,
+            chunks: vec![None; program_data.len().div_ceil(chunk_size.max(1))],
+            superseded: Vec::new(),
+        }
+    }
+
+    /// `Ok(None)` if there is no journal at `path`.
+    fn load(path: &Path) -> Result<Option<Self>> {
+        if !path.exists() {
+            return Ok(None);
+        }
+        let content = fs::read_to_string(path)
+            .map_err(|e| anyhow!("Failed to read deploy journal {}: {}", path.display(), e))?;
+        let journal = serde_json::from_str(&content)
+            .map_err(|e| anyhow!("Failed to parse deploy journal {}: {}", path.display(), e))?;
+        Ok(Some(journal))
+    }
+
+    fn save(&self, path: &Path) -> Result<()> {
+        let content = serde_json::to_string_pretty(self)?;
+        fs::write(path, content)
+            .map_err(|e| anyhow!("Failed to write deploy journal {}: {}", path.display(), e))
+    }
+
+    fn remove(path: &Path) -> Result<()> {
+        if path.exists() {
+            fs::remove_file(path).map_err(|e| {
+                anyhow!("Failed to remove deploy journal {}: {}", path.display(), e)
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Record which chunks of `program_data` are present in the on-chain
+    /// `buffer_data`. Chunks whose bytes no longer match are forgotten.
+    fn record(&mut self, program_data: &[u8], buffer_data: &[u8]) {
+        let chunk_size = self.chunk_size.max(1);
+        self.chunks = program_data
+            .chunks(chunk_size)
+            .enumerate()
+            .map(|(i, chunk)| {
+                let start = i.saturating_mul(chunk_size);
+                let written = buffer_data
+                    .get(start..start.saturating_add(chunk.len()))
+                    .is_some_and(|on_chain| on_chain == chunk);
+                written.then(|| sha256_hex(chunk))
+            })
+            .collect();
+    }
+
+    /// Mark every chunk of `program_data` as written.
+    fn record_all(&mut self, program_data: &[u8]) {
+        self.record(program_data, program_data);
+    }
+
+    /// Whether each chunk of `program_data` is already in the buffer, i.e.
+    /// its hash matches the one recorded for it.
+    fn written(&self, program_data: &[u8]) -> Vec<bool> {
+        program_data
+            .chunks(self.chunk_size.max(1))
+            .enumerate()
+            .map(|(i, chunk)| {
+                self.chunks
+                    .get(i)
+                    .and_then(Option::as_deref)
+                    .is_some_and(|hash| hash == sha256_hex(chunk))
+            })
+            .collect()
+    }
+
+    fn written_chunks(&self) -> usize {
+        self.chunks.iter().filter(|chunk| chunk.is_some()).count()
+    }
+
+    /// Forget `buffer` once it has been closed or consumed by a deploy.
+    /// Returns whether the journal still tracks any buffer.
+    fn forget(&mut self, buffer: &str) -> bool {
+        self.superseded.retain(|superseded| superseded != buffer);
+        if self.buffer.as_deref() == Some(buffer) {
+            self.buffer = None;
+            self.chunks.fill(None);
+        }
+        self.buffer.is_some() || !self.superseded.is_empty()
+    }
+
+    /// Forget `buffer` and save the journal, or remove it if nothing is left
+    /// to track.
+    fn forget_and_save(mut self, buffer: &str, path: &Path) -> Result<()> {
+        if self.forget(buffer) {
+            self.save(path)
+        } else {
+            Self::remove(path)
+        }
+    }
+}
+
+pub(crate) fn sha256_hex(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+/// Start the journal of a deploy into `buffer`, carrying over the journal of
+/// an interrupted deploy (if any): its chunk hashes when it used the same
+/// buffer, and its buffers as superseded otherwise.
+fn start_deploy_journal(
+    path: &Path,
+    program_id: &Pubkey,
+    buffer: &Pubkey,
+    program_data: &[u8],
+    chunk_size: usize,
+) -> Result<DeployJournal> {
+    let mut journal = DeployJournal::new(program_id, buffer, program_data, chunk_size);
+    if let Some(previous) = DeployJournal::load(path)? {
+        journal.superseded = previous.superseded;
+        match previous.buffer {
+            Some(previous_buffer) if journal.buffer.as_ref() != Some(&previous_buffer) => {
+                println!(
+                    "Buffer {} of the interrupted deploy is superseded by {}. Use `anchor \
+                     program buffers --close` to reclaim its rent.",
+                    previous_buffer, buffer
+                );
+                journal.superseded.push(previous_buffer);
+            }
+            Some(_)
+                if previous.chunk_size == chunk_size
+                    && previous.program_len == program_data.len() =>
+            {
+                journal.chunks = previous.chunks;
+                if previous.program_hash == journal.program_hash {
+                    println!(
+                        "Resuming deploy into buffer {} ({}/{} chunks written)",
+                        buffer,
+                        journal.written_chunks(),
+                        journal.chunks.len()
+                    );
+                } else {
+                    println!(
+                        "Program binary changed since the interrupted deploy into buffer {}; \
+                         only the chunks that differ will be rewritten.",
+                        buffer
+                    );
+                }
+            }
+            // Nothing to resume: the chunk layout changed or the buffer was closed
+            _ => {}
+        }
+        journal
+            .superseded
+            .retain(|superseded| journal.buffer.as_ref() != Some(superseded));
+    }
+
+    journal.save(path)?;
+    Ok(journal)
+}
+
+/// Buffer of the interrupted deploy recorded at `path` if it is not `buffer`
+/// but can still be resumed: it is a buffer of `authority` for `program_id`
+/// sized for the current binary.
+fn journaled_resume_buffer(
+    rpc_client: &RpcClient,
+    path: &Path,
+    program_id: &Pubkey,
+    buffer: &Pubkey,
+    authority: &Pubkey,
+    program_len: usize,
+) -> Result<Option<Pubkey>> {
+    let Some(journal) = DeployJournal::load(path)? else {
+        return Ok(None);
+    };
+    let Some(journaled) = journal.buffer else {
+        return Ok(None);
+    };
+    if journal.program_id != program_id.to_string() || journaled == buffer.to_string() {
+        return Ok(None);
+    }
+    let journaled: Pubkey = journaled
+        .parse()
+        .map_err(|e| anyhow!("Invalid buffer in deploy journal {}: {}", path.display(), e))?;
+    let resumable = rpc_client
+        .get_account_with_commitment(&journaled, CommitmentConfig::confirmed())?
+        .value
+        .is_some_and(|account| {
+            account.owner == bpf_loader_upgradeable_id::id()
+                && account.data.starts_with(&buffer_header(authority))
+                && account.data.len() == UpgradeableLoaderState::size_of_buffer(program_len)
+        });
+    Ok(resumable.then_some(journaled))
+}
+
+/// A buffer recorded in one of the deploy journals in `target/deploy`.
+struct JournaledBuffer {
+    program_name: String,
+    /// Whether it is the buffer of the interrupted deploy rather than a
+    /// superseded one.
+    resumable: bool,
+}
+
+/// Buffers referenced by the deploy journals in `target/deploy`, keyed by
+/// buffer address.
+fn journaled_buffers() -> BTreeMap<String, JournaledBuffer> {
+    let Ok(deploy_dir) = target_dir().map(|dir| dir.join("deploy")) else {
+        return BTreeMap::new();
+    };
+    let Ok(entries) = fs::read_dir(deploy_dir) else {
+        return BTreeMap::new();
+    };
+    entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let file_name = entry.file_name().to_string_lossy().into_owned();
+            let program_name = file_name.strip_suffix("-deploy-journal.json")?.to_owned();
+            let journal = DeployJournal::load(&entry.path()).ok()??;
+            Some((program_name, journal))
+        })
+        .flat_map(|(program_name, journal)| {
+            let current = journal.buffer.map(|buffer| (buffer, true));
+            let superseded = journal.superseded.into_iter().map(|buffer| (buffer, false));
+            current
+                .into_iter()
+                .chain(superseded)
+                .map(move |(buffer, resumable)| {
+                    let program_name = program_name.clone();
+                    (
+                        buffer,
+                        JournaledBuffer {
+                            program_name,
+                            resumable,
+                        },
+                    )
+                })
+        })
+        .collect()
+}
+
 fn discover_cargo_metadata(start_dir: &Path) -> Result<Option<Metadata>> {
     match MetadataCommand::new()
         .current_dir(start_dir)
@@ -422,6 +705,8 @@ pub fn process_deploy(
     verifiable: bool,
     no_idl: bool,
     make_final: bool,
+    extend_headroom: u16,
+    estimate: bool,
     solana_args: Vec<String>,
 ) -> Result<()> {
     // If explicit filepath provided, deploy single program
@@ -438,6 +723,8 @@ pub fn process_deploy(
             use_rpc,
             no_idl,
             make_final,
+            extend_headroom,
+            estimate,
             solana_args,
         );
     }
@@ -488,6 +775,8 @@ pub fn process_deploy(
             verifiable,
             no_idl,
             make_final,
+            extend_headroom,
+            estimate,
             solana_args,
         );
     }
@@ -505,6 +794,8 @@ pub fn process_deploy(
         use_rpc,
         no_idl,
         make_final,
+        extend_headroom,
+        estimate,
         solana_args,
     )
 }
@@ -519,11 +810,34 @@ fn deploy_workspace(
     verifiable: bool,
     no_idl: bool,
     make_final: bool,
+    extend_headroom: u16,
+    estimate: bool,
     solana_args: Vec<String>,
 ) -> Result<()> {
     // Get programs from workspace (Anchor or non-Anchor)
     let programs = get_programs_from_workspace(cfg_override, program_name.clone())?;
 
+    if estimate {
+        let programs = programs
+            .iter()
+            .map(|program| {
+                let keypair_path = match &program_keypair {
+                    Some(path) => path.clone(),
+                    None => program.keypair_file()?.path().clone(),
+                };
+                Ok((program.binary_path(verifiable)?, keypair_path))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        return estimate_programs_deploy(
+            cfg_override,
+            &programs,
+            None,
+            extend_headroom,
+            no_idl,
+            &solana_args,
+        );
+    }
+
     // For Cargo workspaces, we don't have cluster/wallet in config, so just print basic info
     if let Ok(Some(cfg)) = Config::discover(cfg_override) {
         // Anchor workspace - we have cluster/wallet config
@@ -563,6 +877,8 @@ fn deploy_workspace(
             use_rpc,
             no_idl,
             make_final,
+            extend_headroom,
+            false, // estimate
             solana_args.clone(),
         )?;
     }
@@ -585,6 +901,8 @@ pub fn program(cfg_override: &ConfigOverride, cmd: ProgramCommand) -> Result<()>
             use_rpc,
             no_idl,
             make_final,
+            extend_headroom,
+            estimate,
             solana_args,
         } => process_deploy(
             cfg_override,
@@ -599,6 +917,8 @@ pub fn program(cfg_override: &ConfigOverride, cmd: ProgramCommand) -> Result<()>
             false, // verifiable
             no_idl,
             make_final,
+            extend_headroom,
+            estimate,
             solana_args,
         ),
         ProgramCommand::WriteBuffer {
@@ -646,6 +966,9 @@ pub fn program(cfg_override: &ConfigOverride, cmd: ProgramCommand) -> Result<()>
             program_name,
             buffer,
             upgrade_authority,
+            multisig,
+            spill,
+            extend_headroom,
             max_retries,
             use_rpc,
             solana_args,
@@ -656,6 +979,9 @@ pub fn program(cfg_override: &ConfigOverride, cmd: ProgramCommand) -> Result<()>
             program_name,
             buffer,
             upgrade_authority,
+            multisig,
+            spill,
+            extend_headroom,
             max_retries,
             use_rpc,
             solana_args,
@@ -678,6 +1004,24 @@ pub fn program(cfg_override: &ConfigOverride, cmd: ProgramCommand) -> Result<()>
             recipient,
             bypass_warning,
         ),
+        ProgramCommand::Buffers {
+            authority,
+            close,
+            include_resumable,
+            buffers,
+            all,
+            recipient,
+            bypass_warning,
+        } => program_buffers(
+            cfg_override,
+            authority,
+            close,
+            include_resumable,
+            buffers,
+            all,
+            recipient,
+            bypass_warning,
+        ),
         ProgramCommand::Extend {
             program_id,
             program_name,
@@ -698,18 +1042,17 @@ fn get_rpc_client_and_config(
     Ok((rpc_client, config))
 }
 
-/// Get payer keypair from either Anchor config or Solana CLI config
-fn get_payer_keypair(
+/// Get the payer signer from either Anchor config or Solana CLI config
+fn get_payer_signer(
     cfg_override: &ConfigOverride,
     config: &Option<WithPath<Config>>,
-) -> Result<Keypair> {
+) -> Result<Box<dyn Signer>> {
     if let Some(cfg) = config {
-        cfg.wallet_kp()
+        cfg.wallet_signer()
     } else {
         // No Anchor config - get wallet from Solana CLI config
         let (_url, wallet_path) = crate::get_cluster_and_wallet(cfg_override)?;
-        Keypair::read_from_file(&wallet_path)
-            .map_err(|e| anyhow!("Failed to read wallet keypair from {}: {}", wallet_path, e))
+        signer_from_path(&wallet_path, "wallet")
     }
 }
 
@@ -727,10 +1070,12 @@ pub fn program_deploy(
     use_rpc: bool,
     no_idl: bool,
     make_final: bool,
+    extend_headroom: u16,
+    estimate: bool,
     solana_args: Vec<String>,
 ) -> Result<()> {
     let (rpc_client, config) = get_rpc_client_and_config(cfg_override)?;
-    let payer = get_payer_keypair(cfg_override, &config)?;
+    let payer = get_payer_signer(cfg_override, &config)?;
 
     // Determine the program filepath
     let program_filepath = if let Some(filepath) = program_filepath {
@@ -751,6 +1096,17 @@ pub fn program_deploy(
     // Determine program keypair (loaded before fee discovery so program_id can
     // scope the recent-prioritization-fees query to this program's contention).
     let loaded_program_keypair = if let Some(keypair_path) = program_keypair {
+        if estimate {
+            return estimate_programs_deploy(
+                cfg_override,
+                &[(program_filepath, keypair_path)],
+                max_len,
+                extend_headroom,
+                no_idl,
+                &solana_args,
+            );
+        }
+
         // Load from specified keypair file
         Keypair::read_from_file(&keypair_path).map_err(|e| {
             anyhow!(
@@ -773,6 +1129,17 @@ pub fn program_deploy(
         let keypair_path = target_dir()?
             .join("deploy")
             .join(format!("{program_name}-keypair.json"));
+        if estimate {
+            return estimate_programs_deploy(
+                cfg_override,
+                &[(program_filepath, keypair_path)],
+                max_len,
+                extend_headroom,
+                no_idl,
+                &solana_args,
+            );
+        }
+
         Keypair::read_from_file(&keypair_path).map_err(|e| {
             anyhow!(
                 "Failed to read program keypair from {}: {}. Use --program-keypair to specify a \
@@ -817,17 +1184,13 @@ pub fn program_deploy(
     })?;
 
     // Determine upgrade authority
-    let upgrade_authority = if let Some(auth_path) = upgrade_authority {
-        let authority_keypair = Keypair::read_from_file(&auth_path)
-            .map_err(|e| anyhow!("Failed to read upgrade authority keypair: {}", e))?;
-        println!(
-            "Using custom upgrade authority: {}",
-            authority_keypair.pubkey()
-        );
-        authority_keypair
-    } else {
-        payer.insecure_clone()
-    };
+    let custom_upgrade_authority = upgrade_authority
+        .map(|auth_path| signer_from_path(&auth_path, "upgrade authority"))
+        .transpose()?;
+    if let Some(authority) = &custom_upgrade_authority {
+        println!("Using custom upgrade authority: {}", authority.pubkey());
+    }
+    let upgrade_authority = custom_upgrade_authority.as_deref().unwrap_or(&*payer);
 
     // Check if program already exists → decides deploy vs upgrade path
     let is_upgrade = rpc_client.get_account(&program_id).is_ok();
@@ -835,22 +1198,67 @@ pub fn program_deploy(
     if is_upgrade {
         println!("Program already exists, upgrading...");
         // Verify program can be upgraded before doing expensive buffer write
-        verify_program_can_be_upgraded(&rpc_client, &program_id, &upgrade_authority)?;
+        verify_program_can_be_upgraded(&rpc_client, &program_id, &upgrade_authority.pubkey())?;
     }
 
     // Resolve buffer: explicit pubkey from CLI flag (caller manages keypair
     // out-of-band) vs persistent keypair loaded from the path
     // `ensure_buffer_keypair_arg` injected. The persistent path is what
     // enables auto-resume across runs.
+    // A buffer recorded by an interrupted deploy takes precedence over the
+    // persistent keypair as long as it can still be resumed.
+    let program_name_stem = Path::new(&program_filepath)
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .ok_or_else(|| anyhow!("Invalid program filepath"))?;
+    let journal_path = DeployJournal::path(program_name_stem)?;
+    let write_buffer = buffer.is_none();
     let (buffer_pubkey, buffer_keypair): (Pubkey, Option<Keypair>) = if let Some(b) = buffer {
         (b, None)
     } else {
         let kp = read_buffer_keypair_from_args(&solana_args)?.ok_or_else(|| {
             anyhow!("internal: --buffer not injected by ensure_buffer_keypair_arg")
         })?;
-        (kp.pubkey(), Some(kp))
+        match journaled_resume_buffer(
+            &rpc_client,
+            &journal_path,
+            &program_id,
+            &kp.pubkey(),
+            &upgrade_authority.pubkey(),
+            program_data.len(),
+        )? {
+            Some(journaled) => {
+                println!("Resuming the interrupted deploy into buffer {journaled}");
+                (journaled, None)
+            }
+            None => (kp.pubkey(), Some(kp)),
+        }
     };
 
+    // Fail before creating the buffer rather than partway through the upload if the payer can't
+    // fund the deployment. A buffer left by a previous run is already funded.
+    let mut cost = EstimateTotals::default();
+    cost.add(&estimate_deploy_cost(
+        &rpc_client,
+        &program_id,
+        program_data.len(),
+        max_len,
+        &payer.pubkey(),
+        priority_fee,
+        (!no_idl)
+            .then(|| idl_path_of(&program_filepath))
+            .transpose()?,
+        extend_headroom,
+    )?);
+    if rpc_client
+        .get_account_with_commitment(&buffer_pubkey, CommitmentConfig::confirmed())?
+        .value
+        .is_some()
+    {
+        cost.buffer_rent = 0;
+    }
+    ensure_payer_balance(&rpc_client, &payer.pubkey(), cost.required())?;
+
     let max_data_len = max_len.unwrap_or(program_data.len());
     let send_config = RpcSendTransactionConfig {
         skip_preflight,
@@ -860,6 +1268,21 @@ pub fn program_deploy(
         min_context_slot: None,
     };
 
+    // Journal the buffer before touching it so an interrupted deploy can be
+    // resumed, or its buffer reclaimed, by a later run.
+    let mut journal = start_deploy_journal(
+        &journal_path,
+        &program_id,
+        &buffer_pubkey,
+        &program_data,
+        write_chunk_size(
+            &buffer_pubkey,
+            &upgrade_authority.pubkey(),
+            &payer.pubkey(),
+            priority_fee,
+        ),
+    )?;
+
     // Retry the write+commit cycle. Each iteration re-fetches buffer state, so
     // only chunks that didn't land last time are re-sent (diff-only resume).
     let mut last_err: Option<anyhow::Error> = None;
@@ -919,6 +1342,17 @@ pub fn program_deploy(
                 None => None,
             };
 
+            // The journal decides which chunks are resent, once checked
+            // against what actually landed in the buffer.
+            let written_chunks = match existing_data {
+                Some(ref data) => {
+                    journal.record(&program_data, data);
+                    journal.save(&journal_path)?;
+                    Some(journal.written(&program_data))
+                }
+                None => None,
+            };
+
             // Need keypair to create a fresh buffer; if it doesn't exist and
             // user gave us only a pubkey, we can't proceed.
             if existing_data.is_none() && buffer_keypair.is_none() {
@@ -928,7 +1362,7 @@ pub fn program_deploy(
                 );
             }
 
-            if let Some(ref kp) = buffer_keypair {
+            if write_buffer {
                 // Keep the intermediate buffer at the actual binary length.
                 // --max-len is applied only by deploy_with_max_program_len.
                 write_program_buffer(
@@ -936,14 +1370,17 @@ pub fn program_deploy(
                     &payer,
                     &program_data,
                     &upgrade_authority.pubkey(),
-                    kp,
+                    &buffer_pubkey,
+                    buffer_keypair.as_ref().map(|kp| kp as &dyn Signer),
                     CommitmentConfig::confirmed(),
                     send_config,
                     priority_fee,
                     max_sign_attempts,
                     use_rpc,
-                    existing_data,
+                    written_chunks.as_deref(),
                 )?;
+                journal.record_all(&program_data);
+                journal.save(&journal_path)?;
             }
 
             if is_upgrade {
@@ -957,6 +1394,7 @@ pub fn program_deploy(
                     priority_fee,
                     true, // skip_program_verification - done above
                     skip_preflight,
+                    extend_headroom,
                 )?;
             } else {
                 deploy_program(
@@ -995,11 +1433,16 @@ pub fn program_deploy(
                 "          or anchor program deploy ... --buffer {}",
                 buffer_pubkey
             );
-            eprintln!("Reclaim:  solana program close {}", buffer_pubkey);
+            eprintln!("Reclaim:  anchor program close {}", buffer_pubkey);
         }
+        eprintln!("Journal:  {}", journal_path.display());
         return Err(err);
     }
 
+    // The loader drained the buffer into the program; keep the journal only
+    // for superseded buffers that still need to be closed.
+    journal.forget_and_save(&buffer_pubkey.to_string(), &journal_path)?;
+
     // Print the program ID
     println!("Program ID: {}", program_id);
 
@@ -1104,7 +1547,7 @@ pub fn program_deploy(
         let tx = Transaction::new_signed_with_payer(
             &[set_authority_ix],
             Some(&payer.pubkey()),
-            &[&payer, &upgrade_authority],
+            &[&*payer, upgrade_authority],
             recent_blockhash,
         );
 
@@ -1172,7 +1615,7 @@ fn verify_buffer_account(
 fn verify_program_can_be_upgraded(
     rpc_client: &RpcClient,
     program_id: &Pubkey,
-    upgrade_authority: &Keypair,
+    upgrade_authority: &Pubkey,
 ) -> Result<()> {
     // Verify the program exists
     let program_account = rpc_client
@@ -1222,10 +1665,10 @@ fn verify_program_can_be_upgraded(
                 ));
             }
             // Verify the authority matches
-            if upgrade_authority_address != Some(upgrade_authority.pubkey()) {
+            if upgrade_authority_address != Some(*upgrade_authority) {
                 return Err(anyhow!(
                     "Upgrade authority mismatch. Expected {:?}, but ProgramData has {:?}",
-                    Some(upgrade_authority.pubkey()),
+                    Some(upgrade_authority),
                     upgrade_authority_address
                 ));
             }
@@ -1245,10 +1688,10 @@ fn verify_program_can_be_upgraded(
 #[allow(clippy::too_many_arguments)]
 fn deploy_program(
     rpc_client: &RpcClient,
-    payer: &Keypair,
+    payer: &dyn Signer,
     buffer: &Pubkey,
     program_keypair: &Keypair,
-    upgrade_authority: &Keypair,
+    upgrade_authority: &dyn Signer,
     max_data_len: usize,
     priority_fee: Option<u64>,
     skip_preflight: bool,
@@ -1302,13 +1745,28 @@ fn deploy_program(
     Ok(())
 }
 
-/// Extend programdata in-place if the new buffer exceeds the current allocation.
+/// Bytes to extend a `ProgramData` account of `programdata_len` bytes by so that it fits a
+/// `program_len` byte program, plus `headroom` percent of the program for future upgrades. Zero
+/// if the program already fits.
+fn programdata_extension(programdata_len: usize, program_len: usize, headroom: u16) -> usize {
+    if programdata_len >= UpgradeableLoaderState::size_of_programdata(program_len) {
+        return 0;
+    }
+    let target_len = program_len.saturating_add(program_len * usize::from(headroom) / 100);
+    UpgradeableLoaderState::size_of_programdata(target_len)
+        .min(MAX_PERMITTED_DATA_LENGTH as usize)
+        .saturating_sub(programdata_len)
+}
+
+/// Extend programdata in-place if the new buffer exceeds the current allocation, leaving
+/// `headroom` percent of the program for future upgrades.
 fn auto_extend_program_data_if_needed(
     rpc_client: &RpcClient,
-    payer: &Keypair,
+    payer: &dyn Signer,
     program_id: &Pubkey,
     program_len: usize,
-    upgrade_authority: &Keypair,
+    headroom: u16,
+    upgrade_authority: &dyn Signer,
     skip_preflight: bool,
 ) -> Result<()> {
     let programdata_metadata_size = UpgradeableLoaderState::size_of_programdata_metadata();
@@ -1323,23 +1781,24 @@ fn auto_extend_program_data_if_needed(
             e
         )
     })?;
-    let programdata_body_len = programdata_account
-        .data
-        .len()
-        .saturating_sub(programdata_metadata_size);
-    let required_programdata_len = UpgradeableLoaderState::size_of_programdata(program_len);
-    let required_programdata_body_len =
-        required_programdata_len.saturating_sub(programdata_metadata_size);
-
-    if required_programdata_body_len <= programdata_body_len {
+    let programdata_len = programdata_account.data.len();
+    let additional_bytes = programdata_extension(programdata_len, program_len, headroom);
+    if additional_bytes == 0 {
         return Ok(()); // already large enough
     }
 
-    let additional_bytes = (required_programdata_body_len - programdata_body_len) as u32;
+    let extended_len = programdata_len + additional_bytes;
+    let added_rent = rpc_client
+        .get_minimum_balance_for_rent_exemption(extended_len)?
+        .saturating_sub(programdata_account.lamports);
     println!(
-        "Auto-extending program data by {} bytes ({} → {}) before upgrade…",
-        additional_bytes, programdata_body_len, required_programdata_body_len
+        "Auto-extending program data by {} bytes ({} → {}, {}% headroom) before upgrade…",
+        additional_bytes,
+        programdata_len.saturating_sub(programdata_metadata_size),
+        extended_len - programdata_metadata_size,
+        headroom
     );
+    let additional_bytes = additional_bytes as u32;
 
     let extend_ix =
         loader_v3_instruction::extend_program(program_id, Some(&payer.pubkey()), additional_bytes);
@@ -1363,6 +1822,7 @@ fn auto_extend_program_data_if_needed(
             },
         )
         .map_err(|e| anyhow!("Auto-extend failed: {}", e))?;
+    println!("Added rent: {}", crate::format_sol(added_rent));
 
     let extended_slot = rpc_client
         .get_slot()
@@ -1383,18 +1843,19 @@ fn auto_extend_program_data_if_needed(
 #[allow(clippy::too_many_arguments)]
 fn upgrade_program(
     rpc_client: &RpcClient,
-    payer: &Keypair,
+    payer: &dyn Signer,
     program_id: &Pubkey,
     buffer: &Pubkey,
     program_len: usize,
-    upgrade_authority: &Keypair,
+    upgrade_authority: &dyn Signer,
     priority_fee: Option<u64>,
     skip_program_verification: bool,
     skip_preflight: bool,
+    extend_headroom: u16,
 ) -> Result<()> {
     // Verify program can be upgraded (unless caller already verified)
     if !skip_program_verification {
-        verify_program_can_be_upgraded(rpc_client, program_id, upgrade_authority)?;
+        verify_program_can_be_upgraded(rpc_client, program_id, &upgrade_authority.pubkey())?;
     }
 
     // Verify the buffer account is valid
@@ -1407,6 +1868,7 @@ fn upgrade_program(
         payer,
         program_id,
         program_len,
+        extend_headroom,
         upgrade_authority,
         skip_preflight,
     )?;
@@ -1453,6 +1915,154 @@ fn upgrade_program(
     Ok(())
 }
 
+/// Roles of the accounts of the loader-v3 `Upgrade` instruction, in order.
+const UPGRADE_ACCOUNT_ROLES: [&str; 7] = [
+    "ProgramData",
+    "Program",
+    "Buffer",
+    "Spill",
+    "Rent sysvar",
+    "Clock sysvar",
+    "Upgrade authority",
+];
+
+/// Roles of the accounts of the loader-v3 `ExtendProgram` instruction paid by a payer, in order.
+const EXTEND_ACCOUNT_ROLES: [&str; 4] = ["ProgramData", "Program", "System program", "Payer"];
+
+/// Hand `buffer` over to the multisig `vault` and print the unsigned upgrade
+/// transaction for the multisig to approve. `program_data`, if known, is
+/// checked against the buffer contents. The transaction extends ProgramData
+/// first if the new program doesn't fit, with `extend_headroom` percent to spare.
+#[allow(clippy::too_many_arguments)]
+fn propose_multisig_upgrade(
+    rpc_client: &RpcClient,
+    payer: &dyn Signer,
+    program_id: &Pubkey,
+    buffer: &Pubkey,
+    vault: &Pubkey,
+    spill: &Pubkey,
+    program_data: Option<&[u8]>,
+    extend_headroom: u16,
+) -> Result<()> {
+    let buffer_account = rpc_client
+        .get_account_with_commitment(buffer, CommitmentConfig::confirmed())?
+        .value
+        .ok_or_else(|| anyhow!("Buffer {} does not exist", buffer))?;
+    let authority_address =
+        match bincode::deserialize::<UpgradeableLoaderState>(&buffer_account.data) {
+            Ok(UpgradeableLoaderState::Buffer { authority_address })
+                if buffer_account.owner == bpf_loader_upgradeable_id::id() =>
+            {
+                authority_address
+            }
+            _ => bail!("Account {} is not a Buffer account", buffer),
+        };
+    let buffer_data = &buffer_account.data[UpgradeableLoaderState::size_of_buffer_metadata()..];
+    if program_data.is_some_and(|program_data| program_data != buffer_data) {
+        bail!("Buffer {} contents do not match the program binary", buffer);
+    }
+
+    // The loader requires the ProgramData account to fit the new binary, so the proposal extends
+    // it first if needed, paid by the vault
+    let (programdata_address, _) =
+        Pubkey::find_program_address(&[program_id.as_ref()], &bpf_loader_upgradeable_id::id());
+    let programdata = rpc_client
+        .get_account(&programdata_address)
+        .map_err(|e| anyhow!("Failed to get ProgramData account: {}", e))?;
+    let additional_bytes =
+        programdata_extension(programdata.data.len(), buffer_data.len(), extend_headroom);
+    let added_rent = match additional_bytes {
+        0 => 0,
+        _ => rpc_client
+            .get_minimum_balance_for_rent_exemption(programdata.data.len() + additional_bytes)?
+            .saturating_sub(programdata.lamports),
+    };
+
+    if authority_address == Some(payer.pubkey()) {
+        println!("Transferring buffer authority to the multisig {}...", vault);
+        let set_authority_ix =
+            loader_v3_instruction::set_buffer_authority(buffer, &payer.pubkey(), vault);
+        let recent_blockhash = rpc_client.get_latest_blockhash()?;
+        let tx = Transaction::new_signed_with_payer(
+            &[set_authority_ix],
+            Some(&payer.pubkey()),
+            &[payer],
+            recent_blockhash,
+        );
+        rpc_client
+            .send_and_confirm_transaction(&tx)
+            .map_err(|e| anyhow!("Failed to set buffer authority: {}", e))?;
+    } else if authority_address != Some(*vault) {
+        bail!(
+            "Buffer {} authority {:?} is neither the wallet nor the multisig {}",
+            buffer,
+            authority_address,
+            vault
+        );
+    }
+
+    let mut instructions = Vec::with_capacity(2);
+    if additional_bytes > 0 {
+        instructions.push(loader_v3_instruction::extend_program(
+            program_id,
+            Some(vault),
+            additional_bytes as u32,
+        ));
+    }
+    instructions.push(loader_v3_instruction::upgrade(
+        program_id, buffer, vault, spill,
+    ));
+    let recent_blockhash = rpc_client.get_latest_blockhash()?;
+    let message = Message::new_with_blockhash(&instructions, Some(vault), &recent_blockhash);
+    let tx = bincode::serialize(&Transaction::new_unsigned(message))?;
+
+    println!();
+    println!("Upgrade proposal for program {}", program_id);
+    println!("Buffer: {}", buffer);
+    println!("Buffer authority: {}", vault);
+    println!("Buffer size: {} bytes", buffer_data.len());
+    println!("Buffer hash (sha256): {}", sha256_hex(buffer_data));
+    if program_data.is_some() {
+        println!("The buffer contents match the local program binary.");
+    }
+    if additional_bytes > 0 {
+        println!(
+            "ProgramData is too small for the new program, the proposal extends it by {} bytes \
+             ({}% headroom), adding {} of rent paid by the multisig.",
+            additional_bytes,
+            extend_headroom,
+            crate::format_sol(added_rent)
+        );
+    }
+    for ix in &instructions {
+        let (name, roles) = match ix.accounts.len() {
+            len if len == UPGRADE_ACCOUNT_ROLES.len() => ("Upgrade", &UPGRADE_ACCOUNT_ROLES[..]),
+            _ => ("ExtendProgram", &EXTEND_ACCOUNT_ROLES[..]),
+        };
+        println!();
+        println!("{} instruction:", name);
+        println!("  Program: {}", ix.program_id);
+        println!("  Accounts:");
+        for (meta, role) in ix.accounts.iter().zip(roles) {
+            let mut flags = Vec::new();
+            if meta.is_writable {
+                flags.push("writable");
+            }
+            if meta.is_signer {
+                flags.push("signer");
+            }
+            println!("    {} ({}) [{}]", meta.pubkey, role, flags.join(", "));
+        }
+        println!("  Data (base58): {}", bs58::encode(&ix.data).into_string());
+        println!("  Data (base64): {}", STANDARD.encode(&ix.data));
+    }
+    println!();
+    println!("Unsigned transaction (fee payer {}):", vault);
+    println!("  base58: {}", bs58::encode(&tx).into_string());
+    println!("  base64: {}", STANDARD.encode(&tx));
+    Ok(())
+}
+
 fn program_write_buffer(
     cfg_override: &ConfigOverride,
     program_filepath: Option<PathBuf>,
@@ -1461,7 +2071,7 @@ fn program_write_buffer(
     buffer_authority: Option<String>,
 ) -> Result<()> {
     let (rpc_client, config) = get_rpc_client_and_config(cfg_override)?;
-    let payer = get_payer_keypair(cfg_override, &config)?;
+    let payer = get_payer_signer(cfg_override, &config)?;
 
     // Determine the program filepath
     let program_filepath = if let Some(filepath) = program_filepath {
@@ -1496,12 +2106,10 @@ fn program_write_buffer(
     })?;
 
     // Determine buffer authority
-    let buffer_authority_keypair = if let Some(auth_path) = buffer_authority {
-        Keypair::read_from_file(&auth_path)
-            .map_err(|e| anyhow!("Failed to read buffer authority keypair: {}", e))?
-    } else {
-        payer.insecure_clone()
-    };
+    let custom_buffer_authority = buffer_authority
+        .map(|auth_path| signer_from_path(&auth_path, "buffer authority"))
+        .transpose()?;
+    let buffer_authority_keypair = custom_buffer_authority.as_deref().unwrap_or(&*payer);
 
     let buffer_keypair = Keypair::new();
     let buffer_pubkey = write_program_buffer(
@@ -1509,7 +2117,8 @@ fn program_write_buffer(
         &payer,
         &program_data,
         &buffer_authority_keypair.pubkey(),
-        &buffer_keypair,
+        &buffer_keypair.pubkey(),
+        Some(&buffer_keypair),
         CommitmentConfig::confirmed(),
         RpcSendTransactionConfig {
             skip_preflight: false,
@@ -1534,7 +2143,7 @@ fn program_set_buffer_authority(
     new_buffer_authority: Pubkey,
 ) -> Result<()> {
     let (rpc_client, config) = get_rpc_client_and_config(cfg_override)?;
-    let payer = get_payer_keypair(cfg_override, &config)?;
+    let payer = get_payer_signer(cfg_override, &config)?;
 
     println!("Setting buffer authority...");
     println!("Buffer: {}", buffer);
@@ -1562,7 +2171,7 @@ fn program_set_buffer_authority(
     Ok(())
 }
 
-fn program_set_upgrade_authority(
+pub(crate) fn program_set_upgrade_authority(
     cfg_override: &ConfigOverride,
     program_id: Pubkey,
     new_upgrade_authority: Option<Pubkey>,
@@ -1572,7 +2181,7 @@ fn program_set_upgrade_authority(
     current_upgrade_authority: Option<String>,
 ) -> Result<()> {
     let (rpc_client, config) = get_rpc_client_and_config(cfg_override)?;
-    let payer = get_payer_keypair(cfg_override, &config)?;
+    let payer = get_payer_signer(cfg_override, &config)?;
 
     // Validate that this is a Program account, not ProgramData
     let program_account = rpc_client
@@ -1624,20 +2233,18 @@ fn program_set_upgrade_authority(
     }
 
     // Determine current authority keypair (must be a signer)
-    let current_authority_keypair = if let Some(auth_path) = current_upgrade_authority {
-        let keypair = Keypair::read_from_file(&auth_path)
-            .map_err(|e| anyhow!("Failed to read current upgrade authority keypair: {}", e))?;
-        println!("Using custom current authority: {}", keypair.pubkey());
-        keypair
-    } else {
-        payer.insecure_clone()
-    };
+    let custom_current_authority = current_upgrade_authority
+        .map(|auth_path| signer_from_path(&auth_path, "current upgrade authority"))
+        .transpose()?;
+    if let Some(authority) = &custom_current_authority {
+        println!("Using custom current authority: {}", authority.pubkey());
+    }
+    let current_authority_keypair = custom_current_authority.as_deref().unwrap_or(&*payer);
 
     // Validate signer requirements and load keypair
     let new_auth_keypair_opt = if let Some(signer_path) = new_upgrade_authority_signer {
         // Signer provided - use checked mode
-        let keypair = Keypair::read_from_file(&signer_path)
-            .map_err(|e| anyhow!("Failed to read new upgrade authority signer keypair: {}", e))?;
+        let keypair = signer_from_path(&signer_path, "new upgrade authority signer")?;
 
         // Verify the pubkey matches if both are provided
         if let Some(pubkey) = new_upgrade_authority {
@@ -1698,7 +2305,7 @@ fn program_set_upgrade_authority(
         let tx = Transaction::new_signed_with_payer(
             &[set_authority_ixs],
             Some(&payer.pubkey()),
-            &[&payer, &current_authority_keypair, new_auth_keypair],
+            &[&*payer, current_authority_keypair, new_auth_keypair],
             recent_blockhash,
         );
         rpc_client
@@ -1709,7 +2316,7 @@ fn program_set_upgrade_authority(
         let tx = Transaction::new_signed_with_payer(
             &[set_authority_ixs],
             Some(&payer.pubkey()),
-            &[&payer, &current_authority_keypair],
+            &[&*payer, current_authority_keypair],
             recent_blockhash,
         );
         rpc_client
@@ -1810,12 +2417,15 @@ pub fn program_upgrade(
     program_name: Option<String>,
     buffer: Option<Pubkey>,
     upgrade_authority: Option<String>,
+    multisig: Option<Pubkey>,
+    spill: Option<Pubkey>,
+    extend_headroom: u16,
     max_retries: u32,
     use_rpc: bool,
     solana_args: Vec<String>,
 ) -> Result<()> {
     let (rpc_client, config) = get_rpc_client_and_config(cfg_override)?;
-    let payer = get_payer_keypair(cfg_override, &config)?;
+    let payer = get_payer_signer(cfg_override, &config)?;
 
     // Augment solana_args with recommended defaults if provided.
     // Pass program_id so recent prio-fee query reflects past upgrade contention
@@ -1832,21 +2442,36 @@ pub fn program_upgrade(
     let skip_preflight = parse_skip_preflight_from_args(&solana_args);
 
     // Determine upgrade authority
-    let upgrade_authority_keypair = if let Some(auth_path) = upgrade_authority {
-        let keypair = Keypair::read_from_file(&auth_path)
-            .map_err(|e| anyhow!("Failed to read upgrade authority keypair: {}", e))?;
-        println!("Using custom upgrade authority: {}", keypair.pubkey());
-        keypair
-    } else {
-        payer.insecure_clone()
-    };
+    let custom_upgrade_authority = upgrade_authority
+        .map(|auth_path| signer_from_path(&auth_path, "upgrade authority"))
+        .transpose()?;
+    if let Some(authority) = &custom_upgrade_authority {
+        println!("Using custom upgrade authority: {}", authority.pubkey());
+    }
+    let upgrade_authority_keypair = custom_upgrade_authority.as_deref().unwrap_or(&*payer);
 
     // Verify the program can be upgraded BEFORE doing expensive operations
     // This prevents wasting time/money on buffer writes if the program is closed or immutable
-    verify_program_can_be_upgraded(&rpc_client, &program_id, &upgrade_authority_keypair)?;
+    verify_program_can_be_upgraded(
+        &rpc_client,
+        &program_id,
+        &multisig.unwrap_or_else(|| upgrade_authority_keypair.pubkey()),
+    )?;
 
     // Case 1: Using existing buffer (no retries needed)
     if let Some(buffer_pubkey) = buffer {
+        if let Some(vault) = multisig {
+            return propose_multisig_upgrade(
+                &rpc_client,
+                &payer,
+                &program_id,
+                &buffer_pubkey,
+                &vault,
+                &spill.unwrap_or(vault),
+                None,
+                extend_headroom,
+            );
+        }
         let buffer_account = rpc_client.get_account(&buffer_pubkey).map_err(|e| {
             anyhow!(
                 "Failed to fetch buffer {} for upgrade length check: {}",
@@ -1868,6 +2493,7 @@ pub fn program_upgrade(
             priority_fee,
             true, // skip_program_verification - already done above
             skip_preflight,
+            extend_headroom,
         );
     }
 
@@ -1906,10 +2532,25 @@ pub fn program_upgrade(
     // Persistent buffer keypair: reuse across retries so partial writes from a
     // failed attempt survive on-chain and the next attempt only re-sends the
     // chunks that didn't land. `ensure_buffer_keypair_arg` guarantees the
-    // injection above; absence here is an internal bug.
+    // injection above; absence here is an internal bug. A buffer recorded by
+    // an interrupted upgrade takes precedence while it can still be resumed.
     let buffer_keypair = read_buffer_keypair_from_args(&solana_args)?
         .ok_or_else(|| anyhow!("internal: --buffer not injected by ensure_buffer_keypair_arg"))?;
-    let buffer_pubkey = buffer_keypair.pubkey();
+    let journal_path = DeployJournal::path(program_name_stem)?;
+    let (buffer_pubkey, buffer_keypair) = match journaled_resume_buffer(
+        &rpc_client,
+        &journal_path,
+        &program_id,
+        &buffer_keypair.pubkey(),
+        &upgrade_authority_keypair.pubkey(),
+        program_data.len(),
+    )? {
+        Some(journaled) => {
+            println!("Resuming the interrupted upgrade into buffer {journaled}");
+            (journaled, None)
+        }
+        None => (buffer_keypair.pubkey(), Some(buffer_keypair)),
+    };
 
     let send_config = RpcSendTransactionConfig {
         skip_preflight,
@@ -1919,6 +2560,47 @@ pub fn program_upgrade(
         min_context_slot: None,
     };
 
+    let mut journal = start_deploy_journal(
+        &journal_path,
+        &program_id,
+        &buffer_pubkey,
+        &program_data,
+        write_chunk_size(
+            &buffer_pubkey,
+            &upgrade_authority_keypair.pubkey(),
+            &payer.pubkey(),
+            priority_fee,
+        ),
+    )?;
+
+    // A previous run may have already handed the buffer over to the multisig
+    if let Some(vault) = multisig {
+        if let Ok(Some(existing)) = fetch_buffer_program_data(&rpc_client, &buffer_pubkey, &vault) {
+            if existing.data != program_data {
+                bail!(
+                    "Buffer {} is already owned by the multisig {} but holds a different program \
+                     binary. Remove {} to write a new buffer.",
+                    buffer_pubkey,
+                    vault,
+                    parse_buffer_keypair_path_from_args(&solana_args)
+                        .map(|path| path.display().to_string())
+                        .unwrap_or_default()
+                );
+            }
+            journal.forget_and_save(&buffer_pubkey.to_string(), &journal_path)?;
+            return propose_multisig_upgrade(
+                &rpc_client,
+                &payer,
+                &program_id,
+                &buffer_pubkey,
+                &vault,
+                &spill.unwrap_or(vault),
+                Some(&program_data),
+                extend_headroom,
+            );
+        }
+    }
+
     // Retry loop for buffer write + upgrade
     let mut last_err: Option<anyhow::Error> = None;
     for retry in 0..(1 + max_retries) {
@@ -1963,19 +2645,44 @@ pub fn program_upgrade(
                 None => None,
             };
 
+            let written_chunks = match existing_data {
+                Some(ref data) => {
+                    journal.record(&program_data, data);
+                    journal.save(&journal_path)?;
+                    Some(journal.written(&program_data))
+                }
+                None => None,
+            };
+
             write_program_buffer(
                 &rpc_client,
                 &payer,
                 &program_data,
                 &upgrade_authority_keypair.pubkey(),
-                &buffer_keypair,
+                &buffer_pubkey,
+                buffer_keypair.as_ref().map(|kp| kp as &dyn Signer),
                 CommitmentConfig::confirmed(),
                 send_config,
                 priority_fee,
                 max_sign_attempts,
                 use_rpc,
-                existing_data,
+                written_chunks.as_deref(),
             )?;
+            journal.record_all(&program_data);
+            journal.save(&journal_path)?;
+
+            if let Some(vault) = multisig {
+                return propose_multisig_upgrade(
+                    &rpc_client,
+                    &payer,
+                    &program_id,
+                    &buffer_pubkey,
+                    &vault,
+                    &spill.unwrap_or(vault),
+                    Some(&program_data),
+                    extend_headroom,
+                );
+            }
 
             upgrade_program(
                 &rpc_client,
@@ -1987,6 +2694,7 @@ pub fn program_upgrade(
                 priority_fee,
                 true, // skip_program_verification
                 skip_preflight,
+                extend_headroom,
             )?;
             Ok(())
         })();
@@ -1996,7 +2704,7 @@ pub fn program_upgrade(
                 if max_retries > 0 {
                     println!("\nUpgrade success");
                 }
-                return Ok(());
+                return journal.forget_and_save(&buffer_pubkey.to_string(), &journal_path);
             }
             Err(e) => {
                 println!("Attempt {} failed: {}", retry + 1, e);
@@ -2018,8 +2726,9 @@ pub fn program_upgrade(
                 "          or anchor program upgrade {} <FILE> --buffer {}",
                 program_id, buffer_pubkey
             );
-            eprintln!("Reclaim:  solana program close {}", buffer_pubkey);
+            eprintln!("Reclaim:  anchor program close {}", buffer_pubkey);
         }
+        eprintln!("Journal:  {}", journal_path.display());
         return Err(err);
     }
 
@@ -2088,7 +2797,7 @@ fn program_close(
     bypass_warning: bool,
 ) -> Result<()> {
     let (rpc_client, config) = get_rpc_client_and_config(cfg_override)?;
-    let payer = get_payer_keypair(cfg_override, &config)?;
+    let payer = get_payer_signer(cfg_override, &config)?;
 
     // Determine the account to close
     let account = if let Some(acc) = account {
@@ -2148,12 +2857,10 @@ fn program_close(
         };
 
     // Determine authority
-    let authority_keypair = if let Some(auth_path) = authority {
-        Keypair::read_from_file(&auth_path)
-            .map_err(|e| anyhow!("Failed to read authority keypair: {}", e))?
-    } else {
-        payer.insecure_clone()
-    };
+    let custom_authority = authority
+        .map(|auth_path| signer_from_path(&auth_path, "authority"))
+        .transpose()?;
+    let authority_keypair = custom_authority.as_deref().unwrap_or(&*payer);
 
     // Determine recipient
     let recipient_pubkey = recipient.unwrap_or_else(|| authority_keypair.pubkey());
@@ -2199,7 +2906,7 @@ fn program_close(
     let tx = Transaction::new_signed_with_payer(
         &[close_ixs],
         Some(&payer.pubkey()),
-        &[&payer, &authority_keypair],
+        &[&*payer, authority_keypair],
         recent_blockhash,
     );
 
@@ -2212,16 +2919,208 @@ fn program_close(
     Ok(())
 }
 
-fn program_extend(
-    cfg_override: &ConfigOverride,
-    program_id: Option<Pubkey>,
-    program_name: Option<String>,
-    additional_bytes: usize,
-) -> Result<()> {
-    let (rpc_client, config) = get_rpc_client_and_config(cfg_override)?;
-    let payer = get_payer_keypair(cfg_override, &config)?;
+/// Account data prefix of a buffer whose authority is `authority`, i.e. the
+/// serialized `UpgradeableLoaderState::Buffer` header.
+fn buffer_header(authority: &Pubkey) -> Vec<u8> {
+    let mut header = vec![1, 0, 0, 0, 1];
+    header.extend_from_slice(authority.as_ref());
+    header
+}
 
-    if additional_bytes == 0 {
+#[allow(clippy::too_many_arguments)]
+fn program_buffers(
+    cfg_override: &ConfigOverride,
+    authority: Option<String>,
+    close: bool,
+    include_resumable: bool,
+    explicit: Vec<Pubkey>,
+    all: bool,
+    recipient: Option<Pubkey>,
+    bypass_warning: bool,
+) -> Result<()> {
+    let (rpc_client, config) = get_rpc_client_and_config(cfg_override)?;
+    let payer = get_payer_signer(cfg_override, &config)?;
+
+    let custom_authority = authority
+        .map(|auth_path| signer_from_path(&auth_path, "authority"))
+        .transpose()?;
+    let authority_keypair = custom_authority.as_deref().unwrap_or(&*payer);
+    let authority_pubkey = authority_keypair.pubkey();
+
+    // Only the header is needed, skip downloading the program bytes
+    #[allow(deprecated)]
+    let buffers = rpc_client
+        .get_program_accounts_with_config(
+            &bpf_loader_upgradeable_id::id(),
+            RpcProgramAccountsConfig {
+                filters: Some(vec![RpcFilterType::Memcmp(Memcmp::new_base58_encoded(
+                    0,
+                    &buffer_header(&authority_pubkey),
+                ))]),
+                account_config: RpcAccountInfoConfig {
+                    encoding: Some(UiAccountEncoding::Base64),
+                    data_slice: Some(UiDataSliceConfig {
+                        offset: 0,
+                        length: 0,
+                    }),
+                    ..RpcAccountInfoConfig::default()
+                },
+                ..RpcProgramAccountsConfig::default()
+            },
+        )
+        .map_err(|e| anyhow!("Failed to fetch buffers of {}: {}", authority_pubkey, e))?;
+
+    if let Some(missing) = explicit
+        .iter()
+        .find(|buffer| !buffers.iter().any(|(pubkey, _)| pubkey == *buffer))
+    {
+        bail!(
+            "{} is not a buffer of authority {}",
+            missing,
+            authority_pubkey
+        );
+    }
+
+    if buffers.is_empty() {
+        println!("No buffers found for authority {}", authority_pubkey);
+        return Ok(());
+    }
+
+    // Only buffers recorded by deploys in this workspace are known to be safe
+    // to close; anything else may belong to another workspace or machine, or
+    // be staged for a multisig upgrade.
+    let journaled = journaled_buffers();
+
+    println!("Buffers of authority {}:", authority_pubkey);
+    let mut to_close = Vec::new();
+    for (buffer, account) in &buffers {
+        let journaled = journaled.get(&buffer.to_string());
+        match journaled {
+            Some(JournaledBuffer {
+                program_name,
+                resumable: true,
+            }) => println!(
+                "  {} | {} lamports | resumable ({})",
+                buffer, account.lamports, program_name
+            ),
+            Some(JournaledBuffer { program_name, .. }) => println!(
+                "  {} | {} lamports | superseded ({})",
+                buffer, account.lamports, program_name
+            ),
+            None => println!(
+                "  {} | {} lamports | not in a local deploy journal",
+                buffer, account.lamports
+            ),
+        }
+        let selected = if all {
+            true
+        } else if !explicit.is_empty() {
+            explicit.contains(buffer)
+        } else {
+            journaled.is_some_and(|journaled| !journaled.resumable || include_resumable)
+        };
+        if selected {
+            to_close.push((*buffer, account.lamports, journaled));
+        }
+    }
+
+    let reclaimable = to_close
+        .iter()
+        .map(|(_, lamports, _)| lamports)
+        .sum::<u64>();
+    if !close {
+        if !to_close.is_empty() {
+            println!(
+                "\n{} lamports can be reclaimed from {} superseded buffer(s) with --close",
+                reclaimable,
+                to_close.len()
+            );
+        }
+        return Ok(());
+    }
+    if to_close.is_empty() {
+        println!(
+            "\nNo journaled buffers to close. Pass --buffer <ADDRESS> to close other buffers."
+        );
+        return Ok(());
+    }
+
+    if all {
+        println!();
+        println!(
+            "WARNING: --all closes every buffer of {}, including buffers that are not recorded \
+             in this workspace's deploy journals: buffers of other workspaces or machines, \
+             `anchor program write-buffer` outputs and buffers staged for multisig upgrades. \
+             Their contents are lost.",
+            authority_pubkey
+        );
+    }
+
+    let recipient_pubkey = recipient.unwrap_or(authority_pubkey);
+
+    if !bypass_warning {
+        println!();
+        println!(
+            "WARNING: This will close {} buffer(s) and send {} lamports to {}.",
+            to_close.len(),
+            reclaimable,
+            recipient_pubkey
+        );
+        println!();
+        print!("Continue? (y/n): ");
+        std::io::Write::flush(&mut std::io::stdout())?;
+
+        let mut input = String::new();
+        std::io::stdin().read_line(&mut input)?;
+
+        if !input.trim().eq_ignore_ascii_case("y") {
+            println!("Cancelled");
+            return Ok(());
+        }
+    }
+
+    for (buffer, _, journaled) in to_close {
+        let close_ix = loader_v3_instruction::close_any(
+            &buffer,
+            &recipient_pubkey,
+            Some(&authority_pubkey),
+            None,
+        );
+        let recent_blockhash = rpc_client.get_latest_blockhash()?;
+        let tx = Transaction::new_signed_with_payer(
+            &[close_ix],
+            Some(&payer.pubkey()),
+            &[&*payer, authority_keypair],
+            recent_blockhash,
+        );
+        rpc_client
+            .send_and_confirm_transaction(&tx)
+            .map_err(|e| anyhow!("Failed to close buffer {}: {}", buffer, e))?;
+
+        // The journal no longer needs to track this buffer
+        if let Some(JournaledBuffer { program_name, .. }) = journaled {
+            let path = DeployJournal::path(program_name)?;
+            if let Some(journal) = DeployJournal::load(&path)? {
+                journal.forget_and_save(&buffer.to_string(), &path)?;
+            }
+        }
+        println!("Closed buffer {}", buffer);
+    }
+
+    println!("Reclaimed {} lamports to {}", reclaimable, recipient_pubkey);
+    Ok(())
+}
+
+fn program_extend(
+    cfg_override: &ConfigOverride,
+    program_id: Option<Pubkey>,
+    program_name: Option<String>,
+    additional_bytes: usize,
+) -> Result<()> {
+    let (rpc_client, config) = get_rpc_client_and_config(cfg_override)?;
+    let payer = get_payer_signer(cfg_override, &config)?;
+
+    if additional_bytes == 0 {
         return Err(anyhow!("Additional bytes must be greater than zero"));
     }
 
@@ -2519,24 +3418,26 @@ pub fn send_deploy_messages(
     Ok(None)
 }
 
-/// Complete buffer writing implementation. If `existing_buffer_data` is
-/// `Some`, the on-chain buffer already exists (resume case): skip the
-/// `CreateBuffer` ix and only send writes for chunks that differ.
+/// Complete buffer writing implementation. If `written_chunks` is `Some`, the
+/// on-chain buffer already exists (resume case): skip the `CreateBuffer` ix
+/// and only send writes for the chunks not marked as written. The buffer
+/// keypair is only needed to create the buffer.
 #[allow(clippy::too_many_arguments)]
 pub fn write_program_buffer(
     rpc_client: &RpcClient,
     payer: &dyn Signer,
     program_data: &[u8],
     buffer_authority: &Pubkey,
-    buffer_keypair: &dyn Signer,
+    buffer_pubkey: &Pubkey,
+    buffer_keypair: Option<&dyn Signer>,
     commitment: CommitmentConfig,
     send_transaction_config: RpcSendTransactionConfig,
     priority_fee: Option<u64>,
     max_sign_attempts: usize,
     use_rpc: bool,
-    existing_buffer_data: Option<Vec<u8>>,
+    written_chunks: Option<&[bool]>,
 ) -> Result<Pubkey> {
-    let buffer_pubkey = buffer_keypair.pubkey();
+    let buffer_pubkey = *buffer_pubkey;
 
     let program_len = program_data.len();
     let buffer_len = program_len;
@@ -2546,7 +3447,7 @@ pub fn write_program_buffer(
 
     // Build CreateBuffer ix only if the buffer doesn't already exist on-chain.
     // On resume, we skip this and the loader keeps the existing account.
-    let initial_message = if existing_buffer_data.is_none() {
+    let initial_message = if written_chunks.is_none() {
         let buffer_data_len = UpgradeableLoaderState::size_of_buffer(buffer_len);
         let min_balance = rpc_client
             .get_minimum_balance_for_rent_exemption(buffer_data_len)
@@ -2578,7 +3479,7 @@ pub fn write_program_buffer(
         None
     };
 
-    // Prepare write messages — skip chunks that already match on-chain bytes
+    // Prepare write messages — skip chunks already in the buffer
     let write_messages = prepare_write_messages(
         program_data,
         &buffer_pubkey,
@@ -2586,7 +3487,7 @@ pub fn write_program_buffer(
         &payer.pubkey(),
         &blockhash,
         priority_fee,
-        existing_buffer_data.as_deref(),
+        written_chunks,
     );
 
     send_deploy_messages(
@@ -2595,7 +3496,7 @@ pub fn write_program_buffer(
         write_messages,
         None,
         payer,
-        Some(buffer_keypair),
+        buffer_keypair,
         Some(payer),
         None,
         max_sign_attempts,
@@ -2606,51 +3507,474 @@ pub fn write_program_buffer(
     Ok(buffer_pubkey)
 }
 
-/// Prepare write messages. When `existing_buffer_data` is provided, skip
-/// chunks that already match on-chain bytes — letting resume after a failed
-/// deploy only re-send the chunks that didn't land.
-fn prepare_write_messages(
-    program_data: &[u8],
+/// Build a single `Write` message for `bytes` at `offset` into the buffer.
+fn create_write_message(
     buffer_pubkey: &Pubkey,
     buffer_authority: &Pubkey,
     fee_payer: &Pubkey,
     blockhash: &Hash,
     priority_fee: Option<u64>,
-    existing_buffer_data: Option<&[u8]>,
-) -> Vec<Message> {
-    let create_msg = |offset: u32, bytes: Vec<u8>| {
-        let mut instructions: Vec<Instruction> = Vec::with_capacity(3);
-        if let Some(price) = priority_fee {
-            if price > 0 {
-                instructions.push(ComputeBudgetInstruction::set_compute_unit_price(price));
+    offset: u32,
+    bytes: Vec<u8>,
+) -> Message {
+    let mut instructions: Vec<Instruction> = Vec::with_capacity(3);
+    if let Some(price) = priority_fee {
+        if price > 0 {
+            instructions.push(ComputeBudgetInstruction::set_compute_unit_price(price));
+        }
+    }
+    instructions.push(ComputeBudgetInstruction::set_compute_unit_limit(
+        WRITE_COMPUTE_UNIT_LIMIT,
+    ));
+    instructions.push(loader_v3_instruction::write(
+        buffer_pubkey,
+        buffer_authority,
+        offset,
+        bytes,
+    ));
+    Message::new_with_blockhash(&instructions, Some(fee_payer), blockhash)
+}
+
+/// Number of program bytes that fit in a single `Write` transaction.
+fn write_chunk_size(
+    buffer_pubkey: &Pubkey,
+    buffer_authority: &Pubkey,
+    fee_payer: &Pubkey,
+    priority_fee: Option<u64>,
+) -> usize {
+    calculate_max_chunk_size(create_write_message(
+        buffer_pubkey,
+        buffer_authority,
+        fee_payer,
+        &Hash::default(),
+        priority_fee,
+        0,
+        Vec::new(),
+    ))
+}
+
+/// Base fee charged per transaction signature.
+pub(crate) const LAMPORTS_PER_SIGNATURE: u64 = 5_000;
+
+/// Accounts, transactions and lamports needed to deploy or upgrade a program binary.
+pub(crate) struct DeployEstimate {
+    /// Whether the program already exists and is upgraded rather than deployed.
+    pub is_upgrade: bool,
+    pub program_len: usize,
+    /// Program bytes written by each `Write` transaction.
+    pub chunk_size: usize,
+    pub write_txs: usize,
+    /// Rent of the buffer, refunded to the payer once the program is deployed or upgraded.
+    pub buffer_rent: u64,
+    /// Rent of the `Program` account, zero for upgrades.
+    pub program_rent: u64,
+    /// Capacity of the `ProgramData` account of a new program.
+    pub program_data_len: usize,
+    /// Rent of the `ProgramData` account of a new program, or the rent added by extending it
+    /// before an upgrade.
+    pub program_data_rent: u64,
+    /// Bytes the `ProgramData` account has to be extended by before an upgrade.
+    pub extend_len: usize,
+}
+
+impl DeployEstimate {
+    /// Lamports left in the program accounts after the deployment.
+    pub fn rent(&self) -> u64 {
+        self.program_rent + self.program_data_rent
+    }
+
+    /// Number of transactions sent by the loader.
+    pub fn transactions(&self) -> usize {
+        // CreateBuffer, the writes, and DeployWithMaxDataLen or an optional ExtendProgram and
+        // Upgrade
+        let finalize = match self.is_upgrade {
+            true => 1 + usize::from(self.extend_len > 0),
+            false => 1,
+        };
+        1 + self.write_txs + finalize
+    }
+
+    /// Base fees of the transactions, excluding priority fees. The buffer and new program
+    /// keypairs co-sign the transactions creating them.
+    pub fn fees(&self) -> u64 {
+        let signatures = self.transactions() + 1 + usize::from(!self.is_upgrade);
+        signatures as u64 * LAMPORTS_PER_SIGNATURE
+    }
+}
+
+/// Estimate the cost of deploying `program_len` bytes to `program_id`, paid by `payer` which is
+/// also the upgrade authority.
+fn estimate_deploy(
+    rpc_client: &RpcClient,
+    program_id: &Pubkey,
+    program_len: usize,
+    max_len: Option<usize>,
+    payer: &Pubkey,
+    priority_fee: Option<u64>,
+    extend_headroom: u16,
+) -> Result<DeployEstimate> {
+    let rent = |len: usize| {
+        rpc_client
+            .get_minimum_balance_for_rent_exemption(len)
+            .map_err(|e| anyhow!("Failed to get rent exemption for {len} bytes: {e}"))
+    };
+
+    // The chunk size only depends on the number of distinct accounts, not on the buffer address
+    let chunk_size = write_chunk_size(&Pubkey::new_unique(), payer, payer, priority_fee);
+    let mut estimate = DeployEstimate {
+        is_upgrade: rpc_client
+            .get_account_with_commitment(program_id, rpc_client.commitment())?
+            .value
+            .is_some(),
+        program_len,
+        chunk_size,
+        write_txs: program_len.div_ceil(chunk_size),
+        buffer_rent: rent(UpgradeableLoaderState::size_of_buffer(program_len))?,
+        program_rent: 0,
+        program_data_len: max_len.unwrap_or(program_len),
+        program_data_rent: 0,
+        extend_len: 0,
+    };
+
+    if estimate.is_upgrade {
+        let (programdata_address, _) =
+            Pubkey::find_program_address(&[program_id.as_ref()], &bpf_loader_upgradeable_id::id());
+        let programdata = rpc_client
+            .get_account(&programdata_address)
+            .map_err(|e| anyhow!("Failed to get ProgramData {programdata_address}: {e}"))?;
+        estimate.extend_len =
+            programdata_extension(programdata.data.len(), program_len, extend_headroom);
+        let extended_len = programdata.data.len() + estimate.extend_len;
+        estimate.program_data_len =
+            extended_len - UpgradeableLoaderState::size_of_programdata_metadata();
+        if estimate.extend_len > 0 {
+            estimate.program_data_rent = rent(extended_len)?.saturating_sub(programdata.lamports);
+        }
+    } else {
+        estimate.program_rent = rent(UpgradeableLoaderState::size_of_program())?;
+        estimate.program_data_rent = rent(UpgradeableLoaderState::size_of_programdata(
+            estimate.program_data_len,
+        ))?;
+    }
+
+    Ok(estimate)
+}
+
+/// Estimate the size of the canonical IDL metadata account after uploading `idl`, and the rent
+/// it needs on top of its current balance.
+fn estimate_idl_upload(
+    rpc_client: &RpcClient,
+    program_id: &Pubkey,
+    idl: &[u8],
+) -> Result<(usize, u64)> {
+    // `program-metadata` uploads IDLs zlib compressed
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(idl)?;
+    let len = PMP_METADATA_HEADER_SIZE + encoder.finish()?.len();
+
+    let existing = rpc_client
+        .get_account(&pmp_metadata_address(program_id, None))
+        .map_or(0, |account| account.lamports);
+    let rent = rpc_client
+        .get_minimum_balance_for_rent_exemption(len)
+        .map_err(|e| anyhow!("Failed to get rent exemption for {len} bytes: {e}"))?;
+    Ok((len, rent.saturating_sub(existing)))
+}
+
+/// IDL upload following a program deployment.
+pub(crate) enum IdlUpload {
+    /// The IDL isn't uploaded, e.g. with `--no-idl`.
+    Disabled,
+    /// IDLs aren't uploaded on localnet.
+    Localnet,
+    /// No IDL was generated at the path.
+    Missing(PathBuf),
+    /// Size of the IDL metadata account and the rent it needs on top of its current balance.
+    Upload { len: usize, rent: u64 },
+}
+
+/// Cost of deploying a program and uploading its IDL.
+pub(crate) struct DeployCost {
+    pub loader: DeployEstimate,
+    pub idl: IdlUpload,
+}
+
+impl DeployCost {
+    /// Print the transactions of the deployment.
+    pub fn print(&self) {
+        let loader = &self.loader;
+        print_estimate_step(
+            "CreateBuffer",
+            format!(
+                "{} bytes, rent {} (refunded)",
+                loader.program_len,
+                crate::format_sol(loader.buffer_rent)
+            ),
+        );
+        print_estimate_step(
+            &format!("Write x {}", loader.write_txs),
+            format!("{} bytes each", loader.chunk_size),
+        );
+        if loader.is_upgrade {
+            if loader.extend_len > 0 {
+                print_estimate_step(
+                    "ExtendProgram",
+                    format!(
+                        "{} bytes, rent {}",
+                        loader.extend_len,
+                        crate::format_sol(loader.program_data_rent)
+                    ),
+                );
             }
+            print_estimate_step("Upgrade", String::new());
+        } else {
+            print_estimate_step(
+                "DeployWithMaxDataLen",
+                format!(
+                    "{} bytes, rent {}",
+                    loader.program_data_len,
+                    crate::format_sol(loader.rent())
+                ),
+            );
         }
-        instructions.push(ComputeBudgetInstruction::set_compute_unit_limit(
-            WRITE_COMPUTE_UNIT_LIMIT,
-        ));
-        instructions.push(loader_v3_instruction::write(
-            buffer_pubkey,
-            buffer_authority,
-            offset,
-            bytes,
-        ));
-        Message::new_with_blockhash(&instructions, Some(fee_payer), blockhash)
+
+        match &self.idl {
+            IdlUpload::Disabled => {}
+            IdlUpload::Localnet => print_estimate_step("IDL upload", "skipped on localnet".into()),
+            IdlUpload::Missing(path) => print_estimate_step(
+                "IDL upload",
+                format!("skipped, `{}` not found", path.display()),
+            ),
+            IdlUpload::Upload { len, rent } => print_estimate_step(
+                "IDL upload",
+                format!(
+                    "{len} bytes of metadata, rent {} (sent by program-metadata)",
+                    crate::format_sol(*rent)
+                ),
+            ),
+        }
+    }
+}
+
+/// Transactions and lamports of a deployment of one or more programs.
+#[derive(Default)]
+pub(crate) struct EstimateTotals {
+    pub transactions: usize,
+    /// Lamports left in the program and IDL accounts.
+    pub rent: u64,
+    /// Largest buffer rent, as buffers are refunded after each program is deployed.
+    pub buffer_rent: u64,
+    pub fees: u64,
+}
+
+impl EstimateTotals {
+    pub fn add(&mut self, cost: &DeployCost) {
+        self.transactions += cost.loader.transactions();
+        self.rent += cost.loader.rent();
+        if let IdlUpload::Upload { rent, .. } = cost.idl {
+            self.rent += rent;
+        }
+        self.buffer_rent = self.buffer_rent.max(cost.loader.buffer_rent);
+        self.fees += cost.loader.fees();
+    }
+
+    /// Lamports the payer needs to hold before the deployment starts.
+    pub fn required(&self) -> u64 {
+        self.rent + self.buffer_rent + self.fees
+    }
+
+    pub fn print(&self) {
+        println!("Transactions: {}", self.transactions);
+        println!(
+            "Rent:         {} (locked in program and IDL accounts)",
+            crate::format_sol(self.rent)
+        );
+        println!(
+            "Buffer rent:  {} (refunded after each deploy)",
+            crate::format_sol(self.buffer_rent)
+        );
+        println!(
+            "Fees:         {} (base fees, excluding priority fees)",
+            crate::format_sol(self.fees)
+        );
+        println!("Required:     {}", crate::format_sol(self.required()));
+    }
+}
+
+/// Print a transaction of a deployment estimate.
+pub(crate) fn print_estimate_step(step: &str, detail: String) {
+    println!("   {step:<22}{detail}");
+}
+
+/// Estimate the cost of deploying `program_len` bytes to `program_id` and uploading the IDL at
+/// `idl_path`, if any.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn estimate_deploy_cost(
+    rpc_client: &RpcClient,
+    program_id: &Pubkey,
+    program_len: usize,
+    max_len: Option<usize>,
+    payer: &Pubkey,
+    priority_fee: Option<u64>,
+    idl_path: Option<PathBuf>,
+    extend_headroom: u16,
+) -> Result<DeployCost> {
+    let loader = estimate_deploy(
+        rpc_client,
+        program_id,
+        program_len,
+        max_len,
+        payer,
+        priority_fee,
+        extend_headroom,
+    )?;
+    let is_localnet =
+        rpc_client.url().contains("localhost") || rpc_client.url().contains("127.0.0.1");
+    let idl = match idl_path {
+        None => IdlUpload::Disabled,
+        Some(_) if is_localnet => IdlUpload::Localnet,
+        Some(path) => match fs::read(&path) {
+            Ok(idl) => {
+                let (len, rent) = estimate_idl_upload(rpc_client, program_id, &idl)?;
+                IdlUpload::Upload { len, rent }
+            }
+            Err(_) => IdlUpload::Missing(path),
+        },
     };
+    Ok(DeployCost { loader, idl })
+}
+
+/// Fail early with the missing amount if `payer` can't fund `required` lamports, otherwise
+/// return its balance.
+pub(crate) fn ensure_payer_balance(
+    rpc_client: &RpcClient,
+    payer: &Pubkey,
+    required: u64,
+) -> Result<u64> {
+    let balance = rpc_client
+        .get_balance(payer)
+        .map_err(|e| anyhow!("Failed to get the balance of payer {payer}: {e}"))?;
+    if balance < required {
+        bail!(
+            "Insufficient funds: payer {payer} has {}, but the deployment needs {} ({} more)",
+            crate::format_sol(balance),
+            crate::format_sol(required),
+            crate::format_sol(required - balance)
+        );
+    }
+    Ok(balance)
+}
+
+/// Print the cost of deploying each `(binary, program keypair)` of `programs` and check that the
+/// payer can fund them all.
+pub(crate) fn estimate_programs_deploy(
+    cfg_override: &ConfigOverride,
+    programs: &[(PathBuf, PathBuf)],
+    max_len: Option<usize>,
+    extend_headroom: u16,
+    no_idl: bool,
+    solana_args: &[String],
+) -> Result<()> {
+    let (rpc_client, config) = get_rpc_client_and_config(cfg_override)?;
+    let payer = get_payer_signer(cfg_override, &config)?.pubkey();
+    let priority_fee = parse_priority_fee_from_args(solana_args);
+
+    println!("Cluster: {}", rpc_client.url());
+    println!("Payer:   {payer}");
+    let mut totals = EstimateTotals::default();
+    for (program_filepath, keypair_path) in programs {
+        let program_id = Keypair::read_from_file(keypair_path)
+            .map_err(|e| {
+                anyhow!(
+                    "Failed to read program keypair from {}: {e}",
+                    keypair_path.display()
+                )
+            })?
+            .pubkey();
+        let program_len = fs::metadata(program_filepath)
+            .map_err(|e| {
+                anyhow!(
+                    "Failed to read program file {}: {e}",
+                    program_filepath.display()
+                )
+            })?
+            .len() as usize;
+        let cost = estimate_deploy_cost(
+            &rpc_client,
+            &program_id,
+            program_len,
+            max_len,
+            &payer,
+            priority_fee,
+            (!no_idl)
+                .then(|| idl_path_of(program_filepath))
+                .transpose()?,
+            extend_headroom,
+        )?;
+
+        println!();
+        println!(
+            "{} ({program_id}): {}",
+            program_filepath.display(),
+            if cost.loader.is_upgrade {
+                "upgrade"
+            } else {
+                "deploy"
+            }
+        );
+        cost.print();
+        totals.add(&cost);
+    }
+
+    println!();
+    totals.print();
+    let balance = ensure_payer_balance(&rpc_client, &payer, totals.required())?;
+    println!("Balance:      {}", crate::format_sol(balance));
+    Ok(())
+}
+
+/// Path of the IDL generated for the program binary at `program_filepath`.
+fn idl_path_of(program_filepath: &Path) -> Result<PathBuf> {
+    let program_name = program_filepath
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .ok_or_else(|| anyhow!("Invalid program filepath"))?;
+    Ok(target_dir()?
+        .join("idl")
+        .join(program_name)
+        .with_extension("json"))
+}
 
+/// Prepare write messages. When `written_chunks` is provided (one entry per
+/// `write_chunk_size` chunk), skip the chunks marked as written — letting
+/// resume after a failed deploy only re-send the chunks that didn't land.
+fn prepare_write_messages(
+    program_data: &[u8],
+    buffer_pubkey: &Pubkey,
+    buffer_authority: &Pubkey,
+    fee_payer: &Pubkey,
+    blockhash: &Hash,
+    priority_fee: Option<u64>,
+    written_chunks: Option<&[bool]>,
+) -> Vec<Message> {
     let mut write_messages = Vec::new();
-    let chunk_size = calculate_max_chunk_size(create_msg(0, Vec::new()));
+    let chunk_size = write_chunk_size(buffer_pubkey, buffer_authority, fee_payer, priority_fee);
 
     for (chunk, i) in program_data.chunks(chunk_size).zip(0usize..) {
         let offset = i.saturating_mul(chunk_size);
-        let already_written = match existing_buffer_data {
-            Some(existing) => {
-                let end = offset.saturating_add(chunk.len());
-                end <= existing.len() && &existing[offset..end] == chunk
-            }
-            None => false,
-        };
+        let already_written = written_chunks
+            .and_then(|written| written.get(i))
+            .is_some_and(|written| *written);
         if !already_written {
-            write_messages.push(create_msg(offset as u32, chunk.to_vec()));
+            write_messages.push(create_write_message(
+                buffer_pubkey,
+                buffer_authority,
+                fee_payer,
+                blockhash,
+                priority_fee,
+                offset as u32,
+                chunk.to_vec(),
+            ));
         }
     }
 
@@ -2825,6 +4149,211 @@ resolver = "2"
         );
     }
 
+    #[test]
+    fn deploy_journal_records_chunks_present_on_chain() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("foo-deploy-journal.json");
+        let program_data = (0u8..10).collect::<Vec<_>>();
+        let mut journal = DeployJournal::new(
+            &Pubkey::new_unique(),
+            &Pubkey::new_unique(),
+            &program_data,
+            4,
+        );
+        assert_eq!(journal.chunks.len(), 3);
+        assert_eq!(journal.written_chunks(), 0);
+
+        // First chunk matches, second differs and third has not landed yet
+        let mut buffer_data = program_data.clone();
+        buffer_data[5] = 0xff;
+        buffer_data.truncate(8);
+        journal.record(&program_data, &buffer_data);
+        assert_eq!(
+            journal.chunks,
+            [Some(sha256_hex(&program_data[..4])), None, None]
+        );
+
+        journal.save(&path).unwrap();
+        assert_eq!(DeployJournal::load(&path).unwrap(), Some(journal));
+        DeployJournal::remove(&path).unwrap();
+        assert_eq!(DeployJournal::load(&path).unwrap(), None);
+    }
+
+    #[test]
+    fn deploy_journal_record_all_marks_every_chunk() {
+        let program_data = vec![7u8; 9];
+        let mut journal = DeployJournal::new(
+            &Pubkey::new_unique(),
+            &Pubkey::new_unique(),
+            &program_data,
+            4,
+        );
+        journal.record_all(&program_data);
+        assert_eq!(journal.written_chunks(), 3);
+        assert_eq!(journal.chunks[2], Some(sha256_hex(&[7])));
+
+        // Only the chunk whose bytes changed has to be written again
+        let mut changed = program_data.clone();
+        changed[5] = 0;
+        assert_eq!(journal.written(&changed), [true, false, true]);
+    }
+
+    #[test]
+    fn deploy_journal_carries_over_interrupted_deploys() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("foo-deploy-journal.json");
+        let program_id = Pubkey::new_unique();
+        let (first, second) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let program_data = (0u8..10).collect::<Vec<_>>();
+
+        let mut journal =
+            start_deploy_journal(&path, &program_id, &first, &program_data, 4).unwrap();
+        journal.record(&program_data, &program_data[..4]);
+        journal.save(&path).unwrap();
+
+        // Resuming into the same buffer keeps the chunks already written
+        let journal = start_deploy_journal(&path, &program_id, &first, &program_data, 4).unwrap();
+        assert_eq!(journal.written(&program_data), [true, false, false]);
+
+        // A new buffer supersedes the previous one until it is closed
+        let journal = start_deploy_journal(&path, &program_id, &second, &program_data, 4).unwrap();
+        assert_eq!(journal.written_chunks(), 0);
+        assert_eq!(journal.superseded, [first.to_string()]);
+
+        let mut journal =
+            start_deploy_journal(&path, &program_id, &first, &program_data, 4).unwrap();
+        assert_eq!(journal.superseded, [second.to_string()]);
+        assert_eq!(DeployJournal::load(&path).unwrap().as_ref(), Some(&journal));
+
+        assert!(journal.forget(&first.to_string()));
+        assert_eq!(journal.written_chunks(), 0);
+        assert!(!journal.forget(&second.to_string()));
+    }
+
+    #[test]
+    fn buffer_header_matches_loader_state() {
+        let authority = Pubkey::new_unique();
+        let state = UpgradeableLoaderState::Buffer {
+            authority_address: Some(authority),
+        };
+        assert_eq!(
+            buffer_header(&authority),
+            bincode::serialize(&state).unwrap()
+        );
+    }
+
+    #[test]
+    fn deploy_estimate_counts_loader_transactions() {
+        let mut estimate = DeployEstimate {
+            is_upgrade: false,
+            program_len: 2048,
+            chunk_size: 1024,
+            write_txs: 2,
+            buffer_rent: 0,
+            program_rent: 1,
+            program_data_len: 4096,
+            program_data_rent: 2,
+            extend_len: 0,
+        };
+        // CreateBuffer, 2 writes and DeployWithMaxDataLen, co-signed by the buffer and program
+        assert_eq!(estimate.transactions(), 4);
+        assert_eq!(estimate.fees(), 6 * LAMPORTS_PER_SIGNATURE);
+        assert_eq!(estimate.rent(), 3);
+
+        // CreateBuffer, 2 writes, ExtendProgram and Upgrade, co-signed by the buffer
+        estimate.is_upgrade = true;
+        estimate.extend_len = 100;
+        assert_eq!(estimate.transactions(), 5);
+        assert_eq!(estimate.fees(), 6 * LAMPORTS_PER_SIGNATURE);
+    }
+
+    #[test]
+    fn estimate_totals_fund_the_largest_buffer() {
+        let cost = |buffer_rent, idl| DeployCost {
+            loader: DeployEstimate {
+                is_upgrade: true,
+                program_len: 1024,
+                chunk_size: 1024,
+                write_txs: 1,
+                buffer_rent,
+                program_rent: 0,
+                program_data_len: 1024,
+                program_data_rent: 0,
+                extend_len: 0,
+            },
+            idl,
+        };
+        let mut totals = EstimateTotals::default();
+        totals.add(&cost(100, IdlUpload::Upload { len: 200, rent: 7 }));
+        totals.add(&cost(300, IdlUpload::Localnet));
+
+        // Buffers are refunded after each deploy, so only the largest one is needed at once
+        assert_eq!(totals.transactions, 6);
+        assert_eq!(totals.rent, 7);
+        assert_eq!(totals.buffer_rent, 300);
+        assert_eq!(totals.required(), 307 + 8 * LAMPORTS_PER_SIGNATURE);
+    }
+
+    #[test]
+    fn programdata_extension_adds_headroom() {
+        let programdata_len = UpgradeableLoaderState::size_of_programdata(1000);
+        assert_eq!(programdata_extension(programdata_len, 1000, 50), 0);
+        assert_eq!(programdata_extension(programdata_len, 800, 50), 0);
+        assert_eq!(programdata_extension(programdata_len, 1200, 0), 200);
+        assert_eq!(programdata_extension(programdata_len, 1200, 25), 500);
+        assert_eq!(
+            programdata_extension(programdata_len, 8 * 1024 * 1024, 100),
+            MAX_PERMITTED_DATA_LENGTH as usize - programdata_len
+        );
+    }
+
+    #[test]
+    fn extend_account_roles_match_instruction() {
+        let program_id = Pubkey::new_unique();
+        let vault = Pubkey::new_unique();
+        let ix = loader_v3_instruction::extend_program(&program_id, Some(&vault), 1);
+        let (programdata, _) =
+            Pubkey::find_program_address(&[program_id.as_ref()], &bpf_loader_upgradeable_id::id());
+
+        assert_eq!(ix.accounts.len(), EXTEND_ACCOUNT_ROLES.len());
+        assert_ne!(ix.accounts.len(), UPGRADE_ACCOUNT_ROLES.len());
+        let accounts = ix
+            .accounts
+            .iter()
+            .map(|meta| meta.pubkey)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            accounts,
+            [
+                programdata,
+                program_id,
+                solana_sdk_ids::system_program::id(),
+                vault
+            ]
+        );
+    }
+
+    #[test]
+    fn upgrade_account_roles_match_instruction() {
+        let program_id = Pubkey::new_unique();
+        let buffer = Pubkey::new_unique();
+        let vault = Pubkey::new_unique();
+        let spill = Pubkey::new_unique();
+        let ix = loader_v3_instruction::upgrade(&program_id, &buffer, &vault, &spill);
+        let (programdata, _) =
+            Pubkey::find_program_address(&[program_id.as_ref()], &bpf_loader_upgradeable_id::id());
+
+        assert_eq!(ix.accounts.len(), UPGRADE_ACCOUNT_ROLES.len());
+        let accounts = ix
+            .accounts
+            .iter()
+            .map(|meta| meta.pubkey)
+            .collect::<Vec<_>>();
+        assert_eq!(accounts[..4], [programdata, program_id, buffer, spill]);
+        assert_eq!(ix.accounts[6].pubkey, vault);
+        assert!(ix.accounts[6].is_signer);
+    }
+
     #[test]
     fn discover_solana_programs_errors_for_nonmember_current_crate() {
         let dir = tempdir().unwrap();
@@ -0,0 +1,443 @@
+//! Signers for wallets and authorities that aren't keypair files.
+//!
+//! Wherever the CLI signs with the wallet or an upgrade authority, it also accepts:
+//!
+//! - `usb://ledger[/<pubkey>][?key=<account>[/<change>]]`: a Ledger device, in the same format
+//!   as the Solana CLI. Requires the `ledger` feature.
+//! - `exec://<command>`: a local process, run through the shell once per request.
+//! - `unix://<path>`: a signer listening on a Unix socket, connected to once per request.
+//!
+//! External signers (`exec://` and `unix://`) read a single JSON request line and reply with a
+//! single JSON response line:
+//!
+//! ```text
+//! {"method":"getPubkey"}                         -> {"pubkey":"<base58>"}
+//! {"method":"signMessage","message":"<base64>"}  -> {"signature":"<base58>"}
+//!                                                -> {"error":"<reason>"}
+//! ```
+//!
+//! `anchor signer mock <keypair>` implements the protocol on top of a keypair file for testing.
+
+use {
+    crate::{
+        abs_path::AbsolutePath,
+        config::{ConfigOverride, WalletPath},
+        get_cluster_and_wallet,
+        keystore::{self, Keystore},
+    },
+    anchor_cli_macros::AbsolutePath,
+    anyhow::{anyhow, bail, Context, Result},
+    base64::{engine::general_purpose::STANDARD, Engine},
+    clap::Parser,
+    serde::{Deserialize, Serialize},
+    solana_derivation_path::DerivationPath,
+    solana_keypair::Keypair,
+    solana_pubkey::Pubkey,
+    solana_remote_wallet::{
+        locator::Locator, remote_keypair::generate_remote_keypair,
+        remote_wallet::maybe_wallet_manager,
+    },
+    solana_signature::Signature,
+    solana_signer::{EncodableKey, Signer, SignerError},
+    std::{
+        io::{self, BufRead, BufReader, Read, Write},
+        path::{Path, PathBuf},
+        process::{Command, Stdio},
+    },
+    uriparse::URIReference,
+};
+
+#[derive(Debug, Parser, AbsolutePath)]
+pub enum SignerCommand {
+    /// Display the pubkey of a wallet, either a keypair file, a keystore or a signer URI
+    Pubkey {
+        /// Keypair filepath, keystore or signer URI (defaults to configured wallet)
+        wallet: Option<WalletPath>,
+    },
+    /// Run a mock external signer backed by a keypair file, for testing signer URIs.
+    /// Answers a single request on stdin for `exec://anchor signer mock <keypair>`, or serves
+    /// `unix://<socket>` when `--socket` is given.
+    Mock {
+        /// Keypair filepath to sign with
+        keypair: PathBuf,
+        /// Unix socket to listen on
+        #[clap(long)]
+        socket: Option<PathBuf>,
+    },
+}
+
+pub fn signer(cfg_override: &ConfigOverride, cmd: SignerCommand) -> Result<()> {
+    match cmd {
+        SignerCommand::Pubkey { wallet } => {
+            let wallet = match wallet {
+                Some(wallet) => wallet.to_string(),
+                None => get_cluster_and_wallet(cfg_override)?.1,
+            };
+            println!("{}", pubkey_from_path(&wallet, "wallet")?);
+            Ok(())
+        }
+        SignerCommand::Mock { keypair, socket } => {
+            let keypair = Keypair::read_from_file(&keypair)
+                .map_err(|e| anyhow!("Failed to read keypair from {}: {e}", keypair.display()))?;
+            match socket {
+                Some(socket) => serve_mock_signer(&keypair, socket),
+                None => respond_mock_signer(&keypair, io::stdin().lock(), io::stdout().lock()),
+            }
+        }
+    }
+}
+
+/// Pubkey of a keypair file, keystore or signer URI. Keystores aren't unlocked for this.
+pub fn pubkey_from_path(path: &str, name: &str) -> Result<Pubkey> {
+    match Keystore::read(Path::new(path))? {
+        Some(keystore) => keystore.pubkey(),
+        None => signer_from_path(path, name).map(|signer| signer.pubkey()),
+    }
+}
+
+/// Whether the given wallet is a plaintext keypair file, which external tools can read.
+pub(crate) fn is_plaintext_keypair(path: &str) -> bool {
+    !is_signer_uri(path) && !keystore::is_keystore(Path::new(path))
+}
+
+/// Whether the given wallet is a signer URI rather than a keypair file.
+pub(crate) fn is_signer_uri(path: &str) -> bool {
+    path.split_once("://").is_some_and(|(scheme, _)| {
+        !scheme.is_empty()
+            && scheme
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+    })
+}
+
+/// Load the signer of a keypair file or a signer URI. `name` describes the signer in prompts
+/// and errors, e.g. "wallet" or "upgrade authority".
+pub fn signer_from_path(path: &str, name: &str) -> Result<Box<dyn Signer>> {
+    if !is_signer_uri(path) {
+        if let Some(keystore) = Keystore::read(Path::new(path))? {
+            return Ok(Box::new(keystore::unlock(Path::new(path), &keystore)?));
+        }
+
+        let keypair = Keypair::read_from_file(path)
+            .map_err(|e| anyhow!("Failed to read {name} keypair from {path}: {e}"))?;
+        return Ok(Box::new(keypair));
+    }
+
+    let (scheme, location) = path.split_once("://").unwrap_or_default();
+    match scheme {
+        "usb" => ledger_signer(path, name),
+        "exec" => ExternalSigner::connect(Transport::Exec(location.to_owned()), name),
+        #[cfg(unix)]
+        "unix" => ExternalSigner::connect(Transport::Unix(PathBuf::from(location)), name),
+        #[cfg(not(unix))]
+        "unix" => bail!("`unix://` signers are only supported on Unix"),
+        _ => bail!(
+            "Unsupported signer URI `{path}` for the {name}, expected a keypair file, \
+             `usb://ledger`, `exec://<command>` or `unix://<socket>`"
+        ),
+    }
+}
+
+fn ledger_signer(path: &str, name: &str) -> Result<Box<dyn Signer>> {
+    if !cfg!(feature = "ledger") {
+        bail!(
+            "Ledger support is not enabled in this build, reinstall the CLI with \
+             `--features ledger` to sign with `{path}`"
+        );
+    }
+
+    let uri = URIReference::try_from(path).map_err(|e| anyhow!("Invalid URI `{path}`: {e}"))?;
+    let derivation_path = DerivationPath::from_uri_key_query(&uri)
+        .map_err(|e| anyhow!("Invalid derivation path in `{path}`: {e}"))?
+        .unwrap_or_default();
+    let locator = Locator::new_from_uri(&uri).map_err(|e| anyhow!("Invalid `{path}`: {e}"))?;
+    let wallet_manager = maybe_wallet_manager()
+        .map_err(|e| anyhow!("Failed to access USB devices: {e}"))?
+        .ok_or_else(|| anyhow!("No Ledger device found for the {name}"))?;
+    let keypair = generate_remote_keypair(locator, derivation_path, &wallet_manager, false, name)
+        .map_err(|e| anyhow!("Failed to load the {name} from `{path}`: {e}"))?;
+    Ok(Box::new(keypair))
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "method", rename_all = "camelCase")]
+enum SignerRequest {
+    GetPubkey,
+    SignMessage { message: String },
+}
+
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+struct SignerResponse {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pubkey: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    signature: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// How requests reach an external signer.
+enum Transport {
+    Exec(String),
+    #[cfg(unix)]
+    Unix(PathBuf),
+}
+
+impl Transport {
+    fn exchange(&self, request: &SignerRequest) -> Result<SignerResponse> {
+        let request = serde_json::to_string(request)? + "\n";
+        let output = match self {
+            Self::Exec(command) => {
+                let mut child = shell(command)
+                    .stdin(Stdio::piped())
+                    .stdout(Stdio::piped())
+                    .stderr(Stdio::inherit())
+                    .spawn()
+                    .with_context(|| format!("Failed to run signer `{command}`"))?;
+                child
+                    .stdin
+                    .take()
+                    .ok_or_else(|| anyhow!("Failed to open the stdin of `{command}`"))?
+                    .write_all(request.as_bytes())?;
+                let output = child.wait_with_output()?;
+                if !output.status.success() {
+                    bail!("Signer `{command}` exited with {}", output.status);
+                }
+                String::from_utf8(output.stdout)?
+            }
+            #[cfg(unix)]
+            Self::Unix(path) => {
+                let mut stream = std::os::unix::net::UnixStream::connect(path)
+                    .with_context(|| format!("Failed to connect to signer {}", path.display()))?;
+                stream.write_all(request.as_bytes())?;
+                stream.shutdown(std::net::Shutdown::Write)?;
+                let mut output = String::new();
+                stream.read_to_string(&mut output)?;
+                output
+            }
+        };
+
+        let response: SignerResponse = serde_json::from_str(output.lines().next().unwrap_or(""))
+            .context("Invalid response from the signer")?;
+        match response.error {
+            Some(error) => bail!("Signer rejected the request: {error}"),
+            None => Ok(response),
+        }
+    }
+}
+
+fn shell(command: &str) -> Command {
+    if cfg!(windows) {
+        let mut cmd = Command::new("cmd");
+        cmd.args(["/C", command]);
+        cmd
+    } else {
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", command]);
+        cmd
+    }
+}
+
+/// Signer that delegates to a local process or socket, see the module docs for the protocol.
+struct ExternalSigner {
+    transport: Transport,
+    pubkey: Pubkey,
+}
+
+impl ExternalSigner {
+    fn connect(transport: Transport, name: &str) -> Result<Box<dyn Signer>> {
+        let pubkey = transport
+            .exchange(&SignerRequest::GetPubkey)
+            .and_then(|response| {
+                response
+                    .pubkey
+                    .ok_or_else(|| anyhow!("Signer did not return a pubkey"))?
+                    .parse::<Pubkey>()
+                    .map_err(|e| anyhow!("Signer returned an invalid pubkey: {e}"))
+            })
+            .with_context(|| format!("Failed to get the pubkey of the {name}"))?;
+        Ok(Box::new(Self { transport, pubkey }))
+    }
+}
+
+impl Signer for ExternalSigner {
+    fn try_pubkey(&self) -> Result<Pubkey, SignerError> {
+        Ok(self.pubkey)
+    }
+
+    fn try_sign_message(&self, message: &[u8]) -> Result<Signature, SignerError> {
+        let response = self
+            .transport
+            .exchange(&SignerRequest::SignMessage {
+                message: STANDARD.encode(message),
+            })
+            .map_err(|e| SignerError::Connection(format!("{e:#}")))?;
+        let signature = response
+            .signature
+            .ok_or_else(|| SignerError::Protocol("missing signature".into()))?
+            .parse::<Signature>()
+            .map_err(|e| SignerError::Protocol(format!("invalid signature: {e}")))?;
+        // Never hand out a signature that the cluster would reject anyway
+        if !signature.verify(self.pubkey.as_ref(), message) {
+            return Err(SignerError::Protocol(format!(
+                "signature does not verify against {}",
+                self.pubkey
+            )));
+        }
+        Ok(signature)
+    }
+
+    fn is_interactive(&self) -> bool {
+        false
+    }
+}
+
+/// Answer a single request of the signer protocol with the given keypair.
+fn mock_response(keypair: &Keypair, request: &str) -> SignerResponse {
+    let request = match serde_json::from_str(request) {
+        Ok(request) => request,
+        Err(e) => {
+            return SignerResponse {
+                error: Some(format!("invalid request: {e}")),
+                ..Default::default()
+            }
+        }
+    };
+    match request {
+        SignerRequest::GetPubkey => SignerResponse {
+            pubkey: Some(keypair.pubkey().to_string()),
+            ..Default::default()
+        },
+        SignerRequest::SignMessage { message } => match STANDARD.decode(message) {
+            Ok(message) => {
+                eprintln!("Mock signer: signing a {}-byte message", message.len());
+                SignerResponse {
+                    signature: Some(keypair.sign_message(&message).to_string()),
+                    ..Default::default()
+                }
+            }
+            Err(e) => SignerResponse {
+                error: Some(format!("invalid message: {e}")),
+                ..Default::default()
+            },
+        },
+    }
+}
+
+fn respond_mock_signer(keypair: &Keypair, reader: impl Read, mut writer: impl Write) -> Result<()> {
+    let mut request = String::new();
+    BufReader::new(reader).read_line(&mut request)?;
+    let response = mock_response(keypair, request.trim_end());
+    writeln!(writer, "{}", serde_json::to_string(&response)?)?;
+    Ok(writer.flush()?)
+}
+
+#[cfg(unix)]
+fn serve_mock_signer(keypair: &Keypair, socket: PathBuf) -> Result<()> {
+    let listener = std::os::unix::net::UnixListener::bind(&socket)
+        .with_context(|| format!("Failed to listen on {}", socket.display()))?;
+    eprintln!(
+        "Mock signer for {} listening on unix://{}",
+        keypair.pubkey(),
+        socket.display()
+    );
+    for stream in listener.incoming() {
+        let result = stream
+            .map_err(anyhow::Error::from)
+            .and_then(|stream| respond_mock_signer(keypair, &stream, &stream));
+        if let Err(e) = result {
+            eprintln!("Mock signer: {e}");
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn serve_mock_signer(_keypair: &Keypair, _socket: PathBuf) -> Result<()> {
+    bail!("`--socket` is only supported on Unix")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mock(keypair: &Keypair, request: &SignerRequest) -> SignerResponse {
+        let request = serde_json::to_string(request).unwrap() + "\n";
+        let mut output = Vec::new();
+        respond_mock_signer(keypair, request.as_bytes(), &mut output).unwrap();
+        serde_json::from_slice(&output).unwrap()
+    }
+
+    #[test]
+    fn detects_signer_uris() {
+        assert!(is_signer_uri("usb://ledger?key=0/0"));
+        assert!(is_signer_uri("exec://anchor signer mock id.json"));
+        assert!(is_signer_uri("unix:///tmp/signer.sock"));
+        assert!(!is_signer_uri("~/.config/solana/id.json"));
+        assert!(!is_signer_uri("/tmp/a://b.json"));
+        assert!(!is_signer_uri("C:\\Users\\me\\id.json"));
+    }
+
+    #[test]
+    fn signer_protocol_format() {
+        assert_eq!(
+            serde_json::to_string(&SignerRequest::GetPubkey).unwrap(),
+            r#"{"method":"getPubkey"}"#
+        );
+        assert_eq!(
+            serde_json::to_string(&SignerRequest::SignMessage {
+                message: "AQI=".into()
+            })
+            .unwrap(),
+            r#"{"method":"signMessage","message":"AQI="}"#
+        );
+    }
+
+    #[test]
+    fn mock_signer_answers_requests() {
+        let keypair = Keypair::new();
+        let response = mock(&keypair, &SignerRequest::GetPubkey);
+        assert_eq!(response.pubkey, Some(keypair.pubkey().to_string()));
+
+        let response = mock(
+            &keypair,
+            &SignerRequest::SignMessage {
+                message: STANDARD.encode(b"message"),
+            },
+        );
+        let signature: Signature = response.signature.unwrap().parse().unwrap();
+        assert!(signature.verify(keypair.pubkey().as_ref(), b"message"));
+
+        let mut output = Vec::new();
+        respond_mock_signer(&keypair, &b"{\"method\":\"nope\"}\n"[..], &mut output).unwrap();
+        let response: SignerResponse = serde_json::from_slice(&output).unwrap();
+        assert!(response.error.unwrap().starts_with("invalid request"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn external_signer_signs_over_unix_socket() {
+        let dir = tempfile::tempdir().unwrap();
+        let socket = dir.path().join("signer.sock");
+        let listener = std::os::unix::net::UnixListener::bind(&socket).unwrap();
+        let keypair = Keypair::new();
+        let pubkey = keypair.pubkey();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let stream = stream.unwrap();
+                respond_mock_signer(&keypair, &stream, &stream).unwrap();
+            }
+        });
+
+        let signer = signer_from_path(&format!("unix://{}", socket.display()), "wallet").unwrap();
+        assert_eq!(signer.pubkey(), pubkey);
+        let signature = signer.try_sign_message(b"message").unwrap();
+        assert!(signature.verify(pubkey.as_ref(), b"message"));
+    }
+
+    #[test]
+    fn rejects_unknown_signer_uris() {
+        let err = signer_from_path("prompt://", "wallet").unwrap_err();
+        assert!(err.to_string().contains("Unsupported signer URI"));
+    }
+}
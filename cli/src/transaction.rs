@@ -0,0 +1,126 @@
+//! Offline and multi-party transaction signing.
+//!
+//! Transactions are exchanged as base58/base64 strings created with
+//! [`anchor_client::serialize_transaction`], e.g. by a program admin tool that uses
+//! `RequestBuilder::partially_signed_transaction_versioned`.
+
+use {
+    crate::{
+        abs_path::AbsolutePath,
+        config::{ConfigOverride, WalletPath},
+        create_client, get_cluster_and_wallet,
+        signer::signer_from_path,
+    },
+    anchor_cli_macros::AbsolutePath,
+    anchor_client::{
+        deserialize_transaction, missing_signers, partial_sign, serialize_transaction,
+        TransactionEncoding,
+    },
+    anyhow::{anyhow, bail, Result},
+    clap::Parser,
+    solana_transaction::versioned::VersionedTransaction,
+    std::io::Read,
+};
+
+#[derive(Debug, Parser, AbsolutePath)]
+pub enum TransactionCommand {
+    /// Add signatures to a serialized transaction
+    Sign {
+        /// Serialized transaction (`-` to read from stdin)
+        transaction: String,
+        /// Keypair file or signer URI to sign with, can be repeated (defaults to configured wallet)
+        #[clap(long = "signer")]
+        signers: Vec<WalletPath>,
+        /// Encoding of the serialized transaction
+        #[clap(long, default_value = "base64")]
+        encoding: TransactionEncoding,
+        /// Submit the transaction after signing. Fails if any signatures are still missing.
+        #[clap(long)]
+        send: bool,
+    },
+    /// Submit a fully signed serialized transaction
+    Send {
+        /// Serialized transaction (`-` to read from stdin)
+        transaction: String,
+        /// Encoding of the serialized transaction
+        #[clap(long, default_value = "base64")]
+        encoding: TransactionEncoding,
+    },
+}
+
+pub fn transaction(cfg_override: &ConfigOverride, cmd: TransactionCommand) -> Result<()> {
+    let (cluster_url, wallet_path) = get_cluster_and_wallet(cfg_override)?;
+    match cmd {
+        TransactionCommand::Sign {
+            transaction,
+            signers,
+            encoding,
+            send,
+        } => {
+            let mut tx = read_transaction(&transaction, encoding)?;
+            let signers = if signers.is_empty() {
+                vec![signer_from_path(&wallet_path, "wallet")?]
+            } else {
+                signers
+                    .iter()
+                    .map(|path| signer_from_path(&path.to_string(), "signer"))
+                    .collect::<Result<Vec<_>>>()?
+            };
+            let signers = signers.iter().map(|s| s.as_ref()).collect::<Vec<_>>();
+            partial_sign(&mut tx, &signers)?;
+
+            if send {
+                return send_transaction(&cluster_url, &tx);
+            }
+
+            println!("{}", serialize_transaction(&tx, encoding)?);
+            print_missing_signers(&tx);
+            Ok(())
+        }
+        TransactionCommand::Send {
+            transaction,
+            encoding,
+        } => send_transaction(&cluster_url, &read_transaction(&transaction, encoding)?),
+    }
+}
+
+fn read_transaction(
+    transaction: &str,
+    encoding: TransactionEncoding,
+) -> Result<VersionedTransaction> {
+    let transaction = if transaction == "-" {
+        let mut buf = String::new();
+        std::io::stdin().read_to_string(&mut buf)?;
+        buf
+    } else {
+        transaction.to_owned()
+    };
+
+    deserialize_transaction(&transaction, encoding)
+        .map_err(|e| anyhow!("Invalid {encoding} transaction: {e}"))
+}
+
+fn send_transaction(cluster_url: &str, tx: &VersionedTransaction) -> Result<()> {
+    if !missing_signers(tx).is_empty() {
+        print_missing_signers(tx);
+        bail!("Transaction is not fully signed");
+    }
+
+    let client = create_client(cluster_url);
+    let signature = client
+        .send_and_confirm_transaction(tx)
+        .map_err(|e| anyhow!("Failed to send transaction: {e}"))?;
+    println!("Signature: {signature}");
+
+    Ok(())
+}
+
+fn print_missing_signers(tx: &VersionedTransaction) {
+    let missing = missing_signers(tx);
+    if !missing.is_empty() {
+        eprintln!("Missing signatures:");
+        for pubkey in missing {
+            eprintln!("  {pubkey}");
+        }
+    }
+}
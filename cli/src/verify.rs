@@ -0,0 +1,336 @@
+//! Native verification of a deployed program against a local verifiable build.
+//!
+//! The on-chain `ProgramData` bytes are compared against `target/verifiable/<lib>.so` after
+//! trimming the trailing zero padding the loader leaves behind (`--max-len` or extends), and the
+//! on-chain IDL metadata can optionally be compared against the locally generated IDL.
+
+use {
+    crate::{
+        build, cd_member,
+        config::{BootstrapMode, ConfigOverride, Manifest},
+        create_client,
+        fetch::fetch_pmp_idl,
+        get_cluster_and_wallet,
+        idl_diff::diff_idls,
+        program::sha256_hex,
+        target_dir,
+    },
+    anchor_lang_idl::convert::convert_idl,
+    anyhow::{anyhow, bail, Context, Result},
+    solana_loader_v3_interface::state::UpgradeableLoaderState,
+    solana_pubkey::Pubkey,
+    solana_rpc_client::rpc_client::RpcClient,
+    solana_sdk_ids::bpf_loader_upgradeable,
+    std::fs,
+};
+
+pub fn verify_native(
+    cfg_override: &ConfigOverride,
+    program_id: Pubkey,
+    program_name: Option<String>,
+    skip_build: bool,
+    check_idl: bool,
+    cargo_args: Vec<String>,
+) -> Result<()> {
+    if let Some(program_name) = program_name.as_ref() {
+        cd_member(cfg_override, program_name)?;
+    }
+    let lib_name = Manifest::discover()?
+        .ok_or_else(|| anyhow!("Cargo.toml not found"))?
+        .lib_name()
+        .map_err(|_| {
+            anyhow!("Run this command inside a program directory or provide `--program-name`")
+        })?;
+
+    if !skip_build {
+        build(
+            cfg_override,
+            false,
+            None,
+            None,
+            true,
+            false,
+            false,
+            None,
+            None,
+            None,
+            BootstrapMode::None,
+            None,
+            None,
+            vec![],
+            cargo_args,
+            false,
+        )?;
+    }
+
+    let binary_path = target_dir()?
+        .join("verifiable")
+        .join(&lib_name)
+        .with_extension("so");
+    let local_binary = fs::read(&binary_path).with_context(|| {
+        format!(
+            "Failed to read the verifiable build `{}`",
+            binary_path.display()
+        )
+    })?;
+
+    let (cluster_url, _) = get_cluster_and_wallet(cfg_override)?;
+    let client = create_client(cluster_url);
+    let onchain_binary = fetch_program_data(&client, &program_id)?;
+
+    println!("Verifying program {program_id}");
+    let mut matches = compare_binaries(&local_binary, &onchain_binary);
+    if check_idl {
+        matches &= compare_idls(&client, &program_id, &lib_name)?;
+    }
+
+    if !matches {
+        bail!("Program {program_id} does not match the local build");
+    }
+    println!("Program {program_id} matches the local build");
+    Ok(())
+}
+
+/// Fetch the program bytes stored in the `ProgramData` account of `program_id`.
+fn fetch_program_data(client: &RpcClient, program_id: &Pubkey) -> Result<Vec<u8>> {
+    let program = client
+        .get_account(program_id)
+        .with_context(|| format!("Failed to get program account {program_id}"))?;
+    if program.owner != bpf_loader_upgradeable::ID {
+        bail!("Program {program_id} is not owned by the BPF Upgradeable Loader");
+    }
+    let programdata_address = match bincode::deserialize(&program.data) {
+        Ok(UpgradeableLoaderState::Program {
+            programdata_address,
+        }) => programdata_address,
+        _ => bail!("{program_id} is not an upgradeable program account"),
+    };
+
+    let programdata = client
+        .get_account(&programdata_address)
+        .with_context(|| format!("Failed to get ProgramData account {programdata_address}"))?;
+    Ok(programdata
+        .data
+        .get(UpgradeableLoaderState::size_of_programdata_metadata()..)
+        .unwrap_or_default()
+        .to_vec())
+}
+
+/// Print the hashes and sizes of both binaries, and where they start to differ if they do.
+fn compare_binaries(local: &[u8], onchain: &[u8]) -> bool {
+    let local_trimmed = trim_trailing_zeros(local);
+    let onchain_trimmed = trim_trailing_zeros(onchain);
+    println!(
+        "Local build: {} bytes, sha256 {}",
+        local_trimmed.len(),
+        sha256_hex(local_trimmed)
+    );
+    println!(
+        "On-chain:    {} bytes, sha256 {}",
+        onchain_trimmed.len(),
+        sha256_hex(onchain_trimmed)
+    );
+    if local_trimmed == onchain_trimmed {
+        return true;
+    }
+
+    if local_trimmed.len() != onchain_trimmed.len() {
+        println!(
+            "Size mismatch: local build is {} bytes, on-chain program is {} bytes",
+            local_trimmed.len(),
+            onchain_trimmed.len()
+        );
+    }
+    let offset = first_difference(local_trimmed, onchain_trimmed);
+    println!(
+        "First difference at byte {offset} ({})",
+        describe_offset(local, offset)
+    );
+    false
+}
+
+/// Compare the on-chain IDL metadata of the program with the locally generated IDL.
+fn compare_idls(client: &RpcClient, program_id: &Pubkey, lib_name: &str) -> Result<bool> {
+    let idl_path = target_dir()?
+        .join("idl")
+        .join(lib_name)
+        .with_extension("json");
+    let local_idl = fs::read(&idl_path)
+        .map_err(anyhow::Error::from)
+        .and_then(|idl| convert_idl(&idl))
+        .with_context(|| format!("Failed to read IDL `{}`", idl_path.display()))?;
+    let Some(onchain_idl) = fetch_pmp_idl(client, program_id)? else {
+        println!("IDL: no IDL metadata found on-chain");
+        return Ok(false);
+    };
+    let onchain_idl = convert_idl(&onchain_idl).context("Failed to parse the on-chain IDL")?;
+
+    if onchain_idl == local_idl {
+        println!("IDL: on-chain IDL matches the local IDL");
+        return Ok(true);
+    }
+
+    let changes = diff_idls(&onchain_idl, &local_idl);
+    if changes.is_empty() {
+        println!("IDL: on-chain IDL differs from the local IDL in metadata or docs");
+    } else {
+        println!("IDL: on-chain IDL differs from the local IDL:");
+        for change in changes {
+            println!("  - {change}");
+        }
+    }
+    Ok(false)
+}
+
+/// Strip the zero padding at the end of a program, e.g. `ProgramData` allocated with `--max-len`.
+fn trim_trailing_zeros(bytes: &[u8]) -> &[u8] {
+    let len = bytes
+        .iter()
+        .rposition(|byte| *byte != 0)
+        .map_or(0, |pos| pos + 1);
+    &bytes[..len]
+}
+
+/// Offset of the first byte that differs, or the length of the shorter input if one is a prefix
+/// of the other.
+fn first_difference(a: &[u8], b: &[u8]) -> usize {
+    a.iter()
+        .zip(b)
+        .position(|(a, b)| a != b)
+        .unwrap_or_else(|| a.len().min(b.len()))
+}
+
+/// Describe the part of the ELF that contains `offset`.
+fn describe_offset(elf: &[u8], offset: usize) -> String {
+    const ELF64_HEADER_SIZE: usize = 64;
+
+    if let Some(name) = section_at(elf, offset) {
+        return format!("section `{name}`");
+    }
+    if offset < ELF64_HEADER_SIZE {
+        return "ELF header".into();
+    }
+    if offset >= elf.len() {
+        return "past the end of the local build".into();
+    }
+    "outside of any section".into()
+}
+
+/// Name of the ELF64 section whose file contents include `offset`.
+fn section_at(elf: &[u8], offset: usize) -> Option<String> {
+    const SHT_NULL: u32 = 0;
+    const SHT_NOBITS: u32 = 8;
+
+    let read = |at: usize, len: usize| -> Option<u64> {
+        let bytes = elf.get(at..at.checked_add(len)?)?;
+        Some(
+            bytes
+                .iter()
+                .rev()
+                .fold(0, |acc, byte| (acc << 8) | u64::from(*byte)),
+        )
+    };
+    let read_usize = |at: usize, len: usize| read(at, len).and_then(|v| usize::try_from(v).ok());
+
+    // Only little-endian 64-bit ELFs are used for SBF programs
+    if elf.get(..6)? != b"\x7fELF\x02\x01" {
+        return None;
+    }
+    let shoff = read_usize(0x28, 8)?;
+    let shentsize = read_usize(0x3a, 2)?;
+    let shnum = read_usize(0x3c, 2)?;
+    let shstrndx = read_usize(0x3e, 2)?;
+    let header = |index: usize| shoff.checked_add(index.checked_mul(shentsize)?);
+    let strtab = read_usize(header(shstrndx)?.checked_add(0x18)?, 8)?;
+
+    (0..shnum).find_map(|index| {
+        let header = header(index)?;
+        let ty = read(header.checked_add(4)?, 4)? as u32;
+        let start = read_usize(header.checked_add(0x18)?, 8)?;
+        let size = read_usize(header.checked_add(0x20)?, 8)?;
+        if ty == SHT_NULL
+            || ty == SHT_NOBITS
+            || !(start..start.saturating_add(size)).contains(&offset)
+        {
+            return None;
+        }
+
+        let name_start = strtab.checked_add(read_usize(header, 4)?)?;
+        let name = elf.get(name_start..)?;
+        let name = &name[..name.iter().position(|byte| *byte == 0)?];
+        Some(String::from_utf8_lossy(name).into_owned())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Minimal ELF64 with a 16 byte `.text` section at offset 64.
+    fn elf() -> Vec<u8> {
+        let strtab = b"\0.text\0.shstrtab\0";
+        let text_offset = 64u64;
+        let strtab_offset = text_offset + 16;
+        let shoff = strtab_offset + strtab.len() as u64;
+
+        let mut elf = vec![0u8; 64];
+        elf[..6].copy_from_slice(b"\x7fELF\x02\x01");
+        elf[0x28..0x30].copy_from_slice(&shoff.to_le_bytes());
+        elf[0x3a..0x3c].copy_from_slice(&64u16.to_le_bytes());
+        elf[0x3c..0x3e].copy_from_slice(&3u16.to_le_bytes());
+        elf[0x3e..0x40].copy_from_slice(&2u16.to_le_bytes());
+        elf.extend([0xaa; 16]);
+        elf.extend(strtab);
+
+        let section = |name: u32, ty: u32, offset: u64, size: u64| {
+            let mut header = vec![0u8; 64];
+            header[..4].copy_from_slice(&name.to_le_bytes());
+            header[4..8].copy_from_slice(&ty.to_le_bytes());
+            header[0x18..0x20].copy_from_slice(&offset.to_le_bytes());
+            header[0x20..0x28].copy_from_slice(&size.to_le_bytes());
+            header
+        };
+        elf.extend(section(0, 0, 0, 0));
+        elf.extend(section(1, 1, text_offset, 16));
+        elf.extend(section(7, 3, strtab_offset, strtab.len() as u64));
+        elf
+    }
+
+    #[test]
+    fn trailing_zero_padding_is_ignored() {
+        assert_eq!(trim_trailing_zeros(&[1, 0, 2, 0, 0]), [1, 0, 2]);
+        assert_eq!(trim_trailing_zeros(&[0, 0]), [] as [u8; 0]);
+        assert!(compare_binaries(&[1, 2, 3], &[1, 2, 3, 0, 0, 0]));
+        assert!(!compare_binaries(&[1, 2, 3], &[1, 2, 4]));
+    }
+
+    #[test]
+    fn first_difference_of_prefix_is_its_length() {
+        assert_eq!(first_difference(&[1, 2, 3], &[1, 5, 3]), 1);
+        assert_eq!(first_difference(&[1, 2], &[1, 2, 3]), 2);
+    }
+
+    #[test]
+    fn differences_are_located_in_elf_sections() {
+        let elf = elf();
+        assert_eq!(describe_offset(&elf, 3), "ELF header");
+        assert_eq!(describe_offset(&elf, 70), "section `.text`");
+        assert_eq!(describe_offset(&elf, 82), "section `.shstrtab`");
+        assert_eq!(
+            describe_offset(&elf, elf.len() - 1),
+            "outside of any section"
+        );
+        assert_eq!(
+            describe_offset(&elf, elf.len()),
+            "past the end of the local build"
+        );
+    }
+    #[test]
+    fn malformed_section_headers_are_ignored() {
+        let mut elf = elf();
+        elf[0x28..0x30].copy_from_slice(&(u64::MAX - 0x10).to_le_bytes());
+        elf[0x3a..0x3c].copy_from_slice(&0u16.to_le_bytes());
+        assert_eq!(section_at(&elf, 70), None);
+        assert_eq!(describe_offset(&elf, 70), "outside of any section");
+    }
+}
@@ -2,8 +2,9 @@
 use solana_rpc_client::rpc_client::RpcClient;
 use {
     crate::{
-        ClientError, Config, EventContext, EventUnsubscriber, Program, ProgramAccountsIterator,
-        RequestBuilder, TxVersion,
+        AccountContext, ClientError, Config, EventContext, EventPages, EventSubscriptionConfig,
+        EventUnsubscriber, EventsSince, Program, ProgramAccountsIterator, RequestBuilder,
+        ResolveAccounts, TxVersion,
     },
     anchor_lang::{prelude::Pubkey, AccountDeserialize, Discriminator},
     solana_commitment_config::CommitmentConfig,
@@ -12,7 +13,7 @@ use {
     solana_signature::Signature,
     solana_signer::Signer,
     solana_transaction::Transaction,
-    std::{marker::PhantomData, ops::Deref},
+    std::{marker::PhantomData, ops::Deref, sync::Arc},
     tokio::{
         runtime::{Builder, Handle},
         sync::OnceCell,
@@ -26,6 +27,24 @@ impl EventUnsubscriber<'_> {
     }
 }
 
+impl<T: anchor_lang::Event + anchor_lang::AnchorDeserialize> EventPages<'_, T> {
+    /// Fetches the next page of transactions and returns their events, oldest first.
+    ///
+    /// Returns `None` once all transactions have been fetched. Pages can be empty if their
+    /// transactions didn't emit any events of type `T`.
+    pub fn next_page(&mut self) -> Result<Option<Vec<(EventContext, T)>>, ClientError> {
+        self.runtime_handle.block_on(self.next_page_internal())
+    }
+}
+
+impl<T: anchor_lang::Event + anchor_lang::AnchorDeserialize> Iterator for EventPages<'_, T> {
+    type Item = Result<Vec<(EventContext, T)>, ClientError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_page().transpose()
+    }
+}
+
 impl<C: Deref<Target = impl Signer> + Clone> Program<C> {
     pub fn new(
         program_id: Pubkey,
@@ -45,7 +64,7 @@ impl<C: Deref<Target = impl Signer> + Clone> Program<C> {
             program_id,
             cfg,
             sub_client: OnceCell::new(),
-            internal_rpc_client: rpc_client,
+            internal_rpc_client: Arc::new(rpc_client),
             rt,
         })
     }
@@ -81,6 +100,14 @@ impl<C: Deref<Target = impl Signer> + Clone> Program<C> {
         self.rt.block_on(self.account_internal(address))
     }
 
+    /// Fetches the accounts whose data is needed to derive the remaining accounts of the
+    /// instruction builder, until no more accounts can be derived.
+    ///
+    /// Accounts that don't exist are skipped.
+    pub fn resolve<B: ResolveAccounts>(&self, builder: B) -> Result<B, ClientError> {
+        self.rt.block_on(self.resolve_internal(builder))
+    }
+
     /// Returns all program accounts of the given type matching the given filters
     pub fn accounts<T: AccountDeserialize + Discriminator>(
         &self,
@@ -102,11 +129,95 @@ impl<C: Deref<Target = impl Signer> + Clone> Program<C> {
         &self,
         f: impl FnMut(&EventContext, T) + Send + 'static,
     ) -> Result<EventUnsubscriber<'_>, ClientError> {
-        let (handle, rx) = self.rt.block_on(self.on_internal(f))?;
+        self.on_with_config(EventSubscriptionConfig::default(), f)
+    }
+
+    /// Subscribe to program events with the given configuration, e.g. to also decode events
+    /// emitted with `emit_cpi!`.
+    ///
+    /// Returns an [`EventUnsubscriber`] to unsubscribe and close connection gracefully.
+    pub fn on_with_config<T: anchor_lang::Event + anchor_lang::AnchorDeserialize>(
+        &self,
+        config: EventSubscriptionConfig,
+        f: impl FnMut(&EventContext, T) + Send + 'static,
+    ) -> Result<EventUnsubscriber<'_>, ClientError> {
+        let (handle, rx, counters) = self.rt.block_on(self.on_internal(config, f))?;
+
+        Ok(EventUnsubscriber {
+            handle,
+            rx,
+            counters,
+            runtime_handle: self.rt.handle(),
+            _lifetime_marker: PhantomData,
+        })
+    }
+
+    /// Returns the events of type `T` emitted by the program since the given transaction
+    /// signature or slot, oldest first.
+    ///
+    /// Both events emitted with `emit!` and `emit_cpi!` are returned. Failed transactions are
+    /// skipped. The transactions are fetched one page at a time while iterating the returned
+    /// [`EventPages`].
+    pub fn events_since<T: anchor_lang::Event + anchor_lang::AnchorDeserialize>(
+        &self,
+        since: impl Into<EventsSince>,
+    ) -> Result<EventPages<'_, T>, ClientError> {
+        let signatures = self
+            .rt
+            .block_on(self.event_signatures_since(since.into()))?;
+
+        Ok(EventPages {
+            program_id: self.program_id,
+            rpc_client: self.internal_rpc_client.clone(),
+            commitment: self.transaction_commitment(),
+            signatures,
+            runtime_handle: self.rt.handle(),
+            _lifetime_marker: PhantomData,
+        })
+    }
+
+    /// Subscribe to changes of the account at the given address.
+    ///
+    /// Updates that can't be deserialized as `T` (e.g. after the account is closed) are skipped.
+    /// The subscription reconnects automatically if the websocket connection drops.
+    ///
+    /// Returns an [`EventUnsubscriber`] to unsubscribe and close connection gracefully.
+    pub fn subscribe_account<T: AccountDeserialize>(
+        &self,
+        address: Pubkey,
+        f: impl FnMut(&AccountContext, T) + Send + 'static,
+    ) -> Result<EventUnsubscriber<'_>, ClientError> {
+        let (handle, rx) = self
+            .rt
+            .block_on(self.subscribe_account_internal(address, f))?;
+
+        Ok(EventUnsubscriber {
+            handle,
+            rx,
+            counters: Arc::default(),
+            runtime_handle: self.rt.handle(),
+            _lifetime_marker: PhantomData,
+        })
+    }
+
+    /// Subscribe to changes of all program accounts of the given type matching the given filters.
+    ///
+    /// The subscription reconnects automatically if the websocket connection drops.
+    ///
+    /// Returns an [`EventUnsubscriber`] to unsubscribe and close connection gracefully.
+    pub fn subscribe_program_accounts<T: AccountDeserialize + Discriminator>(
+        &self,
+        filters: Vec<RpcFilterType>,
+        f: impl FnMut(&AccountContext, T) + Send + 'static,
+    ) -> Result<EventUnsubscriber<'_>, ClientError> {
+        let (handle, rx) = self
+            .rt
+            .block_on(self.subscribe_program_accounts_internal(filters, f))?;
 
         Ok(EventUnsubscriber {
             handle,
             rx,
+            counters: Arc::default(),
             runtime_handle: self.rt.handle(),
             _lifetime_marker: PhantomData,
         })
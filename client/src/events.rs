@@ -0,0 +1,385 @@
+//! Parsing of events emitted in confirmed transactions.
+//!
+//! Events are emitted either as program logs with `emit!`, or as self-CPI instructions with
+//! `emit_cpi!`. Both are returned by [`parse_transaction_events`], which is also used for replaying
+//! historical events with `Program::events_since`.
+
+use {
+    crate::{parse_logs_with_cpis, ClientError},
+    anchor_lang::{event::EVENT_IX_TAG_LE, solana_program::pubkey::Pubkey, AnchorDeserialize},
+    regex::Regex,
+    solana_signature::Signature,
+    solana_transaction_status_client_types::{
+        EncodedConfirmedTransactionWithStatusMeta, UiInnerInstructions, UiInstruction,
+        UiLoadedAddresses,
+    },
+    std::sync::{
+        atomic::{AtomicU64, Ordering},
+        LazyLock,
+    },
+};
+
+/// Starting point for replaying historical events.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventsSince {
+    /// Events of the transactions confirmed after the given transaction (exclusive).
+    Signature(Signature),
+    /// Events of the transactions confirmed in or after the given slot.
+    Slot(u64),
+}
+
+impl From<Signature> for EventsSince {
+    fn from(signature: Signature) -> Self {
+        Self::Signature(signature)
+    }
+}
+
+impl From<u64> for EventsSince {
+    fn from(slot: u64) -> Self {
+        Self::Slot(slot)
+    }
+}
+
+/// Configuration of program event subscriptions.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct EventSubscriptionConfig {
+    /// Also decode events emitted with `emit_cpi!`.
+    ///
+    /// Self-CPI events are not part of the program logs, so the transaction of each notification
+    /// is fetched with an additional RPC request once it's at least `confirmed`. Transactions are
+    /// fetched in the background, and events are still delivered in the order of the
+    /// notifications. Events are decoded from the logs only if the transaction can't be fetched,
+    /// which is counted in [`EventSubscriptionStats::failed_fetches`].
+    pub cpi_events: bool,
+}
+
+/// Notifications of an event subscription whose events were not all delivered, returned by
+/// `EventUnsubscriber::stats`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct EventSubscriptionStats {
+    /// Notifications dropped because too many transactions were waiting to be fetched.
+    pub dropped_notifications: u64,
+    /// Transactions that couldn't be fetched, so only the events in their logs were delivered.
+    pub failed_fetches: u64,
+}
+
+/// Counters of [`EventSubscriptionStats`], shared with the subscription task.
+#[derive(Debug, Default)]
+pub(crate) struct SubscriptionCounters {
+    pub(crate) dropped_notifications: AtomicU64,
+    pub(crate) failed_fetches: AtomicU64,
+}
+
+impl SubscriptionCounters {
+    pub(crate) fn stats(&self) -> EventSubscriptionStats {
+        EventSubscriptionStats {
+            dropped_notifications: self.dropped_notifications.load(Ordering::Relaxed),
+            failed_fetches: self.failed_fetches.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Returns the events of type `T` the given program emitted in the transaction.
+///
+/// Events are returned in the order they were emitted. Events emitted with `emit_cpi!` are placed
+/// at the log of their invocation, or after the events in the logs if the logs are truncated.
+/// Failed transactions don't emit any events.
+///
+/// The transaction must be fetched with a binary encoding (e.g. base64).
+pub fn parse_transaction_events<T: anchor_lang::Event + AnchorDeserialize>(
+    program_id: &Pubkey,
+    tx: &EncodedConfirmedTransactionWithStatusMeta,
+) -> Result<Vec<T>, ClientError> {
+    let Some(meta) = &tx.transaction.meta else {
+        return Ok(vec![]);
+    };
+    if meta.err.is_some() {
+        return Ok(vec![]);
+    }
+
+    let transaction = tx
+        .transaction
+        .transaction
+        .decode()
+        .ok_or_else(|| ClientError::other("Unable to decode transaction"))?;
+    let account_keys = account_keys(
+        transaction.message.static_account_keys(),
+        Option::<&UiLoadedAddresses>::from(meta.loaded_addresses.as_ref()),
+    )?;
+    let logs = Option::<&Vec<String>>::from(meta.log_messages.as_ref())
+        .map(|logs| split_logs(logs))
+        .unwrap_or_default();
+    let inner_instructions =
+        Option::<&Vec<UiInnerInstructions>>::from(meta.inner_instructions.as_ref())
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+
+    let program_id_str = program_id.to_string();
+    let mut events = Vec::new();
+    for index in 0..transaction.message.instructions().len() {
+        // Inner instructions are recorded in invocation order, so each one matches the next CPI
+        // in the logs
+        let mut cpis = inner_instructions
+            .iter()
+            .filter(|inner| usize::from(inner.index) == index)
+            .flat_map(|inner| &inner.instructions);
+        if let Some(logs) = logs.get(index) {
+            events.extend(parse_logs_with_cpis(logs, &program_id_str, |events| {
+                if let Some(ix) = cpis.next() {
+                    events.extend(parse_cpi_events(
+                        program_id,
+                        &account_keys,
+                        std::slice::from_ref(ix),
+                    )?);
+                }
+                Ok(())
+            })?);
+        }
+        for ix in cpis {
+            events.extend(parse_cpi_events(
+                program_id,
+                &account_keys,
+                std::slice::from_ref(ix),
+            )?);
+        }
+    }
+
+    Ok(events)
+}
+
+/// Returns the events of type `T` emitted with `emit_cpi!` in the given inner instructions.
+pub fn parse_cpi_events<T: anchor_lang::Event + AnchorDeserialize>(
+    program_id: &Pubkey,
+    account_keys: &[Pubkey],
+    instructions: &[UiInstruction],
+) -> Result<Vec<T>, ClientError> {
+    let mut events = Vec::new();
+    for ix in instructions {
+        let UiInstruction::Compiled(ix) = ix else {
+            continue;
+        };
+        if account_keys.get(usize::from(ix.program_id_index)) != Some(program_id) {
+            continue;
+        }
+
+        let data = bs58::decode(&ix.data)
+            .into_vec()
+            .map_err(ClientError::other)?;
+        let Some(mut data) = data
+            .strip_prefix(EVENT_IX_TAG_LE)
+            .and_then(|data| data.strip_prefix(T::DISCRIMINATOR))
+        else {
+            continue;
+        };
+        let event =
+            T::deserialize(&mut data).map_err(|e| ClientError::LogParseError(e.to_string()))?;
+        events.push(event);
+    }
+
+    Ok(events)
+}
+
+/// Returns all account keys of the transaction, including the ones loaded from lookup tables.
+pub(crate) fn account_keys(
+    static_keys: &[Pubkey],
+    loaded_addresses: Option<&UiLoadedAddresses>,
+) -> Result<Vec<Pubkey>, ClientError> {
+    let mut keys = static_keys.to_vec();
+    if let Some(loaded) = loaded_addresses {
+        for key in loaded.writable.iter().chain(&loaded.readonly) {
+            keys.push(key.parse().map_err(ClientError::other)?);
+        }
+    }
+
+    Ok(keys)
+}
+
+/// Splits the transaction logs into the logs of each top-level instruction.
+fn split_logs(logs: &[String]) -> Vec<&[String]> {
+    static RE: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r"^Program [1-9A-HJ-NP-Za-km-z]+ invoke \[1\]$").unwrap());
+
+    let starts = logs
+        .iter()
+        .enumerate()
+        .filter(|(_, l)| RE.is_match(l))
+        .map(|(i, _)| i)
+        .collect::<Vec<_>>();
+    starts
+        .iter()
+        .enumerate()
+        .map(|(i, start)| &logs[*start..starts.get(i + 1).copied().unwrap_or(logs.len())])
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        anchor_lang::{__private::base64, prelude::*},
+        base64::{engine::general_purpose::STANDARD, Engine},
+        solana_hash::Hash,
+        solana_instruction::{AccountMeta, Instruction},
+        solana_message::{legacy::Message, VersionedMessage},
+        solana_transaction::versioned::VersionedTransaction,
+        solana_transaction_status_client_types::{
+            option_serializer::OptionSerializer, EncodedTransaction,
+            EncodedTransactionWithStatusMeta, TransactionBinaryEncoding, UiCompiledInstruction,
+            UiTransactionStatusMeta,
+        },
+    };
+
+    #[event]
+    #[derive(Debug, PartialEq, Eq)]
+    pub struct MockEvent {
+        pub value: u64,
+    }
+
+    fn event_log(value: u64) -> String {
+        let data = anchor_lang::Event::data(&MockEvent { value });
+        format!("Program data: {}", STANDARD.encode(data))
+    }
+
+    fn event_ix_data(value: u64) -> String {
+        let mut data = EVENT_IX_TAG_LE.to_vec();
+        data.extend(anchor_lang::Event::data(&MockEvent { value }));
+        bs58::encode(data).into_string()
+    }
+
+    fn confirmed_transaction(
+        program_id: Pubkey,
+        err: bool,
+    ) -> EncodedConfirmedTransactionWithStatusMeta {
+        let payer = Pubkey::new_unique();
+        let ix = Instruction::new_with_bytes(
+            program_id,
+            &[],
+            vec![AccountMeta::new_readonly(Pubkey::new_unique(), false)],
+        );
+        let message =
+            Message::new_with_blockhash(&[ix.clone(), ix], Some(&payer), &Hash::new_unique());
+        let program_id_index = message
+            .account_keys
+            .iter()
+            .position(|key| *key == program_id)
+            .unwrap() as u8;
+        let tx = VersionedTransaction {
+            signatures: vec![Signature::default()],
+            message: VersionedMessage::Legacy(message),
+        };
+
+        let event_cpi = |value| {
+            UiInstruction::Compiled(UiCompiledInstruction {
+                program_id_index,
+                accounts: vec![],
+                data: event_ix_data(value),
+                stack_height: Some(2),
+            })
+        };
+        let other_cpi = UiInstruction::Compiled(UiCompiledInstruction {
+            program_id_index: 0,
+            accounts: vec![],
+            data: String::new(),
+            stack_height: Some(2),
+        });
+        let inner_instructions = vec![
+            UiInnerInstructions {
+                index: 0,
+                instructions: vec![event_cpi(2)],
+            },
+            UiInnerInstructions {
+                index: 1,
+                instructions: vec![other_cpi, event_cpi(5)],
+            },
+        ];
+        let logs = vec![
+            format!("Program {program_id} invoke [1]"),
+            event_log(1),
+            format!("Program {program_id} invoke [2]"),
+            format!("Program {program_id} success"),
+            event_log(3),
+            format!("Program {program_id} success"),
+            format!("Program {program_id} invoke [1]"),
+            format!("Program {payer} invoke [2]"),
+            format!("Program {payer} success"),
+            event_log(4),
+            format!("Program {program_id} invoke [2]"),
+            format!("Program {program_id} success"),
+            format!("Program {program_id} success"),
+        ];
+        let meta = UiTransactionStatusMeta {
+            err: err.then(|| serde_json::from_str("\"AccountInUse\"").unwrap()),
+            status: Ok(()),
+            fee: 5000,
+            pre_balances: vec![],
+            post_balances: vec![],
+            inner_instructions: OptionSerializer::Some(inner_instructions),
+            log_messages: OptionSerializer::Some(logs),
+            pre_token_balances: OptionSerializer::None,
+            post_token_balances: OptionSerializer::None,
+            rewards: OptionSerializer::None,
+            loaded_addresses: OptionSerializer::Skip,
+            return_data: OptionSerializer::Skip,
+            compute_units_consumed: OptionSerializer::Skip,
+            cost_units: OptionSerializer::Skip,
+        };
+
+        EncodedConfirmedTransactionWithStatusMeta {
+            slot: 1,
+            transaction: EncodedTransactionWithStatusMeta {
+                transaction: EncodedTransaction::Binary(
+                    STANDARD.encode(bincode::serialize(&tx).unwrap()),
+                    TransactionBinaryEncoding::Base64,
+                ),
+                meta: Some(meta),
+                version: None,
+            },
+            block_time: None,
+        }
+    }
+
+    #[test]
+    fn parses_log_and_cpi_events_in_order() {
+        let program_id = Pubkey::new_unique();
+        let tx = confirmed_transaction(program_id, false);
+
+        // Log, CPI and log events are interleaved in emission order, and CPIs of other programs
+        // are skipped
+        let events = parse_transaction_events::<MockEvent>(&program_id, &tx).unwrap();
+        assert_eq!(
+            events.iter().map(|e| e.value).collect::<Vec<_>>(),
+            vec![1, 2, 3, 4, 5]
+        );
+
+        // Events of other programs are ignored
+        let events = parse_transaction_events::<MockEvent>(&Pubkey::new_unique(), &tx).unwrap();
+        assert!(events.is_empty());
+    }
+
+    #[test]
+    fn appends_cpi_events_of_truncated_logs() {
+        let program_id = Pubkey::new_unique();
+        let mut tx = confirmed_transaction(program_id, false);
+        let meta = tx.transaction.meta.as_mut().unwrap();
+        let OptionSerializer::Some(logs) = &mut meta.log_messages else {
+            panic!("Missing logs");
+        };
+        logs.truncate(8);
+        logs.push("Log truncated".to_owned());
+
+        let events = parse_transaction_events::<MockEvent>(&program_id, &tx).unwrap();
+        assert_eq!(
+            events.iter().map(|e| e.value).collect::<Vec<_>>(),
+            vec![1, 2, 3, 5]
+        );
+    }
+
+    #[test]
+    fn failed_transactions_have_no_events() {
+        let program_id = Pubkey::new_unique();
+        let tx = confirmed_transaction(program_id, true);
+
+        let events = parse_transaction_events::<MockEvent>(&program_id, &tx).unwrap();
+        assert!(events.is_empty());
+    }
+}
@@ -62,27 +62,15 @@
 //! This feature allows passing in a custom RPC client when creating program instances, which is
 //! useful for mocking RPC responses, e.g. via [`RpcClient::new_mock`].
 //!
+//! It also adds the [`mock`] module, which serves accounts, transactions and program log
+//! subscriptions in-process, so that off-chain code can be tested without a validator.
+//!
 //! [`RpcClient::new_mock`]: https://docs.rs/solana-rpc-client/3.0.0/solana_rpc_client/rpc_client/struct.RpcClient.html#method.new_mock
 
 #[cfg(feature = "async")]
 pub use nonblocking::ThreadSafeSigner;
-pub use {
-    anchor_lang,
-    cluster::Cluster,
-    solana_commitment_config::CommitmentConfig,
-    solana_hash::Hash,
-    solana_instruction::Instruction,
-    solana_message::AddressLookupTableAccount,
-    solana_pubsub_client::nonblocking::pubsub_client::PubsubClientError,
-    solana_rpc_client_api::{
-        client_error::{Error as SolanaClientError, ErrorKind as SolanaClientErrorKind},
-        config::RpcSendTransactionConfig,
-        filter::RpcFilterType,
-    },
-    solana_signer::{Signer, SignerError},
-    solana_transaction::{versioned::VersionedTransaction, Transaction},
-};
 use {
+    crate::events::SubscriptionCounters,
     anchor_lang::{
         solana_program::{program_error::ProgramError, pubkey::Pubkey},
         AccountDeserialize, Discriminator, InstructionData, ToAccountMetas,
@@ -93,37 +81,76 @@ use {
     solana_instruction::AccountMeta,
     solana_message::v0,
     solana_pubsub_client::nonblocking::pubsub_client::PubsubClient,
-    solana_rpc_client::nonblocking::rpc_client::RpcClient as AsyncRpcClient,
+    solana_rpc_client::{
+        nonblocking::rpc_client::RpcClient as AsyncRpcClient,
+        rpc_client::GetConfirmedSignaturesForAddress2Config,
+    },
     solana_rpc_client_api::{
         config::{
-            RpcAccountInfoConfig, RpcProgramAccountsConfig, RpcTransactionLogsConfig,
-            RpcTransactionLogsFilter,
+            RpcAccountInfoConfig, RpcProgramAccountsConfig, RpcTransactionConfig,
+            RpcTransactionLogsConfig, RpcTransactionLogsFilter,
         },
         filter::Memcmp,
         request::RpcError,
         response::{Response as RpcResponse, RpcLogsResponse},
     },
     solana_signature::Signature,
+    solana_transaction_status_client_types::{
+        EncodedConfirmedTransactionWithStatusMeta, UiTransactionEncoding,
+    },
     std::{
+        collections::HashSet,
         iter::Map,
         marker::PhantomData,
         ops::Deref,
         pin::Pin,
-        sync::{Arc, LazyLock},
+        sync::{atomic::Ordering, Arc, LazyLock},
         vec::IntoIter,
     },
     thiserror::Error,
     tokio::{
         runtime::Handle,
         sync::{
-            mpsc::{unbounded_channel, UnboundedReceiver},
+            mpsc::{
+                channel, error::TrySendError, unbounded_channel, UnboundedReceiver, UnboundedSender,
+            },
             OnceCell,
         },
         task::JoinHandle,
     },
 };
+pub use {
+    anchor_lang,
+    cluster::Cluster,
+    events::{
+        parse_cpi_events, parse_transaction_events, EventSubscriptionConfig,
+        EventSubscriptionStats, EventsSince,
+    },
+    offline::{
+        add_signature, deserialize_transaction, missing_signers, partial_sign,
+        serialize_transaction, TransactionEncoding,
+    },
+    parser::{ParseInstruction, ParsedAccount, ParsedInstruction, TransactionParser},
+    solana_commitment_config::CommitmentConfig,
+    solana_hash::Hash,
+    solana_instruction::Instruction,
+    solana_message::AddressLookupTableAccount,
+    solana_pubsub_client::nonblocking::pubsub_client::PubsubClientError,
+    solana_rpc_client_api::{
+        client_error::{Error as SolanaClientError, ErrorKind as SolanaClientErrorKind},
+        config::RpcSendTransactionConfig,
+        filter::RpcFilterType,
+    },
+    solana_signer::{Signer, SignerError},
+    solana_transaction::{versioned::VersionedTransaction, Transaction},
+};
 
 mod cluster;
+mod events;
+#[cfg(feature = "mock")]
+pub mod mock;
+mod offline;
+mod parser;
 
 /// Specifies which transaction version to use when building transactions.
 #[derive(Debug, Clone, Default)]
@@ -140,6 +167,68 @@ mod blocking;
 #[cfg(feature = "async")]
 mod nonblocking;
 
+/// Declare an external program based on its IDL, with client helpers.
+///
+/// This is the same as [`anchor_lang::declare_program!`], but the generated module doesn't
+/// require `anchor_lang` to be in scope, and its `client` module additionally includes
+/// `fetch_<account>` functions that fetch and deserialize the program's accounts via [`Program`]:
+///
+/// ```ignore
+/// anchor_client::declare_program!(my_program);
+///
+/// let ix = my_program::client::builders::Initialize::new()
+///     .authority(program.payer())
+///     .instruction()?;
+/// let my_account = my_program::client::fetch_my_account(&program, address)?;
+/// ```
+#[macro_export]
+macro_rules! declare_program {
+    ($name:ident $(, $($arg:tt)*)?) => {
+        $crate::anchor_lang::declare_program!($name, __client = $crate $(, $($arg)*)?);
+    };
+}
+
+#[doc(hidden)]
+#[cfg(not(feature = "async"))]
+#[macro_export]
+macro_rules! __fetch_account_fn {
+    ($(#[$attr:meta])* $name:ident, $account:ty) => {
+        $(#[$attr])*
+        pub fn $name<C, S>(
+            program: &$crate::Program<C>,
+            address: $crate::anchor_lang::prelude::Pubkey,
+        ) -> ::core::result::Result<$account, $crate::ClientError>
+        where
+            C: ::core::ops::Deref<Target = S> + ::core::clone::Clone,
+            S: $crate::Signer,
+        {
+            program.account::<$account>(address)
+        }
+    };
+}
+
+#[doc(hidden)]
+#[cfg(feature = "async")]
+#[macro_export]
+macro_rules! __fetch_account_fn {
+    ($(#[$attr:meta])* $name:ident, $account:ty) => {
+        $(#[$attr])*
+        pub async fn $name<C, S>(
+            program: &$crate::Program<C>,
+            address: $crate::anchor_lang::prelude::Pubkey,
+        ) -> ::core::result::Result<$account, $crate::ClientError>
+        where
+            C: ::core::ops::Deref<Target = S> + ::core::clone::Clone,
+            S: $crate::Signer,
+        {
+            program.account::<$account>(address).await
+        }
+    };
+}
+
+/// Maximum number of accounts of a `getMultipleAccounts` request
+const MAX_MULTIPLE_ACCOUNTS: usize = 100;
+
 const PROGRAM_LOG: &str = "Program log: ";
 const PROGRAM_DATA: &str = "Program data: ";
 
@@ -230,18 +319,92 @@ pub struct Config<C> {
 pub struct EventUnsubscriber<'a> {
     handle: JoinHandle<Result<(), ClientError>>,
     rx: UnboundedReceiver<UnsubscribeFn>,
+    counters: Arc<SubscriptionCounters>,
     #[cfg(not(feature = "async"))]
     runtime_handle: &'a Handle,
     _lifetime_marker: PhantomData<&'a Handle>,
 }
 
 impl EventUnsubscriber<'_> {
-    async fn unsubscribe_internal(mut self) {
-        if let Some(unsubscribe) = self.rx.recv().await {
+    /// Returns the number of notifications whose events were not all delivered so far.
+    pub fn stats(&self) -> EventSubscriptionStats {
+        self.counters.stats()
+    }
+
+    async fn unsubscribe_internal(self) {
+        let Self { handle, mut rx, .. } = self;
+        if let Some(unsubscribe) = rx.recv().await {
+            unsubscribe().await;
+        }
+
+        // Reconnecting subscriptions register a new unsubscribe function for each connection.
+        // Closing the channel signals them to stop, while the functions that were already sent
+        // can still be received. Unsubscribing from a closed connection is a no-op.
+        rx.close();
+        while let Some(unsubscribe) = rx.recv().await {
             unsubscribe().await;
         }
 
-        let _ = self.handle.await;
+        let _ = handle.await;
+    }
+}
+
+/// Historical events of a program, returned by `Program::events_since`.
+///
+/// The signatures of the transactions since the starting point are listed upfront, since the RPC
+/// returns them newest first. The transactions themselves are only fetched one page at a time.
+pub struct EventPages<'a, T> {
+    program_id: Pubkey,
+    rpc_client: Arc<AsyncRpcClient>,
+    commitment: CommitmentConfig,
+    /// Signatures and slots of the transactions that haven't been fetched yet, newest first.
+    signatures: Vec<(Signature, u64)>,
+    #[cfg(not(feature = "async"))]
+    runtime_handle: &'a Handle,
+    _lifetime_marker: PhantomData<(&'a Handle, T)>,
+}
+
+impl<T: anchor_lang::Event + anchor_lang::AnchorDeserialize> EventPages<'_, T> {
+    /// Number of transactions fetched for each page.
+    const PAGE_SIZE: usize = 100;
+
+    /// Returns the number of transactions that haven't been fetched yet.
+    pub fn remaining_transactions(&self) -> usize {
+        self.signatures.len()
+    }
+
+    /// Fetches the next page of transactions, oldest first, and returns their events.
+    ///
+    /// If a transaction can't be fetched, the page is kept so that it can be retried.
+    async fn next_page_internal(&mut self) -> Result<Option<Vec<(EventContext, T)>>, ClientError> {
+        if self.signatures.is_empty() {
+            return Ok(None);
+        }
+
+        let config = RpcTransactionConfig {
+            encoding: Some(UiTransactionEncoding::Base64),
+            commitment: Some(self.commitment),
+            max_supported_transaction_version: Some(0),
+        };
+        let start = self.signatures.len().saturating_sub(Self::PAGE_SIZE);
+        let mut events = Vec::new();
+        for (signature, slot) in self.signatures[start..].iter().rev() {
+            let tx = self
+                .rpc_client
+                .get_transaction_with_config(signature, config)
+                .await
+                .map_err(Box::new)?;
+            for event in parse_transaction_events(&self.program_id, &tx)? {
+                let ctx = EventContext {
+                    signature: *signature,
+                    slot: *slot,
+                };
+                events.push((ctx, event));
+            }
+        }
+        self.signatures.truncate(start);
+
+        Ok(Some(events))
     }
 }
 
@@ -252,7 +415,7 @@ pub struct Program<C> {
     sub_client: OnceCell<Arc<PubsubClient>>,
     #[cfg(not(feature = "async"))]
     rt: tokio::runtime::Runtime,
-    internal_rpc_client: AsyncRpcClient,
+    internal_rpc_client: Arc<AsyncRpcClient>,
 }
 
 impl<C: Deref<Target = impl Signer> + Clone> Program<C> {
@@ -284,6 +447,37 @@ impl<C: Deref<Target = impl Signer> + Clone> Program<C> {
         T::try_deserialize(&mut data).map_err(Into::into)
     }
 
+    async fn resolve_internal<B: ResolveAccounts>(&self, mut builder: B) -> Result<B, ClientError> {
+        let mut fetched = HashSet::new();
+        loop {
+            let addresses = builder
+                .missing_account_data()
+                .into_iter()
+                .filter(|address| fetched.insert(*address))
+                .collect::<Vec<_>>();
+            if addresses.is_empty() {
+                return Ok(builder);
+            }
+
+            for chunk in addresses.chunks(MAX_MULTIPLE_ACCOUNTS) {
+                let accounts = self
+                    .internal_rpc_client
+                    .get_multiple_accounts_with_commitment(
+                        chunk,
+                        self.internal_rpc_client.commitment(),
+                    )
+                    .await
+                    .map_err(Box::new)?
+                    .value;
+                for (address, account) in chunk.iter().zip(accounts) {
+                    if let Some(account) = account {
+                        builder = builder.account_data(*address, account.data);
+                    }
+                }
+            }
+        }
+    }
+
     async fn accounts_lazy_internal<T: AccountDeserialize + Discriminator>(
         &self,
         filters: Vec<RpcFilterType>,
@@ -324,35 +518,33 @@ impl<C: Deref<Target = impl Signer> + Clone> Program<C> {
 
     async fn on_internal<T: anchor_lang::Event + anchor_lang::AnchorDeserialize>(
         &self,
+        config: EventSubscriptionConfig,
         mut f: impl FnMut(&EventContext, T) + Send + 'static,
     ) -> Result<
         (
             JoinHandle<Result<(), ClientError>>,
             UnboundedReceiver<UnsubscribeFn>,
+            Arc<SubscriptionCounters>,
         ),
         ClientError,
     > {
-        let client = self
-            .sub_client
-            .get_or_try_init(|| async {
-                PubsubClient::new(self.cfg.cluster.ws_url())
-                    .await
-                    .map(Arc::new)
-                    .map_err(|e| ClientError::SolanaClientPubsubError(Box::new(e)))
-            })
-            .await?
-            .clone();
+        let client = self.sub_client().await?;
 
         let (tx, rx) = unbounded_channel::<_>();
-        let config = RpcTransactionLogsConfig {
+        let logs_config = RpcTransactionLogsConfig {
             commitment: self.cfg.options,
         };
-        let program_id_str = self.program_id.to_string();
+        let program_id = self.program_id;
+        let program_id_str = program_id.to_string();
         let filter = RpcTransactionLogsFilter::Mentions(vec![program_id_str.clone()]);
+        let rpc_client = self.internal_rpc_client.clone();
+        let tx_commitment = self.transaction_commitment();
+        let counters = Arc::new(SubscriptionCounters::default());
+        let task_counters = counters.clone();
 
         let handle = tokio::spawn(async move {
             let (mut notifications, unsubscribe) = client
-                .logs_subscribe(filter, config)
+                .logs_subscribe(filter, logs_config)
                 .await
                 .map_err(Box::new)?;
 
@@ -363,27 +555,401 @@ impl<C: Deref<Target = impl Signer> + Clone> Program<C> {
                 }))
             })?;
 
+            if !config.cpi_events {
+                while let Some(logs) = notifications.next().await {
+                    let ctx = event_context(&logs)?;
+                    for e in parse_logs_response(logs, &program_id_str)? {
+                        f(&ctx, e);
+                    }
+                }
+                return Ok(());
+            }
+
+            // Transactions are fetched in a separate task so that a slow or failing fetch doesn't
+            // block the notifications. If the queue is full, notifications are dropped instead.
+            let (queue_tx, mut queue_rx) =
+                channel::<RpcResponse<RpcLogsResponse>>(EVENT_QUEUE_CAPACITY);
+            let worker_counters = task_counters.clone();
+            let worker = tokio::spawn(async move {
+                let mut transactions = futures::stream::poll_fn(|cx| queue_rx.poll_recv(cx))
+                    .map(|logs| {
+                        let rpc_client = rpc_client.clone();
+                        async move {
+                            let ctx = event_context(&logs)?;
+                            let tx = match logs.value.err {
+                                None => Some(
+                                    fetch_transaction(&rpc_client, &ctx.signature, tx_commitment)
+                                        .await,
+                                ),
+                                Some(_) => None,
+                            };
+                            Ok::<_, ClientError>((ctx, logs, tx))
+                        }
+                    })
+                    .buffered(FETCH_CONCURRENCY);
+
+                while let Some(fetched) = transactions.next().await {
+                    let (ctx, logs, tx) = fetched?;
+                    let events = match tx {
+                        Some(Ok(tx)) => parse_transaction_events(&program_id, &tx)?,
+                        Some(Err(_e)) => {
+                            #[cfg(feature = "debug")]
+                            println!("Could not fetch transaction {}: {_e}", ctx.signature);
+                            worker_counters
+                                .failed_fetches
+                                .fetch_add(1, Ordering::Relaxed);
+                            parse_logs_response(logs, &program_id_str)?
+                        }
+                        None => parse_logs_response(logs, &program_id_str)?,
+                    };
+                    for e in events {
+                        f(&ctx, e);
+                    }
+                }
+                Ok::<(), ClientError>(())
+            });
+
             while let Some(logs) = notifications.next().await {
-                let signature: Signature = logs.value.signature.parse().map_err(|e| {
-                    ClientError::LogParseError(format!(
-                        "Invalid signature '{}': {e}",
-                        logs.value.signature
-                    ))
+                match queue_tx.try_send(logs) {
+                    Ok(()) => {}
+                    Err(TrySendError::Full(_)) => {
+                        task_counters
+                            .dropped_notifications
+                            .fetch_add(1, Ordering::Relaxed);
+                    }
+                    // The worker stopped with an error
+                    Err(TrySendError::Closed(_)) => break,
+                }
+            }
+
+            // Deliver the events of the queued notifications
+            drop(queue_tx);
+            worker.await.map_err(ClientError::other)?
+        });
+
+        Ok((handle, rx, counters))
+    }
+
+    /// Returns the signatures and slots of the successful transactions of the program since the
+    /// given starting point, newest first.
+    async fn event_signatures_since(
+        &self,
+        since: EventsSince,
+    ) -> Result<Vec<(Signature, u64)>, ClientError> {
+        // The RPC limit for a single `getSignaturesForAddress` request
+        const PAGE_LIMIT: usize = 1000;
+
+        let until = match since {
+            EventsSince::Signature(signature) => Some(signature),
+            EventsSince::Slot(_) => None,
+        };
+
+        // Signatures are returned newest first
+        let mut signatures = Vec::new();
+        let mut before = None;
+        'pages: loop {
+            let page = self
+                .internal_rpc_client
+                .get_signatures_for_address_with_config(
+                    &self.program_id,
+                    GetConfirmedSignaturesForAddress2Config {
+                        before,
+                        until,
+                        limit: Some(PAGE_LIMIT),
+                        commitment: Some(self.transaction_commitment()),
+                    },
+                )
+                .await
+                .map_err(Box::new)?;
+            let is_last_page = page.len() < PAGE_LIMIT;
+
+            for status in page {
+                if let EventsSince::Slot(slot) = since {
+                    if status.slot < slot {
+                        break 'pages;
+                    }
+                }
+
+                let signature: Signature = status.signature.parse().map_err(|e| {
+                    ClientError::other(format!("Invalid signature '{}': {e}", status.signature))
                 })?;
-                let ctx = EventContext {
-                    signature,
-                    slot: logs.context.slot,
-                };
-                let events = parse_logs_response(logs, &program_id_str)?;
-                for e in events {
-                    f(&ctx, e);
+                before = Some(signature);
+                if status.err.is_none() {
+                    signatures.push((signature, status.slot));
+                }
+            }
+
+            if is_last_page {
+                break;
+            }
+        }
+
+        Ok(signatures)
+    }
+
+    async fn subscribe_account_internal<T: AccountDeserialize>(
+        &self,
+        address: Pubkey,
+        f: impl FnMut(&AccountContext, T) + Send + 'static,
+    ) -> Result<
+        (
+            JoinHandle<Result<(), ClientError>>,
+            UnboundedReceiver<UnsubscribeFn>,
+        ),
+        ClientError,
+    > {
+        let config = RpcAccountInfoConfig {
+            encoding: Some(UiAccountEncoding::Base64),
+            commitment: self.cfg.options,
+            ..RpcAccountInfoConfig::default()
+        };
+        self.subscribe_accounts_internal(AccountSubscription::Account(address, config), f)
+            .await
+    }
+
+    async fn subscribe_program_accounts_internal<T: AccountDeserialize + Discriminator>(
+        &self,
+        filters: Vec<RpcFilterType>,
+        f: impl FnMut(&AccountContext, T) + Send + 'static,
+    ) -> Result<
+        (
+            JoinHandle<Result<(), ClientError>>,
+            UnboundedReceiver<UnsubscribeFn>,
+        ),
+        ClientError,
+    > {
+        let account_type_filter =
+            RpcFilterType::Memcmp(Memcmp::new_base58_encoded(0, T::DISCRIMINATOR));
+        let config = RpcProgramAccountsConfig {
+            filters: Some([vec![account_type_filter], filters].concat()),
+            account_config: RpcAccountInfoConfig {
+                encoding: Some(UiAccountEncoding::Base64),
+                commitment: self.cfg.options,
+                ..RpcAccountInfoConfig::default()
+            },
+            ..RpcProgramAccountsConfig::default()
+        };
+        self.subscribe_accounts_internal(AccountSubscription::Program(self.program_id, config), f)
+            .await
+    }
+
+    /// Subscribe to account changes, reconnecting whenever the websocket connection drops.
+    ///
+    /// The first connection uses the shared pubsub client. Since a dropped connection can't be
+    /// reused, reconnections create a dedicated client for the subscription.
+    async fn subscribe_accounts_internal<T: AccountDeserialize>(
+        &self,
+        subscription: AccountSubscription,
+        mut f: impl FnMut(&AccountContext, T) + Send + 'static,
+    ) -> Result<
+        (
+            JoinHandle<Result<(), ClientError>>,
+            UnboundedReceiver<UnsubscribeFn>,
+        ),
+        ClientError,
+    > {
+        let mut client = self.sub_client().await?;
+        let ws_url = self.cfg.cluster.ws_url().to_string();
+
+        let (tx, rx) = unbounded_channel::<UnsubscribeFn>();
+        let handle = tokio::spawn(async move {
+            let mut is_reconnect = false;
+            loop {
+                match watch_accounts(&client, &subscription, &tx, &mut f).await {
+                    // Notifications also end after unsubscribing, in which case the receiver is
+                    // closed
+                    Ok(()) if tx.is_closed() => return Ok(()),
+                    Ok(()) => {}
+                    Err(e) if !is_reconnect => {
+                        return Err(ClientError::SolanaClientPubsubError(Box::new(e)))
+                    }
+                    Err(_) => {}
+                }
+
+                is_reconnect = true;
+                match reconnect_sub_client(&ws_url, &tx).await {
+                    Some(new_client) => client = new_client,
+                    None => return Ok(()),
                 }
             }
-            Ok::<(), ClientError>(())
         });
 
         Ok((handle, rx))
     }
+
+    /// Transaction history can't be queried with a lower commitment than `confirmed`.
+    fn transaction_commitment(&self) -> CommitmentConfig {
+        self.cfg
+            .options
+            .filter(|commitment| commitment.is_at_least_confirmed())
+            .unwrap_or_else(CommitmentConfig::confirmed)
+    }
+
+    async fn sub_client(&self) -> Result<Arc<PubsubClient>, ClientError> {
+        self.sub_client
+            .get_or_try_init(|| async {
+                PubsubClient::new(self.cfg.cluster.ws_url())
+                    .await
+                    .map(Arc::new)
+                    .map_err(|e| ClientError::SolanaClientPubsubError(Box::new(e)))
+            })
+            .await
+            .cloned()
+    }
+}
+
+/// Accounts to watch in [`Program::subscribe_account`] and [`Program::subscribe_program_accounts`].
+enum AccountSubscription {
+    Account(Pubkey, RpcAccountInfoConfig),
+    Program(Pubkey, RpcProgramAccountsConfig),
+}
+
+/// Deliver account notifications until the connection drops or the subscription is cancelled.
+async fn watch_accounts<T: AccountDeserialize>(
+    client: &PubsubClient,
+    subscription: &AccountSubscription,
+    tx: &UnboundedSender<UnsubscribeFn>,
+    f: &mut impl FnMut(&AccountContext, T),
+) -> Result<(), PubsubClientError> {
+    let (mut notifications, unsubscribe) = match subscription {
+        AccountSubscription::Account(address, config) => {
+            let (notifications, unsubscribe) = client
+                .account_subscribe(address, Some(config.clone()))
+                .await?;
+            let notifications = notifications
+                .map(|n| (*address, n.context.slot, n.value))
+                .boxed();
+            (notifications, unsubscribe)
+        }
+        AccountSubscription::Program(program_id, config) => {
+            let (notifications, unsubscribe) = client
+                .program_subscribe(program_id, Some(config.clone()))
+                .await?;
+            let notifications = notifications
+                .filter_map(|n| async move {
+                    let pubkey = n.value.pubkey.parse().ok()?;
+                    Some((pubkey, n.context.slot, n.value.account))
+                })
+                .boxed();
+            (notifications, unsubscribe)
+        }
+    };
+
+    // The subscription was cancelled while reconnecting
+    if let Err(e) = tx.send(unsubscribe) {
+        (e.0)().await;
+        return Ok(());
+    }
+
+    while let Some((pubkey, slot, account)) = notifications.next().await {
+        let Some(data) = account.data.decode() else {
+            continue;
+        };
+        match T::try_deserialize(&mut data.as_slice()) {
+            Ok(account) => f(&AccountContext { pubkey, slot }, account),
+            Err(_e) => {
+                #[cfg(feature = "debug")]
+                println!("Could not deserialize account {pubkey}: {_e}");
+            }
+        }
+    }
+
+    Ok(())
+}
+
+const RECONNECT_MIN_DELAY: std::time::Duration = std::time::Duration::from_millis(500);
+const RECONNECT_MAX_DELAY: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// Create a new pubsub client, retrying with exponential backoff until the connection succeeds.
+///
+/// Returns `None` if the subscription is cancelled while reconnecting.
+async fn reconnect_sub_client(
+    ws_url: &str,
+    tx: &UnboundedSender<UnsubscribeFn>,
+) -> Option<Arc<PubsubClient>> {
+    let mut delay = RECONNECT_MIN_DELAY;
+    loop {
+        if tx.is_closed() {
+            return None;
+        }
+
+        match PubsubClient::new(ws_url).await {
+            Ok(client) => return Some(Arc::new(client)),
+            Err(_e) => {
+                #[cfg(feature = "debug")]
+                println!("Could not reconnect to {ws_url}: {_e}");
+                tokio::time::sleep(delay).await;
+                delay = (delay * 2).min(RECONNECT_MAX_DELAY);
+            }
+        }
+    }
+}
+
+/// Maximum number of notifications waiting for their transaction to be fetched.
+const EVENT_QUEUE_CAPACITY: usize = 1024;
+/// Maximum number of transactions fetched at the same time.
+const FETCH_CONCURRENCY: usize = 8;
+
+#[cfg(not(test))]
+const FETCH_ATTEMPTS: usize = 20;
+#[cfg(test)]
+const FETCH_ATTEMPTS: usize = 3;
+#[cfg(not(test))]
+const FETCH_DELAY: std::time::Duration = std::time::Duration::from_millis(500);
+#[cfg(test)]
+const FETCH_DELAY: std::time::Duration = std::time::Duration::from_millis(10);
+
+/// Returns the context of a logs notification.
+fn event_context(logs: &RpcResponse<RpcLogsResponse>) -> Result<EventContext, ClientError> {
+    let signature = logs.value.signature.parse().map_err(|e| {
+        ClientError::LogParseError(format!("Invalid signature '{}': {e}", logs.value.signature))
+    })?;
+
+    Ok(EventContext {
+        signature,
+        slot: logs.context.slot,
+    })
+}
+
+/// Fetches the transaction of a logs notification, waiting for it to reach the given commitment.
+///
+/// Returns the error of the last attempt if the transaction couldn't be fetched.
+async fn fetch_transaction(
+    rpc_client: &AsyncRpcClient,
+    signature: &Signature,
+    commitment: CommitmentConfig,
+) -> Result<EncodedConfirmedTransactionWithStatusMeta, SolanaClientError> {
+    let config = RpcTransactionConfig {
+        encoding: Some(UiTransactionEncoding::Base64),
+        commitment: Some(commitment),
+        max_supported_transaction_version: Some(0),
+    };
+    let mut attempt = 1;
+    loop {
+        match rpc_client
+            .get_transaction_with_config(signature, config)
+            .await
+        {
+            Ok(tx) => return Ok(tx),
+            Err(e) if attempt == FETCH_ATTEMPTS => return Err(e),
+            Err(_) => {
+                attempt += 1;
+                tokio::time::sleep(FETCH_DELAY).await;
+            }
+        }
+    }
+}
+
+/// Instruction builders that derive accounts from the data of other accounts, e.g. the
+/// builders generated by [`declare_program!`].
+///
+/// See [`Program::resolve`].
+pub trait ResolveAccounts: Sized {
+    /// Addresses of the accounts whose data is needed to derive more accounts.
+    fn missing_account_data(&self) -> Vec<Pubkey>;
+
+    /// Provide the data of the account at `address`.
+    fn account_data(self, address: Pubkey, data: Vec<u8>) -> Self;
 }
 
 /// Iterator with items of type (Pubkey, T). Used to lazily deserialize account structs.
@@ -516,6 +1082,15 @@ pub struct EventContext {
     pub slot: u64,
 }
 
+/// Context of an account change notification.
+#[derive(Debug)]
+pub struct AccountContext {
+    /// Address of the changed account.
+    pub pubkey: Pubkey,
+    /// Slot in which the change was observed.
+    pub slot: u64,
+}
+
 #[derive(Debug, Error)]
 pub enum ClientError {
     #[error("Account not found")]
@@ -747,6 +1322,32 @@ impl<C: Deref<Target = impl Signer> + Clone, S: AsSigner> RequestBuilder<'_, C,
         }
     }
 
+    /// Build a transaction signed only by the signers that are available locally.
+    ///
+    /// Signers that can't sign locally (e.g. a [`NullSigner`] payer standing in for a multisig or
+    /// a cold wallet) are left unsigned, so that the transaction can be serialized with
+    /// [`serialize_transaction`] and signed by the remaining parties via [`partial_sign`] or
+    /// [`add_signature`].
+    ///
+    /// # Arguments
+    ///
+    /// * `version` - The transaction version to use ([`TxVersion::Legacy`] or [`TxVersion::V0`]).
+    /// * `recent_blockhash` - A recent (or durable nonce) blockhash to include in the message.
+    ///
+    /// [`NullSigner`]: solana_signer::null_signer::NullSigner
+    pub fn partially_signed_transaction_versioned(
+        &self,
+        version: TxVersion<'_>,
+        recent_blockhash: Hash,
+    ) -> Result<solana_transaction::versioned::VersionedTransaction, ClientError> {
+        let mut tx = self.transaction_versioned(version, recent_blockhash)?;
+        let mut signers: Vec<&dyn Signer> = self.signers.iter().map(|s| s.as_signer()).collect();
+        signers.push(&*self.payer);
+        partial_sign(&mut tx, &signers)?;
+
+        Ok(tx)
+    }
+
     fn signed_transaction_with_blockhash_versioned(
         &self,
         version: TxVersion<'_>,
@@ -879,7 +1480,23 @@ fn parse_logs_response<T: anchor_lang::Event + anchor_lang::AnchorDeserialize>(
     logs: RpcResponse<RpcLogsResponse>,
     program_id_str: &str,
 ) -> Result<Vec<T>, ClientError> {
-    let mut logs = &logs.value.logs[..];
+    parse_logs(&logs.value.logs, program_id_str)
+}
+
+fn parse_logs<T: anchor_lang::Event + anchor_lang::AnchorDeserialize>(
+    logs: &[String],
+    program_id_str: &str,
+) -> Result<Vec<T>, ClientError> {
+    parse_logs_with_cpis(logs, program_id_str, |_| Ok(()))
+}
+
+/// Parse the events in the logs, calling `on_cpi` at the log of each cross-program invocation so
+/// that events can be inserted in the order they were emitted.
+pub(crate) fn parse_logs_with_cpis<T: anchor_lang::Event + anchor_lang::AnchorDeserialize>(
+    mut logs: &[String],
+    program_id_str: &str,
+    mut on_cpi: impl FnMut(&mut Vec<T>) -> Result<(), ClientError>,
+) -> Result<Vec<T>, ClientError> {
     let mut events: Vec<T> = Vec::new();
     if !logs.is_empty() {
         if let Ok(mut execution) = Execution::new(&mut logs) {
@@ -890,6 +1507,9 @@ fn parse_logs_response<T: anchor_lang::Event + anchor_lang::AnchorDeserialize>(
             });
 
             while let Some(l) = logs_iter.next() {
+                if RE.captures(l).is_some_and(|caps| &caps[2] != "1") {
+                    on_cpi(&mut events)?;
+                }
                 // Parse the log.
                 let (event, new_program, did_pop) = {
                     if program_id_str == execution.program() {
@@ -1283,7 +1903,13 @@ mod tests {
             super::Cluster::Custom(ws_url.clone(), ws_url),
             std::sync::Arc::new(solana_keypair::Keypair::new()),
         );
-        let program = client.program(Pubkey::new_unique()).unwrap();
+        let program = client
+            .program(
+                Pubkey::new_unique(),
+                #[cfg(feature = "mock")]
+                AsyncRpcClient::new_mock("succeeds".to_string()),
+            )
+            .unwrap();
 
         // With the old RwLock-based code, the second call would deadlock.
         // Use a timeout to ensure the test fails instead of hanging forever.
@@ -1329,4 +1955,127 @@ mod tests {
 
         handle.join().unwrap();
     }
+
+    #[derive(Debug, AnchorSerialize, AnchorDeserialize)]
+    pub struct MockAccount {
+        pub value: u64,
+    }
+
+    impl Discriminator for MockAccount {
+        const DISCRIMINATOR: &'static [u8] = &[1, 2, 3, 4, 5, 6, 7, 8];
+    }
+
+    impl AccountDeserialize for MockAccount {
+        fn try_deserialize_unchecked(buf: &mut &[u8]) -> Result<Self> {
+            let mut data = &buf[MockAccount::DISCRIMINATOR.len()..];
+            AnchorDeserialize::deserialize(&mut data).map_err(Into::into)
+        }
+    }
+
+    /// The account subscription should resubscribe after the server drops the connection.
+    #[test]
+    fn account_subscription_reconnects() {
+        use {
+            anchor_lang::__private::base64,
+            base64::{engine::general_purpose::STANDARD, Engine},
+        };
+
+        let rt = tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+
+        let (addr_tx, addr_rx) = std::sync::mpsc::channel();
+
+        // Each connection acknowledges the subscription and sends a single notification, using the
+        // connection index as the slot. Only the first connection is dropped afterwards.
+        rt.spawn(async move {
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            addr_tx.send(listener.local_addr().unwrap()).unwrap();
+
+            for slot in 0u64.. {
+                let (stream, _) = listener.accept().await.unwrap();
+                let mut ws = tokio_tungstenite::accept_async(stream).await.unwrap();
+                let Some(Ok(Message::Text(req))) = ws.next().await else {
+                    continue;
+                };
+                let req: serde_json::Value = serde_json::from_str(&req).unwrap();
+                assert_eq!(req["method"], "accountSubscribe");
+
+                let resp = serde_json::json!({"jsonrpc": "2.0", "result": 7, "id": req["id"]});
+                ws.send(Message::Text(resp.to_string().into()))
+                    .await
+                    .unwrap();
+
+                let mut data = MockAccount::DISCRIMINATOR.to_vec();
+                data.extend((slot + 42).to_le_bytes());
+                let notification = serde_json::json!({
+                    "jsonrpc": "2.0",
+                    "method": "accountNotification",
+                    "params": {
+                        "subscription": 7,
+                        "result": {
+                            "context": { "slot": slot },
+                            "value": {
+                                "lamports": 1,
+                                "data": [STANDARD.encode(&data), "base64"],
+                                "owner": Pubkey::default().to_string(),
+                                "executable": false,
+                                "rentEpoch": 0,
+                                "space": data.len(),
+                            },
+                        },
+                    },
+                });
+                ws.send(Message::Text(notification.to_string().into()))
+                    .await
+                    .unwrap();
+
+                if slot == 0 {
+                    ws.close(None).await.unwrap();
+                } else {
+                    tokio::spawn(async move { while ws.next().await.is_some() {} });
+                }
+            }
+        });
+
+        let addr = addr_rx.recv().unwrap();
+        let ws_url = format!("ws://{}", addr);
+
+        let client = super::Client::new(
+            super::Cluster::Custom(ws_url.clone(), ws_url),
+            std::sync::Arc::new(solana_keypair::Keypair::new()),
+        );
+        let program = client
+            .program(
+                Pubkey::new_unique(),
+                #[cfg(feature = "mock")]
+                AsyncRpcClient::new_mock("succeeds".to_string()),
+            )
+            .unwrap();
+        let address = Pubkey::new_unique();
+
+        let (update_tx, update_rx) = std::sync::mpsc::channel();
+        let callback = move |ctx: &AccountContext, account: MockAccount| {
+            let _ = update_tx.send((ctx.pubkey, ctx.slot, account.value));
+        };
+
+        #[cfg(not(feature = "async"))]
+        let unsubscriber = program
+            .subscribe_account::<MockAccount>(address, callback)
+            .unwrap();
+        #[cfg(feature = "async")]
+        let unsubscriber = rt
+            .block_on(program.subscribe_account::<MockAccount>(address, callback))
+            .unwrap();
+
+        let timeout = std::time::Duration::from_secs(10);
+        assert_eq!(update_rx.recv_timeout(timeout).unwrap(), (address, 0, 42));
+        assert_eq!(update_rx.recv_timeout(timeout).unwrap(), (address, 1, 43));
+
+        #[cfg(not(feature = "async"))]
+        unsubscriber.unsubscribe();
+        #[cfg(feature = "async")]
+        rt.block_on(unsubscriber.unsubscribe());
+    }
 }
@@ -0,0 +1,1602 @@
+//! In-process RPC backend for testing off-chain code without a validator.
+//!
+//! [`MockRpc`] serves the RPC requests made by [`Program`] (account fetching, `getProgramAccounts`,
+//! sending transactions and querying the transaction history) from an [`RpcBackend`], and log and
+//! account subscriptions from a local websocket server:
+//!
+//! ```ignore
+//! let mock = MockRpc::default();
+//! mock.set_anchor_account(address, &MyAccount { count: 1 })?;
+//!
+//! let client = Client::new(mock.cluster(), payer);
+//! let program = client.program(my_program::ID, mock.rpc_client())?;
+//! let account: MyAccount = program.account(address)?;
+//! ```
+//!
+//! The default backend, [`AccountStore`], only stores accounts and accepts every transaction
+//! without executing it. Implement [`RpcBackend`] to serve the requests from elsewhere, e.g. an
+//! in-process SVM that executes the transactions.
+//!
+//! [`Program`]: crate::Program
+
+use {
+    crate::{ClientError, Cluster},
+    anchor_lang::{
+        __private::{
+            base64::{engine::general_purpose::STANDARD, Engine},
+            bytemuck,
+        },
+        solana_program::{pubkey::Pubkey, rent::Rent},
+        AccountSerialize, Owner, ZeroCopy,
+    },
+    async_trait::async_trait,
+    futures::{SinkExt, StreamExt},
+    serde::de::DeserializeOwned,
+    serde_json::{json, Value},
+    solana_account::Account,
+    solana_account_decoder::{encode_ui_account, UiAccount, UiAccountEncoding},
+    solana_commitment_config::CommitmentConfig,
+    solana_hash::Hash,
+    solana_rpc_client::{
+        nonblocking::rpc_client::RpcClient as AsyncRpcClient,
+        rpc_client::RpcClientConfig,
+        rpc_sender::{RpcSender, RpcTransportStats},
+    },
+    solana_rpc_client_api::{
+        client_error::{Error as SolanaClientError, ErrorKind, Result as RpcResult},
+        config::{
+            RpcEncodingConfigWrapper, RpcProgramAccountsConfig, RpcSendTransactionConfig,
+            RpcSignaturesForAddressConfig, RpcTransactionConfig, RpcTransactionLogsFilter,
+        },
+        filter::RpcFilterType,
+        request::{RpcError, RpcRequest, RpcResponseErrorData},
+        response::{
+            Response, RpcBlockhash, RpcConfirmedTransactionStatusWithSignature, RpcKeyedAccount,
+            RpcLogsResponse, RpcResponseContext, RpcSimulateTransactionResult,
+        },
+    },
+    solana_signature::Signature,
+    solana_transaction::versioned::VersionedTransaction,
+    solana_transaction_error::{TransactionError, TransactionResult},
+    solana_transaction_status_client_types::{
+        option_serializer::OptionSerializer, EncodedConfirmedTransactionWithStatusMeta,
+        EncodedTransaction, EncodedTransactionWithStatusMeta, TransactionBinaryEncoding,
+        TransactionConfirmationStatus, TransactionStatus, UiInnerInstructions,
+        UiTransactionEncoding, UiTransactionStatusMeta,
+    },
+    std::{
+        collections::HashMap,
+        net::TcpListener,
+        sync::{
+            atomic::{AtomicU64, AtomicUsize, Ordering},
+            Arc, Mutex, OnceLock, RwLock,
+        },
+    },
+    tokio::sync::{
+        broadcast::{self, error::RecvError},
+        oneshot,
+    },
+    tokio_tungstenite::tungstenite::Message,
+};
+
+/// URL reported by the mock RPC client.
+const MOCK_URL: &str = "http://mock.rpc";
+
+/// Storage and execution backend of [`MockRpc`].
+pub trait RpcBackend: Send + Sync + 'static {
+    /// Returns the account at the given address.
+    fn get_account(&self, pubkey: &Pubkey) -> Option<Account>;
+
+    /// Returns all accounts owned by the given program.
+    fn get_program_accounts(&self, program_id: &Pubkey) -> Vec<(Pubkey, Account)>;
+
+    /// Stores the account at the given address.
+    fn set_account(&self, pubkey: Pubkey, account: Account);
+
+    /// Processes a transaction whose signatures have already been verified.
+    fn process_transaction(&self, tx: &VersionedTransaction) -> TransactionOutcome;
+}
+
+/// Result of processing a transaction with an [`RpcBackend`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransactionOutcome {
+    pub result: TransactionResult<()>,
+    /// Logs of the transaction, sent to the program log subscribers.
+    pub logs: Vec<String>,
+    /// Inner instructions of the transaction, returned by `getTransaction`.
+    pub inner_instructions: Vec<UiInnerInstructions>,
+}
+
+impl TransactionOutcome {
+    pub fn success(logs: Vec<String>) -> Self {
+        Self {
+            result: Ok(()),
+            logs,
+            inner_instructions: vec![],
+        }
+    }
+
+    pub fn failure(err: TransactionError, logs: Vec<String>) -> Self {
+        Self {
+            result: Err(err),
+            logs,
+            inner_instructions: vec![],
+        }
+    }
+
+    pub fn with_inner_instructions(mut self, inner_instructions: Vec<UiInnerInstructions>) -> Self {
+        self.inner_instructions = inner_instructions;
+        self
+    }
+}
+
+/// In-memory account storage that accepts every transaction without executing it.
+#[derive(Debug, Default)]
+pub struct AccountStore {
+    accounts: RwLock<HashMap<Pubkey, Account>>,
+}
+
+impl RpcBackend for AccountStore {
+    fn get_account(&self, pubkey: &Pubkey) -> Option<Account> {
+        self.accounts.read().unwrap().get(pubkey).cloned()
+    }
+
+    fn get_program_accounts(&self, program_id: &Pubkey) -> Vec<(Pubkey, Account)> {
+        self.accounts
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|(_, account)| account.owner == *program_id)
+            .map(|(pubkey, account)| (*pubkey, account.clone()))
+            .collect()
+    }
+
+    fn set_account(&self, pubkey: Pubkey, account: Account) {
+        self.accounts.write().unwrap().insert(pubkey, account);
+    }
+
+    fn process_transaction(&self, _tx: &VersionedTransaction) -> TransactionOutcome {
+        TransactionOutcome::success(vec![])
+    }
+}
+
+/// Mock RPC node backed by an [`RpcBackend`].
+///
+/// Clones share the same state, so the mock can still be seeded and inspected after it's passed
+/// to [`Client::program`](crate::Client::program) via [`MockRpc::rpc_client`].
+#[derive(Clone)]
+pub struct MockRpc {
+    inner: Arc<MockRpcInner>,
+}
+
+struct MockRpcInner {
+    backend: Box<dyn RpcBackend>,
+    blockhash: Hash,
+    slot: AtomicU64,
+    request_count: AtomicUsize,
+    signature_count: AtomicU64,
+    /// Transactions that were sent, oldest first.
+    transactions: Mutex<Vec<SentTransaction>>,
+    statuses: Mutex<HashMap<Signature, (u64, TransactionResult<()>)>>,
+    notifications: broadcast::Sender<Notification>,
+    /// Websocket URL of the pubsub server, which stops once the sender is dropped.
+    pubsub: OnceLock<(String, oneshot::Sender<()>)>,
+}
+
+struct SentTransaction {
+    slot: u64,
+    tx: VersionedTransaction,
+    outcome: TransactionOutcome,
+}
+
+/// Notification sent to the subscribers of the pubsub server.
+#[derive(Debug, Clone)]
+enum Notification {
+    Logs(LogsNotification),
+    Account(AccountNotification),
+}
+
+#[derive(Debug, Clone)]
+struct LogsNotification {
+    slot: u64,
+    signature: Signature,
+    err: Option<TransactionError>,
+    logs: Vec<String>,
+    mentions: Vec<Pubkey>,
+}
+
+#[derive(Debug, Clone)]
+struct AccountNotification {
+    slot: u64,
+    pubkey: Pubkey,
+    account: Account,
+}
+
+/// Active subscription of a pubsub connection.
+enum Subscription {
+    Logs(RpcTransactionLogsFilter),
+    Account(Pubkey),
+    Program(Pubkey, Vec<RpcFilterType>),
+}
+
+impl Default for MockRpc {
+    fn default() -> Self {
+        Self::new(AccountStore::default())
+    }
+}
+
+impl MockRpc {
+    pub fn new(backend: impl RpcBackend) -> Self {
+        let (notifications, _) = broadcast::channel(1024);
+        Self {
+            inner: Arc::new(MockRpcInner {
+                backend: Box::new(backend),
+                blockhash: Hash::new_unique(),
+                slot: AtomicU64::new(1),
+                request_count: AtomicUsize::new(0),
+                signature_count: AtomicU64::new(0),
+                transactions: Mutex::default(),
+                statuses: Mutex::default(),
+                notifications,
+                pubsub: OnceLock::new(),
+            }),
+        }
+    }
+
+    /// Returns an RPC client served by this mock, to be passed to
+    /// [`Client::program`](crate::Client::program).
+    pub fn rpc_client(&self) -> AsyncRpcClient {
+        AsyncRpcClient::new_sender(
+            self.clone(),
+            RpcClientConfig::with_commitment(CommitmentConfig::confirmed()),
+        )
+    }
+
+    /// Returns a cluster whose websocket URL points to the pubsub server of this mock.
+    ///
+    /// The server is started on the first call.
+    pub fn cluster(&self) -> Cluster {
+        Cluster::Custom(MOCK_URL.to_owned(), self.pubsub_url())
+    }
+
+    /// Returns the websocket URL of the pubsub server, starting the server if needed.
+    ///
+    /// # Panics
+    ///
+    /// If the server can't be started.
+    pub fn pubsub_url(&self) -> String {
+        self.inner
+            .pubsub
+            .get_or_init(|| {
+                start_pubsub(self.inner.notifications.clone())
+                    .expect("Failed to start the pubsub server")
+            })
+            .0
+            .clone()
+    }
+
+    /// Returns the account at the given address.
+    pub fn account(&self, pubkey: &Pubkey) -> Option<Account> {
+        self.inner.backend.get_account(pubkey)
+    }
+
+    /// Stores the account at the given address and notifies its subscribers.
+    pub fn set_account(&self, pubkey: Pubkey, account: Account) {
+        self.inner.backend.set_account(pubkey, account.clone());
+        self.notify_account(self.slot(), pubkey, account);
+    }
+
+    /// Stores a rent-exempt account with the serialized Anchor account, owned by `T::owner()`.
+    pub fn set_anchor_account<T: AccountSerialize + Owner>(
+        &self,
+        pubkey: Pubkey,
+        account: &T,
+    ) -> Result<(), ClientError> {
+        let mut data = Vec::new();
+        account.try_serialize(&mut data)?;
+        self.set_program_account(pubkey, T::owner(), data);
+        Ok(())
+    }
+
+    /// Stores a rent-exempt account with the zero-copy Anchor account, owned by `T::owner()`.
+    pub fn set_zero_copy_account<T: ZeroCopy + Owner>(&self, pubkey: Pubkey, account: &T) {
+        let mut data = T::DISCRIMINATOR.to_vec();
+        data.extend_from_slice(bytemuck::bytes_of(account));
+        self.set_program_account(pubkey, T::owner(), data);
+    }
+
+    /// Adds lamports to the account at the given address, creating a system account if needed.
+    pub fn airdrop(&self, pubkey: Pubkey, lamports: u64) {
+        let mut account = self.account(&pubkey).unwrap_or_else(|| Account {
+            owner: anchor_lang::system_program::ID,
+            ..Account::default()
+        });
+        account.lamports += lamports;
+        self.set_account(pubkey, account);
+    }
+
+    /// Returns the transactions that were sent, oldest first.
+    ///
+    /// Transactions rejected by preflight aren't recorded, but the ones that failed when sent with
+    /// `skip_preflight` are, as they would have landed on chain.
+    pub fn sent_transactions(&self) -> Vec<VersionedTransaction> {
+        self.inner
+            .transactions
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|sent| sent.tx.clone())
+            .collect()
+    }
+
+    /// Sends the logs to the log subscribers whose filter matches one of the `mentions`.
+    pub fn emit_logs(&self, signature: Signature, mentions: Vec<Pubkey>, logs: Vec<String>) {
+        let _ = self
+            .inner
+            .notifications
+            .send(Notification::Logs(LogsNotification {
+                slot: self.slot(),
+                signature,
+                err: None,
+                logs,
+                mentions,
+            }));
+    }
+
+    fn notify_account(&self, slot: u64, pubkey: Pubkey, account: Account) {
+        let _ = self
+            .inner
+            .notifications
+            .send(Notification::Account(AccountNotification {
+                slot,
+                pubkey,
+                account,
+            }));
+    }
+
+    fn set_program_account(&self, pubkey: Pubkey, owner: Pubkey, data: Vec<u8>) {
+        let lamports = Rent::default().minimum_balance(data.len());
+        let lamports = self
+            .account(&pubkey)
+            .map_or(lamports, |account| account.lamports.max(lamports));
+        self.set_account(
+            pubkey,
+            Account {
+                lamports,
+                data,
+                owner,
+                executable: false,
+                rent_epoch: 0,
+            },
+        );
+    }
+
+    fn slot(&self) -> u64 {
+        self.inner.slot.load(Ordering::SeqCst)
+    }
+
+    fn next_signature(&self) -> Signature {
+        let count = self.inner.signature_count.fetch_add(1, Ordering::SeqCst) + 1;
+        let mut bytes = [0; 64];
+        bytes[..8].copy_from_slice(&count.to_le_bytes());
+        Signature::from(bytes)
+    }
+
+    fn handle(&self, request: RpcRequest, params: &Value) -> Result<Value, Box<RpcError>> {
+        let context = RpcResponseContext {
+            slot: self.slot(),
+            api_version: None,
+        };
+        let value = match request {
+            RpcRequest::GetAccountInfo => {
+                let pubkey = pubkey_param(params, 0)?;
+                let account = self
+                    .account(&pubkey)
+                    .map(|account| encode_account(&pubkey, &account));
+                json!(Response {
+                    context,
+                    value: account
+                })
+            }
+            RpcRequest::GetMultipleAccounts => {
+                let pubkeys: Vec<String> = param(params, 0)?;
+                let accounts = pubkeys
+                    .iter()
+                    .map(|pubkey| {
+                        let pubkey = parse_pubkey(pubkey)?;
+                        Ok(self
+                            .account(&pubkey)
+                            .map(|account| encode_account(&pubkey, &account)))
+                    })
+                    .collect::<Result<Vec<_>, Box<RpcError>>>()?;
+                json!(Response {
+                    context,
+                    value: accounts
+                })
+            }
+            RpcRequest::GetProgramAccounts => {
+                let program_id = pubkey_param(params, 0)?;
+                let config: RpcProgramAccountsConfig = config_param(params, 1)?;
+                let filters = config.filters.unwrap_or_default();
+                let accounts = self
+                    .inner
+                    .backend
+                    .get_program_accounts(&program_id)
+                    .into_iter()
+                    .filter(|(_, account)| filters.iter().all(|f| filter_matches(f, account)))
+                    .map(|(pubkey, account)| RpcKeyedAccount {
+                        pubkey: pubkey.to_string(),
+                        account: encode_account(&pubkey, &account),
+                    })
+                    .collect::<Vec<_>>();
+                if config.with_context.unwrap_or_default() {
+                    json!(Response {
+                        context,
+                        value: accounts
+                    })
+                } else {
+                    json!(accounts)
+                }
+            }
+            RpcRequest::GetBalance => {
+                let pubkey = pubkey_param(params, 0)?;
+                let lamports = self.account(&pubkey).map_or(0, |account| account.lamports);
+                json!(Response {
+                    context,
+                    value: lamports
+                })
+            }
+            RpcRequest::GetLatestBlockhash => json!(Response {
+                context,
+                value: RpcBlockhash {
+                    blockhash: self.inner.blockhash.to_string(),
+                    last_valid_block_height: u64::MAX,
+                },
+            }),
+            RpcRequest::IsBlockhashValid => json!(Response {
+                context,
+                value: true
+            }),
+            RpcRequest::GetMinimumBalanceForRentExemption => {
+                let data_len: usize = param(params, 0)?;
+                json!(Rent::default().minimum_balance(data_len))
+            }
+            RpcRequest::GetSlot | RpcRequest::GetBlockHeight => json!(self.slot()),
+            RpcRequest::RequestAirdrop => {
+                let pubkey = pubkey_param(params, 0)?;
+                let lamports: u64 = param(params, 1)?;
+                self.airdrop(pubkey, lamports);
+                let signature = self.next_signature();
+                self.inner
+                    .statuses
+                    .lock()
+                    .unwrap()
+                    .insert(signature, (self.slot(), Ok(())));
+                json!(signature.to_string())
+            }
+            RpcRequest::SendTransaction => json!(self.send_transaction(params)?.to_string()),
+            RpcRequest::GetTransaction => {
+                let signature = signature_param(params, 0)?;
+                let config = param::<RpcEncodingConfigWrapper<RpcTransactionConfig>>(params, 1)?
+                    .convert_to_current();
+                let transactions = self.inner.transactions.lock().unwrap();
+                match transactions
+                    .iter()
+                    .find(|sent| sent.tx.signatures[0] == signature)
+                {
+                    Some(sent) => json!(encode_transaction(sent, &config)?),
+                    None => Value::Null,
+                }
+            }
+            RpcRequest::GetSignaturesForAddress => {
+                // The RPC limit for a single request
+                const MAX_LIMIT: usize = 1000;
+
+                let address = pubkey_param(params, 0)?;
+                let config: RpcSignaturesForAddressConfig = config_param(params, 1)?;
+                let before = config.before.as_deref().map(parse_signature).transpose()?;
+                let until = config.until.as_deref().map(parse_signature).transpose()?;
+                let limit = config.limit.unwrap_or(MAX_LIMIT);
+                if limit == 0 || limit > MAX_LIMIT {
+                    return Err(RpcError::ForUser(format!("Invalid limit; max {MAX_LIMIT}")).into());
+                }
+
+                // Newest first, starting after `before` and ending before `until`
+                let transactions = self.inner.transactions.lock().unwrap();
+                let statuses = transactions
+                    .iter()
+                    .rev()
+                    .filter(|sent| sent.tx.message.static_account_keys().contains(&address))
+                    .skip_while(|sent| before.is_some_and(|before| sent.tx.signatures[0] != before))
+                    .skip(usize::from(before.is_some()))
+                    .take_while(|sent| Some(sent.tx.signatures[0]) != until)
+                    .take(limit)
+                    .map(|sent| RpcConfirmedTransactionStatusWithSignature {
+                        signature: sent.tx.signatures[0].to_string(),
+                        slot: sent.slot,
+                        err: sent.outcome.result.clone().err().map(Into::into),
+                        memo: None,
+                        block_time: None,
+                        confirmation_status: Some(TransactionConfirmationStatus::Finalized),
+                    })
+                    .collect::<Vec<_>>();
+                json!(statuses)
+            }
+            RpcRequest::GetSignatureStatuses => {
+                let signatures: Vec<String> = param(params, 0)?;
+                let statuses = self.inner.statuses.lock().unwrap();
+                let value = signatures
+                    .iter()
+                    .map(|signature| {
+                        let signature = signature.parse::<Signature>().ok()?;
+                        let (slot, result) = statuses.get(&signature)?;
+                        Some(TransactionStatus {
+                            slot: *slot,
+                            confirmations: None,
+                            status: result.clone(),
+                            err: result.clone().err(),
+                            confirmation_status: Some(TransactionConfirmationStatus::Finalized),
+                        })
+                    })
+                    .collect::<Vec<_>>();
+                json!(Response { context, value })
+            }
+            _ => {
+                return Err(RpcError::ForUser(format!(
+                    "`{request}` is not supported by the mock RPC"
+                ))
+                .into())
+            }
+        };
+
+        Ok(value)
+    }
+
+    fn send_transaction(&self, params: &Value) -> Result<Signature, Box<RpcError>> {
+        let tx: String = param(params, 0)?;
+        let config: RpcSendTransactionConfig = config_param(params, 1)?;
+        let tx = match config.encoding.unwrap_or(UiTransactionEncoding::Base58) {
+            UiTransactionEncoding::Base58 => bs58::decode(tx).into_vec().ok(),
+            UiTransactionEncoding::Base64 => STANDARD.decode(tx).ok(),
+            encoding => {
+                return Err(RpcError::ForUser(format!(
+                    "Unsupported transaction encoding: {encoding}"
+                ))
+                .into())
+            }
+        }
+        .and_then(|tx| bincode::deserialize::<VersionedTransaction>(&tx).ok())
+        .filter(|tx| tx.sanitize().is_ok())
+        .ok_or_else(|| RpcError::ForUser("Invalid transaction".to_owned()))?;
+
+        let message = tx.message.serialize();
+        let num_signers = usize::from(tx.message.header().num_required_signatures);
+        let keys = tx.message.static_account_keys();
+        if tx.signatures.len() != num_signers
+            || !tx
+                .signatures
+                .iter()
+                .zip(keys)
+                .all(|(signature, pubkey)| signature.verify(pubkey.as_ref(), &message))
+        {
+            return Err(
+                RpcError::ForUser("Transaction signature verification failure".to_owned()).into(),
+            );
+        }
+
+        let outcome = self.inner.backend.process_transaction(&tx);
+        if let Err(err) = &outcome.result {
+            if !config.skip_preflight {
+                return Err(preflight_failure(err, outcome.logs).into());
+            }
+        }
+
+        let slot = self.inner.slot.fetch_add(1, Ordering::SeqCst) + 1;
+        let signature = tx.signatures[0];
+        self.inner
+            .statuses
+            .lock()
+            .unwrap()
+            .insert(signature, (slot, outcome.result.clone()));
+        if outcome.result.is_ok() {
+            for (index, pubkey) in keys.iter().enumerate() {
+                if tx.message.is_maybe_writable(index, None) {
+                    if let Some(account) = self.account(pubkey) {
+                        self.notify_account(slot, *pubkey, account);
+                    }
+                }
+            }
+        }
+        // Record the transaction first, so that it can be fetched once the logs are received
+        let notification = Notification::Logs(LogsNotification {
+            slot,
+            signature,
+            err: outcome.result.clone().err(),
+            logs: outcome.logs.clone(),
+            mentions: keys.to_vec(),
+        });
+        self.inner
+            .transactions
+            .lock()
+            .unwrap()
+            .push(SentTransaction { slot, tx, outcome });
+        let _ = self.inner.notifications.send(notification);
+
+        Ok(signature)
+    }
+}
+
+#[async_trait]
+impl RpcSender for MockRpc {
+    async fn send(&self, request: RpcRequest, params: Value) -> RpcResult<Value> {
+        self.inner.request_count.fetch_add(1, Ordering::SeqCst);
+        self.handle(request, &params)
+            .map_err(|e| SolanaClientError::new_with_request(ErrorKind::RpcError(*e), request))
+    }
+
+    fn get_transport_stats(&self) -> RpcTransportStats {
+        RpcTransportStats {
+            request_count: self.inner.request_count.load(Ordering::SeqCst),
+            ..RpcTransportStats::default()
+        }
+    }
+
+    fn url(&self) -> String {
+        MOCK_URL.to_owned()
+    }
+}
+
+fn param<T: DeserializeOwned>(params: &Value, index: usize) -> Result<T, Box<RpcError>> {
+    let param = params.get(index).cloned().unwrap_or_default();
+    serde_json::from_value(param)
+        .map_err(|e| RpcError::ParseError(format!("param {index}: {e}")).into())
+}
+
+fn config_param<T: DeserializeOwned + Default>(
+    params: &Value,
+    index: usize,
+) -> Result<T, Box<RpcError>> {
+    match params.get(index) {
+        None | Some(Value::Null) => Ok(T::default()),
+        Some(_) => param(params, index),
+    }
+}
+
+fn pubkey_param(params: &Value, index: usize) -> Result<Pubkey, Box<RpcError>> {
+    parse_pubkey(&param::<String>(params, index)?)
+}
+
+fn parse_pubkey(pubkey: &str) -> Result<Pubkey, Box<RpcError>> {
+    pubkey
+        .parse()
+        .map_err(|_| RpcError::ForUser(format!("Invalid pubkey: {pubkey}")).into())
+}
+
+fn signature_param(params: &Value, index: usize) -> Result<Signature, Box<RpcError>> {
+    parse_signature(&param::<String>(params, index)?)
+}
+
+fn parse_signature(signature: &str) -> Result<Signature, Box<RpcError>> {
+    signature
+        .parse()
+        .map_err(|_| RpcError::ForUser(format!("Invalid signature: {signature}")).into())
+}
+
+fn encode_transaction(
+    sent: &SentTransaction,
+    config: &RpcTransactionConfig,
+) -> Result<EncodedConfirmedTransactionWithStatusMeta, Box<RpcError>> {
+    let tx = bincode::serialize(&sent.tx)
+        .map_err(|e| RpcError::ForUser(format!("Failed to serialize transaction: {e}")))?;
+    let transaction = match config.encoding.unwrap_or(UiTransactionEncoding::Json) {
+        UiTransactionEncoding::Binary => {
+            EncodedTransaction::LegacyBinary(bs58::encode(tx).into_string())
+        }
+        UiTransactionEncoding::Base58 => EncodedTransaction::Binary(
+            bs58::encode(tx).into_string(),
+            TransactionBinaryEncoding::Base58,
+        ),
+        UiTransactionEncoding::Base64 => {
+            EncodedTransaction::Binary(STANDARD.encode(tx), TransactionBinaryEncoding::Base64)
+        }
+        encoding => {
+            return Err(
+                RpcError::ForUser(format!("Unsupported transaction encoding: {encoding}")).into(),
+            )
+        }
+    };
+
+    let outcome = &sent.outcome;
+    let meta = UiTransactionStatusMeta {
+        err: outcome.result.clone().err().map(Into::into),
+        status: outcome.result.clone().map_err(Into::into),
+        fee: 0,
+        pre_balances: vec![],
+        post_balances: vec![],
+        inner_instructions: OptionSerializer::Some(outcome.inner_instructions.clone()),
+        log_messages: OptionSerializer::Some(outcome.logs.clone()),
+        pre_token_balances: OptionSerializer::None,
+        post_token_balances: OptionSerializer::None,
+        rewards: OptionSerializer::None,
+        loaded_addresses: OptionSerializer::Skip,
+        return_data: OptionSerializer::Skip,
+        compute_units_consumed: OptionSerializer::Skip,
+        cost_units: OptionSerializer::Skip,
+    };
+
+    Ok(EncodedConfirmedTransactionWithStatusMeta {
+        slot: sent.slot,
+        transaction: EncodedTransactionWithStatusMeta {
+            transaction,
+            meta: Some(meta),
+            version: config
+                .max_supported_transaction_version
+                .map(|_| sent.tx.version()),
+        },
+        block_time: None,
+    })
+}
+
+fn encode_account(pubkey: &Pubkey, account: &Account) -> UiAccount {
+    encode_ui_account(pubkey, account, UiAccountEncoding::Base64, None, None)
+}
+
+fn filter_matches(filter: &RpcFilterType, account: &Account) -> bool {
+    match filter {
+        RpcFilterType::DataSize(size) => account.data.len() as u64 == *size,
+        RpcFilterType::Memcmp(memcmp) => memcmp.bytes_match(&account.data),
+        RpcFilterType::TokenAccountState => true,
+    }
+}
+
+fn preflight_failure(err: &TransactionError, logs: Vec<String>) -> RpcError {
+    RpcError::RpcResponseError {
+        code: -32002,
+        message: format!("Transaction simulation failed: {err}"),
+        data: RpcResponseErrorData::SendTransactionPreflightFailure(RpcSimulateTransactionResult {
+            err: Some(err.clone().into()),
+            logs: Some(logs),
+            accounts: None,
+            units_consumed: None,
+            loaded_accounts_data_size: None,
+            return_data: None,
+            inner_instructions: None,
+            replacement_blockhash: None,
+            fee: None,
+            pre_balances: None,
+            post_balances: None,
+            pre_token_balances: None,
+            post_token_balances: None,
+            loaded_addresses: None,
+        }),
+    }
+}
+
+/// Starts a websocket server for log and account subscriptions on a separate thread.
+fn start_pubsub(
+    notifications: broadcast::Sender<Notification>,
+) -> std::io::Result<(String, oneshot::Sender<()>)> {
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    listener.set_nonblocking(true)?;
+    let url = format!("ws://{}", listener.local_addr()?);
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?;
+
+    let (shutdown_tx, shutdown_rx) = oneshot::channel::<()>();
+    std::thread::spawn(move || {
+        rt.block_on(async move {
+            let Ok(listener) = tokio::net::TcpListener::from_std(listener) else {
+                return;
+            };
+            let accept = async {
+                loop {
+                    if let Ok((stream, _)) = listener.accept().await {
+                        tokio::spawn(serve_pubsub(stream, notifications.subscribe()));
+                    }
+                }
+            };
+            tokio::select! {
+                _ = accept => {}
+                _ = shutdown_rx => {}
+            }
+        })
+    });
+
+    Ok((url, shutdown_tx))
+}
+
+async fn serve_pubsub(
+    stream: tokio::net::TcpStream,
+    mut notifications: broadcast::Receiver<Notification>,
+) {
+    let Ok(mut ws) = tokio_tungstenite::accept_async(stream).await else {
+        return;
+    };
+
+    let mut subscriptions = HashMap::new();
+    let mut next_id = 0;
+    loop {
+        let messages = tokio::select! {
+            message = ws.next() => match message {
+                Some(Ok(Message::Text(request))) => {
+                    vec![handle_pubsub_request(&request, &mut subscriptions, &mut next_id)]
+                }
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => continue,
+            },
+            notification = notifications.recv() => match notification {
+                Ok(notification) => subscriptions
+                    .iter()
+                    .filter_map(|(id, subscription)| notification.to_message(*id, subscription))
+                    .collect(),
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => break,
+            },
+        };
+
+        for message in messages {
+            if ws
+                .send(Message::Text(message.to_string().into()))
+                .await
+                .is_err()
+            {
+                return;
+            }
+        }
+    }
+}
+
+fn handle_pubsub_request(
+    request: &str,
+    subscriptions: &mut HashMap<u64, Subscription>,
+    next_id: &mut u64,
+) -> Value {
+    let Ok(request) = serde_json::from_str::<Value>(request) else {
+        return pubsub_error(Value::Null, -32700, "Parse error".to_owned());
+    };
+    let id = request["id"].clone();
+    let params = &request["params"];
+
+    let subscription = match request["method"].as_str() {
+        Some("logsSubscribe") => param(params, 0).map(Subscription::Logs),
+        Some("accountSubscribe") => pubkey_param(params, 0).map(Subscription::Account),
+        Some("programSubscribe") => pubkey_param(params, 0).and_then(|program_id| {
+            let config: RpcProgramAccountsConfig = config_param(params, 1)?;
+            Ok(Subscription::Program(
+                program_id,
+                config.filters.unwrap_or_default(),
+            ))
+        }),
+        Some("logsUnsubscribe" | "accountUnsubscribe" | "programUnsubscribe") => {
+            let removed = params[0]
+                .as_u64()
+                .and_then(|subscription| subscriptions.remove(&subscription))
+                .is_some();
+            return json!({ "jsonrpc": "2.0", "result": removed, "id": id });
+        }
+        _ => return pubsub_error(id, -32601, "Method not found".to_owned()),
+    };
+
+    match subscription {
+        Ok(subscription) => {
+            *next_id += 1;
+            subscriptions.insert(*next_id, subscription);
+            json!({ "jsonrpc": "2.0", "result": *next_id, "id": id })
+        }
+        Err(e) => pubsub_error(id, -32602, format!("Invalid params: {e}")),
+    }
+}
+
+fn pubsub_error(id: Value, code: i64, message: String) -> Value {
+    json!({ "jsonrpc": "2.0", "error": { "code": code, "message": message }, "id": id })
+}
+
+impl Notification {
+    /// Returns the notification message for the subscription, if it matches.
+    fn to_message(&self, id: u64, subscription: &Subscription) -> Option<Value> {
+        let (method, result) = match (self, subscription) {
+            (Self::Logs(logs), Subscription::Logs(filter)) if logs.matches(filter) => {
+                ("logsNotification", json!(logs.to_response()))
+            }
+            (Self::Account(account), Subscription::Account(pubkey))
+                if account.pubkey == *pubkey =>
+            {
+                let value = encode_account(&account.pubkey, &account.account);
+                ("accountNotification", json!(account.to_response(value)))
+            }
+            (Self::Account(account), Subscription::Program(program_id, filters))
+                if account.account.owner == *program_id
+                    && filters.iter().all(|f| filter_matches(f, &account.account)) =>
+            {
+                let value = RpcKeyedAccount {
+                    pubkey: account.pubkey.to_string(),
+                    account: encode_account(&account.pubkey, &account.account),
+                };
+                ("programNotification", json!(account.to_response(value)))
+            }
+            _ => return None,
+        };
+
+        Some(json!({
+            "jsonrpc": "2.0",
+            "method": method,
+            "params": { "result": result, "subscription": id },
+        }))
+    }
+}
+
+impl LogsNotification {
+    fn matches(&self, filter: &RpcTransactionLogsFilter) -> bool {
+        match filter {
+            RpcTransactionLogsFilter::All | RpcTransactionLogsFilter::AllWithVotes => true,
+            RpcTransactionLogsFilter::Mentions(mentions) => mentions
+                .iter()
+                .any(|mention| self.mentions.iter().any(|key| key.to_string() == *mention)),
+        }
+    }
+
+    fn to_response(&self) -> Response<RpcLogsResponse> {
+        Response {
+            context: RpcResponseContext {
+                slot: self.slot,
+                api_version: None,
+            },
+            value: RpcLogsResponse {
+                signature: self.signature.to_string(),
+                err: self.err.clone().map(Into::into),
+                logs: self.logs.clone(),
+            },
+        }
+    }
+}
+
+impl AccountNotification {
+    fn to_response<T>(&self, value: T) -> Response<T> {
+        Response {
+            context: RpcResponseContext {
+                slot: self.slot,
+                api_version: None,
+            },
+            value,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{AccountContext, Client, EventContext, Program},
+        anchor_lang::{
+            error::ErrorCode, event, event::EVENT_IX_TAG_LE,
+            solana_program::instruction::Instruction, AccountDeserialize, AnchorDeserialize,
+            AnchorSerialize, Discriminator, Result,
+        },
+        solana_keypair::Keypair,
+        solana_rpc_client::rpc_client::GetConfirmedSignaturesForAddress2Config,
+        solana_signer::Signer,
+        solana_transaction::Transaction,
+        solana_transaction_status_client_types::{UiCompiledInstruction, UiInstruction},
+        std::{io::Write, sync::mpsc, time::Duration},
+    };
+
+    const PROGRAM_ID: Pubkey = Pubkey::new_from_array([7; 32]);
+
+    #[derive(Debug, PartialEq, Eq, AnchorSerialize, AnchorDeserialize)]
+    pub struct Counter {
+        pub count: u64,
+    }
+
+    impl Discriminator for Counter {
+        const DISCRIMINATOR: &'static [u8] = &[1, 2, 3, 4, 5, 6, 7, 8];
+    }
+
+    impl Owner for Counter {
+        fn owner() -> Pubkey {
+            PROGRAM_ID
+        }
+    }
+
+    impl AccountSerialize for Counter {
+        fn try_serialize<W: Write>(&self, writer: &mut W) -> Result<()> {
+            writer
+                .write_all(Self::DISCRIMINATOR)
+                .and_then(|_| AnchorSerialize::serialize(self, writer))
+                .map_err(|_| ErrorCode::AccountDidNotSerialize.into())
+        }
+    }
+
+    impl AccountDeserialize for Counter {
+        fn try_deserialize(buf: &mut &[u8]) -> Result<Self> {
+            if !buf.starts_with(Self::DISCRIMINATOR) {
+                return Err(ErrorCode::AccountDiscriminatorMismatch.into());
+            }
+            Self::try_deserialize_unchecked(buf)
+        }
+
+        fn try_deserialize_unchecked(buf: &mut &[u8]) -> Result<Self> {
+            let mut data = &buf[Self::DISCRIMINATOR.len()..];
+            AnchorDeserialize::deserialize(&mut data).map_err(Into::into)
+        }
+    }
+
+    #[event]
+    pub struct Incremented {
+        pub count: u64,
+    }
+
+    /// Backend that fails every transaction.
+    #[derive(Default)]
+    struct FailingBackend(AccountStore);
+
+    impl RpcBackend for FailingBackend {
+        fn get_account(&self, pubkey: &Pubkey) -> Option<Account> {
+            self.0.get_account(pubkey)
+        }
+
+        fn get_program_accounts(&self, program_id: &Pubkey) -> Vec<(Pubkey, Account)> {
+            self.0.get_program_accounts(program_id)
+        }
+
+        fn set_account(&self, pubkey: Pubkey, account: Account) {
+            self.0.set_account(pubkey, account)
+        }
+
+        fn process_transaction(&self, _tx: &VersionedTransaction) -> TransactionOutcome {
+            TransactionOutcome::failure(TransactionError::AccountInUse, vec![])
+        }
+    }
+
+    /// Backend that emits `Incremented` events with `emit!`, `emit_cpi!` and `emit!` again in each
+    /// instruction, counting up from the first byte of the instruction data.
+    #[derive(Default)]
+    struct EventBackend(AccountStore);
+
+    impl RpcBackend for EventBackend {
+        fn get_account(&self, pubkey: &Pubkey) -> Option<Account> {
+            self.0.get_account(pubkey)
+        }
+
+        fn get_program_accounts(&self, program_id: &Pubkey) -> Vec<(Pubkey, Account)> {
+            self.0.get_program_accounts(program_id)
+        }
+
+        fn set_account(&self, pubkey: Pubkey, account: Account) {
+            self.0.set_account(pubkey, account)
+        }
+
+        fn process_transaction(&self, tx: &VersionedTransaction) -> TransactionOutcome {
+            let mut logs = Vec::new();
+            let mut inner_instructions = Vec::new();
+            for (index, ix) in tx.message.instructions().iter().enumerate() {
+                let count = u64::from(ix.data[0]);
+                logs.extend([
+                    format!("Program {PROGRAM_ID} invoke [1]"),
+                    event_log(count),
+                    format!("Program {PROGRAM_ID} invoke [2]"),
+                    format!("Program {PROGRAM_ID} success"),
+                    event_log(count + 2),
+                    format!("Program {PROGRAM_ID} success"),
+                ]);
+                inner_instructions.push(UiInnerInstructions {
+                    index: index as u8,
+                    instructions: vec![UiInstruction::Compiled(UiCompiledInstruction {
+                        program_id_index: ix.program_id_index,
+                        accounts: vec![],
+                        data: event_ix_data(count + 1),
+                        stack_height: Some(2),
+                    })],
+                });
+            }
+            TransactionOutcome::success(logs).with_inner_instructions(inner_instructions)
+        }
+    }
+
+    fn event_log(count: u64) -> String {
+        let data = anchor_lang::Event::data(&Incremented { count });
+        format!("Program data: {}", STANDARD.encode(data))
+    }
+
+    fn event_ix_data(count: u64) -> String {
+        let mut data = EVENT_IX_TAG_LE.to_vec();
+        data.extend(anchor_lang::Event::data(&Incremented { count }));
+        bs58::encode(data).into_string()
+    }
+
+    fn increment_ix() -> Instruction {
+        increment_by_ix(0)
+    }
+
+    fn increment_by_ix(count: u8) -> Instruction {
+        Instruction::new_with_bytes(PROGRAM_ID, &[count], vec![])
+    }
+
+    fn send(program: &Program<Arc<Keypair>>, ix: Instruction) -> Signature {
+        #[cfg(not(feature = "async"))]
+        return program.request().instruction(ix).send().unwrap();
+        #[cfg(feature = "async")]
+        tokio::runtime::Runtime::new()
+            .unwrap()
+            .block_on(program.request().instruction(ix).send())
+            .unwrap()
+    }
+
+    #[test]
+    fn serves_accounts_and_transactions() {
+        let mock = MockRpc::default();
+        let address = Pubkey::new_unique();
+        mock.set_anchor_account(address, &Counter { count: 5 })
+            .unwrap();
+        mock.set_anchor_account(Pubkey::new_unique(), &Counter { count: 6 })
+            .unwrap();
+        // Not owned by the program
+        mock.airdrop(Pubkey::new_unique(), 1);
+
+        let payer = Arc::new(Keypair::new());
+        let client = Client::new(mock.cluster(), payer.clone());
+        let program = client.program(PROGRAM_ID, mock.rpc_client()).unwrap();
+
+        #[cfg(not(feature = "async"))]
+        let (counter, counters, signature) = (
+            program.account::<Counter>(address).unwrap(),
+            program.accounts::<Counter>(vec![]).unwrap(),
+            program
+                .request()
+                .instruction(increment_ix())
+                .send()
+                .unwrap(),
+        );
+        #[cfg(feature = "async")]
+        let (counter, counters, signature) = {
+            let rt = tokio::runtime::Runtime::new().unwrap();
+            rt.block_on(async {
+                (
+                    program.account::<Counter>(address).await.unwrap(),
+                    program.accounts::<Counter>(vec![]).await.unwrap(),
+                    program
+                        .request()
+                        .instruction(increment_ix())
+                        .send()
+                        .await
+                        .unwrap(),
+                )
+            })
+        };
+
+        assert_eq!(counter, Counter { count: 5 });
+        assert_eq!(counters.len(), 2);
+        let sent = mock.sent_transactions();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].signatures[0], signature);
+        assert_eq!(sent[0].message.static_account_keys()[0], payer.pubkey());
+    }
+
+    #[test]
+    fn rejects_failed_transactions() {
+        let mock = MockRpc::new(FailingBackend::default());
+        let client = Client::new(mock.cluster(), Arc::new(Keypair::new()));
+        let program = client.program(PROGRAM_ID, mock.rpc_client()).unwrap();
+
+        #[cfg(not(feature = "async"))]
+        let err = program
+            .request()
+            .instruction(increment_ix())
+            .send()
+            .unwrap_err();
+        #[cfg(feature = "async")]
+        let err = tokio::runtime::Runtime::new()
+            .unwrap()
+            .block_on(program.request().instruction(increment_ix()).send())
+            .unwrap_err();
+
+        let crate::ClientError::SolanaClientError(err) = err else {
+            panic!("Unexpected error: {err}");
+        };
+        assert_eq!(
+            err.get_transaction_error(),
+            Some(TransactionError::AccountInUse)
+        );
+        assert!(mock.sent_transactions().is_empty());
+    }
+
+    #[test]
+    fn records_failed_transactions_without_preflight() {
+        let mock = MockRpc::new(FailingBackend::default());
+        let rpc = mock.rpc_client();
+        let payer = Keypair::new();
+
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let (signature, status) = rt.block_on(async {
+            let blockhash = rpc.get_latest_blockhash().await.unwrap();
+            let tx = Transaction::new_signed_with_payer(
+                &[increment_ix()],
+                Some(&payer.pubkey()),
+                &[&payer],
+                blockhash,
+            );
+            let config = RpcSendTransactionConfig {
+                skip_preflight: true,
+                ..RpcSendTransactionConfig::default()
+            };
+            let signature = rpc.send_transaction_with_config(&tx, config).await.unwrap();
+            (
+                signature,
+                rpc.get_signature_status(&signature).await.unwrap(),
+            )
+        });
+
+        assert_eq!(status, Some(Err(TransactionError::AccountInUse)));
+        let sent = mock.sent_transactions();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].signatures[0], signature);
+    }
+
+    /// Builder that needs the data of every account in a chain of accounts, where the data of
+    /// each account is the address of the next one.
+    #[derive(Default)]
+    struct ChainBuilder {
+        start: Pubkey,
+        data: Vec<(Pubkey, Vec<u8>)>,
+    }
+
+    impl crate::ResolveAccounts for ChainBuilder {
+        fn missing_account_data(&self) -> Vec<Pubkey> {
+            match self.data.last() {
+                Some((_, data)) => vec![Pubkey::try_from(data.as_slice()).unwrap()],
+                None => vec![self.start],
+            }
+        }
+
+        fn account_data(mut self, address: Pubkey, data: Vec<u8>) -> Self {
+            self.data.push((address, data));
+            self
+        }
+    }
+
+    #[test]
+    fn resolves_accounts() {
+        let mock = MockRpc::default();
+        let addresses = [(); 3].map(|_| Pubkey::new_unique());
+        for window in addresses.windows(2) {
+            mock.set_account(
+                window[0],
+                Account {
+                    lamports: 1,
+                    data: window[1].to_bytes().to_vec(),
+                    ..Account::default()
+                },
+            );
+        }
+
+        let client = Client::new(mock.cluster(), Arc::new(Keypair::new()));
+        let program = client.program(PROGRAM_ID, mock.rpc_client()).unwrap();
+        let builder = ChainBuilder {
+            start: addresses[0],
+            ..ChainBuilder::default()
+        };
+
+        #[cfg(not(feature = "async"))]
+        let builder = program.resolve(builder).unwrap();
+        #[cfg(feature = "async")]
+        let builder = tokio::runtime::Runtime::new()
+            .unwrap()
+            .block_on(program.resolve(builder))
+            .unwrap();
+
+        // The last account doesn't exist
+        let resolved = builder
+            .data
+            .iter()
+            .map(|(address, _)| *address)
+            .collect::<Vec<_>>();
+        assert_eq!(resolved, addresses[..2]);
+    }
+
+    #[test]
+    fn streams_program_logs() {
+        let mock = MockRpc::default();
+        let client = Client::new(mock.cluster(), Arc::new(Keypair::new()));
+        let program = client.program(PROGRAM_ID, mock.rpc_client()).unwrap();
+
+        let (event_tx, event_rx) = mpsc::channel();
+        let callback = move |ctx: &EventContext, event: Incremented| {
+            let _ = event_tx.send((ctx.signature, event.count));
+        };
+        #[cfg(not(feature = "async"))]
+        let unsubscriber = program.on::<Incremented>(callback).unwrap();
+        #[cfg(feature = "async")]
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        #[cfg(feature = "async")]
+        let unsubscriber = rt.block_on(program.on::<Incremented>(callback)).unwrap();
+
+        let signature = Signature::from([1; 64]);
+        let data = anchor_lang::Event::data(&Incremented { count: 3 });
+        let logs = vec![
+            format!("Program {PROGRAM_ID} invoke [1]"),
+            format!("Program data: {}", STANDARD.encode(data)),
+            format!("Program {PROGRAM_ID} success"),
+        ];
+
+        // The subscription is created in the background, so keep emitting until it's received
+        let received = (0..50).find_map(|_| {
+            mock.emit_logs(signature, vec![PROGRAM_ID], logs.clone());
+            event_rx.recv_timeout(Duration::from_millis(100)).ok()
+        });
+        assert_eq!(received, Some((signature, 3)));
+
+        #[cfg(not(feature = "async"))]
+        unsubscriber.unsubscribe();
+        #[cfg(feature = "async")]
+        rt.block_on(unsubscriber.unsubscribe());
+    }
+
+    #[test]
+    fn serves_transaction_history() {
+        let mock = MockRpc::new(EventBackend::default());
+        let client = Client::new(mock.cluster(), Arc::new(Keypair::new()));
+        let program = client.program(PROGRAM_ID, mock.rpc_client()).unwrap();
+        let signatures = [0, 10, 20].map(|count| send(&program, increment_by_ix(count)));
+
+        let rpc = mock.rpc_client();
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let signatures_for_address = |before, until, limit| {
+            rt.block_on(rpc.get_signatures_for_address_with_config(
+                &PROGRAM_ID,
+                GetConfirmedSignaturesForAddress2Config {
+                    before,
+                    until,
+                    limit,
+                    commitment: None,
+                },
+            ))
+            .unwrap()
+            .into_iter()
+            .map(|status| status.signature.parse::<Signature>().unwrap())
+            .collect::<Vec<_>>()
+        };
+
+        // Newest first
+        assert_eq!(
+            signatures_for_address(None, None, None),
+            [signatures[2], signatures[1], signatures[0]]
+        );
+        assert_eq!(
+            signatures_for_address(Some(signatures[2]), Some(signatures[0]), None),
+            [signatures[1]]
+        );
+        assert_eq!(signatures_for_address(None, None, Some(1)), [signatures[2]]);
+
+        let tx = rt
+            .block_on(rpc.get_transaction(&signatures[1], UiTransactionEncoding::Base64))
+            .unwrap();
+        assert_eq!(
+            tx.transaction.transaction.decode().unwrap().signatures[0],
+            signatures[1]
+        );
+        let meta = tx.transaction.meta.unwrap();
+        assert_eq!(
+            Option::<Vec<String>>::from(meta.log_messages).unwrap()[1],
+            event_log(10)
+        );
+        let inner_instructions = Option::<Vec<UiInnerInstructions>>::from(meta.inner_instructions);
+        assert_eq!(inner_instructions.unwrap().len(), 1);
+
+        // Unknown transactions are returned as `null`
+        assert!(rt
+            .block_on(rpc.get_transaction(&Signature::default(), UiTransactionEncoding::Base64))
+            .is_err());
+    }
+
+    #[test]
+    fn streams_account_changes() {
+        let mock = MockRpc::default();
+        let client = Client::new(mock.cluster(), Arc::new(Keypair::new()));
+        let program = client.program(PROGRAM_ID, mock.rpc_client()).unwrap();
+        let address = Pubkey::new_unique();
+
+        let (account_tx, account_rx) = mpsc::channel();
+        let account_callback = move |ctx: &AccountContext, counter: Counter| {
+            let _ = account_tx.send((ctx.pubkey, counter.count));
+        };
+        let (program_tx, program_rx) = mpsc::channel();
+        let program_callback = move |ctx: &AccountContext, counter: Counter| {
+            let _ = program_tx.send((ctx.pubkey, counter.count));
+        };
+        #[cfg(not(feature = "async"))]
+        let unsubscribers = [
+            program
+                .subscribe_account::<Counter>(address, account_callback)
+                .unwrap(),
+            program
+                .subscribe_program_accounts::<Counter>(vec![], program_callback)
+                .unwrap(),
+        ];
+        #[cfg(feature = "async")]
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        #[cfg(feature = "async")]
+        let unsubscribers = rt.block_on(async {
+            [
+                program
+                    .subscribe_account::<Counter>(address, account_callback)
+                    .await
+                    .unwrap(),
+                program
+                    .subscribe_program_accounts::<Counter>(vec![], program_callback)
+                    .await
+                    .unwrap(),
+            ]
+        });
+
+        // The subscriptions are created in the background, so keep updating the account until
+        // the update is received
+        for rx in [account_rx, program_rx] {
+            let received = (0..50).find_map(|count| {
+                mock.set_anchor_account(address, &Counter { count })
+                    .unwrap();
+                rx.recv_timeout(Duration::from_millis(100)).ok()
+            });
+            assert!(matches!(received, Some((pubkey, _)) if pubkey == address));
+        }
+
+        for unsubscriber in unsubscribers {
+            #[cfg(not(feature = "async"))]
+            unsubscriber.unsubscribe();
+            #[cfg(feature = "async")]
+            rt.block_on(unsubscriber.unsubscribe());
+        }
+    }
+
+    #[test]
+    fn replays_events_in_pages() {
+        let mock = MockRpc::new(EventBackend::default());
+        let client = Client::new(mock.cluster(), Arc::new(Keypair::new()));
+        let program = client.program(PROGRAM_ID, mock.rpc_client()).unwrap();
+        let signatures = (0..=101)
+            .map(|count| send(&program, increment_by_ix(count)))
+            .collect::<Vec<_>>();
+
+        #[cfg(not(feature = "async"))]
+        let pages = program
+            .events_since::<Incremented>(signatures[0])
+            .unwrap()
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .unwrap();
+        #[cfg(feature = "async")]
+        let pages = tokio::runtime::Runtime::new().unwrap().block_on(async {
+            let mut events = program
+                .events_since::<Incremented>(signatures[0])
+                .await
+                .unwrap();
+            let mut pages = Vec::new();
+            while let Some(page) = events.next_page().await.unwrap() {
+                pages.push(page);
+            }
+            pages
+        });
+
+        // 100 transactions per page, with the `emit!`, `emit_cpi!` and `emit!` events of each
+        // instruction in emission order
+        assert_eq!(pages.iter().map(Vec::len).collect::<Vec<_>>(), [300, 3]);
+        let events = pages.into_iter().flatten().collect::<Vec<_>>();
+        assert_eq!(
+            events
+                .iter()
+                .map(|(_, event)| event.count)
+                .collect::<Vec<_>>(),
+            (1..=101)
+                .flat_map(|count| [count, count + 1, count + 2])
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(events[0].0.signature, signatures[1]);
+        assert_eq!(events.last().unwrap().0.signature, signatures[101]);
+    }
+
+    #[test]
+    fn streams_cpi_events_in_order() {
+        let mock = MockRpc::new(EventBackend::default());
+        let client = Client::new(mock.cluster(), Arc::new(Keypair::new()));
+        let program = client.program(PROGRAM_ID, mock.rpc_client()).unwrap();
+
+        let (event_tx, event_rx) = mpsc::channel();
+        let callback = move |_: &EventContext, event: Incremented| {
+            let _ = event_tx.send(event.count);
+        };
+        let config = crate::EventSubscriptionConfig { cpi_events: true };
+        #[cfg(not(feature = "async"))]
+        let unsubscriber = program.on_with_config(config, callback).unwrap();
+        #[cfg(feature = "async")]
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        #[cfg(feature = "async")]
+        let unsubscriber = rt
+            .block_on(program.on_with_config(config, callback))
+            .unwrap();
+
+        // Transactions that can't be fetched fall back to the events in the logs, so keep
+        // emitting logs of an unknown transaction until the subscription is created
+        let logs = vec![
+            format!("Program {PROGRAM_ID} invoke [1]"),
+            event_log(100),
+            format!("Program {PROGRAM_ID} success"),
+        ];
+        let received = (0..50).find_map(|_| {
+            mock.emit_logs(Signature::from([1; 64]), vec![PROGRAM_ID], logs.clone());
+            event_rx.recv_timeout(Duration::from_secs(2)).ok()
+        });
+        assert_eq!(received, Some(100));
+        assert_eq!(unsubscriber.stats().failed_fetches, 1);
+
+        send(&program, increment_by_ix(0));
+        send(&program, increment_by_ix(10));
+        let events = (0..6)
+            .map(|_| event_rx.recv_timeout(Duration::from_secs(5)).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(events, [0, 1, 2, 10, 11, 12]);
+        assert_eq!(
+            unsubscriber.stats(),
+            crate::EventSubscriptionStats {
+                dropped_notifications: 0,
+                failed_fetches: 1,
+            }
+        );
+
+        #[cfg(not(feature = "async"))]
+        unsubscriber.unsubscribe();
+        #[cfg(feature = "async")]
+        rt.block_on(unsubscriber.unsubscribe());
+    }
+
+    #[test]
+    fn counts_dropped_notifications() {
+        let mock = MockRpc::default();
+        let client = Client::new(mock.cluster(), Arc::new(Keypair::new()));
+        let program = client.program(PROGRAM_ID, mock.rpc_client()).unwrap();
+
+        let (event_tx, event_rx) = mpsc::channel();
+        let callback = move |_: &EventContext, event: Incremented| {
+            let _ = event_tx.send(event.count);
+        };
+        let config = crate::EventSubscriptionConfig { cpi_events: true };
+        #[cfg(not(feature = "async"))]
+        let unsubscriber = program.on_with_config(config, callback).unwrap();
+        #[cfg(feature = "async")]
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        #[cfg(feature = "async")]
+        let unsubscriber = rt
+            .block_on(program.on_with_config(config, callback))
+            .unwrap();
+
+        let signature = Signature::from([1; 64]);
+        let logs = vec![
+            format!("Program {PROGRAM_ID} invoke [1]"),
+            event_log(1),
+            format!("Program {PROGRAM_ID} success"),
+        ];
+        let received = (0..50).any(|_| {
+            mock.emit_logs(signature, vec![PROGRAM_ID], logs.clone());
+            event_rx.recv_timeout(Duration::from_secs(2)).is_ok()
+        });
+        assert!(received);
+
+        // Unknown transactions are retried before falling back to the logs, so notifications
+        // emitted faster than that pile up. Emit in batches so that the mock's own notification
+        // buffer doesn't overflow.
+        for _ in 0..20 {
+            for _ in 0..100 {
+                mock.emit_logs(signature, vec![PROGRAM_ID], logs.clone());
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        let dropped = (0..50).any(|_| {
+            std::thread::sleep(Duration::from_millis(100));
+            unsubscriber.stats().dropped_notifications > 0
+        });
+        assert!(dropped);
+        assert!(unsubscriber.stats().failed_fetches > 0);
+
+        #[cfg(not(feature = "async"))]
+        unsubscriber.unsubscribe();
+        #[cfg(feature = "async")]
+        rt.block_on(unsubscriber.unsubscribe());
+    }
+}
@@ -1,7 +1,8 @@
 use {
     crate::{
-        AsSigner, ClientError, Config, EventContext, EventUnsubscriber, Program,
-        ProgramAccountsIterator, RequestBuilder, TxVersion,
+        AccountContext, AsSigner, ClientError, Config, EventContext, EventPages,
+        EventSubscriptionConfig, EventUnsubscriber, EventsSince, Program, ProgramAccountsIterator,
+        RequestBuilder, ResolveAccounts, TxVersion,
     },
     anchor_lang::{prelude::Pubkey, AccountDeserialize, Discriminator},
     solana_commitment_config::CommitmentConfig,
@@ -21,6 +22,16 @@ impl<'a> EventUnsubscriber<'a> {
     }
 }
 
+impl<T: anchor_lang::Event + anchor_lang::AnchorDeserialize> EventPages<'_, T> {
+    /// Fetches the next page of transactions and returns their events, oldest first.
+    ///
+    /// Returns `None` once all transactions have been fetched. Pages can be empty if their
+    /// transactions didn't emit any events of type `T`.
+    pub async fn next_page(&mut self) -> Result<Option<Vec<(EventContext, T)>>, ClientError> {
+        self.next_page_internal().await
+    }
+}
+
 pub trait ThreadSafeSigner: Signer + Send + Sync + 'static {
     fn to_signer(&self) -> &dyn Signer;
 }
@@ -54,7 +65,7 @@ impl<C: Deref<Target = impl Signer> + Clone> Program<C> {
             program_id,
             cfg,
             sub_client: OnceCell::new(),
-            internal_rpc_client: rpc_client,
+            internal_rpc_client: Arc::new(rpc_client),
         })
     }
 
@@ -87,6 +98,14 @@ impl<C: Deref<Target = impl Signer> + Clone> Program<C> {
         self.account_internal(address).await
     }
 
+    /// Fetches the accounts whose data is needed to derive the remaining accounts of the
+    /// instruction builder, until no more accounts can be derived.
+    ///
+    /// Accounts that don't exist are skipped.
+    pub async fn resolve<B: ResolveAccounts>(&self, builder: B) -> Result<B, ClientError> {
+        self.resolve_internal(builder).await
+    }
+
     /// Returns all program accounts of the given type matching the given filters
     pub async fn accounts<T: AccountDeserialize + Discriminator>(
         &self,
@@ -111,11 +130,86 @@ impl<C: Deref<Target = impl Signer> + Clone> Program<C> {
         &self,
         f: impl FnMut(&EventContext, T) + Send + 'static,
     ) -> Result<EventUnsubscriber<'_>, ClientError> {
-        let (handle, rx) = self.on_internal(f).await?;
+        self.on_with_config(EventSubscriptionConfig::default(), f)
+            .await
+    }
+
+    /// Subscribe to program events with the given configuration, e.g. to also decode events
+    /// emitted with `emit_cpi!`.
+    ///
+    /// Returns an [`EventUnsubscriber`] to unsubscribe and close connection gracefully.
+    pub async fn on_with_config<T: anchor_lang::Event + anchor_lang::AnchorDeserialize>(
+        &self,
+        config: EventSubscriptionConfig,
+        f: impl FnMut(&EventContext, T) + Send + 'static,
+    ) -> Result<EventUnsubscriber<'_>, ClientError> {
+        let (handle, rx, counters) = self.on_internal(config, f).await?;
+
+        Ok(EventUnsubscriber {
+            handle,
+            rx,
+            counters,
+            _lifetime_marker: PhantomData,
+        })
+    }
+
+    /// Returns the events of type `T` emitted by the program since the given transaction
+    /// signature or slot, oldest first.
+    ///
+    /// Both events emitted with `emit!` and `emit_cpi!` are returned. Failed transactions are
+    /// skipped. The transactions are fetched one page at a time with [`EventPages::next_page`].
+    pub async fn events_since<T: anchor_lang::Event + anchor_lang::AnchorDeserialize>(
+        &self,
+        since: impl Into<EventsSince>,
+    ) -> Result<EventPages<'_, T>, ClientError> {
+        let signatures = self.event_signatures_since(since.into()).await?;
+
+        Ok(EventPages {
+            program_id: self.program_id,
+            rpc_client: self.internal_rpc_client.clone(),
+            commitment: self.transaction_commitment(),
+            signatures,
+            _lifetime_marker: PhantomData,
+        })
+    }
+
+    /// Subscribe to changes of the account at the given address.
+    ///
+    /// Updates that can't be deserialized as `T` (e.g. after the account is closed) are skipped.
+    /// The subscription reconnects automatically if the websocket connection drops.
+    ///
+    /// Returns an [`EventUnsubscriber`] to unsubscribe and close connection gracefully.
+    pub async fn subscribe_account<T: AccountDeserialize>(
+        &self,
+        address: Pubkey,
+        f: impl FnMut(&AccountContext, T) + Send + 'static,
+    ) -> Result<EventUnsubscriber<'_>, ClientError> {
+        let (handle, rx) = self.subscribe_account_internal(address, f).await?;
+
+        Ok(EventUnsubscriber {
+            handle,
+            rx,
+            counters: Arc::default(),
+            _lifetime_marker: PhantomData,
+        })
+    }
+
+    /// Subscribe to changes of all program accounts of the given type matching the given filters.
+    ///
+    /// The subscription reconnects automatically if the websocket connection drops.
+    ///
+    /// Returns an [`EventUnsubscriber`] to unsubscribe and close connection gracefully.
+    pub async fn subscribe_program_accounts<T: AccountDeserialize + Discriminator>(
+        &self,
+        filters: Vec<RpcFilterType>,
+        f: impl FnMut(&AccountContext, T) + Send + 'static,
+    ) -> Result<EventUnsubscriber<'_>, ClientError> {
+        let (handle, rx) = self.subscribe_program_accounts_internal(filters, f).await?;
 
         Ok(EventUnsubscriber {
             handle,
             rx,
+            counters: Arc::default(),
             _lifetime_marker: PhantomData,
         })
     }
@@ -0,0 +1,212 @@
+//! Helpers for offline and multi-party signing.
+//!
+//! A transaction can be assembled on one machine (e.g. with
+//! [`RequestBuilder::partially_signed_transaction_versioned`]), serialized with
+//! [`serialize_transaction`], passed around as a base58/base64 string, and signed by the remaining
+//! parties with [`partial_sign`] or [`add_signature`] before it's submitted.
+//!
+//! [`RequestBuilder::partially_signed_transaction_versioned`]: crate::RequestBuilder::partially_signed_transaction_versioned
+
+use {
+    crate::ClientError,
+    anchor_lang::{
+        __private::base64::{engine::general_purpose::STANDARD, Engine},
+        solana_program::pubkey::Pubkey,
+    },
+    solana_signature::Signature,
+    solana_signer::Signer,
+    solana_transaction::versioned::VersionedTransaction,
+    std::{fmt, str::FromStr},
+};
+
+/// Encoding used to exchange serialized transactions between signing parties.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TransactionEncoding {
+    Base58,
+    #[default]
+    Base64,
+}
+
+impl FromStr for TransactionEncoding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "base58" => Ok(Self::Base58),
+            "base64" => Ok(Self::Base64),
+            _ => Err(format!(
+                "Invalid transaction encoding `{s}` (expected `base58` or `base64`)"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for TransactionEncoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Base58 => write!(f, "base58"),
+            Self::Base64 => write!(f, "base64"),
+        }
+    }
+}
+
+/// Serialize the given transaction, including any signatures it already has.
+pub fn serialize_transaction(
+    tx: &VersionedTransaction,
+    encoding: TransactionEncoding,
+) -> Result<String, ClientError> {
+    let bytes = bincode::serialize(tx).map_err(ClientError::other)?;
+    Ok(match encoding {
+        TransactionEncoding::Base58 => bs58::encode(bytes).into_string(),
+        TransactionEncoding::Base64 => STANDARD.encode(bytes),
+    })
+}
+
+/// Deserialize a transaction created with [`serialize_transaction`].
+pub fn deserialize_transaction(
+    data: &str,
+    encoding: TransactionEncoding,
+) -> Result<VersionedTransaction, ClientError> {
+    let data = data.trim();
+    let bytes = match encoding {
+        TransactionEncoding::Base58 => bs58::decode(data).into_vec().map_err(ClientError::other)?,
+        TransactionEncoding::Base64 => STANDARD.decode(data).map_err(ClientError::other)?,
+    };
+    let tx: VersionedTransaction = bincode::deserialize(&bytes).map_err(ClientError::other)?;
+    tx.sanitize().map_err(ClientError::other)?;
+
+    Ok(tx)
+}
+
+/// Returns the signers whose signatures are still missing from the transaction.
+pub fn missing_signers(tx: &VersionedTransaction) -> Vec<Pubkey> {
+    required_signers(tx)
+        .iter()
+        .zip(&tx.signatures)
+        .filter(|(_, signature)| **signature == Signature::default())
+        .map(|(pubkey, _)| *pubkey)
+        .collect()
+}
+
+/// Sign the transaction with the given signers, keeping the existing signatures.
+///
+/// Signers that produce the default signature, e.g. [`NullSigner`], are skipped, which allows
+/// building a transaction for parties whose keys are not available locally.
+///
+/// [`NullSigner`]: solana_signer::null_signer::NullSigner
+pub fn partial_sign(
+    tx: &mut VersionedTransaction,
+    signers: &[&dyn Signer],
+) -> Result<(), ClientError> {
+    let message = tx.message.serialize();
+    for signer in signers {
+        let signature = signer.try_sign_message(&message)?;
+        if signature == Signature::default() {
+            continue;
+        }
+
+        let index = signer_index(tx, &signer.try_pubkey()?)?;
+        tx.signatures[index] = signature;
+    }
+
+    Ok(())
+}
+
+/// Add a signature that was created by another party.
+///
+/// The signature is verified against the transaction message before it's added.
+pub fn add_signature(
+    tx: &mut VersionedTransaction,
+    pubkey: &Pubkey,
+    signature: Signature,
+) -> Result<(), ClientError> {
+    let index = signer_index(tx, pubkey)?;
+    if !signature.verify(pubkey.as_ref(), &tx.message.serialize()) {
+        return Err(ClientError::other(format!(
+            "Invalid signature for signer {pubkey}"
+        )));
+    }
+
+    tx.signatures[index] = signature;
+    Ok(())
+}
+
+fn required_signers(tx: &VersionedTransaction) -> &[Pubkey] {
+    let keys = tx.message.static_account_keys();
+    let num_signers = tx.message.header().num_required_signatures as usize;
+    &keys[..num_signers.min(keys.len())]
+}
+
+fn signer_index(tx: &VersionedTransaction, pubkey: &Pubkey) -> Result<usize, ClientError> {
+    required_signers(tx)
+        .iter()
+        .position(|key| key == pubkey)
+        .ok_or_else(|| {
+            ClientError::other(format!(
+                "{pubkey} is not a required signer of the transaction"
+            ))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        solana_hash::Hash,
+        solana_instruction::{AccountMeta, Instruction},
+        solana_keypair::Keypair,
+        solana_message::{legacy::Message, VersionedMessage},
+        solana_signer::null_signer::NullSigner,
+    };
+
+    fn unsigned_transaction(payer: &Pubkey, other: &Pubkey) -> VersionedTransaction {
+        let ix = Instruction {
+            program_id: Pubkey::new_unique(),
+            accounts: vec![AccountMeta::new_readonly(*other, true)],
+            data: vec![1, 2, 3],
+        };
+        let message = Message::new_with_blockhash(&[ix], Some(payer), &Hash::new_unique());
+        VersionedTransaction {
+            signatures: vec![Signature::default(); message.header.num_required_signatures as usize],
+            message: VersionedMessage::Legacy(message),
+        }
+    }
+
+    #[test]
+    fn multi_party_signing_roundtrip() {
+        let payer = Keypair::new();
+        let other = Keypair::new();
+        let mut tx = unsigned_transaction(&payer.pubkey(), &other.pubkey());
+
+        // First party only has their own key
+        partial_sign(&mut tx, &[&NullSigner::new(&payer.pubkey()), &other]).unwrap();
+        assert_eq!(missing_signers(&tx), vec![payer.pubkey()]);
+
+        for encoding in [TransactionEncoding::Base58, TransactionEncoding::Base64] {
+            let blob = serialize_transaction(&tx, encoding).unwrap();
+            let mut tx = deserialize_transaction(&blob, encoding).unwrap();
+
+            // Second party signs offline and hands over the signature
+            let signature = payer.sign_message(&tx.message.serialize());
+            add_signature(&mut tx, &payer.pubkey(), signature).unwrap();
+            assert!(missing_signers(&tx).is_empty());
+            let message = tx.message.serialize();
+            assert!(tx
+                .signatures
+                .iter()
+                .zip(required_signers(&tx))
+                .all(|(signature, pubkey)| signature.verify(pubkey.as_ref(), &message)));
+        }
+    }
+
+    #[test]
+    fn rejects_unknown_signer_and_invalid_signature() {
+        let payer = Keypair::new();
+        let other = Keypair::new();
+        let mut tx = unsigned_transaction(&payer.pubkey(), &other.pubkey());
+
+        assert!(partial_sign(&mut tx, &[&Keypair::new()]).is_err());
+        assert!(add_signature(&mut tx, &payer.pubkey(), other.sign_message(b"other")).is_err());
+        assert_eq!(missing_signers(&tx).len(), 2);
+    }
+}
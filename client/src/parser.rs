@@ -0,0 +1,415 @@
+//! Parsing of the instructions of confirmed transactions.
+//!
+//! [`TransactionParser`] walks the top-level and inner instructions of a transaction and parses
+//! the instructions of the registered programs, returning a tree of [`ParsedInstruction`]s where
+//! each instruction contains the instructions it invoked.
+//!
+//! ```ignore
+//! anchor_client::declare_program!(amm);
+//! anchor_client::declare_program!(lending);
+//!
+//! enum Parsed {
+//!     Amm(amm::parsers::Instruction),
+//!     Lending(lending::parsers::Instruction),
+//! }
+//!
+//! let parser = TransactionParser::new()
+//!     .program(Parsed::Amm)
+//!     .program(Parsed::Lending);
+//! let instructions = parser.parse_transaction(&tx)?;
+//! ```
+
+use {
+    crate::{events::account_keys, ClientError},
+    anchor_lang::solana_program::{
+        instruction::{AccountMeta, Instruction},
+        pubkey::Pubkey,
+    },
+    solana_transaction_status_client_types::{
+        EncodedConfirmedTransactionWithStatusMeta, UiInnerInstructions, UiInstruction,
+        UiLoadedAddresses,
+    },
+    std::collections::HashMap,
+};
+
+/// Instructions of a program that can be parsed from raw instructions.
+///
+/// This is implemented for the `parsers::Instruction` enum generated by
+/// [`declare_program!`](crate::declare_program).
+pub trait ParseInstruction: Sized {
+    /// ID of the program.
+    fn program_id() -> Pubkey;
+
+    /// Parse an instruction of the program, without checking the signer and writable attributes
+    /// of its accounts.
+    fn parse_unchecked(ix: &Instruction) -> anchor_lang::Result<Self>;
+
+    /// Name of the instruction.
+    fn name(&self) -> &'static str;
+
+    /// Names of the accounts of the instruction, in order.
+    fn account_names(&self) -> &'static [&'static str];
+}
+
+/// An account of a [`ParsedInstruction`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParsedAccount {
+    /// Name of the account, if the instruction was parsed and the account is not one of the
+    /// remaining accounts.
+    pub name: Option<&'static str>,
+    pub pubkey: Pubkey,
+    /// Whether the account is a signer of the transaction.
+    pub is_signer: bool,
+    /// Whether the account is writable in the transaction.
+    pub is_writable: bool,
+}
+
+/// An instruction of a transaction, and the instructions it invoked.
+#[derive(Debug, Clone)]
+pub struct ParsedInstruction<T> {
+    pub program_id: Pubkey,
+    pub accounts: Vec<ParsedAccount>,
+    pub data: Vec<u8>,
+    /// Name of the instruction, if the instruction was parsed.
+    pub name: Option<&'static str>,
+    /// The parsed instruction, if the program is registered and the instruction could be parsed.
+    pub instruction: Option<T>,
+    /// Instructions invoked by the instruction.
+    pub inner_instructions: Vec<ParsedInstruction<T>>,
+}
+
+type ParseFn<T> = Box<dyn Fn(&Instruction) -> Option<Parsed<T>> + Send + Sync>;
+
+struct Parsed<T> {
+    name: &'static str,
+    account_names: &'static [&'static str],
+    instruction: T,
+}
+
+/// Parser of the instructions of transactions that invoke multiple programs.
+///
+/// See the [module documentation](self).
+pub struct TransactionParser<T> {
+    parsers: HashMap<Pubkey, ParseFn<T>>,
+}
+
+impl<T> Default for TransactionParser<T> {
+    fn default() -> Self {
+        Self {
+            parsers: HashMap::new(),
+        }
+    }
+}
+
+impl<T> TransactionParser<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register the program of the instructions of type `P`, whose parsed instructions are
+    /// converted with `map`.
+    pub fn program<P: ParseInstruction>(
+        mut self,
+        map: impl Fn(P) -> T + Send + Sync + 'static,
+    ) -> Self {
+        self.parsers.insert(
+            P::program_id(),
+            Box::new(move |ix| {
+                let ix = P::parse_unchecked(ix).ok()?;
+                Some(Parsed {
+                    name: ix.name(),
+                    account_names: ix.account_names(),
+                    instruction: map(ix),
+                })
+            }),
+        );
+        self
+    }
+
+    /// Parse an instruction. Its inner instructions are not set.
+    pub fn parse_instruction(&self, ix: &Instruction) -> ParsedInstruction<T> {
+        let parsed = self.parsers.get(&ix.program_id).and_then(|parse| parse(ix));
+        let account_names = parsed
+            .as_ref()
+            .map(|parsed| parsed.account_names)
+            .unwrap_or_default();
+        let accounts = ix
+            .accounts
+            .iter()
+            .enumerate()
+            .map(|(i, acc)| ParsedAccount {
+                name: account_names.get(i).copied(),
+                pubkey: acc.pubkey,
+                is_signer: acc.is_signer,
+                is_writable: acc.is_writable,
+            })
+            .collect();
+        let (name, instruction) = match parsed {
+            Some(parsed) => (Some(parsed.name), Some(parsed.instruction)),
+            None => (None, None),
+        };
+
+        ParsedInstruction {
+            program_id: ix.program_id,
+            accounts,
+            data: ix.data.clone(),
+            name,
+            instruction,
+            inner_instructions: vec![],
+        }
+    }
+
+    /// Parse the instructions of the transaction, including the inner instructions.
+    ///
+    /// Instructions of programs that are not registered, or that can't be parsed, are returned
+    /// without a parsed instruction. The instructions of failed transactions are also parsed.
+    ///
+    /// The transaction must be fetched with a binary encoding (e.g. base64). Inner instructions
+    /// are nested based on their stack height, or all treated as direct inner instructions of
+    /// the top-level instruction if the stack height is not available.
+    pub fn parse_transaction(
+        &self,
+        tx: &EncodedConfirmedTransactionWithStatusMeta,
+    ) -> Result<Vec<ParsedInstruction<T>>, ClientError> {
+        let transaction = tx
+            .transaction
+            .transaction
+            .decode()
+            .ok_or_else(|| ClientError::other("Unable to decode transaction"))?;
+        let meta = tx.transaction.meta.as_ref();
+        let loaded_addresses = meta
+            .and_then(|meta| Option::<&UiLoadedAddresses>::from(meta.loaded_addresses.as_ref()));
+        let message = &transaction.message;
+        let static_keys = message.static_account_keys();
+        let account_keys = account_keys(static_keys, loaded_addresses)?;
+        let loaded_writable = loaded_addresses.map_or(0, |loaded| loaded.writable.len());
+        let account_meta = |index: u8| -> Result<AccountMeta, ClientError> {
+            let index = usize::from(index);
+            let pubkey = *account_keys
+                .get(index)
+                .ok_or_else(|| ClientError::other("Invalid account index"))?;
+            let is_writable = if index < static_keys.len() {
+                message.is_maybe_writable(index, None)
+            } else {
+                index < static_keys.len() + loaded_writable
+            };
+            Ok(AccountMeta {
+                pubkey,
+                is_signer: message.is_signer(index),
+                is_writable,
+            })
+        };
+        let instruction = |program_id_index: u8, accounts: &[u8], data: Vec<u8>| {
+            Ok::<_, ClientError>(Instruction {
+                program_id: account_meta(program_id_index)?.pubkey,
+                accounts: accounts
+                    .iter()
+                    .map(|index| account_meta(*index))
+                    .collect::<Result<_, _>>()?,
+                data,
+            })
+        };
+
+        let inner_instructions = meta
+            .and_then(|meta| {
+                Option::<&Vec<UiInnerInstructions>>::from(meta.inner_instructions.as_ref())
+            })
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        let mut instructions = vec![];
+        for (index, ix) in message.instructions().iter().enumerate() {
+            let ix = instruction(ix.program_id_index, &ix.accounts, ix.data.clone())?;
+            let mut parsed = self.parse_instruction(&ix);
+            for inner in inner_instructions
+                .iter()
+                .filter(|inner| usize::from(inner.index) == index)
+                .flat_map(|inner| &inner.instructions)
+            {
+                let UiInstruction::Compiled(inner) = inner else {
+                    continue;
+                };
+                let data = bs58::decode(&inner.data)
+                    .into_vec()
+                    .map_err(ClientError::other)?;
+                let ix = instruction(inner.program_id_index, &inner.accounts, data)?;
+                // Top-level instructions have a stack height of 1
+                let depth = inner
+                    .stack_height
+                    .map_or(0, |height| height.saturating_sub(2) as usize);
+                insert(
+                    &mut parsed.inner_instructions,
+                    depth,
+                    self.parse_instruction(&ix),
+                );
+            }
+            instructions.push(parsed);
+        }
+
+        Ok(instructions)
+    }
+}
+
+/// Insert the instruction as an inner instruction at `depth` of the last instruction.
+fn insert<T>(instructions: &mut Vec<ParsedInstruction<T>>, depth: usize, ix: ParsedInstruction<T>) {
+    match instructions.last_mut() {
+        Some(last) if depth > 0 => insert(&mut last.inner_instructions, depth - 1, ix),
+        _ => instructions.push(ix),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        anchor_lang::__private::base64::{engine::general_purpose::STANDARD, Engine},
+        solana_hash::Hash,
+        solana_message::{legacy::Message, VersionedMessage},
+        solana_signature::Signature,
+        solana_transaction::versioned::VersionedTransaction,
+        solana_transaction_status_client_types::{
+            option_serializer::OptionSerializer, EncodedTransaction,
+            EncodedTransactionWithStatusMeta, TransactionBinaryEncoding, UiCompiledInstruction,
+            UiTransactionStatusMeta,
+        },
+    };
+
+    const PROGRAM_ID: Pubkey = Pubkey::new_from_array([1; 32]);
+
+    /// Instructions with a single byte of data, and a `source` and `destination` account.
+    #[derive(Debug, PartialEq, Eq)]
+    struct Transfer(u8);
+
+    impl ParseInstruction for Transfer {
+        fn program_id() -> Pubkey {
+            PROGRAM_ID
+        }
+
+        fn parse_unchecked(ix: &Instruction) -> anchor_lang::Result<Self> {
+            match (ix.data.as_slice(), ix.accounts.len()) {
+                ([value], 2..) => Ok(Self(*value)),
+                _ => Err(anchor_lang::error::ErrorCode::InstructionDidNotDeserialize.into()),
+            }
+        }
+
+        fn name(&self) -> &'static str {
+            "transfer"
+        }
+
+        fn account_names(&self) -> &'static [&'static str] {
+            &["source", "destination"]
+        }
+    }
+
+    fn confirmed_transaction(
+        instructions: &[Instruction],
+    ) -> (EncodedConfirmedTransactionWithStatusMeta, Vec<Pubkey>) {
+        let payer = Pubkey::new_unique();
+        let message = Message::new_with_blockhash(instructions, Some(&payer), &Hash::new_unique());
+        let account_keys = message.account_keys.clone();
+        let tx = VersionedTransaction {
+            signatures: vec![Signature::default()],
+            message: VersionedMessage::Legacy(message),
+        };
+        let meta = UiTransactionStatusMeta {
+            err: None,
+            status: Ok(()),
+            fee: 5000,
+            pre_balances: vec![],
+            post_balances: vec![],
+            inner_instructions: OptionSerializer::Some(vec![]),
+            log_messages: OptionSerializer::None,
+            pre_token_balances: OptionSerializer::None,
+            post_token_balances: OptionSerializer::None,
+            rewards: OptionSerializer::None,
+            loaded_addresses: OptionSerializer::Skip,
+            return_data: OptionSerializer::Skip,
+            compute_units_consumed: OptionSerializer::Skip,
+            cost_units: OptionSerializer::Skip,
+        };
+
+        let tx = EncodedConfirmedTransactionWithStatusMeta {
+            slot: 1,
+            transaction: EncodedTransactionWithStatusMeta {
+                transaction: EncodedTransaction::Binary(
+                    STANDARD.encode(bincode::serialize(&tx).unwrap()),
+                    TransactionBinaryEncoding::Base64,
+                ),
+                meta: Some(meta),
+                version: None,
+            },
+            block_time: None,
+        };
+        (tx, account_keys)
+    }
+
+    #[test]
+    fn parses_instruction_tree() {
+        let source = Pubkey::new_unique();
+        let destination = Pubkey::new_unique();
+        let other_program = Pubkey::new_unique();
+        let transfer = Instruction::new_with_bytes(
+            PROGRAM_ID,
+            &[1],
+            vec![
+                AccountMeta::new(source, false),
+                AccountMeta::new(destination, false),
+                AccountMeta::new_readonly(other_program, false),
+            ],
+        );
+        let other = Instruction::new_with_bytes(other_program, &[2], vec![]);
+        let (mut tx, keys) = confirmed_transaction(&[transfer, other]);
+        let index = |key: &Pubkey| keys.iter().position(|k| k == key).unwrap() as u8;
+
+        // `other` -> `transfer` -> `transfer` (invalid), `transfer`
+        let compiled = |program_id: &Pubkey, data: &[u8], height| {
+            UiInstruction::Compiled(UiCompiledInstruction {
+                program_id_index: index(program_id),
+                accounts: vec![index(&destination), index(&source)],
+                data: bs58::encode(data).into_string(),
+                stack_height: Some(height),
+            })
+        };
+        tx.transaction.meta.as_mut().unwrap().inner_instructions =
+            OptionSerializer::Some(vec![UiInnerInstructions {
+                index: 1,
+                instructions: vec![
+                    compiled(&PROGRAM_ID, &[3], 2),
+                    compiled(&PROGRAM_ID, &[], 3),
+                    compiled(&PROGRAM_ID, &[4], 2),
+                ],
+            }]);
+
+        let parser = TransactionParser::new().program(|ix: Transfer| ix);
+        let instructions = parser.parse_transaction(&tx).unwrap();
+        assert_eq!(instructions.len(), 2);
+
+        let [transfer, other] = instructions.as_slice() else {
+            unreachable!()
+        };
+        assert_eq!(transfer.name, Some("transfer"));
+        assert_eq!(transfer.instruction, Some(Transfer(1)));
+        assert_eq!(
+            transfer
+                .accounts
+                .iter()
+                .map(|acc| (acc.name, acc.pubkey, acc.is_writable))
+                .collect::<Vec<_>>(),
+            [
+                (Some("source"), source, true),
+                (Some("destination"), destination, true),
+                (None, other_program, false),
+            ]
+        );
+
+        assert_eq!(other.instruction, None);
+        assert_eq!(other.data, [2]);
+        let inner = &other.inner_instructions;
+        assert_eq!(inner.len(), 2);
+        assert_eq!(inner[0].instruction, Some(Transfer(3)));
+        assert_eq!(inner[0].accounts[0].name, Some("source"));
+        assert_eq!(inner[0].accounts[0].pubkey, destination);
+        assert_eq!(inner[0].inner_instructions.len(), 1);
+        assert_eq!(inner[0].inner_instructions[0].instruction, None);
+        assert_eq!(inner[1].instruction, Some(Transfer(4)));
+    }
+}
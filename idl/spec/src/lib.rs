@@ -6,7 +6,16 @@ use {
 
 pub const IDL_SPEC: &str = env!("CARGO_PKG_VERSION");
 
+/// Returns the JSON Schema of the IDL spec.
+///
+/// The generated schema is also published as `idl.schema.json` in the root of this crate.
+#[cfg(feature = "schema")]
+pub fn schema() -> schemars::Schema {
+    schemars::schema_for!(Idl)
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct Idl {
     pub address: String,
     pub metadata: IdlMetadata,
@@ -26,6 +35,7 @@ pub struct Idl {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct IdlMetadata {
     pub name: String,
     pub version: String,
@@ -43,12 +53,14 @@ pub struct IdlMetadata {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct IdlDependency {
     pub name: String,
     pub version: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct IdlDeployments {
     pub mainnet: Option<String>,
     pub testnet: Option<String>,
@@ -57,6 +69,7 @@ pub struct IdlDeployments {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct IdlInstruction {
     pub name: String,
     #[serde(default, skip_serializing_if = "is_default")]
@@ -69,6 +82,7 @@ pub struct IdlInstruction {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[serde(untagged)]
 pub enum IdlInstructionAccountItem {
     Composite(IdlInstructionAccounts),
@@ -76,6 +90,7 @@ pub enum IdlInstructionAccountItem {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct IdlInstructionAccount {
     pub name: String,
     #[serde(default, skip_serializing_if = "is_default")]
@@ -95,12 +110,14 @@ pub struct IdlInstructionAccount {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct IdlInstructionAccounts {
     pub name: String,
     pub accounts: Vec<IdlInstructionAccountItem>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct IdlPda {
     pub seeds: Vec<IdlSeed>,
     #[serde(skip_serializing_if = "is_default")]
@@ -108,6 +125,7 @@ pub struct IdlPda {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[serde(tag = "kind", rename_all = "lowercase")]
 pub enum IdlSeed {
     Const(IdlSeedConst),
@@ -116,16 +134,19 @@ pub enum IdlSeed {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct IdlSeedConst {
     pub value: Vec<u8>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct IdlSeedArg {
     pub path: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct IdlSeedAccount {
     pub path: String,
     #[serde(skip_serializing_if = "is_default")]
@@ -133,18 +154,21 @@ pub struct IdlSeedAccount {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct IdlAccount {
     pub name: String,
     pub discriminator: IdlDiscriminator,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct IdlEvent {
     pub name: String,
     pub discriminator: IdlDiscriminator,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct IdlConst {
     pub name: String,
     #[serde(default, skip_serializing_if = "is_default")]
@@ -155,6 +179,7 @@ pub struct IdlConst {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct IdlErrorCode {
     pub code: u32,
     pub name: String,
@@ -163,6 +188,7 @@ pub struct IdlErrorCode {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct IdlField {
     pub name: String,
     #[serde(default, skip_serializing_if = "is_default")]
@@ -172,6 +198,7 @@ pub struct IdlField {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct IdlTypeDef {
     pub name: String,
     #[serde(default, skip_serializing_if = "is_default")]
@@ -187,6 +214,7 @@ pub struct IdlTypeDef {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[serde(rename_all = "lowercase")]
 #[non_exhaustive]
 pub enum IdlSerialization {
@@ -198,6 +226,7 @@ pub enum IdlSerialization {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[serde(tag = "kind", rename_all = "lowercase")]
 #[non_exhaustive]
 pub enum IdlRepr {
@@ -207,6 +236,7 @@ pub enum IdlRepr {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct IdlReprModifier {
     #[serde(default, skip_serializing_if = "is_default")]
     pub packed: bool,
@@ -215,6 +245,7 @@ pub struct IdlReprModifier {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[serde(tag = "kind", rename_all = "lowercase")]
 pub enum IdlTypeDefGeneric {
     Type {
@@ -228,6 +259,7 @@ pub enum IdlTypeDefGeneric {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[serde(tag = "kind", rename_all = "lowercase")]
 pub enum IdlTypeDefTy {
     Struct {
@@ -243,6 +275,7 @@ pub enum IdlTypeDefTy {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct IdlEnumVariant {
     pub name: String,
     #[serde(skip_serializing_if = "is_default")]
@@ -250,6 +283,7 @@ pub struct IdlEnumVariant {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[serde(untagged)]
 pub enum IdlDefinedFields {
     Named(Vec<IdlField>),
@@ -257,6 +291,7 @@ pub enum IdlDefinedFields {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[serde(rename_all = "lowercase")]
 pub enum IdlArrayLen {
     Generic(String),
@@ -265,6 +300,7 @@ pub enum IdlArrayLen {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[serde(tag = "kind", rename_all = "lowercase")]
 pub enum IdlGenericArg {
     Type {
@@ -277,6 +313,7 @@ pub enum IdlGenericArg {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[serde(rename_all = "lowercase")]
 #[non_exhaustive]
 pub enum IdlType {
@@ -459,6 +496,22 @@ fn is_default<T: Default + PartialEq>(it: &T) -> bool {
 mod tests {
     use super::*;
 
+    #[cfg(feature = "schema")]
+    #[test]
+    fn schema_is_up_to_date() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/idl.schema.json");
+        let schema = serde_json::to_string_pretty(&schema()).unwrap() + "\n";
+        if std::env::var_os("UPDATE_IDL_SCHEMA").is_some() {
+            std::fs::write(path, &schema).unwrap();
+        }
+
+        assert!(
+            std::fs::read_to_string(path).is_ok_and(|published| published == schema),
+            "`idl.schema.json` is outdated, run \
+             `UPDATE_IDL_SCHEMA=1 cargo test -p anchor-lang-idl-spec --features schema`"
+        );
+    }
+
     #[test]
     fn option() {
         assert_eq!(
@@ -1,10 +1,10 @@
 use {
-    crate::types::{Idl, IdlEvent, IdlTypeDef},
+    crate::types::{Idl, IdlEvent, IdlTypeDef, IdlTypeDefTy},
     anyhow::{anyhow, Result},
     regex::Regex,
     serde::Deserialize,
     std::{
-        collections::BTreeMap,
+        collections::{BTreeMap, BTreeSet},
         env, mem,
         path::{Path, PathBuf},
         process::{Command, Stdio},
@@ -62,6 +62,8 @@ pub struct IdlBuilder {
     resolution: Option<bool>,
     skip_lint: Option<bool>,
     no_docs: Option<bool>,
+    static_analysis: Option<bool>,
+    external_types: Option<BTreeMap<String, IdlTypeDefTy>>,
     cargo_args: Option<Vec<String>>,
 }
 
@@ -94,6 +96,25 @@ impl IdlBuilder {
         self
     }
 
+    /// Set whether to generate the IDL by parsing the program's source code instead of compiling
+    /// it (default: false).
+    ///
+    /// This is faster and doesn't run any code, but only types that are defined in the program
+    /// crate or set with [`Self::external_types`] can be resolved. Requires the `parse` feature.
+    pub fn static_analysis(mut self, static_analysis: bool) -> Self {
+        self.static_analysis.replace(static_analysis);
+        self
+    }
+
+    /// Set the IDL definitions of the types from other crates that don't implement [`IdlBuild`],
+    /// keyed by their paths e.g. `pyth_sdk::Price` (default: empty).
+    ///
+    /// Only the definitions that are referenced in the IDL are included.
+    pub fn external_types(mut self, external_types: BTreeMap<String, IdlTypeDefTy>) -> Self {
+        self.external_types.replace(external_types);
+        self
+    }
+
     /// Set the `cargo` args that will get passed to the underlying `cargo` command when building
     /// IDLs (default: empty).
     pub fn cargo_args(mut self, cargo_args: Vec<String>) -> Self {
@@ -103,15 +124,33 @@ impl IdlBuilder {
 
     /// Build the IDL with the current configuration.
     pub fn build(self) -> Result<Idl> {
-        let idl = build(
-            &self
-                .program_path
-                .unwrap_or_else(|| std::env::current_dir().expect("Failed to get program path")),
-            self.resolution.unwrap_or(true),
-            self.skip_lint.unwrap_or_default(),
-            self.no_docs.unwrap_or_default(),
-            &self.cargo_args.unwrap_or_default(),
-        )
+        let program_path = self
+            .program_path
+            .unwrap_or_else(|| std::env::current_dir().expect("Failed to get program path"));
+        let resolution = self.resolution.unwrap_or(true);
+        let skip_lint = self.skip_lint.unwrap_or_default();
+        let no_docs = self.no_docs.unwrap_or_default();
+        let external_types = self.external_types.unwrap_or_default();
+        let external_type_paths = external_types.keys().cloned().collect::<Vec<_>>();
+        let idl = if self.static_analysis.unwrap_or_default() {
+            parse(
+                &program_path,
+                resolution,
+                skip_lint,
+                no_docs,
+                &external_type_paths,
+            )
+        } else {
+            build(
+                &program_path,
+                resolution,
+                skip_lint,
+                no_docs,
+                &external_type_paths,
+                &self.cargo_args.unwrap_or_default(),
+            )
+        }
+        .map(|idl| insert_external_types(idl, &external_types))
         .map(convert_module_paths)
         .map(sort)?;
         verify(&idl)?;
@@ -136,18 +175,56 @@ pub fn build_idl(
         .build()
 }
 
+/// Generate IDL by parsing the program's source code.
+#[cfg(feature = "parse")]
+fn parse(
+    program_path: &Path,
+    resolution: bool,
+    skip_lint: bool,
+    no_docs: bool,
+    external_types: &[String],
+) -> Result<Idl> {
+    let parsed = anchor_syn::idl::parse::parse_idl(
+        program_path,
+        resolution,
+        no_docs,
+        skip_lint,
+        external_types,
+    )
+    .map_err(|e| anyhow!("Parsing IDL failed:\n{e}"))?;
+    for warning in &parsed.warnings {
+        eprintln!("Warning: {warning}");
+    }
+
+    Ok(parsed.idl)
+}
+
+#[cfg(not(feature = "parse"))]
+fn parse(
+    _program_path: &Path,
+    _resolution: bool,
+    _skip_lint: bool,
+    _no_docs: bool,
+    _external_types: &[String],
+) -> Result<Idl> {
+    Err(anyhow!(
+        "Generating the IDL via static analysis requires the `parse` feature"
+    ))
+}
+
 /// Build IDL.
 fn build(
     program_path: &Path,
     resolution: bool,
     skip_lint: bool,
     no_docs: bool,
+    external_types: &[String],
     cargo_args: &[String],
 ) -> Result<Idl> {
     let mut cmd = Command::new("cargo");
     if let Ok(toolchain) = std::env::var("RUSTUP_TOOLCHAIN") {
         install_toolchain_if_needed(&toolchain)?;
-        cmd.arg("+{toolchain}");
+        cmd.arg(format!("+{toolchain}"));
     }
 
     let output = cmd
@@ -171,6 +248,7 @@ fn build(
             "ANCHOR_IDL_BUILD_SKIP_LINT",
             if skip_lint { "TRUE" } else { "FALSE" },
         )
+        .env("ANCHOR_IDL_BUILD_EXTERNAL_TYPES", external_types.join(","))
         .env("ANCHOR_IDL_BUILD_PROGRAM_PATH", program_path)
         .env("RUSTFLAGS", "-A warnings")
         .current_dir(program_path)
@@ -294,7 +372,7 @@ fn build(
 /// Install the given toolchain if it's not already installed.
 fn install_toolchain_if_needed(toolchain: &str) -> Result<()> {
     let is_installed = Command::new("cargo")
-        .arg(toolchain)
+        .arg(format!("+{toolchain}"))
         .output()?
         .status
         .success();
@@ -308,6 +386,52 @@ fn install_toolchain_if_needed(toolchain: &str) -> Result<()> {
     Ok(())
 }
 
+/// Add the definitions of the external types that are referenced in the IDL.
+fn insert_external_types(mut idl: Idl, external_types: &BTreeMap<String, IdlTypeDefTy>) -> Idl {
+    fn collect_defined(value: &serde_json::Value, names: &mut BTreeSet<String>) {
+        match value {
+            serde_json::Value::Object(map) => {
+                if let Some(name) = map
+                    .get("defined")
+                    .and_then(|defined| defined.get("name"))
+                    .and_then(|name| name.as_str())
+                {
+                    names.insert(name.to_owned());
+                }
+                map.values().for_each(|value| collect_defined(value, names));
+            }
+            serde_json::Value::Array(values) => values
+                .iter()
+                .for_each(|value| collect_defined(value, names)),
+            _ => {}
+        }
+    }
+
+    // External definitions may reference other external types
+    loop {
+        let mut referenced = BTreeSet::new();
+        collect_defined(&serde_json::to_value(&idl).unwrap(), &mut referenced);
+        let missing = external_types
+            .iter()
+            .filter(|(name, _)| referenced.contains(*name))
+            .filter(|(name, _)| idl.types.iter().all(|ty| ty.name != **name))
+            .map(|(name, ty)| IdlTypeDef {
+                name: name.to_owned(),
+                docs: vec![],
+                serialization: Default::default(),
+                repr: None,
+                generics: vec![],
+                ty: ty.to_owned(),
+            })
+            .collect::<Vec<_>>();
+        if missing.is_empty() {
+            return idl;
+        }
+
+        idl.types.extend(missing);
+    }
+}
+
 /// Convert paths to name if there are no conflicts.
 fn convert_module_paths(idl: Idl) -> Idl {
     let idl = serde_json::to_string(&idl).unwrap();
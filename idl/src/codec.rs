@@ -0,0 +1,1111 @@
+//! Dynamic encoding and decoding of IDL-defined data.
+//!
+//! [`IdlCodec`] encodes and decodes instructions, accounts, events and any other IDL type based on
+//! an [`Idl`] value alone, using [`serde_json::Value`] as the dynamic representation:
+//!
+//! | IDL type                               | JSON                                       |
+//! | -------------------------------------- | ------------------------------------------ |
+//! | `bool`                                 | boolean                                    |
+//! | `u8`..`u64`, `i8`..`i64`, `f32`, `f64` | number                                     |
+//! | `u128`, `i128`, `u256`, `i256`         | decimal string                             |
+//! | `bytes`                                | array of numbers                           |
+//! | `string`                               | string                                     |
+//! | `pubkey`                               | base58 string                              |
+//! | `option`                               | `null` or the value                        |
+//! | `vec`, `array`                         | array                                      |
+//! | struct                                 | object (named), array (tuple), `{}` (unit) |
+//! | enum                                   | `{ "<variant>": <fields> }`                |
+//!
+//! When encoding, integers are also accepted as strings and vice versa, unit enum variants can be
+//! given as `"<variant>"`, and missing `option` fields are encoded as `None`.
+//!
+//! Types with `bytemuck` serialization (zero-copy) are laid out like `#[repr(C)]` structs,
+//! honoring the `packed` and `align` modifiers of their `repr`. As on the SBF target, 128-bit and
+//! 256-bit integers are aligned to 8 bytes.
+
+use {
+    crate::types::{
+        Idl, IdlAccount, IdlArrayLen, IdlDefinedFields, IdlEvent, IdlField, IdlGenericArg,
+        IdlInstruction, IdlRepr, IdlSerialization, IdlType, IdlTypeDef, IdlTypeDefGeneric,
+        IdlTypeDefTy,
+    },
+    anyhow::{anyhow, bail, Context, Result},
+    serde_json::{Map, Value},
+    std::collections::HashMap,
+};
+
+/// Encoder and decoder of the data defined in an [`Idl`].
+#[derive(Debug, Clone, Copy)]
+pub struct IdlCodec<'a> {
+    idl: &'a Idl,
+}
+
+/// Concrete generic arguments of a type definition, by generic name.
+type Scope<'a> = HashMap<&'a str, IdlGenericArg>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Layout {
+    Borsh,
+    Bytemuck,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Fields<'b> {
+    Named(&'b [IdlField]),
+    Tuple(&'b [IdlType]),
+    Unit,
+}
+
+impl<'b> From<Option<&'b IdlDefinedFields>> for Fields<'b> {
+    fn from(fields: Option<&'b IdlDefinedFields>) -> Self {
+        match fields {
+            Some(IdlDefinedFields::Named(fields)) => Self::Named(fields),
+            Some(IdlDefinedFields::Tuple(fields)) => Self::Tuple(fields),
+            None => Self::Unit,
+        }
+    }
+}
+
+impl Fields<'_> {
+    fn types(&self) -> Vec<&IdlType> {
+        match self {
+            Self::Named(fields) => fields.iter().map(|field| &field.ty).collect(),
+            Self::Tuple(fields) => fields.iter().collect(),
+            Self::Unit => vec![],
+        }
+    }
+}
+
+impl<'a> IdlCodec<'a> {
+    pub fn new(idl: &'a Idl) -> Self {
+        Self { idl }
+    }
+
+    /// Encode the data of the given instruction, including its discriminator.
+    ///
+    /// `args` must be an object with the instruction arguments by name.
+    pub fn encode_instruction(&self, name: &str, args: &Value) -> Result<Vec<u8>> {
+        let ix = self
+            .idl
+            .instructions
+            .iter()
+            .find(|ix| ix.name == name)
+            .ok_or_else(|| anyhow!("Instruction `{name}` not found"))?;
+
+        let mut data = ix.discriminator.clone();
+        data.extend(self.encode_fields(
+            Fields::Named(&ix.args),
+            args,
+            Layout::Borsh,
+            &Scope::new(),
+            None,
+        )?);
+        Ok(data)
+    }
+
+    /// Decode instruction data, identifying the instruction by its discriminator.
+    ///
+    /// The arguments are returned as an object by name.
+    pub fn decode_instruction(&self, data: &[u8]) -> Result<(&'a IdlInstruction, Value)> {
+        let ix = find_by_discriminator(&self.idl.instructions, |ix| &ix.discriminator, data)
+            .ok_or_else(|| anyhow!("Unknown instruction discriminator"))?;
+
+        let mut reader = Reader::new(&data[ix.discriminator.len()..]);
+        let args = self.decode_fields(
+            Fields::Named(&ix.args),
+            &mut reader,
+            Layout::Borsh,
+            &Scope::new(),
+            None,
+        )?;
+        Ok((ix, args))
+    }
+
+    /// Encode the data of the given account, including its discriminator.
+    pub fn encode_account(&self, name: &str, value: &Value) -> Result<Vec<u8>> {
+        let acc = self.find_account(name)?;
+        let mut data = acc.discriminator.clone();
+        data.extend(self.encode_defined(&acc.name, value)?);
+        Ok(data)
+    }
+
+    /// Decode account data, identifying the account by its discriminator.
+    pub fn decode_account(&self, data: &[u8]) -> Result<(&'a IdlAccount, Value)> {
+        let acc = find_by_discriminator(&self.idl.accounts, |acc| &acc.discriminator, data)
+            .ok_or_else(|| anyhow!("Unknown account discriminator"))?;
+        let value = self.decode_defined(&acc.name, &data[acc.discriminator.len()..])?;
+        Ok((acc, value))
+    }
+
+    /// Decode the data of the given account.
+    pub fn decode_account_as(&self, name: &str, data: &[u8]) -> Result<Value> {
+        let acc = self.find_account(name)?;
+        let data = data
+            .strip_prefix(acc.discriminator.as_slice())
+            .ok_or_else(|| anyhow!("Account discriminator of `{name}` doesn't match"))?;
+        self.decode_defined(&acc.name, data)
+    }
+
+    /// Encode the data of the given event, including its discriminator.
+    pub fn encode_event(&self, name: &str, value: &Value) -> Result<Vec<u8>> {
+        let ev = self
+            .idl
+            .events
+            .iter()
+            .find(|ev| ev.name == name)
+            .ok_or_else(|| anyhow!("Event `{name}` not found"))?;
+
+        let mut data = ev.discriminator.clone();
+        data.extend(self.encode_defined(&ev.name, value)?);
+        Ok(data)
+    }
+
+    /// Decode event data, identifying the event by its discriminator.
+    pub fn decode_event(&self, data: &[u8]) -> Result<(&'a IdlEvent, Value)> {
+        let ev = find_by_discriminator(&self.idl.events, |ev| &ev.discriminator, data)
+            .ok_or_else(|| anyhow!("Unknown event discriminator"))?;
+        let value = self.decode_defined(&ev.name, &data[ev.discriminator.len()..])?;
+        Ok((ev, value))
+    }
+
+    /// Encode a value of the given type.
+    pub fn encode(&self, ty: &IdlType, value: &Value) -> Result<Vec<u8>> {
+        let mut data = vec![];
+        self.encode_ty(ty, value, &mut data, Layout::Borsh, &Scope::new())?;
+        Ok(data)
+    }
+
+    /// Decode a value of the given type, advancing `data` past the decoded bytes.
+    pub fn decode(&self, ty: &IdlType, data: &mut &[u8]) -> Result<Value> {
+        let mut reader = Reader::new(data);
+        let value = self.decode_ty(ty, &mut reader, Layout::Borsh, &Scope::new())?;
+        *data = &data[reader.pos..];
+        Ok(value)
+    }
+
+    fn encode_defined(&self, name: &str, value: &Value) -> Result<Vec<u8>> {
+        self.encode(&defined(name), value)
+    }
+
+    fn decode_defined(&self, name: &str, data: &[u8]) -> Result<Value> {
+        self.decode(&defined(name), &mut &data[..])
+    }
+
+    fn find_account(&self, name: &str) -> Result<&'a IdlAccount> {
+        self.idl
+            .accounts
+            .iter()
+            .find(|acc| acc.name == name)
+            .ok_or_else(|| anyhow!("Account `{name}` not found"))
+    }
+
+    /// Find the type definition and resolve its generic arguments in the given scope.
+    fn enter(
+        &self,
+        name: &str,
+        args: &[IdlGenericArg],
+        scope: &Scope,
+    ) -> Result<(&'a IdlTypeDef, Scope<'a>)> {
+        let def = self
+            .idl
+            .types
+            .iter()
+            .find(|ty| ty.name == name)
+            .ok_or_else(|| anyhow!("Type `{name}` not found"))?;
+        if def.generics.len() != args.len() {
+            bail!(
+                "Type `{name}` expects {} generic argument(s), found {}",
+                def.generics.len(),
+                args.len()
+            );
+        }
+
+        let scope =
+            def.generics
+                .iter()
+                .zip(args)
+                .map(|(generic, arg)| {
+                    let name = match generic {
+                        IdlTypeDefGeneric::Type { name }
+                        | IdlTypeDefGeneric::Const { name, .. } => name.as_str(),
+                    };
+                    Ok((name, self.concrete_arg(arg, scope)?))
+                })
+                .collect::<Result<_>>()?;
+        Ok((def, scope))
+    }
+
+    fn concrete_arg(&self, arg: &IdlGenericArg, scope: &Scope) -> Result<IdlGenericArg> {
+        match arg {
+            IdlGenericArg::Type {
+                ty: IdlType::Generic(name),
+            } => generic_arg(name, scope).cloned(),
+            IdlGenericArg::Type { ty } => Ok(IdlGenericArg::Type {
+                ty: self.concrete(ty, scope)?,
+            }),
+            IdlGenericArg::Const { value } => Ok(IdlGenericArg::Const {
+                value: value.to_owned(),
+            }),
+        }
+    }
+
+    /// Replace all generics of the type with their concrete arguments.
+    fn concrete(&self, ty: &IdlType, scope: &Scope) -> Result<IdlType> {
+        Ok(match ty {
+            IdlType::Option(inner) => IdlType::Option(Box::new(self.concrete(inner, scope)?)),
+            IdlType::Vec(inner) => IdlType::Vec(Box::new(self.concrete(inner, scope)?)),
+            IdlType::Array(inner, len) => IdlType::Array(
+                Box::new(self.concrete(inner, scope)?),
+                IdlArrayLen::Value(array_len(len, scope)?),
+            ),
+            IdlType::Defined { name, generics } => IdlType::Defined {
+                name: name.to_owned(),
+                generics: generics
+                    .iter()
+                    .map(|arg| self.concrete_arg(arg, scope))
+                    .collect::<Result<_>>()?,
+            },
+            IdlType::Generic(name) => generic_ty(name, scope)?.clone(),
+            ty => ty.clone(),
+        })
+    }
+
+    fn layout(&self, def: &IdlTypeDef, parent: Layout) -> Result<Layout> {
+        match &def.serialization {
+            IdlSerialization::Borsh => Ok(parent),
+            IdlSerialization::Bytemuck | IdlSerialization::BytemuckUnsafe => Ok(Layout::Bytemuck),
+            IdlSerialization::Custom(serialization) => bail!(
+                "Custom serialization `{serialization}` of type `{}` is not supported",
+                def.name
+            ),
+            serialization => bail!(
+                "Serialization {serialization:?} of type `{}` is not supported",
+                def.name
+            ),
+        }
+    }
+
+    /// Returns the size and the alignment of the type in a `#[repr(C)]` layout.
+    fn c_layout(&self, ty: &IdlType, scope: &Scope) -> Result<(usize, usize)> {
+        Ok(match ty {
+            IdlType::Bool | IdlType::U8 | IdlType::I8 => (1, 1),
+            IdlType::U16 | IdlType::I16 => (2, 2),
+            IdlType::U32 | IdlType::I32 | IdlType::F32 => (4, 4),
+            IdlType::U64 | IdlType::I64 | IdlType::F64 => (8, 8),
+            IdlType::U128 | IdlType::I128 => (16, 8),
+            IdlType::U256 | IdlType::I256 => (32, 8),
+            IdlType::Pubkey => (32, 1),
+            IdlType::Array(inner, len) => {
+                let (size, align) = self.c_layout(inner, scope)?;
+                (size * array_len(len, scope)?, align)
+            }
+            IdlType::Defined { name, generics } => {
+                let (def, scope) = self.enter(name, generics, scope)?;
+                match &def.ty {
+                    IdlTypeDefTy::Struct { fields } => self.c_struct_layout(
+                        &Fields::from(fields.as_ref()).types(),
+                        &scope,
+                        def.repr.as_ref(),
+                    )?,
+                    IdlTypeDefTy::Type { alias } => self.c_layout(alias, &scope)?,
+                    IdlTypeDefTy::Enum { .. } => {
+                        bail!("Enum `{name}` is not supported in zero-copy types")
+                    }
+                }
+            }
+            IdlType::Generic(name) => self.c_layout(generic_ty(name, scope)?, &Scope::new())?,
+            ty => bail!("Type {ty:?} is not supported in zero-copy types"),
+        })
+    }
+
+    fn c_struct_layout(
+        &self,
+        tys: &[&IdlType],
+        scope: &Scope,
+        repr: Option<&IdlRepr>,
+    ) -> Result<(usize, usize)> {
+        let (packed, mut align) = repr_modifiers(repr);
+        let mut size = 0;
+        for ty in tys {
+            let (field_size, field_align) = self.c_layout(ty, scope)?;
+            if !packed {
+                size = round_up(size, field_align);
+                align = align.max(field_align);
+            }
+            size += field_size;
+        }
+
+        Ok((round_up(size, align), align))
+    }
+
+    fn encode_ty(
+        &self,
+        ty: &IdlType,
+        value: &Value,
+        data: &mut Vec<u8>,
+        layout: Layout,
+        scope: &Scope,
+    ) -> Result<()> {
+        match ty {
+            IdlType::Bool => {
+                let value = value
+                    .as_bool()
+                    .ok_or_else(|| anyhow!("Expected a boolean, found {value}"))?;
+                data.push(value.into());
+            }
+            IdlType::U8 => data.extend(int::<u8>(value)?.to_le_bytes()),
+            IdlType::I8 => data.extend(int::<i8>(value)?.to_le_bytes()),
+            IdlType::U16 => data.extend(int::<u16>(value)?.to_le_bytes()),
+            IdlType::I16 => data.extend(int::<i16>(value)?.to_le_bytes()),
+            IdlType::U32 => data.extend(int::<u32>(value)?.to_le_bytes()),
+            IdlType::I32 => data.extend(int::<i32>(value)?.to_le_bytes()),
+            IdlType::U64 => data.extend(int::<u64>(value)?.to_le_bytes()),
+            IdlType::I64 => data.extend(int::<i64>(value)?.to_le_bytes()),
+            IdlType::U128 => data.extend(int::<u128>(value)?.to_le_bytes()),
+            IdlType::I128 => data.extend(int::<i128>(value)?.to_le_bytes()),
+            IdlType::U256 => data.extend(int256(value, false)?),
+            IdlType::I256 => data.extend(int256(value, true)?),
+            IdlType::F32 => data.extend((float(value)? as f32).to_le_bytes()),
+            IdlType::F64 => data.extend(float(value)?.to_le_bytes()),
+            IdlType::Bytes => {
+                let bytes = value
+                    .as_array()
+                    .ok_or_else(|| anyhow!("Expected an array of bytes, found {value}"))?
+                    .iter()
+                    .map(int::<u8>)
+                    .collect::<Result<Vec<_>>>()?;
+                encode_len(bytes.len(), data)?;
+                data.extend(bytes);
+            }
+            IdlType::String => {
+                let value = value
+                    .as_str()
+                    .ok_or_else(|| anyhow!("Expected a string, found {value}"))?;
+                encode_len(value.len(), data)?;
+                data.extend(value.as_bytes());
+            }
+            IdlType::Pubkey => {
+                let pubkey = value
+                    .as_str()
+                    .and_then(|value| bs58::decode(value).into_vec().ok())
+                    .filter(|pubkey| pubkey.len() == 32)
+                    .ok_or_else(|| anyhow!("Expected a base58 public key, found {value}"))?;
+                data.extend(pubkey);
+            }
+            IdlType::Option(inner) => match value {
+                Value::Null => data.push(0),
+                value => {
+                    data.push(1);
+                    self.encode_ty(inner, value, data, layout, scope)?;
+                }
+            },
+            IdlType::Vec(inner) => {
+                let values = value
+                    .as_array()
+                    .ok_or_else(|| anyhow!("Expected an array, found {value}"))?;
+                encode_len(values.len(), data)?;
+                for value in values {
+                    self.encode_ty(inner, value, data, layout, scope)?;
+                }
+            }
+            IdlType::Array(inner, len) => {
+                let len = array_len(len, scope)?;
+                let values = value
+                    .as_array()
+                    .filter(|values| values.len() == len)
+                    .ok_or_else(|| anyhow!("Expected an array of length {len}, found {value}"))?;
+                for value in values {
+                    self.encode_ty(inner, value, data, layout, scope)?;
+                }
+            }
+            IdlType::Defined { name, generics } => {
+                let (def, scope) = self.enter(name, generics, scope)?;
+                let layout = self.layout(def, layout)?;
+                match &def.ty {
+                    IdlTypeDefTy::Struct { fields } => data.extend(self.encode_fields(
+                        fields.as_ref().into(),
+                        value,
+                        layout,
+                        &scope,
+                        def.repr.as_ref(),
+                    )?),
+                    IdlTypeDefTy::Enum { variants } => {
+                        if layout == Layout::Bytemuck {
+                            bail!("Enum `{name}` is not supported in zero-copy types");
+                        }
+
+                        let (variant_name, fields_value) = match value {
+                            Value::String(variant) => (variant.as_str(), &Value::Null),
+                            Value::Object(object) if object.len() == 1 => {
+                                let (variant, value) = object.iter().next().unwrap();
+                                (variant.as_str(), value)
+                            }
+                            _ => bail!("Expected a variant of enum `{name}`, found {value}"),
+                        };
+                        let (index, variant) = variants
+                            .iter()
+                            .enumerate()
+                            .find(|(_, variant)| variant.name == variant_name)
+                            .ok_or_else(|| {
+                                anyhow!("Variant `{variant_name}` not found in enum `{name}`")
+                            })?;
+
+                        data.push(
+                            u8::try_from(index)
+                                .map_err(|_| anyhow!("Too many variants in enum `{name}`"))?,
+                        );
+                        data.extend(self.encode_fields(
+                            variant.fields.as_ref().into(),
+                            fields_value,
+                            layout,
+                            &scope,
+                            None,
+                        )?);
+                    }
+                    IdlTypeDefTy::Type { alias } => {
+                        self.encode_ty(alias, value, data, layout, &scope)?
+                    }
+                }
+            }
+            IdlType::Generic(name) => {
+                self.encode_ty(generic_ty(name, scope)?, value, data, layout, &Scope::new())?
+            }
+            ty => bail!("Type {ty:?} is not supported"),
+        }
+
+        Ok(())
+    }
+
+    fn encode_fields(
+        &self,
+        fields: Fields,
+        value: &Value,
+        layout: Layout,
+        scope: &Scope,
+        repr: Option<&IdlRepr>,
+    ) -> Result<Vec<u8>> {
+        let items = match fields {
+            Fields::Named(fields) => {
+                let object = value
+                    .as_object()
+                    .ok_or_else(|| anyhow!("Expected an object, found {value}"))?;
+                fields
+                    .iter()
+                    .map(|field| match (object.get(&field.name), &field.ty) {
+                        (Some(value), ty) => Ok((field.name.to_owned(), ty, value)),
+                        (None, ty @ IdlType::Option(_)) => {
+                            Ok((field.name.to_owned(), ty, &Value::Null))
+                        }
+                        (None, _) => Err(anyhow!("Missing field `{}`", field.name)),
+                    })
+                    .collect::<Result<Vec<_>>>()?
+            }
+            Fields::Tuple(tys) => {
+                let values = value
+                    .as_array()
+                    .filter(|values| values.len() == tys.len())
+                    .ok_or_else(|| {
+                        anyhow!("Expected an array of length {}, found {value}", tys.len())
+                    })?;
+                tys.iter()
+                    .zip(values)
+                    .enumerate()
+                    .map(|(i, (ty, value))| (i.to_string(), ty, value))
+                    .collect()
+            }
+            Fields::Unit => vec![],
+        };
+
+        let mut data = vec![];
+        let (packed, _) = repr_modifiers(repr);
+        for (name, ty, value) in items {
+            if layout == Layout::Bytemuck && !packed {
+                let (_, align) = self.c_layout(ty, scope)?;
+                data.resize(round_up(data.len(), align), 0);
+            }
+            self.encode_ty(ty, value, &mut data, layout, scope)
+                .with_context(|| format!("Failed to encode field `{name}`"))?;
+        }
+        if layout == Layout::Bytemuck {
+            let (size, _) = self.c_struct_layout(&fields.types(), scope, repr)?;
+            data.resize(size, 0);
+        }
+
+        Ok(data)
+    }
+
+    fn decode_ty(
+        &self,
+        ty: &IdlType,
+        reader: &mut Reader,
+        layout: Layout,
+        scope: &Scope,
+    ) -> Result<Value> {
+        Ok(match ty {
+            IdlType::Bool => match reader.read_array::<1>()? {
+                [0] => false.into(),
+                [1] => true.into(),
+                [value] => bail!("Invalid boolean value {value}"),
+            },
+            IdlType::U8 => u8::from_le_bytes(reader.read_array()?).into(),
+            IdlType::I8 => i8::from_le_bytes(reader.read_array()?).into(),
+            IdlType::U16 => u16::from_le_bytes(reader.read_array()?).into(),
+            IdlType::I16 => i16::from_le_bytes(reader.read_array()?).into(),
+            IdlType::U32 => u32::from_le_bytes(reader.read_array()?).into(),
+            IdlType::I32 => i32::from_le_bytes(reader.read_array()?).into(),
+            IdlType::U64 => u64::from_le_bytes(reader.read_array()?).into(),
+            IdlType::I64 => i64::from_le_bytes(reader.read_array()?).into(),
+            IdlType::U128 => u128::from_le_bytes(reader.read_array()?).to_string().into(),
+            IdlType::I128 => i128::from_le_bytes(reader.read_array()?).to_string().into(),
+            IdlType::U256 => int256_to_string(reader.read_array()?, false).into(),
+            IdlType::I256 => int256_to_string(reader.read_array()?, true).into(),
+            IdlType::F32 => f32::from_le_bytes(reader.read_array()?).into(),
+            IdlType::F64 => f64::from_le_bytes(reader.read_array()?).into(),
+            IdlType::Bytes => {
+                let len = reader.read_len()?;
+                reader.read(len)?.to_vec().into()
+            }
+            IdlType::String => {
+                let len = reader.read_len()?;
+                String::from_utf8(reader.read(len)?.to_vec())?.into()
+            }
+            IdlType::Pubkey => bs58::encode(reader.read(32)?).into_string().into(),
+            IdlType::Option(inner) => match reader.read_array::<1>()? {
+                [0] => Value::Null,
+                [1] => self.decode_ty(inner, reader, layout, scope)?,
+                [tag] => bail!("Invalid option tag {tag}"),
+            },
+            IdlType::Vec(inner) => {
+                let len = reader.read_len()?;
+                (0..len)
+                    .map(|_| self.decode_ty(inner, reader, layout, scope))
+                    .collect::<Result<Vec<_>>>()?
+                    .into()
+            }
+            IdlType::Array(inner, len) => (0..array_len(len, scope)?)
+                .map(|_| self.decode_ty(inner, reader, layout, scope))
+                .collect::<Result<Vec<_>>>()?
+                .into(),
+            IdlType::Defined { name, generics } => {
+                let (def, scope) = self.enter(name, generics, scope)?;
+                let layout = self.layout(def, layout)?;
+                match &def.ty {
+                    IdlTypeDefTy::Struct { fields } => self.decode_fields(
+                        fields.as_ref().into(),
+                        reader,
+                        layout,
+                        &scope,
+                        def.repr.as_ref(),
+                    )?,
+                    IdlTypeDefTy::Enum { variants } => {
+                        if layout == Layout::Bytemuck {
+                            bail!("Enum `{name}` is not supported in zero-copy types");
+                        }
+
+                        let [index] = reader.read_array()?;
+                        let variant = variants.get(usize::from(index)).ok_or_else(|| {
+                            anyhow!("Invalid variant index {index} of enum `{name}`")
+                        })?;
+                        let value = self.decode_fields(
+                            variant.fields.as_ref().into(),
+                            reader,
+                            layout,
+                            &scope,
+                            None,
+                        )?;
+                        Value::Object(Map::from_iter([(variant.name.to_owned(), value)]))
+                    }
+                    IdlTypeDefTy::Type { alias } => {
+                        self.decode_ty(alias, reader, layout, &scope)?
+                    }
+                }
+            }
+            IdlType::Generic(name) => {
+                self.decode_ty(generic_ty(name, scope)?, reader, layout, &Scope::new())?
+            }
+            ty => bail!("Type {ty:?} is not supported"),
+        })
+    }
+
+    fn decode_fields(
+        &self,
+        fields: Fields,
+        reader: &mut Reader,
+        layout: Layout,
+        scope: &Scope,
+        repr: Option<&IdlRepr>,
+    ) -> Result<Value> {
+        let start = reader.pos;
+        let (packed, _) = repr_modifiers(repr);
+        let mut decode = |name: &str, ty: &IdlType| {
+            if layout == Layout::Bytemuck && !packed {
+                let (_, align) = self.c_layout(ty, scope)?;
+                reader.pos = start + round_up(reader.pos - start, align);
+            }
+            self.decode_ty(ty, reader, layout, scope)
+                .with_context(|| format!("Failed to decode field `{name}`"))
+        };
+
+        let value = match fields {
+            Fields::Named(fields) => Value::Object(
+                fields
+                    .iter()
+                    .map(|field| Ok((field.name.to_owned(), decode(&field.name, &field.ty)?)))
+                    .collect::<Result<_>>()?,
+            ),
+            Fields::Tuple(tys) => Value::Array(
+                tys.iter()
+                    .enumerate()
+                    .map(|(i, ty)| decode(&i.to_string(), ty))
+                    .collect::<Result<_>>()?,
+            ),
+            Fields::Unit => Value::Object(Map::new()),
+        };
+        if layout == Layout::Bytemuck {
+            let (size, _) = self.c_struct_layout(&fields.types(), scope, repr)?;
+            reader.pos = start;
+            reader.read(size)?;
+        }
+
+        Ok(value)
+    }
+}
+
+struct Reader<'d> {
+    data: &'d [u8],
+    pos: usize,
+}
+
+impl<'d> Reader<'d> {
+    fn new(data: &'d [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    fn read(&mut self, len: usize) -> Result<&'d [u8]> {
+        let bytes = self
+            .data
+            .get(self.pos..)
+            .and_then(|data| data.get(..len))
+            .ok_or_else(|| anyhow!("Unexpected end of data"))?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn read_array<const N: usize>(&mut self) -> Result<[u8; N]> {
+        Ok(self.read(N)?.try_into().unwrap())
+    }
+
+    fn read_len(&mut self) -> Result<usize> {
+        Ok(u32::from_le_bytes(self.read_array()?) as usize)
+    }
+}
+
+fn defined(name: &str) -> IdlType {
+    IdlType::Defined {
+        name: name.to_owned(),
+        generics: vec![],
+    }
+}
+
+/// Find the item with the longest discriminator that `data` starts with.
+fn find_by_discriminator<'b, T>(
+    items: &'b [T],
+    discriminator: impl Fn(&T) -> &Vec<u8>,
+    data: &[u8],
+) -> Option<&'b T> {
+    items
+        .iter()
+        .filter(|item| {
+            let discriminator = discriminator(item);
+            !discriminator.is_empty() && data.starts_with(discriminator)
+        })
+        .max_by_key(|item| discriminator(item).len())
+}
+
+fn generic_arg<'b>(name: &str, scope: &'b Scope) -> Result<&'b IdlGenericArg> {
+    scope
+        .get(name)
+        .ok_or_else(|| anyhow!("Generic `{name}` not found"))
+}
+
+fn generic_ty<'b>(name: &str, scope: &'b Scope) -> Result<&'b IdlType> {
+    match generic_arg(name, scope)? {
+        IdlGenericArg::Type { ty } => Ok(ty),
+        IdlGenericArg::Const { .. } => bail!("Expected generic `{name}` to be a type"),
+    }
+}
+
+fn array_len(len: &IdlArrayLen, scope: &Scope) -> Result<usize> {
+    match len {
+        IdlArrayLen::Value(len) => Ok(*len),
+        IdlArrayLen::Generic(name) => match generic_arg(name, scope)? {
+            IdlGenericArg::Const { value } => value
+                .parse()
+                .map_err(|_| anyhow!("Invalid array length `{value}` of generic `{name}`")),
+            IdlGenericArg::Type { .. } => bail!("Expected generic `{name}` to be a constant"),
+        },
+    }
+}
+
+/// Returns whether the type is packed, and its minimum alignment.
+fn repr_modifiers(repr: Option<&IdlRepr>) -> (bool, usize) {
+    match repr {
+        Some(IdlRepr::Rust(modifier) | IdlRepr::C(modifier)) => {
+            (modifier.packed, modifier.align.unwrap_or(1))
+        }
+        _ => (false, 1),
+    }
+}
+
+fn round_up(value: usize, align: usize) -> usize {
+    value.div_ceil(align) * align
+}
+
+fn encode_len(len: usize, data: &mut Vec<u8>) -> Result<()> {
+    let len = u32::try_from(len).map_err(|_| anyhow!("Length {len} doesn't fit in `u32`"))?;
+    data.extend(len.to_le_bytes());
+    Ok(())
+}
+
+fn int<T>(value: &Value) -> Result<T>
+where
+    T: std::str::FromStr + TryFrom<u64> + TryFrom<i64>,
+{
+    let int = match value {
+        Value::Number(number) => match (number.as_u64(), number.as_i64()) {
+            (Some(value), _) => T::try_from(value).ok(),
+            (_, Some(value)) => T::try_from(value).ok(),
+            _ => bail!("Expected an integer, found {value}"),
+        },
+        Value::String(value) => value.parse().ok(),
+        _ => bail!("Expected an integer, found {value}"),
+    };
+    int.ok_or_else(|| anyhow!("Integer {value} is out of range"))
+}
+
+fn float(value: &Value) -> Result<f64> {
+    match value {
+        Value::Number(number) => number.as_f64(),
+        Value::String(value) => value.parse().ok(),
+        _ => None,
+    }
+    .ok_or_else(|| anyhow!("Expected a number, found {value}"))
+}
+
+/// Parse a 256-bit integer from a decimal number or string, as little-endian bytes.
+fn int256(value: &Value, signed: bool) -> Result<[u8; 32]> {
+    let s = match value {
+        Value::Number(number) if number.is_u64() || number.is_i64() => number.to_string(),
+        Value::String(value) => value.to_owned(),
+        _ => bail!("Expected an integer, found {value}"),
+    };
+    let out_of_range = || anyhow!("Integer `{s}` is out of range");
+    let (negative, digits) = match s.strip_prefix('-') {
+        Some(_) if !signed => return Err(out_of_range()),
+        Some(digits) => (true, digits),
+        None => (false, s.as_str()),
+    };
+    if digits.is_empty() {
+        bail!("Invalid integer `{s}`");
+    }
+
+    let mut limbs = [0u64; 4];
+    for c in digits.chars() {
+        let digit = c
+            .to_digit(10)
+            .ok_or_else(|| anyhow!("Invalid integer `{s}`"))?;
+        let mut carry = u128::from(digit);
+        for limb in limbs.iter_mut() {
+            let value = u128::from(*limb) * 10 + carry;
+            *limb = value as u64;
+            carry = value >> 64;
+        }
+        if carry != 0 {
+            return Err(out_of_range());
+        }
+    }
+
+    if signed {
+        let max_negative = [0, 0, 0, 1 << 63];
+        let in_range = limbs[3] >> 63 == 0 || (negative && limbs == max_negative);
+        if !in_range {
+            return Err(out_of_range());
+        }
+        if negative {
+            limbs = negate(limbs);
+        }
+    }
+
+    let mut bytes = [0; 32];
+    for (chunk, limb) in bytes.as_chunks_mut::<8>().0.iter_mut().zip(limbs) {
+        *chunk = limb.to_le_bytes();
+    }
+    Ok(bytes)
+}
+
+/// Format a little-endian 256-bit integer as a decimal string.
+fn int256_to_string(bytes: [u8; 32], signed: bool) -> String {
+    let mut limbs = [0u64; 4];
+    for (limb, chunk) in limbs.iter_mut().zip(bytes.as_chunks::<8>().0) {
+        *limb = u64::from_le_bytes(*chunk);
+    }
+    let negative = signed && limbs[3] >> 63 == 1;
+    if negative {
+        limbs = negate(limbs);
+    }
+
+    let mut digits = vec![];
+    while limbs != [0; 4] {
+        let mut rem = 0u128;
+        for limb in limbs.iter_mut().rev() {
+            let value = (rem << 64) | u128::from(*limb);
+            *limb = (value / 10) as u64;
+            rem = value % 10;
+        }
+        digits.push(b'0' + rem as u8);
+    }
+    if digits.is_empty() {
+        digits.push(b'0');
+    }
+    if negative {
+        digits.push(b'-');
+    }
+
+    digits.reverse();
+    String::from_utf8(digits).unwrap()
+}
+
+/// Two's complement negation of little-endian limbs.
+fn negate(limbs: [u64; 4]) -> [u64; 4] {
+    let mut carry = true;
+    limbs.map(|limb| {
+        let (value, overflow) = (!limb).overflowing_add(carry.into());
+        carry = overflow;
+        value
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, serde_json::json};
+
+    fn idl() -> Idl {
+        serde_json::from_value(json!({
+            "address": "Fg6PaFpoGXkYsidMpWTK6W2BeZ7FEfcYkg476zPFsLnS",
+            "metadata": { "name": "test", "version": "0.1.0", "spec": "0.1.0" },
+            "instructions": [{
+                "name": "initialize",
+                "discriminator": [1, 2],
+                "accounts": [],
+                "args": [
+                    { "name": "amount", "type": "u64" },
+                    { "name": "memo", "type": { "option": "string" } },
+                    { "name": "state", "type": { "defined": { "name": "State" } } }
+                ]
+            }],
+            "accounts": [
+                { "name": "Data", "discriminator": [3] },
+                { "name": "ZeroCopy", "discriminator": [4, 4] }
+            ],
+            "events": [{ "name": "Wrapped", "discriminator": [5, 5, 5, 5, 5, 5, 5, 5] }],
+            "types": [
+                {
+                    "name": "State",
+                    "type": {
+                        "kind": "enum",
+                        "variants": [
+                            { "name": "Pending" },
+                            { "name": "Confirmed", "fields": [{ "name": "slot", "type": "u32" }] },
+                            { "name": "Failed", "fields": ["i8", "bool"] }
+                        ]
+                    }
+                },
+                {
+                    "name": "Data",
+                    "type": {
+                        "kind": "struct",
+                        "fields": [
+                            { "name": "authority", "type": "pubkey" },
+                            { "name": "big", "type": "u128" },
+                            { "name": "huge", "type": "i256" },
+                            { "name": "values", "type": { "vec": "i16" } }
+                        ]
+                    }
+                },
+                {
+                    "name": "ZeroCopy",
+                    "serialization": "bytemuck",
+                    "repr": { "kind": "c" },
+                    "type": {
+                        "kind": "struct",
+                        "fields": [
+                            { "name": "flag", "type": "bool" },
+                            { "name": "amount", "type": "u64" },
+                            { "name": "inner", "type": { "defined": { "name": "Packed" } } }
+                        ]
+                    }
+                },
+                {
+                    "name": "Packed",
+                    "serialization": "bytemuck",
+                    "repr": { "kind": "c", "packed": true },
+                    "type": {
+                        "kind": "struct",
+                        "fields": [
+                            { "name": "a", "type": "u8" },
+                            { "name": "b", "type": "u32" }
+                        ]
+                    }
+                },
+                {
+                    "name": "Wrapper",
+                    "generics": [
+                        { "kind": "type", "name": "T" },
+                        { "kind": "const", "name": "N", "type": "usize" }
+                    ],
+                    "type": {
+                        "kind": "struct",
+                        "fields": [
+                            { "name": "items", "type": { "array": [{ "generic": "T" }, { "generic": "N" }] } }
+                        ]
+                    }
+                },
+                {
+                    "name": "Wrapped",
+                    "type": {
+                        "kind": "struct",
+                        "fields": [{
+                            "name": "wrapper",
+                            "type": {
+                                "defined": {
+                                    "name": "Wrapper",
+                                    "generics": [
+                                        { "kind": "type", "type": "u16" },
+                                        { "kind": "const", "value": "2" }
+                                    ]
+                                }
+                            }
+                        }]
+                    }
+                }
+            ]
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn instruction_round_trip() {
+        let idl = idl();
+        let codec = IdlCodec::new(&idl);
+
+        let args = json!({ "amount": 7, "memo": "hi", "state": { "Failed": [-1, true] } });
+        let data = codec.encode_instruction("initialize", &args).unwrap();
+        assert_eq!(
+            data,
+            [1, 2, 7, 0, 0, 0, 0, 0, 0, 0, 1, 2, 0, 0, 0, b'h', b'i', 2, 255, 1]
+        );
+
+        let (ix, decoded) = codec.decode_instruction(&data).unwrap();
+        assert_eq!(ix.name, "initialize");
+        assert_eq!(decoded, args);
+
+        // Missing options are encoded as `None`, and unit variants can be given by name
+        let data = codec
+            .encode_instruction("initialize", &json!({ "amount": "7", "state": "Pending" }))
+            .unwrap();
+        assert_eq!(data, [1, 2, 7, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(
+            codec.decode_instruction(&data).unwrap().1,
+            json!({ "amount": 7, "memo": null, "state": { "Pending": {} } })
+        );
+    }
+
+    #[test]
+    fn account_round_trip() {
+        let idl = idl();
+        let codec = IdlCodec::new(&idl);
+
+        let value = json!({
+            "authority": "Fg6PaFpoGXkYsidMpWTK6W2BeZ7FEfcYkg476zPFsLnS",
+            "big": u128::MAX.to_string(),
+            "huge": "-57896044618658097711785492504343953926634992332820282019728792003956564819968",
+            "values": [-2, 3]
+        });
+        let data = codec.encode_account("Data", &value).unwrap();
+        assert_eq!(data.len(), 1 + 32 + 16 + 32 + 4 + 4);
+        assert_eq!(&data[49..81], &[[0; 31].as_slice(), &[0x80]].concat());
+
+        let (acc, decoded) = codec.decode_account(&data).unwrap();
+        assert_eq!(acc.name, "Data");
+        assert_eq!(decoded, value);
+        assert!(codec.decode_account_as("ZeroCopy", &data).is_err());
+
+        let mut negative = value.clone();
+        negative["big"] = json!(-1);
+        assert!(codec.encode_account("Data", &negative).is_err());
+    }
+
+    #[test]
+    fn zero_copy_layout() {
+        let idl = idl();
+        let codec = IdlCodec::new(&idl);
+
+        let value = json!({ "flag": true, "amount": 2, "inner": { "a": 3, "b": 4 } });
+        let data = codec.encode_account("ZeroCopy", &value).unwrap();
+        assert_eq!(
+            data,
+            [
+                [4, 4].as_slice(),
+                &[1, 0, 0, 0, 0, 0, 0, 0],
+                &[2, 0, 0, 0, 0, 0, 0, 0],
+                &[3, 4, 0, 0, 0],
+                &[0, 0, 0]
+            ]
+            .concat()
+        );
+        assert_eq!(codec.decode_account_as("ZeroCopy", &data).unwrap(), value);
+    }
+
+    #[test]
+    fn generics() {
+        let idl = idl();
+        let codec = IdlCodec::new(&idl);
+
+        let value = json!({ "wrapper": { "items": [1, 2] } });
+        let data = codec.encode_event("Wrapped", &value).unwrap();
+        assert_eq!(data, [[5; 8].as_slice(), &[1, 0, 2, 0]].concat());
+
+        let (ev, decoded) = codec.decode_event(&data).unwrap();
+        assert_eq!(ev.name, "Wrapped");
+        assert_eq!(decoded, value);
+    }
+
+    #[test]
+    fn int256_conversion() {
+        for (value, signed) in [
+            ("0", false),
+            ("123456789012345678901234567890", false),
+            (
+                "115792089237316195423570985008687907853269984665640564039457584007913129639935",
+                false,
+            ),
+            ("-1", true),
+            (
+                "57896044618658097711785492504343953926634992332820282019728792003956564819967",
+                true,
+            ),
+        ] {
+            let bytes = int256(&json!(value), signed).unwrap();
+            assert_eq!(int256_to_string(bytes, signed), value);
+        }
+
+        assert!(int256(&json!("-1"), false).is_err());
+        assert!(int256(
+            &json!(
+                "115792089237316195423570985008687907853269984665640564039457584007913129639936"
+            ),
+            false
+        )
+        .is_err());
+    }
+}
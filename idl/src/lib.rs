@@ -3,9 +3,15 @@
 #[cfg(feature = "build")]
 pub mod build;
 
+#[cfg(feature = "codec")]
+pub mod codec;
+
 #[cfg(feature = "convert")]
 pub mod convert;
 
+#[cfg(feature = "validate")]
+pub mod validate;
+
 pub use anchor_lang_idl_spec as types;
 #[cfg(feature = "build")]
 pub use serde_json;
@@ -0,0 +1,631 @@
+//! IDL validation.
+//!
+//! The structure of an IDL is described by the JSON Schema of the spec (see
+//! [`schema`](crate::types::schema)). In addition to that, [`validate_idl`] checks the references
+//! between the items of an IDL, e.g. whether all `defined` types exist, which can't be expressed
+//! with a schema.
+
+use {
+    crate::types::{
+        Idl, IdlDefinedFields, IdlGenericArg, IdlInstruction, IdlInstructionAccountItem, IdlSeed,
+        IdlType, IdlTypeDef, IdlTypeDefGeneric, IdlTypeDefTy, IDL_SPEC,
+    },
+    std::{collections::HashMap, fmt},
+};
+
+/// An error in an IDL, along with the path of the invalid value, e.g.
+/// `instructions[0].args[1].type.defined.name`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationError {
+    pub path: String,
+    pub message: String,
+}
+
+impl ValidationError {
+    fn new(path: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            path: path.into(),
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+/// Parse and validate an IDL in JSON format.
+///
+/// Unlike [`convert_idl`](crate::convert::convert_idl), legacy IDLs (pre Anchor v0.30) are
+/// rejected, and all errors are reported with the path of the invalid value.
+pub fn validate_idl_json(idl: &[u8]) -> Result<Idl, Vec<ValidationError>> {
+    let value = serde_json::from_slice::<serde_json::Value>(idl)
+        .map_err(|e| vec![ValidationError::new(".", format!("Invalid JSON: {e}"))])?;
+    match value.get("metadata").and_then(|m| m.get("spec")) {
+        Some(serde_json::Value::String(spec)) if spec == IDL_SPEC => {}
+        Some(spec) => {
+            return Err(vec![ValidationError::new(
+                "metadata.spec",
+                format!("IDL spec not supported: `{spec}`, expected `{IDL_SPEC}`"),
+            )])
+        }
+        None => {
+            return Err(vec![ValidationError::new(
+                "metadata.spec",
+                "Missing IDL spec, legacy IDLs (pre Anchor v0.30) must be converted first",
+            )])
+        }
+    }
+
+    let idl = serde_path_to_error::deserialize::<_, Idl>(value).map_err(|e| {
+        vec![ValidationError::new(
+            e.path().to_string(),
+            e.into_inner().to_string(),
+        )]
+    })?;
+    let errors = validate_idl(&idl);
+    if errors.is_empty() {
+        Ok(idl)
+    } else {
+        Err(errors)
+    }
+}
+
+/// Validate the references between the items of the IDL.
+///
+/// This checks that:
+///
+/// - All `defined` types exist and get the expected generic arguments
+/// - Generic types and array lengths refer to the generics of their type definition
+/// - Accounts and events have a type definition
+/// - Names, error codes and discriminators are unique
+/// - PDA seeds and relations refer to existing instruction arguments and accounts
+pub fn validate_idl(idl: &Idl) -> Vec<ValidationError> {
+    let mut validator = Validator {
+        idl,
+        errors: vec![],
+    };
+    validator.validate();
+    validator.errors
+}
+
+struct Validator<'a> {
+    idl: &'a Idl,
+    errors: Vec<ValidationError>,
+}
+
+impl<'a> Validator<'a> {
+    fn error(&mut self, path: impl Into<String>, message: impl Into<String>) {
+        self.errors.push(ValidationError::new(path, message));
+    }
+
+    fn validate(&mut self) {
+        let idl = self.idl;
+
+        self.unique(
+            "instructions",
+            ".name",
+            "instruction name",
+            idl.instructions.iter().map(|ix| ix.name.to_owned()),
+        );
+        self.discriminators(
+            "instructions",
+            idl.instructions.iter().map(|ix| &ix.discriminator),
+        );
+        for (i, ix) in idl.instructions.iter().enumerate() {
+            let path = format!("instructions[{i}]");
+            self.unique(
+                &format!("{path}.args"),
+                ".name",
+                "argument name",
+                ix.args.iter().map(|arg| arg.name.to_owned()),
+            );
+            for (j, arg) in ix.args.iter().enumerate() {
+                self.ty(&format!("{path}.args[{j}].type"), &arg.ty, &[]);
+            }
+            if let Some(returns) = &ix.returns {
+                self.ty(&format!("{path}.returns"), returns, &[]);
+            }
+            self.instruction_accounts(&format!("{path}.accounts"), &ix.accounts, ix);
+        }
+
+        for (kind, items) in [
+            (
+                "accounts",
+                idl.accounts
+                    .iter()
+                    .map(|acc| (&acc.name, &acc.discriminator))
+                    .collect::<Vec<_>>(),
+            ),
+            (
+                "events",
+                idl.events
+                    .iter()
+                    .map(|ev| (&ev.name, &ev.discriminator))
+                    .collect(),
+            ),
+        ] {
+            self.unique(
+                kind,
+                ".name",
+                "name",
+                items.iter().map(|(name, _)| name.to_string()),
+            );
+            self.discriminators(kind, items.iter().map(|(_, disc)| *disc));
+            for (i, (name, _)) in items.iter().enumerate() {
+                if self.find_type(name).is_none() {
+                    self.error(
+                        format!("{kind}[{i}].name"),
+                        format!("Type `{name}` is not defined"),
+                    );
+                }
+            }
+        }
+
+        self.unique(
+            "errors",
+            ".code",
+            "error code",
+            idl.errors.iter().map(|err| err.code.to_string()),
+        );
+        self.unique(
+            "errors",
+            ".name",
+            "error name",
+            idl.errors.iter().map(|err| err.name.to_owned()),
+        );
+
+        self.unique(
+            "types",
+            ".name",
+            "type name",
+            idl.types.iter().map(|ty| ty.name.to_owned()),
+        );
+        for (i, ty_def) in idl.types.iter().enumerate() {
+            self.type_def(&format!("types[{i}]"), ty_def);
+        }
+
+        self.unique(
+            "constants",
+            ".name",
+            "constant name",
+            idl.constants.iter().map(|c| c.name.to_owned()),
+        );
+        for (i, constant) in idl.constants.iter().enumerate() {
+            self.ty(&format!("constants[{i}].type"), &constant.ty, &[]);
+        }
+    }
+
+    /// Report the items with a key that's already used by a previous item.
+    fn unique(&mut self, path: &str, suffix: &str, what: &str, keys: impl Iterator<Item = String>) {
+        let mut seen = HashMap::new();
+        for (i, key) in keys.enumerate() {
+            if let Some(first) = seen.insert(key.clone(), i) {
+                seen.insert(key.clone(), first);
+                self.error(
+                    format!("{path}[{i}]{suffix}"),
+                    format!("Duplicate {what} `{key}`, already used by `{path}[{first}]`"),
+                );
+            }
+        }
+    }
+
+    fn discriminators<'b>(
+        &mut self,
+        path: &str,
+        discriminators: impl Iterator<Item = &'b Vec<u8>>,
+    ) {
+        let discriminators = discriminators.collect::<Vec<_>>();
+        for (i, disc) in discriminators.iter().enumerate() {
+            if disc.is_empty() {
+                self.error(
+                    format!("{path}[{i}].discriminator"),
+                    "Discriminator must not be empty",
+                );
+            }
+        }
+        self.unique(
+            path,
+            ".discriminator",
+            "discriminator",
+            discriminators.iter().map(|disc| format!("{disc:?}")),
+        );
+    }
+
+    fn type_def(&mut self, path: &str, ty_def: &IdlTypeDef) {
+        let generics = &ty_def.generics;
+        self.unique(
+            &format!("{path}.generics"),
+            ".name",
+            "generic name",
+            generics.iter().map(|generic| match generic {
+                IdlTypeDefGeneric::Type { name } | IdlTypeDefGeneric::Const { name, .. } => {
+                    name.to_owned()
+                }
+            }),
+        );
+
+        match &ty_def.ty {
+            IdlTypeDefTy::Struct { fields } => {
+                self.defined_fields(&format!("{path}.type.fields"), fields, generics)
+            }
+            IdlTypeDefTy::Enum { variants } => {
+                self.unique(
+                    &format!("{path}.type.variants"),
+                    ".name",
+                    "variant name",
+                    variants.iter().map(|variant| variant.name.to_owned()),
+                );
+                for (i, variant) in variants.iter().enumerate() {
+                    self.defined_fields(
+                        &format!("{path}.type.variants[{i}].fields"),
+                        &variant.fields,
+                        generics,
+                    );
+                }
+            }
+            IdlTypeDefTy::Type { alias } => self.ty(&format!("{path}.type.alias"), alias, generics),
+        }
+    }
+
+    fn defined_fields(
+        &mut self,
+        path: &str,
+        fields: &Option<IdlDefinedFields>,
+        generics: &[IdlTypeDefGeneric],
+    ) {
+        match fields {
+            Some(IdlDefinedFields::Named(fields)) => {
+                self.unique(
+                    path,
+                    ".name",
+                    "field name",
+                    fields.iter().map(|field| field.name.to_owned()),
+                );
+                for (i, field) in fields.iter().enumerate() {
+                    self.ty(&format!("{path}[{i}].type"), &field.ty, generics);
+                }
+            }
+            Some(IdlDefinedFields::Tuple(fields)) => {
+                for (i, ty) in fields.iter().enumerate() {
+                    self.ty(&format!("{path}[{i}]"), ty, generics);
+                }
+            }
+            None => {}
+        }
+    }
+
+    fn ty(&mut self, path: &str, ty: &IdlType, generics: &[IdlTypeDefGeneric]) {
+        match ty {
+            IdlType::Option(inner) => self.ty(&format!("{path}.option"), inner, generics),
+            IdlType::Vec(inner) => self.ty(&format!("{path}.vec"), inner, generics),
+            IdlType::Array(inner, len) => {
+                self.ty(&format!("{path}.array[0]"), inner, generics);
+                if let crate::types::IdlArrayLen::Generic(name) = len {
+                    let is_const_generic = generics.iter().any(|generic| {
+                        matches!(generic, IdlTypeDefGeneric::Const { name: n, .. } if n == name)
+                    });
+                    if !is_const_generic {
+                        self.error(
+                            format!("{path}.array[1].generic"),
+                            format!("Unknown const generic `{name}`"),
+                        );
+                    }
+                }
+            }
+            IdlType::Defined {
+                name,
+                generics: args,
+            } => {
+                let Some(ty_def) = self.find_type(name) else {
+                    self.error(
+                        format!("{path}.defined.name"),
+                        format!("Type `{name}` is not defined"),
+                    );
+                    return;
+                };
+
+                if ty_def.generics.len() != args.len() {
+                    self.error(
+                        format!("{path}.defined.generics"),
+                        format!(
+                            "Type `{name}` expects {} generic argument(s), found {}",
+                            ty_def.generics.len(),
+                            args.len()
+                        ),
+                    );
+                } else {
+                    for (i, (param, arg)) in ty_def.generics.iter().zip(args).enumerate() {
+                        match (param, arg) {
+                            (IdlTypeDefGeneric::Type { .. }, IdlGenericArg::Type { .. })
+                            | (IdlTypeDefGeneric::Const { .. }, IdlGenericArg::Const { .. }) => {}
+                            (IdlTypeDefGeneric::Type { name }, IdlGenericArg::Const { .. }) => self
+                                .error(
+                                    format!("{path}.defined.generics[{i}]"),
+                                    format!("Expected a type argument for generic `{name}`"),
+                                ),
+                            (IdlTypeDefGeneric::Const { name, .. }, IdlGenericArg::Type { .. }) => {
+                                self.error(
+                                    format!("{path}.defined.generics[{i}]"),
+                                    format!("Expected a const argument for generic `{name}`"),
+                                )
+                            }
+                        }
+                    }
+                }
+
+                for (i, arg) in args.iter().enumerate() {
+                    if let IdlGenericArg::Type { ty } = arg {
+                        self.ty(&format!("{path}.defined.generics[{i}].type"), ty, generics);
+                    }
+                }
+            }
+            IdlType::Generic(name) => {
+                let is_type_generic = generics.iter().any(
+                    |generic| matches!(generic, IdlTypeDefGeneric::Type { name: n } if n == name),
+                );
+                if !is_type_generic {
+                    self.error(
+                        format!("{path}.generic"),
+                        format!("Unknown generic type `{name}`"),
+                    );
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn instruction_accounts(
+        &mut self,
+        path: &str,
+        accounts: &[IdlInstructionAccountItem],
+        ix: &IdlInstruction,
+    ) {
+        for (i, item) in accounts.iter().enumerate() {
+            let path = format!("{path}[{i}]");
+            let acc = match item {
+                IdlInstructionAccountItem::Composite(accs) => {
+                    self.instruction_accounts(&format!("{path}.accounts"), &accs.accounts, ix);
+                    continue;
+                }
+                IdlInstructionAccountItem::Single(acc) => acc,
+            };
+
+            for (j, relation) in acc.relations.iter().enumerate() {
+                if !has_account(&ix.accounts, relation) {
+                    self.error(
+                        format!("{path}.relations[{j}]"),
+                        format!("Unknown account `{relation}`"),
+                    );
+                }
+            }
+            if let Some(pda) = &acc.pda {
+                for (j, seed) in pda.seeds.iter().enumerate() {
+                    self.seed(&format!("{path}.pda.seeds[{j}]"), seed, ix);
+                }
+                if let Some(program) = &pda.program {
+                    self.seed(&format!("{path}.pda.program"), program, ix);
+                }
+            }
+        }
+    }
+
+    fn seed(&mut self, path: &str, seed: &IdlSeed, ix: &IdlInstruction) {
+        match seed {
+            IdlSeed::Const(_) => {}
+            IdlSeed::Arg(arg) => {
+                let mut segments = arg.path.split('.');
+                let name = segments.next().unwrap_or_default();
+                match ix.args.iter().find(|arg| arg.name == name) {
+                    Some(arg) => self.field_path(&format!("{path}.path"), &arg.ty, segments),
+                    None => self.error(
+                        format!("{path}.path"),
+                        format!("Unknown instruction argument `{name}`"),
+                    ),
+                }
+            }
+            IdlSeed::Account(acc) => {
+                let mut segments = acc.path.split('.');
+                let name = segments.next().unwrap_or_default();
+                if !has_account(&ix.accounts, name) {
+                    self.error(format!("{path}.path"), format!("Unknown account `{name}`"));
+                    return;
+                }
+
+                let Some(account) = &acc.account else {
+                    return;
+                };
+                if self.idl.accounts.iter().all(|acc| &acc.name != account) {
+                    self.error(
+                        format!("{path}.account"),
+                        format!("Unknown account type `{account}`"),
+                    );
+                    return;
+                }
+                let ty = IdlType::Defined {
+                    name: account.to_owned(),
+                    generics: vec![],
+                };
+                self.field_path(&format!("{path}.path"), &ty, segments);
+            }
+        }
+    }
+
+    /// Check that the remaining `segments` of a seed path are nested fields of `ty`.
+    fn field_path<'b, 'c>(
+        &mut self,
+        path: &str,
+        mut ty: &'c IdlType,
+        segments: impl Iterator<Item = &'b str>,
+    ) where
+        'a: 'c,
+    {
+        for segment in segments {
+            let IdlType::Defined { name, .. } = ty else {
+                return;
+            };
+            let Some(IdlTypeDef {
+                ty:
+                    IdlTypeDefTy::Struct {
+                        fields: Some(IdlDefinedFields::Named(fields)),
+                    },
+                ..
+            }) = self.find_type(name)
+            else {
+                return;
+            };
+
+            match fields.iter().find(|field| field.name == segment) {
+                Some(field) => ty = &field.ty,
+                None => {
+                    self.error(
+                        path,
+                        format!("Type `{name}` doesn't have a field named `{segment}`"),
+                    );
+                    return;
+                }
+            }
+        }
+    }
+
+    fn find_type(&self, name: &str) -> Option<&'a IdlTypeDef> {
+        self.idl.types.iter().find(|ty| ty.name == name)
+    }
+}
+
+/// Whether an account (or a group of accounts) with the given name exists in the instruction.
+fn has_account(accounts: &[IdlInstructionAccountItem], name: &str) -> bool {
+    accounts.iter().any(|item| match item {
+        IdlInstructionAccountItem::Single(acc) => acc.name == name,
+        IdlInstructionAccountItem::Composite(accs) => {
+            accs.name == name || has_account(&accs.accounts, name)
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, serde_json::json};
+
+    fn errors(idl: serde_json::Value) -> Vec<String> {
+        validate_idl_json(&serde_json::to_vec(&idl).unwrap())
+            .err()
+            .unwrap_or_default()
+            .iter()
+            .map(ToString::to_string)
+            .collect()
+    }
+
+    fn idl(value: serde_json::Value) -> serde_json::Value {
+        let mut idl = json!({
+            "address": "Fg6PaFpoGXkYsidMpWTK6W2BeZ7FEfcYkg476zPFsLnS",
+            "metadata": { "name": "test", "version": "0.1.0", "spec": "0.1.0" },
+            "instructions": [],
+        });
+        idl.as_object_mut()
+            .unwrap()
+            .extend(value.as_object().unwrap().clone());
+        idl
+    }
+
+    #[test]
+    fn valid_idls() {
+        for idl in [
+            include_bytes!("../../tests/declare-program/idls/external.json").as_slice(),
+            include_bytes!("../../tests/declare-program/idls/amm_v3.json"),
+        ] {
+            assert_eq!(validate_idl_json(idl).err(), None);
+        }
+    }
+
+    #[test]
+    fn structural_errors() {
+        assert_eq!(
+            errors(idl(json!({
+                "instructions": [{
+                    "name": "init",
+                    "discriminator": [1],
+                    "accounts": [],
+                    "args": [{ "name": "amount", "type": "u65" }]
+                }]
+            }))),
+            ["instructions[0].args[0].type: unknown variant `u65`, expected one of `bool`, `u8`, \
+              `i8`, `u16`, `i16`, `u32`, `i32`, `f32`, `u64`, `i64`, `f64`, `u128`, `i128`, \
+              `u256`, `i256`, `bytes`, `string`, `pubkey`, `option`, `vec`, `array`, `defined`, \
+              `generic`"]
+        );
+
+        let mut legacy = idl(json!({}));
+        legacy["metadata"].as_object_mut().unwrap().remove("spec");
+        assert_eq!(
+            errors(legacy),
+            ["metadata.spec: Missing IDL spec, legacy IDLs (pre Anchor v0.30) must be converted \
+              first"]
+        );
+    }
+
+    #[test]
+    fn reference_errors() {
+        assert_eq!(
+            errors(idl(json!({
+                "instructions": [
+                    {
+                        "name": "init",
+                        "discriminator": [1],
+                        "accounts": [{
+                            "name": "data",
+                            "pda": {
+                                "seeds": [
+                                    { "kind": "arg", "path": "params.seed" },
+                                    { "kind": "arg", "path": "amount" },
+                                    { "kind": "account", "path": "authority" },
+                                ]
+                            },
+                            "relations": ["owner"]
+                        }],
+                        "args": [
+                            { "name": "params", "type": { "defined": { "name": "Params" } } },
+                            { "name": "wrapper", "type": { "defined": { "name": "Wrapper" } } }
+                        ]
+                    },
+                    { "name": "close", "discriminator": [1], "accounts": [], "args": [] }
+                ],
+                "accounts": [{ "name": "Data", "discriminator": [2] }],
+                "types": [
+                    {
+                        "name": "Params",
+                        "type": { "kind": "struct", "fields": [{ "name": "bump", "type": "u8" }] }
+                    },
+                    {
+                        "name": "Wrapper",
+                        "generics": [{ "kind": "type", "name": "T" }],
+                        "type": {
+                            "kind": "struct",
+                            "fields": [
+                                { "name": "inner", "type": { "generic": "U" } },
+                                { "name": "data", "type": { "array": ["u8", { "generic": "N" }] } }
+                            ]
+                        }
+                    }
+                ]
+            }))),
+            [
+                "instructions[1].discriminator: Duplicate discriminator `[1]`, already used by \
+                 `instructions[0]`",
+                "instructions[0].args[1].type.defined.generics: Type `Wrapper` expects 1 generic \
+                 argument(s), found 0",
+                "instructions[0].accounts[0].relations[0]: Unknown account `owner`",
+                "instructions[0].accounts[0].pda.seeds[0].path: Type `Params` doesn't have a \
+                 field named `seed`",
+                "instructions[0].accounts[0].pda.seeds[1].path: Unknown instruction argument \
+                 `amount`",
+                "instructions[0].accounts[0].pda.seeds[2].path: Unknown account `authority`",
+                "accounts[0].name: Type `Data` is not defined",
+                "types[1].type.fields[0].type.generic: Unknown generic type `U`",
+                "types[1].type.fields[1].type.array[1].generic: Unknown const generic `N`",
+            ]
+        );
+    }
+}
@@ -0,0 +1,37 @@
+use {
+    anchor_lang_idl::{
+        build::IdlBuilder,
+        types::{IdlDefinedFields, IdlField, IdlType, IdlTypeDefTy},
+    },
+    std::{collections::BTreeMap, path::Path},
+};
+
+/// Types whose definitions are provided by the builder are emitted as references by `idl-build`,
+/// and their definitions replace the ones from the `IdlBuild` trait.
+#[test]
+fn external_types() {
+    let my_struct = IdlTypeDefTy::Struct {
+        fields: Some(IdlDefinedFields::Named(vec![IdlField {
+            name: "mapped_field".into(),
+            docs: vec![],
+            ty: IdlType::U16,
+        }])),
+    };
+    let idl = IdlBuilder::new()
+        .program_path(Path::new(env!("CARGO_MANIFEST_DIR")).join("../tests/idl/programs/generics"))
+        .external_types(BTreeMap::from([(
+            "external::MyStruct".into(),
+            my_struct.clone(),
+        )]))
+        .build()
+        .unwrap();
+
+    let defs = idl
+        .types
+        .iter()
+        .filter(|ty| ty.name.ends_with("MyStruct"))
+        .collect::<Vec<_>>();
+    assert_eq!(defs.len(), 1, "{defs:?}");
+    assert_eq!(defs[0].name, "MyStruct");
+    assert_eq!(defs[0].ty, my_struct);
+}
@@ -0,0 +1,130 @@
+use {
+    anchor_lang_idl::{
+        build::IdlBuilder,
+        types::{Idl, IdlDefinedFields, IdlField, IdlType, IdlTypeDefTy},
+    },
+    std::{
+        collections::BTreeMap,
+        path::{Path, PathBuf},
+    },
+};
+
+fn idl_test_path(path: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("../tests/idl")
+        .join(path)
+}
+
+fn parse(program: &str) -> anyhow::Result<Idl> {
+    IdlBuilder::new()
+        .program_path(idl_test_path(&format!("programs/{program}")))
+        .static_analysis(true)
+        .build()
+}
+
+fn expected(idl: &str) -> Idl {
+    let content = std::fs::read_to_string(idl_test_path(&format!("idls/{idl}"))).unwrap();
+    serde_json::from_str(&content).unwrap()
+}
+
+#[test]
+fn relations() {
+    assert_eq!(
+        parse("relations-derivation").unwrap(),
+        expected("relations.json")
+    );
+}
+
+#[test]
+fn types_from_other_crates_are_reported() {
+    let err = parse("generics").unwrap_err().to_string();
+    assert!(err.contains("`external::MyStruct`"), "{err}");
+}
+
+#[test]
+fn external_types() {
+    let my_struct = IdlTypeDefTy::Struct {
+        fields: Some(IdlDefinedFields::Named(vec![IdlField {
+            name: "some_field".into(),
+            docs: vec![],
+            ty: IdlType::U8,
+        }])),
+    };
+    let idl = IdlBuilder::new()
+        .program_path(idl_test_path("programs/generics"))
+        .static_analysis(true)
+        .external_types(BTreeMap::from([("external::MyStruct".into(), my_struct)]))
+        .build()
+        .unwrap();
+    assert_eq!(idl, expected("generics.json"));
+}
+
+/// Events, errors, constants, enums, zero-copy accounts and the rest of the `idl` program match
+/// the output of the `idl-build` feature, except for constructs that can't be parsed statically:
+///
+/// - Manual `IdlBuild` implementations (`wrapped::Feature`), the Rust definition of the type is
+///   used instead
+/// - Addresses of programs from other crates (`Program<'info, external::program::External>`)
+/// - Types of `#[derive(Accounts)]` structs that aren't used by an instruction
+///   (`external::MyAccount`)
+#[test]
+fn idl() {
+    let expected = expected("idl.json");
+    let external_type = |name: &str| {
+        expected
+            .types
+            .iter()
+            .find(|ty| ty.name == name)
+            .map(|ty| ty.ty.clone())
+            .unwrap()
+    };
+    let idl = IdlBuilder::new()
+        .program_path(idl_test_path("programs/idl"))
+        .static_analysis(true)
+        .external_types(BTreeMap::from([
+            ("external::MyStruct".into(), external_type("MyStruct")),
+            (
+                "anchor_lang::solana_program::feature::Feature".into(),
+                external_type("Feature"),
+            ),
+        ]))
+        .build()
+        .unwrap();
+
+    assert_eq!(idl.address, expected.address);
+    assert_eq!(idl.metadata, expected.metadata);
+    assert_eq!(idl.accounts, expected.accounts);
+    assert_eq!(idl.events, expected.events);
+    assert_eq!(idl.errors, expected.errors);
+    assert_eq!(idl.constants, expected.constants);
+
+    let unsupported = [
+        "boxed",
+        "external_non_anchor",
+        "full_path",
+        "AccountWithNonAnchorExternalField",
+        "BoxedAccount",
+        "Feature",
+        "MyAccount",
+        "idl::wrapped::Feature",
+        "anchor_lang::solana_program::feature::Feature",
+    ];
+    let supported = |name: &String| !unsupported.contains(&name.as_str());
+    let instructions = |idl: &Idl| {
+        idl.instructions
+            .iter()
+            .filter(|ix| supported(&ix.name))
+            .cloned()
+            .collect::<Vec<_>>()
+    };
+    let types = |idl: &Idl| {
+        idl.types
+            .iter()
+            .filter(|ty| supported(&ty.name))
+            .cloned()
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(instructions(&idl), instructions(&expected));
+    assert_eq!(types(&idl), types(&expected));
+    assert!(types(&expected).len() > 20);
+}
@@ -4,6 +4,7 @@ mod mods;
 use {
     anchor_lang_idl::{convert::convert_idl, types::Idl},
     anyhow::anyhow,
+    cargo_toml::{Manifest, Value as TomlValue},
     common::gen_docs,
     mods::{
         accounts::gen_accounts_mod, client::gen_client_mod, constants::gen_constants_mod,
@@ -11,47 +12,337 @@ use {
         parsers::gen_parsers_mod, program::gen_program_mod, types::gen_types_mod,
     },
     quote::{quote, ToTokens},
-    std::{env, fs, path::PathBuf},
-    syn::parse::{Parse, ParseStream},
+    std::{
+        collections::{BTreeMap, HashMap},
+        env, fs,
+        path::{Path, PathBuf},
+        process::Command,
+        sync::{Mutex, PoisonError},
+        time::SystemTime,
+    },
+    syn::{
+        ext::IdentExt,
+        parse::{Parse, ParseStream},
+        Token,
+    },
 };
 
 pub struct DeclareProgram {
     name: syn::Ident,
     idl: Idl,
+    idl_path: PathBuf,
+    /// Path to the `anchor_client` crate, set by `anchor_client::declare_program!`
+    client: Option<syn::Path>,
 }
 
 impl Parse for DeclareProgram {
     fn parse(input: ParseStream) -> syn::Result<Self> {
         let name = input.parse()?;
-        let idl = get_idl(&name).map_err(|e| syn::Error::new(name.span(), e))?;
-        Ok(Self { name, idl })
+        let mut source = IdlSource::Idls;
+        let mut client = None;
+        while !input.is_empty() {
+            input.parse::<Token![,]>()?;
+            if input.is_empty() {
+                break;
+            }
+
+            let key = input.call(syn::Ident::parse_any)?;
+            input.parse::<Token![=]>()?;
+            if key == "__client" {
+                client = Some(input.parse()?);
+                continue;
+            }
+
+            let value = input.parse::<syn::LitStr>()?;
+            if !matches!(source, IdlSource::Idls) {
+                return Err(syn::Error::new(
+                    key.span(),
+                    "Only one of `path` and `crate` can be specified",
+                ));
+            }
+            source = match key.to_string().as_str() {
+                "path" => IdlSource::Path(value),
+                "crate" => IdlSource::Crate(value),
+                _ => {
+                    return Err(syn::Error::new(
+                        key.span(),
+                        format!("Unknown argument `{key}`, expected `path` or `crate`"),
+                    ))
+                }
+            };
+        }
+
+        let idl_path = source
+            .idl_path(&name)
+            .map_err(|e| syn::Error::new(source.span(&name), e))?;
+        let idl = fs::read(&idl_path)
+            .map_err(|e| anyhow!("Failed to read IDL `{}`: {e}", idl_path.display()))
+            .and_then(|buf| convert_idl(&buf))
+            .map_err(|e| syn::Error::new(source.span(&name), e))?;
+        Ok(Self {
+            name,
+            idl,
+            idl_path,
+            client,
+        })
     }
 }
 
 impl ToTokens for DeclareProgram {
     fn to_tokens(&self, tokens: &mut proc_macro2::TokenStream) {
-        let program = gen_program(&self.idl, &self.name);
-        tokens.extend(program)
+        let program = gen_program(&self.idl, &self.name, self.client.as_ref());
+        // Rebuild when the IDL changes
+        let idl_path = self.idl_path.to_string_lossy();
+        tokens.extend(quote! {
+            #program
+            const _: &[u8] = include_bytes!(#idl_path);
+        })
+    }
+}
+
+/// Where to load the IDL of the program from.
+enum IdlSource {
+    /// `<name>.json` in the closest `idls` directory of the crate's ancestors
+    Idls,
+    /// Path relative to the crate's manifest directory (`path = "..."`)
+    Path(syn::LitStr),
+    /// IDL exported by a dependency with `package.metadata.anchor.idl` (`crate = "..."`)
+    Crate(syn::LitStr),
+}
+
+impl IdlSource {
+    fn span(&self, name: &syn::Ident) -> proc_macro2::Span {
+        match self {
+            Self::Idls => name.span(),
+            Self::Path(lit) | Self::Crate(lit) => lit.span(),
+        }
+    }
+
+    fn idl_path(&self, name: &syn::Ident) -> anyhow::Result<PathBuf> {
+        let manifest_dir = env::var("CARGO_MANIFEST_DIR")
+            .map(PathBuf::from)
+            .map_err(|e| anyhow!("Failed to get environment variable `CARGO_MANIFEST_DIR`: {e}"))?;
+        match self {
+            Self::Idls => manifest_dir
+                .ancestors()
+                .find_map(|ancestor| {
+                    let idl_dir = ancestor.join("idls");
+                    idl_dir.exists().then_some(idl_dir)
+                })
+                .ok_or_else(|| anyhow!("`idls` directory not found"))
+                .map(|idl_dir| idl_dir.join(name.to_string()).with_extension("json")),
+            Self::Path(path) => Ok(manifest_dir.join(path.value())),
+            Self::Crate(krate) => get_dependency_idl_path(&manifest_dir, &krate.value()),
+        }
+    }
+}
+
+/// Get the path of the IDL exported by the dependency `krate` of the crate in `manifest_dir`.
+///
+/// Crates export their IDL by setting its path, relative to their manifest directory, in the
+/// package metadata:
+///
+/// ```toml
+/// [package.metadata.anchor]
+/// idl = "idl.json"
+/// ```
+fn get_dependency_idl_path(manifest_dir: &Path, krate: &str) -> anyhow::Result<PathBuf> {
+    let manifest = read_manifest(manifest_dir)?;
+    let lib_name = krate.replace('-', "_");
+    let dependency = manifest
+        .dependencies
+        .iter()
+        .chain(&manifest.dev_dependencies)
+        .chain(
+            manifest
+                .target
+                .values()
+                .flat_map(|target| target.dependencies.iter().chain(&target.dev_dependencies)),
+        )
+        .find(|(name, _)| name.replace('-', "_") == lib_name)
+        .map(|(_, dependency)| dependency)
+        .ok_or_else(|| anyhow!("`{krate}` is not a dependency of this crate"))?;
+
+    // Path dependencies, e.g. the other programs of the workspace, are read directly. Only
+    // registry and git dependencies need the dependency graph to be resolved to be found.
+    let dependency_dir = match dependency.detail().and_then(|detail| detail.path.as_ref()) {
+        Some(path) => manifest_dir.join(path),
+        None => resolve_dependency_dir(manifest_dir, &lib_name)?,
+    };
+
+    let idl = read_manifest(&dependency_dir)?
+        .package
+        .and_then(|package| package.metadata)
+        .as_ref()
+        .and_then(|metadata| metadata.get("anchor"))
+        .and_then(|anchor| anchor.get("idl"))
+        .and_then(|idl| idl.as_str())
+        .map(|idl| dependency_dir.join(idl))
+        .ok_or_else(|| {
+            anyhow!("`{krate}` does not export an IDL (`package.metadata.anchor.idl` is not set)")
+        })?;
+    Ok(idl)
+}
+
+/// Read the manifest in `dir`, with dependencies inherited from its workspace.
+fn read_manifest(dir: &Path) -> anyhow::Result<Manifest<TomlValue>> {
+    let manifest_path = dir.join("Cargo.toml");
+    Manifest::from_path_with_metadata(&manifest_path)
+        .map_err(|e| anyhow!("Failed to read {}: {e}", manifest_path.display()))
+}
+
+/// Manifest directories of the dependencies of a crate, by library name.
+struct ResolvedDependencies {
+    /// Lock file of the workspace and its modification time when the graph was resolved
+    lock_file: Option<(PathBuf, SystemTime)>,
+    dirs: HashMap<String, PathBuf>,
+}
+
+impl ResolvedDependencies {
+    fn is_fresh(&self) -> bool {
+        self.lock_file
+            .as_ref()
+            .is_some_and(|(path, modified)| modified_time(path) == Some(*modified))
+    }
+}
+
+/// Dependency graphs resolved by this process, by canonical manifest directory. The same process
+/// expands the macro many times, e.g. rust-analyzer on every change.
+static RESOLVED_DEPENDENCIES: Mutex<BTreeMap<PathBuf, ResolvedDependencies>> =
+    Mutex::new(BTreeMap::new());
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+/// Find the manifest directory of the dependency `lib_name` in the resolved dependency graph.
+///
+/// The graph is resolved once per crate, and again only when the lock file of the workspace
+/// changes.
+fn resolve_dependency_dir(manifest_dir: &Path, lib_name: &str) -> anyhow::Result<PathBuf> {
+    let manifest_dir = manifest_dir
+        .canonicalize()
+        .map_err(|e| anyhow!("Failed to canonicalize {}: {e}", manifest_dir.display()))?;
+    let mut resolved = RESOLVED_DEPENDENCIES
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
+    if !resolved
+        .get(&manifest_dir)
+        .is_some_and(ResolvedDependencies::is_fresh)
+    {
+        let dependencies = resolve_dependencies(&manifest_dir)?;
+        resolved.insert(manifest_dir.clone(), dependencies);
     }
+    resolved
+        .get(&manifest_dir)
+        .and_then(|dependencies| dependencies.dirs.get(lib_name))
+        .cloned()
+        .ok_or_else(|| anyhow!("`{lib_name}` not found in the dependency graph"))
 }
 
-fn get_idl(name: &syn::Ident) -> anyhow::Result<Idl> {
-    env::var("CARGO_MANIFEST_DIR")
-        .map(PathBuf::from)
-        .map_err(|e| anyhow!("Failed to get environment variable `CARGO_MANIFEST_DIR`: {e}"))?
-        .ancestors()
-        .find_map(|ancestor| {
-            let idl_dir = ancestor.join("idls");
-            idl_dir.exists().then_some(idl_dir)
+/// Resolve the dependency graph of the crate in the canonical `manifest_dir` with
+/// `cargo metadata`.
+///
+/// Runs offline, the dependencies of a crate being compiled are already downloaded. The graph is
+/// filtered to the host platform, as the dependencies of other platforms may not be downloaded.
+fn resolve_dependencies(manifest_dir: &Path) -> anyhow::Result<ResolvedDependencies> {
+    let manifest_path = manifest_dir.join("Cargo.toml");
+    let rustc = env::var("RUSTC").unwrap_or_else(|_| "rustc".into());
+    let version = run(Command::new(rustc).arg("-vV").current_dir(manifest_dir))?;
+    let host = String::from_utf8_lossy(&version)
+        .lines()
+        .find_map(|line| line.strip_prefix("host: "))
+        .map(str::to_owned)
+        .ok_or_else(|| anyhow!("Host platform not found in `rustc -vV` output"))?;
+    let cargo = env::var("CARGO").unwrap_or_else(|_| "cargo".into());
+    let output = run(Command::new(cargo)
+        .args([
+            "metadata",
+            "--format-version",
+            "1",
+            "--offline",
+            "--filter-platform",
+            &host,
+            "--manifest-path",
+        ])
+        .arg(&manifest_path))?;
+    let metadata: serde_json::Value = serde_json::from_slice(&output)
+        .map_err(|e| anyhow!("Failed to parse `cargo metadata` output: {e}"))?;
+
+    // Paths are canonicalized on both sides, as checkouts may be behind symlinks
+    let package_dir = |package: &serde_json::Value| {
+        str_field(package, "manifest_path")
+            .and_then(|path| Path::new(path).parent())
+            .and_then(|dir| dir.canonicalize().ok())
+    };
+    let package_id = find_package(&metadata, |package| {
+        package_dir(package).as_deref() == Some(manifest_dir)
+    })?
+    .get("id");
+    let deps = metadata
+        .get("resolve")
+        .and_then(|resolve| resolve.get("nodes"))
+        .and_then(|nodes| nodes.as_array())
+        .and_then(|nodes| nodes.iter().find(|node| node.get("id") == package_id))
+        .and_then(|node| node.get("deps"))
+        .and_then(|deps| deps.as_array())
+        .ok_or_else(|| anyhow!("Crate not found in the dependency graph"))?;
+    let dirs = deps
+        .iter()
+        .filter_map(|dep| {
+            let name = str_field(dep, "name")?;
+            let dependency_id = dep.get("pkg")?;
+            let package = find_package(&metadata, |package| {
+                package.get("id") == Some(dependency_id)
+            })
+            .ok()?;
+            Some((name.to_owned(), package_dir(package)?))
         })
-        .ok_or_else(|| anyhow!("`idls` directory not found"))
-        .map(|idl_dir| idl_dir.join(name.to_string()).with_extension("json"))
-        .map(fs::read)?
-        .map_err(|e| anyhow!("Failed to read IDL `{name}`: {e}"))
-        .map(|buf| convert_idl(&buf))?
+        .collect();
+
+    let lock_file = str_field(&metadata, "workspace_root")
+        .map(|root| Path::new(root).join("Cargo.lock"))
+        .and_then(|path| modified_time(&path).map(|modified| (path, modified)));
+    Ok(ResolvedDependencies { lock_file, dirs })
+}
+
+/// Run the command and return its standard output.
+fn run(command: &mut Command) -> anyhow::Result<Vec<u8>> {
+    let program = command.get_program().to_string_lossy().into_owned();
+    let output = command
+        .output()
+        .map_err(|e| anyhow!("Failed to run `{program}`: {e}"))?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "Failed to run `{program}`: {}",
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+    Ok(output.stdout)
 }
 
-fn gen_program(idl: &Idl, name: &syn::Ident) -> proc_macro2::TokenStream {
+fn find_package(
+    metadata: &serde_json::Value,
+    predicate: impl Fn(&serde_json::Value) -> bool,
+) -> anyhow::Result<&serde_json::Value> {
+    metadata
+        .get("packages")
+        .and_then(|packages| packages.as_array())
+        .and_then(|packages| packages.iter().find(|package| predicate(package)))
+        .ok_or_else(|| anyhow!("Package not found in `cargo metadata` output"))
+}
+
+fn str_field<'a>(value: &'a serde_json::Value, key: &str) -> Option<&'a str> {
+    value.get(key).and_then(|value| value.as_str())
+}
+
+fn gen_program(
+    idl: &Idl,
+    name: &syn::Ident,
+    client: Option<&syn::Path>,
+) -> proc_macro2::TokenStream {
     let docs = gen_program_docs(idl);
     let id = gen_id(idl);
     let program_mod = gen_program_mod(&idl.metadata.name);
@@ -65,11 +356,17 @@ fn gen_program(idl: &Idl, name: &syn::Ident) -> proc_macro2::TokenStream {
 
     // Clients
     let cpi_mod = gen_cpi_mod(idl);
-    let client_mod = gen_client_mod(idl);
+    let client_mod = gen_client_mod(idl, client);
     let internal_mod = gen_internal_mod(idl);
 
     // Utils
-    let parsers_mod = gen_parsers_mod(idl);
+    let parsers_mod = gen_parsers_mod(idl, client);
+
+    // Off-chain, `anchor_lang` is either in scope or re-exported by `anchor_client`
+    let off_chain_anchor_lang = match client {
+        Some(client) => quote! { #client::anchor_lang },
+        None => quote! { super::anchor_lang },
+    };
 
     quote! {
         #docs
@@ -77,7 +374,7 @@ fn gen_program(idl: &Idl, name: &syn::Ident) -> proc_macro2::TokenStream {
             #[cfg(any(target_os = "solana", feature = "idl-build"))]
             use ::anchor_lang;
             #[cfg(all(not(target_os = "solana"), not(feature = "idl-build")))]
-            use super::anchor_lang;
+            use #off_chain_anchor_lang;
 
             use anchor_lang::prelude::*;
             use accounts::*;
@@ -0,0 +1,589 @@
+use {
+    super::common::{gen_docs, get_all_instruction_accounts},
+    anchor_lang_idl::types::{
+        Idl, IdlDefinedFields, IdlInstruction, IdlInstructionAccount, IdlInstructionAccountItem,
+        IdlPda, IdlSeed, IdlType, IdlTypeDefTy,
+    },
+    heck::CamelCase,
+    quote::{format_ident, quote},
+    std::collections::HashSet,
+};
+
+/// Builder methods that account setters must not shadow.
+const RESERVED_METHODS: &[&str] = &[
+    "account_data",
+    "accounts",
+    "args",
+    "instruction",
+    "missing_account_data",
+    "new",
+    "remaining_accounts",
+];
+
+pub fn gen_builders_mod(idl: &Idl, client: Option<&syn::Path>) -> proc_macro2::TokenStream {
+    let builders = idl
+        .instructions
+        .iter()
+        .map(|ix| gen_builder(idl, ix, client));
+
+    quote! {
+        /// Instruction builders.
+        ///
+        /// Accounts that are not set are derived when possible:
+        ///
+        /// - Accounts with a fixed address
+        /// - PDAs whose seeds are constants, instruction arguments, other accounts, or fields of
+        ///   other accounts' data
+        /// - Accounts stored in the data of a related account (`has_one` constraints)
+        ///
+        /// Derivations that depend on account data require the data to be provided with
+        /// `account_data`. The accounts whose data is needed are returned by
+        /// `missing_account_data`, which allows fetching them in as many rounds as necessary.
+        /// With `anchor_client`, use `Program::resolve` to fetch them.
+        pub mod builders {
+            use super::*;
+
+            #(#builders)*
+        }
+    }
+}
+
+fn gen_builder(
+    idl: &Idl,
+    ix: &IdlInstruction,
+    client: Option<&syn::Path>,
+) -> proc_macro2::TokenStream {
+    let name = format_ident!("{}", ix.name.to_camel_case());
+    let docs = gen_docs(&[format!("Builder for the `{}` instruction.", ix.name)]);
+    let all_ix_accs = get_all_instruction_accounts(idl);
+    let derivations = get_derivations(idl, ix);
+
+    let mut fields = vec![];
+    let mut setters = vec![];
+    let mut locals = vec![];
+    let mut struct_fields = vec![];
+    for acc in &ix.accounts {
+        let (acc_name, ty) = match acc {
+            IdlInstructionAccountItem::Single(acc) => (&acc.name, quote!(Pubkey)),
+            IdlInstructionAccountItem::Composite(accs) => {
+                let Some(ty_name) = all_ix_accs
+                    .iter()
+                    .find(|a| a.accounts == accs.accounts)
+                    .map(|a| format_ident!("{}", a.name.to_camel_case()))
+                else {
+                    return syn::Error::new(
+                        proc_macro2::Span::call_site(),
+                        format!(
+                            "Accounts `{}` of instruction `{}` are not in the IDL",
+                            accs.name, ix.name
+                        ),
+                    )
+                    .into_compile_error();
+                };
+                (&accs.name, quote!(super::accounts::#ty_name))
+            }
+        };
+        let ident = format_ident!("{}", acc_name);
+        let setter = if RESERVED_METHODS.contains(&acc_name.as_str()) {
+            format_ident!("{}_account", acc_name)
+        } else {
+            ident.clone()
+        };
+        let setter_docs = if derivations.iter().any(|(acc, _)| acc.name == *acc_name) {
+            format!("Set the `{acc_name}` account, derived if not set.")
+        } else {
+            format!("Set the `{acc_name}` account.")
+        };
+
+        fields.push(quote! { #ident: Option<#ty> });
+        setters.push(quote! {
+            #[doc = #setter_docs]
+            pub fn #setter(mut self, #ident: #ty) -> Self {
+                self.#ident = Some(#ident);
+                self
+            }
+        });
+        locals.push(quote! { let #ident = self.#ident; });
+
+        let optional = matches!(acc, IdlInstructionAccountItem::Single(acc) if acc.optional);
+        struct_fields.push(if optional {
+            quote! { #ident }
+        } else {
+            quote! { #ident: #ident.ok_or(super::BuilderError::MissingAccount(#acc_name))? }
+        });
+    }
+
+    let (derive_fns, derive_stmts): (Vec<_>, Vec<_>) = derivations
+        .iter()
+        .map(|(acc, derivation)| {
+            let ident = format_ident!("{}", acc.name);
+            let fn_name = format_ident!("__derive_{}", acc.name);
+            let deps = derivation.deps.iter().map(|dep| format_ident!("{}", dep));
+            let params = deps.clone().map(|dep| quote! { #dep: Option<Pubkey> });
+            let unwrap_deps = deps.clone().map(|dep| quote! { let #dep = #dep?; });
+            let unwrap_args = derivation
+                .uses_args
+                .then(|| quote! { let __args = self.__args.as_ref()?; });
+            let expr = &derivation.expr;
+
+            let derive_fn = quote! {
+                #[allow(clippy::too_many_arguments)]
+                fn #fn_name(&self, #(#params),*) -> Option<Pubkey> {
+                    #(#unwrap_deps)*
+                    #unwrap_args
+                    Some(#expr)
+                }
+            };
+            let derive_stmt = quote! {
+                let #ident = #ident.or_else(|| self.#fn_name(#(#deps),*));
+            };
+            (derive_fn, derive_stmt)
+        })
+        .unzip();
+
+    let missing_data_stmts = derivations
+        .iter()
+        .filter(|(_, derivation)| !derivation.data_deps.is_empty())
+        .map(|(acc, derivation)| {
+            let ident = format_ident!("{}", acc.name);
+            let data_deps = derivation
+                .data_deps
+                .iter()
+                .map(|dep| format_ident!("{}", dep));
+            quote! {
+                if #ident.is_none() {
+                    #(
+                        addresses.extend(
+                            #data_deps.filter(|address| !self.__account_data.contains_key(address))
+                        );
+                    )*
+                }
+            }
+        })
+        .collect::<Vec<_>>();
+    let missing_account_data = if missing_data_stmts.is_empty() {
+        quote! { Vec::new() }
+    } else {
+        quote! {
+            #(#locals)*
+            #(#derive_stmts)*
+            let mut addresses = Vec::new();
+            #(#missing_data_stmts)*
+            addresses.sort();
+            addresses.dedup();
+            addresses
+        }
+    };
+
+    let resolve_impl = client.map(|client| {
+        quote! {
+            impl #client::ResolveAccounts for #name {
+                fn missing_account_data(&self) -> Vec<Pubkey> {
+                    Self::missing_account_data(self)
+                }
+
+                fn account_data(self, address: Pubkey, data: Vec<u8>) -> Self {
+                    Self::account_data(self, address, data)
+                }
+            }
+        }
+    });
+
+    let (args_field, args_setter, args_expr) = if ix.args.is_empty() {
+        (None, None, quote! { super::args::#name })
+    } else {
+        (
+            Some(quote! { __args: Option<super::args::#name>, }),
+            Some(quote! {
+                /// Set the instruction arguments.
+                pub fn args(mut self, args: super::args::#name) -> Self {
+                    self.__args = Some(args);
+                    self
+                }
+            }),
+            quote! { self.__args.ok_or(super::BuilderError::MissingArgs)? },
+        )
+    };
+
+    quote! {
+        #docs
+        #[derive(Default)]
+        pub struct #name {
+            #(#fields,)*
+            #args_field
+            __remaining_accounts: Vec<anchor_lang::solana_program::instruction::AccountMeta>,
+            __account_data: std::collections::BTreeMap<Pubkey, Vec<u8>>,
+        }
+
+        impl #name {
+            pub fn new() -> Self {
+                Self::default()
+            }
+
+            #(#setters)*
+
+            #args_setter
+
+            /// Set the remaining accounts of the instruction.
+            pub fn remaining_accounts(
+                mut self,
+                accounts: Vec<anchor_lang::solana_program::instruction::AccountMeta>,
+            ) -> Self {
+                self.__remaining_accounts = accounts;
+                self
+            }
+
+            /// Provide the data of the account at `address`, used to derive the accounts that
+            /// depend on it.
+            pub fn account_data(mut self, address: Pubkey, data: Vec<u8>) -> Self {
+                self.__account_data.insert(address, data);
+                self
+            }
+
+            /// Get the addresses of the accounts whose data is needed to derive the accounts
+            /// that are not set.
+            ///
+            /// Providing the data of these accounts might make the data of other accounts
+            /// necessary, so this should be repeated until no addresses are returned.
+            #[allow(unused_variables)]
+            pub fn missing_account_data(&self) -> Vec<Pubkey> {
+                #missing_account_data
+            }
+
+            /// Get the accounts of the instruction, deriving the accounts that are not set.
+            pub fn accounts(&self) -> std::result::Result<super::accounts::#name, super::BuilderError> {
+                #(#locals)*
+                #(#derive_stmts)*
+                Ok(super::accounts::#name {
+                    #(#struct_fields,)*
+                })
+            }
+
+            /// Build the instruction, deriving the accounts that are not set.
+            pub fn instruction(
+                self,
+            ) -> std::result::Result<
+                anchor_lang::solana_program::instruction::Instruction,
+                super::BuilderError,
+            > {
+                let mut accounts =
+                    anchor_lang::ToAccountMetas::to_account_metas(&self.accounts()?, None);
+                accounts.extend(self.__remaining_accounts.iter().cloned());
+                Ok(anchor_lang::solana_program::instruction::Instruction {
+                    program_id: super::super::__ID,
+                    accounts,
+                    data: anchor_lang::InstructionData::data(&#args_expr),
+                })
+            }
+
+            #(#derive_fns)*
+        }
+
+        #resolve_impl
+    }
+}
+
+/// How to derive an account from the other accounts and the instruction arguments.
+struct Derivation {
+    /// Accounts the derivation depends on
+    deps: Vec<String>,
+    /// Accounts whose data the derivation depends on (a subset of `deps`)
+    data_deps: Vec<String>,
+    /// Whether the derivation depends on the instruction arguments
+    uses_args: bool,
+    /// Expression of the derived `Pubkey`
+    expr: proc_macro2::TokenStream,
+}
+
+/// Get the derivations of the derivable accounts of the instruction, in dependency order.
+///
+/// Only the top-level non-optional accounts are derived.
+fn get_derivations<'a>(
+    idl: &Idl,
+    ix: &'a IdlInstruction,
+) -> Vec<(&'a IdlInstructionAccount, Derivation)> {
+    let accounts = ix
+        .accounts
+        .iter()
+        .filter_map(|acc| match acc {
+            IdlInstructionAccountItem::Single(acc) if !acc.optional => Some(acc),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    let names = accounts
+        .iter()
+        .map(|acc| acc.name.as_str())
+        .collect::<HashSet<_>>();
+
+    let mut pending = accounts
+        .iter()
+        .filter_map(|acc| {
+            match (&acc.address, &acc.pda) {
+                (Some(address), _) => Some(Derivation {
+                    deps: vec![],
+                    data_deps: vec![],
+                    uses_args: false,
+                    expr: quote! { Pubkey::from_str_const(#address) },
+                }),
+                (None, Some(pda)) => gen_pda_derivation(idl, ix, pda, &names),
+                _ => None,
+            }
+            .or_else(|| gen_relation_derivation(idl, acc, &names))
+            .map(|derivation| (*acc, derivation))
+        })
+        .collect::<Vec<_>>();
+    let derivable = pending
+        .iter()
+        .map(|(acc, _)| acc.name.clone())
+        .collect::<HashSet<_>>();
+
+    // Dependencies must be derived (or set) before the dependent account
+    let mut derivations = vec![];
+    let mut derived = HashSet::new();
+    while let Some(index) = pending.iter().position(|(_, derivation)| {
+        derivation
+            .deps
+            .iter()
+            .all(|dep| derived.contains(dep) || !derivable.contains(dep))
+    }) {
+        let (acc, derivation) = pending.remove(index);
+        derived.insert(acc.name.clone());
+        derivations.push((acc, derivation));
+    }
+
+    // Accounts that depend on each other, e.g. a PDA of an account that is related to it, can
+    // only be derived from each other when one of them is set
+    derivations.extend(pending);
+    derivations
+}
+
+fn gen_pda_derivation(
+    idl: &Idl,
+    ix: &IdlInstruction,
+    pda: &IdlPda,
+    accounts: &HashSet<&str>,
+) -> Option<Derivation> {
+    let mut deps = vec![];
+    let mut data_deps = vec![];
+    let mut uses_args = false;
+    let mut seed_expr = |seed: &IdlSeed| match seed {
+        IdlSeed::Const(seed) => {
+            let value = syn::LitByteStr::new(&seed.value, proc_macro2::Span::call_site());
+            Some(quote! { &#value[..] })
+        }
+        IdlSeed::Arg(seed) => {
+            uses_args = true;
+            gen_arg_seed(idl, ix, &seed.path)
+        }
+        IdlSeed::Account(seed) => match seed.path.split_once('.') {
+            None => {
+                let name = accounts.get(seed.path.as_str())?;
+                let ident = format_ident!("{}", name);
+                deps.push(name.to_string());
+                Some(quote! { #ident.as_ref() })
+            }
+            Some((name, path)) => {
+                let name = accounts.get(name)?;
+                let ident = format_ident!("{}", name);
+                let value = gen_account_data_value(
+                    idl,
+                    seed.account.as_deref(),
+                    &ident,
+                    path,
+                    |ty, value| {
+                        let seed = gen_seed(ty, value)?;
+                        Some(quote! {{
+                            let __seed: &[u8] = #seed;
+                            __seed.to_vec()
+                        }})
+                    },
+                )?;
+                deps.push(name.to_string());
+                data_deps.push(name.to_string());
+                Some(quote! { &#value[..] })
+            }
+        },
+    };
+    let seeds = pda
+        .seeds
+        .iter()
+        .map(&mut seed_expr)
+        .collect::<Option<Vec<_>>>()?;
+    let program_id = match &pda.program {
+        None => quote! { super::super::__ID },
+        Some(IdlSeed::Const(seed)) => {
+            let value = <[u8; 32]>::try_from(seed.value.as_slice()).ok()?;
+            quote! { Pubkey::new_from_array([#(#value),*]) }
+        }
+        Some(IdlSeed::Account(seed)) => {
+            let name = accounts.get(seed.path.as_str())?;
+            deps.push(name.to_string());
+            let ident = format_ident!("{}", name);
+            quote! { #ident }
+        }
+        Some(IdlSeed::Arg(seed)) => {
+            uses_args = true;
+            if get_arg_type(idl, ix, &seed.path)? != &IdlType::Pubkey {
+                return None;
+            }
+            let path = gen_field_path(quote! { __args }, &seed.path);
+            quote! { #path }
+        }
+    };
+
+    deps.sort();
+    deps.dedup();
+    data_deps.sort();
+    data_deps.dedup();
+    Some(Derivation {
+        deps,
+        data_deps,
+        uses_args,
+        expr: quote! { Pubkey::find_program_address(&[#(#seeds),*], &#program_id).0 },
+    })
+}
+
+/// Derive the account from the data of the first related account that stores it, i.e. the
+/// accounts with a `has_one` constraint on it.
+fn gen_relation_derivation(
+    idl: &Idl,
+    acc: &IdlInstructionAccount,
+    accounts: &HashSet<&str>,
+) -> Option<Derivation> {
+    acc.relations.iter().find_map(|relation| {
+        let name = accounts.get(relation.as_str())?;
+        let ident = format_ident!("{}", name);
+        let expr = gen_account_data_value(idl, None, &ident, &acc.name, |ty, value| {
+            (*ty == IdlType::Pubkey).then_some(value)
+        })?;
+        Some(Derivation {
+            deps: vec![name.to_string()],
+            data_deps: vec![name.to_string()],
+            uses_args: false,
+            expr,
+        })
+    })
+}
+
+/// Generate an expression that deserializes the provided data of the account `ident` and
+/// converts the field at `path` with `convert`.
+///
+/// The account is deserialized as `account` if it's specified, otherwise as any of the program
+/// accounts that have the field.
+fn gen_account_data_value(
+    idl: &Idl,
+    account: Option<&str>,
+    ident: &syn::Ident,
+    path: &str,
+    convert: impl Fn(&IdlType, proc_macro2::TokenStream) -> Option<proc_macro2::TokenStream>,
+) -> Option<proc_macro2::TokenStream> {
+    let branches = idl
+        .accounts
+        .iter()
+        .filter(|acc| account.iter().all(|name| acc.name == *name))
+        .filter_map(|acc| {
+            let ty = get_account_field_type(idl, &acc.name, path)?;
+            let value = convert(ty, gen_field_path(quote! { __account }, path))?;
+            let name = format_ident!("{}", acc.name);
+            Some(quote! {
+                if let Ok(__account) =
+                    <super::super::accounts::#name as anchor_lang::AccountDeserialize>::try_deserialize(
+                        &mut __data.as_slice(),
+                    )
+                {
+                    #value
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    if branches.is_empty() {
+        return None;
+    }
+
+    Some(quote! {{
+        let __data = self.__account_data.get(&#ident)?;
+        #(#branches else)* {
+            return None;
+        }
+    }})
+}
+
+/// Generate the bytes of an instruction argument seed.
+fn gen_arg_seed(idl: &Idl, ix: &IdlInstruction, path: &str) -> Option<proc_macro2::TokenStream> {
+    gen_seed(
+        get_arg_type(idl, ix, path)?,
+        gen_field_path(quote! { __args }, path),
+    )
+}
+
+/// Generate the bytes of a seed of type `ty`, as the TypeScript client encodes them.
+fn gen_seed(ty: &IdlType, value: proc_macro2::TokenStream) -> Option<proc_macro2::TokenStream> {
+    Some(match ty {
+        IdlType::U8
+        | IdlType::I8
+        | IdlType::U16
+        | IdlType::I16
+        | IdlType::U32
+        | IdlType::I32
+        | IdlType::U64
+        | IdlType::I64
+        | IdlType::U128
+        | IdlType::I128 => quote! { &#value.to_le_bytes()[..] },
+        IdlType::Bool => quote! { &[#value as u8][..] },
+        IdlType::Pubkey => quote! { #value.as_ref() },
+        IdlType::String => quote! { #value.as_bytes() },
+        IdlType::Bytes => quote! { &#value[..] },
+        IdlType::Array(ty, _) | IdlType::Vec(ty) if **ty == IdlType::U8 => quote! { &#value[..] },
+        _ => return None,
+    })
+}
+
+fn gen_field_path(base: proc_macro2::TokenStream, path: &str) -> proc_macro2::TokenStream {
+    let fields = path.split('.').map(|field| format_ident!("{}", field));
+    quote! { #base #(.#fields)* }
+}
+
+/// Get the type of the instruction argument (or the field of an argument) at `path`.
+fn get_arg_type<'a>(idl: &'a Idl, ix: &'a IdlInstruction, path: &str) -> Option<&'a IdlType> {
+    let mut fields = path.split('.');
+    let name = fields.next()?;
+    let ty = &ix.args.iter().find(|arg| arg.name == name)?.ty;
+    get_nested_field_type(idl, ty, fields)
+}
+
+/// Get the type of the field at `path` of the account data.
+fn get_account_field_type<'a>(idl: &'a Idl, account: &str, path: &str) -> Option<&'a IdlType> {
+    let mut fields = path.split('.');
+    let ty = get_struct_field_type(idl, account, fields.next()?)?;
+    get_nested_field_type(idl, ty, fields)
+}
+
+fn get_nested_field_type<'a, 'b>(
+    idl: &'a Idl,
+    mut ty: &'a IdlType,
+    fields: impl Iterator<Item = &'b str>,
+) -> Option<&'a IdlType> {
+    for field in fields {
+        let IdlType::Defined { name, generics } = ty else {
+            return None;
+        };
+        if !generics.is_empty() {
+            return None;
+        }
+        ty = get_struct_field_type(idl, name, field)?;
+    }
+    Some(ty)
+}
+
+fn get_struct_field_type<'a>(idl: &'a Idl, name: &str, field: &str) -> Option<&'a IdlType> {
+    let ty_def = idl.types.iter().find(|ty| ty.name == name)?;
+    if !ty_def.generics.is_empty() {
+        return None;
+    }
+    match &ty_def.ty {
+        IdlTypeDefTy::Struct {
+            fields: Some(IdlDefinedFields::Named(fields)),
+        } => fields.iter().find(|f| f.name == field).map(|f| &f.ty),
+        _ => None,
+    }
+}
@@ -1,8 +1,16 @@
-use {super::common::gen_accounts_common, anchor_lang_idl::types::Idl, quote::quote};
+use {
+    super::{builders::gen_builders_mod, common::gen_accounts_common},
+    anchor_lang_idl::types::Idl,
+    heck::SnakeCase,
+    quote::{format_ident, quote},
+};
 
-pub fn gen_client_mod(idl: &Idl) -> proc_macro2::TokenStream {
+pub fn gen_client_mod(idl: &Idl, client: Option<&syn::Path>) -> proc_macro2::TokenStream {
     let client_args_mod = gen_client_args_mod();
     let client_accounts_mod = gen_client_accounts_mod(idl);
+    let builder_error = gen_builder_error();
+    let builders_mod = gen_builders_mod(idl, client);
+    let fetch_fns = client.map(|client| gen_fetch_fns(idl, client));
 
     quote! {
         /// Off-chain client helpers.
@@ -11,6 +19,11 @@ pub fn gen_client_mod(idl: &Idl) -> proc_macro2::TokenStream {
 
             #client_args_mod
             #client_accounts_mod
+
+            #builder_error
+            #builders_mod
+
+            #fetch_fns
         }
     }
 }
@@ -27,3 +40,42 @@ fn gen_client_args_mod() -> proc_macro2::TokenStream {
 fn gen_client_accounts_mod(idl: &Idl) -> proc_macro2::TokenStream {
     gen_accounts_common(idl, "client")
 }
+
+fn gen_builder_error() -> proc_macro2::TokenStream {
+    quote! {
+        /// Error returned by the instruction [`builders`].
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        pub enum BuilderError {
+            /// The account was not set and could not be derived
+            MissingAccount(&'static str),
+            /// The instruction arguments were not set
+            MissingArgs,
+        }
+
+        impl std::fmt::Display for BuilderError {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                match self {
+                    Self::MissingAccount(name) => write!(f, "Missing account `{name}`"),
+                    Self::MissingArgs => write!(f, "Missing instruction arguments"),
+                }
+            }
+        }
+
+        impl std::error::Error for BuilderError {}
+    }
+}
+
+/// Generate `fetch_<account>` functions that fetch and deserialize program accounts with the
+/// `anchor_client` crate at `client`.
+fn gen_fetch_fns(idl: &Idl, client: &syn::Path) -> proc_macro2::TokenStream {
+    let fetch_fns = idl.accounts.iter().map(|acc| {
+        let name = format_ident!("{}", acc.name);
+        let fn_name = format_ident!("fetch_{}", acc.name.to_snake_case());
+        let doc = format!("Fetch and deserialize a `{}` account.", acc.name);
+        quote! {
+            #client::__fetch_account_fn!(#[doc = #doc] #fn_name, super::accounts::#name);
+        }
+    });
+
+    quote! { #(#fetch_fns)* }
+}
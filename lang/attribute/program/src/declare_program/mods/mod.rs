@@ -1,4 +1,5 @@
 pub mod accounts;
+pub mod builders;
 pub mod client;
 pub mod constants;
 pub mod cpi;
@@ -5,10 +5,10 @@ use {
     quote::{format_ident, quote},
 };
 
-pub fn gen_parsers_mod(idl: &Idl) -> proc_macro2::TokenStream {
+pub fn gen_parsers_mod(idl: &Idl, client: Option<&syn::Path>) -> proc_macro2::TokenStream {
     let account = gen_account(idl);
     let event = gen_event(idl);
-    let instruction = gen_instruction(idl);
+    let instruction = gen_instruction(idl, client);
 
     quote! {
         /// Program parsers.
@@ -115,7 +115,7 @@ fn gen_event(idl: &Idl) -> proc_macro2::TokenStream {
     }
 }
 
-fn gen_instruction(idl: &Idl) -> proc_macro2::TokenStream {
+fn gen_instruction(idl: &Idl, client: Option<&syn::Path>) -> proc_macro2::TokenStream {
     let variants = idl
         .instructions
         .iter()
@@ -145,10 +145,10 @@ fn gen_instruction(idl: &Idl) -> proc_macro2::TokenStream {
                                 if acc.pubkey == #program_id {
                                     None
                                 } else {
-                                    if acc.is_signer != #signer {
+                                    if check_privileges && acc.is_signer != #signer {
                                         return Err(ProgramError::InvalidAccountData.into());
                                     }
-                                    if acc.is_writable != #writable {
+                                    if check_privileges && acc.is_writable != #writable {
                                         return Err(ProgramError::InvalidAccountData.into());
                                     }
                                     Some(acc.pubkey)
@@ -159,10 +159,10 @@ fn gen_instruction(idl: &Idl) -> proc_macro2::TokenStream {
                         quote! {
                             #name: {
                                 let acc = accs.next().ok_or_else(|| ProgramError::NotEnoughAccountKeys)?;
-                                if acc.is_signer != #signer {
+                                if check_privileges && acc.is_signer != #signer {
                                     return Err(ProgramError::InvalidAccountData.into());
                                 }
-                                if acc.is_writable != #writable {
+                                if check_privileges && acc.is_writable != #writable {
                                     return Err(ProgramError::InvalidAccountData.into());
                                 }
 
@@ -207,8 +207,64 @@ fn gen_instruction(idl: &Idl) -> proc_macro2::TokenStream {
             .collect::<Vec<_>>()
     };
 
+    let name_arms = idl.instructions.iter().map(|ix| {
+        let variant = format_ident!("{}", ix.name.to_camel_case());
+        let name = &ix.name;
+        quote! { Self::#variant { .. } => #name }
+    });
+    let account_names_arms = {
+        fn get_account_names(
+            prefix: &str,
+            ix_accs: &[IdlInstructionAccountItem],
+            names: &mut Vec<String>,
+        ) {
+            for acc in ix_accs {
+                match acc {
+                    IdlInstructionAccountItem::Single(acc) => {
+                        names.push(format!("{prefix}{}", acc.name));
+                    }
+                    IdlInstructionAccountItem::Composite(accs) => {
+                        let prefix = format!("{prefix}{}.", accs.name);
+                        get_account_names(&prefix, &accs.accounts, names);
+                    }
+                }
+            }
+        }
+
+        idl.instructions
+            .iter()
+            .map(|ix| {
+                let variant = format_ident!("{}", ix.name.to_camel_case());
+                let mut names = vec![];
+                get_account_names("", &ix.accounts, &mut names);
+                quote! { Self::#variant { .. } => &[#(#names),*] }
+            })
+            .collect::<Vec<_>>()
+    };
+
     let solana_instruction = quote!(anchor_lang::solana_program::instruction::Instruction);
     let program_id = get_canonical_program_id();
+    let parse_instruction_impl = client.map(|client| {
+        quote! {
+            impl #client::ParseInstruction for Instruction {
+                fn program_id() -> Pubkey {
+                    #program_id
+                }
+
+                fn parse_unchecked(ix: &#solana_instruction) -> Result<Self> {
+                    Self::parse_unchecked(ix)
+                }
+
+                fn name(&self) -> &'static str {
+                    Self::name(self)
+                }
+
+                fn account_names(&self) -> &'static [&'static str] {
+                    Self::account_names(self)
+                }
+            }
+        }
+    });
 
     quote! {
         /// An enum that includes all instructions of the declared program.
@@ -238,12 +294,35 @@ fn gen_instruction(idl: &Idl) -> proc_macro2::TokenStream {
             pub fn parse(ix: &#solana_instruction) -> Result<Self> {
                 Self::try_from(ix)
             }
-        }
 
-        impl TryFrom<&#solana_instruction> for Instruction {
-            type Error = anchor_lang::error::Error;
+            /// Same as [`Self::parse`], but without checking the signer and writable attributes
+            /// of the accounts.
+            ///
+            /// This is useful for instructions compiled into a transaction, whose accounts have
+            /// the attributes of the transaction rather than the instruction's own, and for inner
+            /// instructions, whose attributes are not part of the transaction status.
+            pub fn parse_unchecked(ix: &#solana_instruction) -> Result<Self> {
+                Self::parse_internal(ix, false)
+            }
 
-            fn try_from(ix: &#solana_instruction) -> Result<Self> {
+            /// Name of the instruction in the IDL.
+            pub fn name(&self) -> &'static str {
+                match *self {
+                    #(#name_arms,)*
+                }
+            }
+
+            /// Names of the accounts of the instruction, in order.
+            ///
+            /// The accounts of composite accounts are prefixed with the name of the composite
+            /// field, e.g. `update.authority`.
+            pub fn account_names(&self) -> &'static [&'static str] {
+                match *self {
+                    #(#account_names_arms,)*
+                }
+            }
+
+            fn parse_internal(ix: &#solana_instruction, check_privileges: bool) -> Result<Self> {
                 if ix.program_id != #program_id {
                     return Err(ProgramError::IncorrectProgramId.into())
                 }
@@ -252,5 +331,15 @@ fn gen_instruction(idl: &Idl) -> proc_macro2::TokenStream {
                 Err(ProgramError::InvalidInstructionData.into())
             }
         }
+
+        impl TryFrom<&#solana_instruction> for Instruction {
+            type Error = anchor_lang::error::Error;
+
+            fn try_from(ix: &#solana_instruction) -> Result<Self> {
+                Self::parse_internal(ix, true)
+            }
+        }
+
+        #parse_instruction_impl
     }
 }
@@ -46,6 +46,27 @@ pub fn program(
 /// This generates a module named `program_name` that can be used to interact with the program
 /// without having to add the program's crate as a dependency.
 ///
+/// The IDL can also be loaded from a path relative to the crate's manifest directory:
+///
+/// ```rs
+/// declare_program!(program_name, path = "../../target/idl/program_name.json");
+/// ```
+///
+/// or from a Cargo dependency that exports its IDL, which allows sharing the interface of a
+/// program without copying its IDL around:
+///
+/// ```rs
+/// declare_program!(program_name, crate = "program-name-interface");
+/// ```
+///
+/// The dependency exports its IDL by setting the path of the IDL, relative to its manifest
+/// directory, in its `Cargo.toml`:
+///
+/// ```toml
+/// [package.metadata.anchor]
+/// idl = "idl.json"
+/// ```
+///
 /// Both on-chain and off-chain usage is supported.
 ///
 /// Use `cargo doc --open` to see the generated modules and their documentation.
@@ -68,6 +89,12 @@ pub fn program(
 /// use anchor_client::anchor_lang;
 /// ```
 ///
+/// Alternatively, use `anchor_client::declare_program!`, which doesn't have this requirement and
+/// also generates `fetch_<account>` functions in the `client` module.
+///
+/// The `client::builders` module contains instruction builders that derive PDAs and accounts
+/// with constant addresses from the IDL.
+///
 /// # Example
 ///
 /// A full on-chain CPI usage example can be found [here].
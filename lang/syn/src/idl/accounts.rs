@@ -1,5 +1,8 @@
 use {
-    super::common::{get_idl_module_path, get_no_docs},
+    super::{
+        common::{get_idl_module_path, get_no_docs},
+        external::get_mapped_type,
+    },
     crate::{AccountField, AccountsStruct, ConstraintSeedsGroup, Field, InitKind, Ty},
     proc_macro2::TokenStream,
     quote::{quote, ToTokens},
@@ -78,6 +81,13 @@ pub fn gen_idl_build_impl_accounts_struct(accounts: &AccountsStruct) -> TokenStr
                             }
                             _ => None,
                         };
+                        // Definitions of the mapped external types are provided by the IDL builder
+                        let defined = defined.filter(|(path, _)| {
+                            let source_path = proc_macro2::Span::call_site()
+                                .local_file()
+                                .unwrap_or_default();
+                            get_mapped_type(&path.path, source_path).is_none()
+                        });
 
                         Ok((
                             quote! {
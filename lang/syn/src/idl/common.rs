@@ -23,6 +23,19 @@ pub fn get_no_docs() -> bool {
         .unwrap_or_default()
 }
 
+/// Get the paths of the external types whose IDL definitions are provided by the IDL builder
+/// instead of the `IdlBuild` trait, e.g. `pyth_sdk::Price`.
+pub fn get_external_type_paths() -> Vec<String> {
+    option_env!("ANCHOR_IDL_BUILD_EXTERNAL_TYPES")
+        .map(|val| {
+            val.split(',')
+                .filter(|path| !path.is_empty())
+                .map(ToOwned::to_owned)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
 pub fn get_program_path() -> Result<PathBuf> {
     std::env::var("ANCHOR_IDL_BUILD_PROGRAM_PATH")
         .map(PathBuf::from)
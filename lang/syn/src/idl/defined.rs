@@ -572,7 +572,10 @@ pub fn gen_idl_type(
             // Handle type aliases and external types
             {
                 use {
-                    super::{common::find_path, external::get_external_type},
+                    super::{
+                        common::find_path,
+                        external::{get_external_type, get_mapped_type},
+                    },
                     crate::parser::context::CrateContext,
                     quote::ToTokens,
                     std::{
@@ -716,6 +719,19 @@ pub fn gen_idl_type(
 
                     // Handle external types
                     if is_external {
+                        // Definition is provided by the IDL builder
+                        if let Some(name) = get_mapped_type(&path.path, &source_path) {
+                            return Ok((
+                                quote! {
+                                    #idl::IdlType::Defined {
+                                        name: #name.into(),
+                                        generics: vec![],
+                                    }
+                                },
+                                vec![],
+                            ));
+                        }
+
                         if let Ok(Some(ty)) = get_external_type(&name, source_path) {
                             return gen_idl_type(&ty, generic_params);
                         }
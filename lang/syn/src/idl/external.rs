@@ -1,5 +1,5 @@
 use {
-    super::common::{find_path, get_program_path},
+    super::common::{find_path, get_external_type_paths, get_program_path},
     crate::{parser::context::CrateContext, AnyResult as Result},
     cargo_toml::Manifest,
     quote::ToTokens,
@@ -24,6 +24,36 @@ pub fn get_external_type(name: &str, path: impl AsRef<Path>) -> Result<Option<sy
     recursively_find_type(name, &use_path, &registry_path, &lock_file)
 }
 
+/// Get the path of the external type the given path refers to if its IDL definition is provided
+/// by the IDL builder, e.g. `pyth_sdk::Price` for `Price` when there is `use pyth_sdk::Price`.
+pub fn get_mapped_type(path: &syn::Path, source_path: impl AsRef<Path>) -> Option<String> {
+    let mapped = get_external_type_paths();
+    if mapped.is_empty() {
+        return None;
+    }
+
+    let written = path
+        .segments
+        .iter()
+        .map(|seg| seg.ident.to_string())
+        .collect::<Vec<_>>()
+        .join("::");
+    let full_path = match path.get_ident() {
+        Some(ident) => get_uses(source_path)
+            .ok()
+            .and_then(|uses| {
+                uses.into_iter()
+                    .find(|u| u.rsplit("::").next() == Some(&ident.to_string()))
+            })
+            .unwrap_or(written),
+        None => written,
+    };
+
+    mapped
+        .into_iter()
+        .find(|mapped| *mapped == full_path || mapped.ends_with(&format!("::{full_path}")))
+}
+
 fn recursively_find_type(
     defined_name: &str,
     use_path: &str,
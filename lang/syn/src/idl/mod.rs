@@ -1,15 +1,27 @@
 #![allow(dead_code)]
 
+#[cfg(feature = "idl-build")]
 mod accounts;
+#[cfg(feature = "idl-build")]
 mod address;
+#[cfg(feature = "idl-build")]
 mod common;
+#[cfg(feature = "idl-build")]
 mod constant;
+#[cfg(feature = "idl-build")]
 mod defined;
+#[cfg(feature = "idl-build")]
 mod error;
+#[cfg(feature = "idl-build")]
 mod event;
+#[cfg(feature = "idl-build")]
 mod external;
+#[cfg(feature = "idl-parse")]
+pub mod parse;
+#[cfg(feature = "idl-build")]
 mod program;
 
+#[cfg(feature = "idl-build")]
 pub use {
     accounts::gen_idl_build_impl_accounts_struct,
     address::gen_idl_print_fn_address,
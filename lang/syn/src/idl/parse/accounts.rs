@@ -0,0 +1,455 @@
+use {
+    super::{
+        defined::AccountArgs, find_attr, parse_optional_args, DefItem, Diagnostic, Parser, Scope,
+        KNOWN_PROGRAMS,
+    },
+    crate::{
+        codegen::program::common::sighash, parser, AccountField, AccountsStruct, Field, InitKind,
+        SeedsExpr, SysvarTy, Ty,
+    },
+    anchor_lang_idl_spec::{
+        IdlAccount, IdlInstructionAccount, IdlInstructionAccountItem, IdlInstructionAccounts,
+        IdlPda, IdlSeed, IdlSeedAccount, IdlSeedArg, IdlSeedConst,
+    },
+    syn::parse_quote,
+};
+
+const SYSVARS: &[(SysvarTy, &str)] = &[
+    (
+        SysvarTy::Clock,
+        "SysvarC1ock11111111111111111111111111111111",
+    ),
+    (
+        SysvarTy::Rent,
+        "SysvarRent111111111111111111111111111111111",
+    ),
+    (
+        SysvarTy::EpochSchedule,
+        "SysvarEpochSchedu1e111111111111111111111111",
+    ),
+    (
+        SysvarTy::Fees,
+        "SysvarFees111111111111111111111111111111111",
+    ),
+    (
+        SysvarTy::RecentBlockhashes,
+        "SysvarRecentB1ockHashes11111111111111111111",
+    ),
+    (
+        SysvarTy::SlotHashes,
+        "SysvarS1otHashes111111111111111111111111111",
+    ),
+    (
+        SysvarTy::SlotHistory,
+        "SysvarS1otHistory11111111111111111111111111",
+    ),
+    (
+        SysvarTy::StakeHistory,
+        "SysvarStakeHistory1111111111111111111111111",
+    ),
+    (
+        SysvarTy::Instructions,
+        "Sysvar1nstructions1111111111111111111111111",
+    ),
+    (
+        SysvarTy::Rewards,
+        "SysvarRewards111111111111111111111111111111",
+    ),
+];
+
+impl<'a> Parser<'a> {
+    /// Get the accounts of the `#[derive(Accounts)]` struct at the given path.
+    pub(super) fn instruction_accounts(
+        &mut self,
+        path: &syn::Path,
+        scope: &Scope<'a>,
+    ) -> Result<Vec<IdlInstructionAccountItem>, Diagnostic> {
+        let def = self
+            .resolve(
+                path,
+                scope,
+                |item| matches!(item, DefItem::Struct(item) if derives(&item.attrs, "Accounts")),
+            )?
+            .ok_or_else(|| {
+                scope.error(
+                    path,
+                    format!(
+                        "Accounts struct `{}` not found in the program crate",
+                        super::path_to_string(path)
+                    ),
+                )
+            })?;
+        let DefItem::Struct(item) = def.item else {
+            return Ok(vec![]);
+        };
+        let scope = Scope::of(def);
+        let accounts =
+            parser::accounts::parse(item).map_err(|e| scope.error(&e.span(), e.to_string()))?;
+
+        let mut items = vec![];
+        for field in &accounts.fields {
+            let item = match field {
+                AccountField::Field(field) => IdlInstructionAccountItem::Single(
+                    self.instruction_account(field, &accounts, &scope)?,
+                ),
+                AccountField::CompositeField(field) => {
+                    let syn::Type::Path(ty) = &field.raw_field.ty else {
+                        return Err(
+                            scope.error(&field.raw_field.ty, "Composite field type must be a path")
+                        );
+                    };
+                    IdlInstructionAccountItem::Composite(IdlInstructionAccounts {
+                        name: field.ident.to_string(),
+                        accounts: self.instruction_accounts(&ty.path, &scope)?,
+                    })
+                }
+            };
+            items.push(item);
+        }
+
+        // `#[event_cpi]` adds the accounts during macro expansion, which is only done by the
+        // parser when the `event-cpi` feature is enabled
+        let has_event_cpi_accounts = accounts
+            .fields
+            .iter()
+            .any(|field| field.ident() == "event_authority");
+        if find_attr(&item.attrs, "event_cpi").is_some() && !has_event_cpi_accounts {
+            for name in ["event_authority", "program"] {
+                items.push(IdlInstructionAccountItem::Single(IdlInstructionAccount {
+                    name: name.into(),
+                    docs: vec![],
+                    writable: false,
+                    signer: false,
+                    optional: false,
+                    address: None,
+                    pda: None,
+                    relations: vec![],
+                }));
+            }
+        }
+
+        Ok(items)
+    }
+
+    fn instruction_account(
+        &mut self,
+        field: &Field,
+        accounts: &AccountsStruct,
+        scope: &Scope<'a>,
+    ) -> Result<IdlInstructionAccount, Diagnostic> {
+        match &field.ty {
+            Ty::Account(ty) => self.account_type(&ty.account_type_path.path, scope)?,
+            Ty::LazyAccount(ty) => self.account_type(&ty.account_type_path.path, scope)?,
+            Ty::AccountLoader(ty) => self.account_type(&ty.account_type_path.path, scope)?,
+            Ty::InterfaceAccount(ty) => self.account_type(&ty.account_type_path.path, scope)?,
+            _ => {}
+        }
+
+        let (address, pda, relations) = if self.resolution {
+            (
+                self.address_of(field, scope),
+                self.pda(field, accounts, scope),
+                relations(field, accounts),
+            )
+        } else {
+            (None, None, vec![])
+        };
+
+        Ok(IdlInstructionAccount {
+            name: field.ident.to_string(),
+            docs: match &field.docs {
+                Some(docs) if !self.no_docs => docs.clone(),
+                _ => vec![],
+            },
+            writable: field.constraints.is_mutable(),
+            signer: matches!(field.ty, Ty::Signer) || field.constraints.is_signer(),
+            optional: field.is_optional,
+            address,
+            pda,
+            relations,
+        })
+    }
+
+    /// Generate the definition of an account type, adding it to the IDL accounts if it's an
+    /// `#[account]` of the program.
+    ///
+    /// Account types from other crates, e.g. `TokenAccount`, are skipped.
+    fn account_type(&mut self, path: &syn::Path, scope: &Scope<'a>) -> Result<(), Diagnostic> {
+        let Some(def) = self.resolve(path, scope, |item| matches!(item, DefItem::Struct(_)))?
+        else {
+            return Ok(());
+        };
+        self.type_def(def)?;
+
+        let Some(attr) = find_attr(def.item.attrs(), "account") else {
+            return Ok(());
+        };
+        let def_scope = Scope::of(def);
+        let args = parse_optional_args::<AccountArgs>(attr)
+            .map_err(|e| def_scope.error(&e.span(), e.to_string()))?
+            .unwrap_or_default();
+        if args.namespace.is_some() {
+            return Ok(());
+        }
+
+        let discriminator = match &args.discriminator {
+            Some(discriminator) => self.eval_discriminator(discriminator, &def_scope)?,
+            None => sighash("account", &def.item.ident().to_string()).into(),
+        };
+        let name = self.full_name(def);
+        self.accounts.insert(
+            name.clone(),
+            IdlAccount {
+                name,
+                discriminator,
+            },
+        );
+
+        Ok(())
+    }
+
+    fn address_of(&mut self, field: &Field, scope: &Scope<'a>) -> Option<String> {
+        match &field.ty {
+            Ty::Program(ty) => {
+                let name = ty.account_type_path.path.segments.last()?.ident.to_string();
+                if name == "__SolanaProgramUnitType" {
+                    return None;
+                }
+
+                let address = KNOWN_PROGRAMS
+                    .iter()
+                    .find(|(ty, ..)| *ty == name)
+                    .map(|(.., address)| (*address).to_owned());
+                if address.is_none() {
+                    self.warn(scope.error(
+                        &field.ident,
+                        format!(
+                            "Address of program `{name}` cannot be resolved statically; the \
+                             `{}` account address is omitted",
+                            field.ident
+                        ),
+                    ));
+                }
+                address
+            }
+            Ty::Sysvar(ty) => SYSVARS
+                .iter()
+                .find(|(sysvar, _)| sysvar == ty)
+                .map(|(_, address)| (*address).to_owned()),
+            _ => {
+                // Same filter as the `idl-build` feature, constants (e.g. `crate::ID`) and
+                // stand-alone function calls without arguments (e.g. `crate::id()`)
+                let address = field
+                    .constraints
+                    .address
+                    .as_ref()
+                    .map(|constraint| &constraint.address)
+                    .filter(|address| match address {
+                        syn::Expr::Path(expr) => expr.path.segments.last().is_some_and(|seg| {
+                            seg.ident
+                                .to_string()
+                                .chars()
+                                .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_')
+                        }),
+                        syn::Expr::Call(expr) => expr.args.is_empty(),
+                        _ => false,
+                    })?;
+                let resolved = self.eval_pubkey(address, scope);
+                if resolved.is_none() {
+                    self.warn(scope.error(
+                        address,
+                        format!(
+                            "Address `{}` cannot be resolved statically; the `{}` account \
+                             address is omitted",
+                            parser::tts_to_string(address),
+                            field.ident
+                        ),
+                    ));
+                }
+                resolved
+            }
+        }
+    }
+
+    fn pda(
+        &mut self,
+        field: &Field,
+        accounts: &AccountsStruct,
+        scope: &Scope<'a>,
+    ) -> Option<IdlPda> {
+        if let Some(group) = &field.constraints.seeds {
+            let pda = match &group.seeds {
+                // Seeds from a function call, e.g. `seeds = pda_seeds(key)`
+                SeedsExpr::Expr(_) => None,
+                SeedsExpr::List(list) => list
+                    .iter()
+                    .map(|seed| self.seed(seed, accounts, scope))
+                    .collect::<Option<Vec<_>>>()
+                    .and_then(|seeds| {
+                        let program = match &group.program_seed {
+                            Some(program) => Some(self.seed(program, accounts, scope)?),
+                            None => None,
+                        };
+                        Some(IdlPda { seeds, program })
+                    }),
+            };
+            if pda.is_none() {
+                self.warn(scope.error(
+                    &field.ident,
+                    format!(
+                        "Seeds of `{}` cannot be resolved statically; PDA information is omitted",
+                        field.ident
+                    ),
+                ));
+            }
+            return pda;
+        }
+
+        // Associated token
+        let (wallet, mint, token_program) = field
+            .constraints
+            .init
+            .as_ref()
+            .and_then(|init| match &init.kind {
+                InitKind::AssociatedToken {
+                    owner,
+                    mint,
+                    token_program,
+                } => Some((owner, mint, token_program)),
+                _ => None,
+            })
+            .or_else(|| {
+                field
+                    .constraints
+                    .associated_token
+                    .as_ref()
+                    .map(|ata| (&ata.wallet, &ata.mint, &ata.token_program))
+            })?;
+
+        // ATA constraints have implicit `.key()` call
+        let ata_seed = |expr: &syn::Expr| -> Option<IdlSeed> {
+            self.seed(&parse_quote!(#expr.key().as_ref()), accounts, scope)
+        };
+        let wallet = ata_seed(wallet)?;
+        let mint = ata_seed(mint)?;
+        let token_program = match token_program {
+            Some(token_program) => ata_seed(token_program)?,
+            None => IdlSeed::Const(IdlSeedConst {
+                value: known_program_bytes("Token")?,
+            }),
+        };
+
+        Some(IdlPda {
+            seeds: vec![wallet, token_program, mint],
+            program: Some(IdlSeed::Const(IdlSeedConst {
+                value: known_program_bytes("AssociatedToken")?,
+            })),
+        })
+    }
+
+    /// Parse a seed, which must be an instruction argument, an account key or field, or a
+    /// constant, same as the `idl-build` feature.
+    fn seed(
+        &self,
+        seed: &syn::Expr,
+        accounts: &AccountsStruct,
+        scope: &Scope<'a>,
+    ) -> Option<IdlSeed> {
+        if let Some((root, mut subfields)) = seed_path(seed) {
+            // `signer.key` is the key of the account, the same as `signer.key()`
+            if subfields == ["key"] {
+                subfields.clear();
+            }
+            let path = std::iter::once(root.clone())
+                .chain(subfields.iter().cloned())
+                .collect::<Vec<_>>()
+                .join(".");
+            let is_arg = accounts
+                .instruction_args()
+                .is_some_and(|args| args.contains_key(&root));
+            if is_arg {
+                return Some(IdlSeed::Arg(IdlSeedArg { path }));
+            }
+
+            if let Some(field) = accounts.fields.iter().find(|field| *field.ident() == root) {
+                return Some(IdlSeed::Account(IdlSeedAccount {
+                    path,
+                    account: field.ty_name().filter(|_| !subfields.is_empty()),
+                }));
+            }
+        }
+
+        self.eval_bytes(seed, scope)
+            .map(|value| IdlSeed::Const(IdlSeedConst { value }))
+    }
+}
+
+/// Get the root variable and the accessed fields of a seed, e.g. `("my_account", ["data"])` for
+/// `my_account.data.key().as_ref()`.
+fn seed_path(seed: &syn::Expr) -> Option<(String, Vec<String>)> {
+    match seed {
+        syn::Expr::Reference(expr) => seed_path(&expr.expr),
+        syn::Expr::Paren(expr) => seed_path(&expr.expr),
+        syn::Expr::MethodCall(call) if call.args.is_empty() => seed_path(&call.receiver),
+        syn::Expr::Field(expr) => {
+            let syn::Member::Named(member) = &expr.member else {
+                return None;
+            };
+            let (root, mut subfields) = seed_path(&expr.base)?;
+            subfields.push(member.to_string());
+            Some((root, subfields))
+        }
+        syn::Expr::Path(path) => path
+            .path
+            .get_ident()
+            .map(|ident| (ident.to_string(), vec![])),
+        _ => None,
+    }
+}
+
+fn relations(field: &Field, accounts: &AccountsStruct) -> Vec<String> {
+    accounts
+        .fields
+        .iter()
+        .filter_map(|af| match af {
+            AccountField::Field(f) => Some(f),
+            _ => None,
+        })
+        .filter(|f| {
+            f.constraints.has_one.iter().any(|c| match &c.join_target {
+                syn::Expr::Path(path) => path
+                    .path
+                    .segments
+                    .first()
+                    .is_some_and(|seg| seg.ident == field.ident),
+                _ => false,
+            })
+        })
+        .map(|f| f.ident.to_string())
+        .collect()
+}
+
+fn derives(attrs: &[syn::Attribute], name: &str) -> bool {
+    attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("derive"))
+        .any(|attr| {
+            let mut found = false;
+            let _ = attr.parse_nested_meta(|meta| {
+                found |= meta
+                    .path
+                    .segments
+                    .last()
+                    .is_some_and(|seg| seg.ident == name);
+                Ok(())
+            });
+            found
+        })
+}
+
+fn known_program_bytes(name: &str) -> Option<Vec<u8>> {
+    KNOWN_PROGRAMS
+        .iter()
+        .find(|(ty, ..)| *ty == name)
+        .and_then(|(.., address)| bs58::decode(address).into_vec().ok())
+}
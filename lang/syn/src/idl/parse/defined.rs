@@ -0,0 +1,558 @@
+use {
+    super::{find_attr, path_to_string, Def, DefItem, Diagnostic, Parser, Scope},
+    crate::{parser, Overrides},
+    anchor_lang_idl_spec::{
+        IdlArrayLen, IdlDefinedFields, IdlEnumVariant, IdlField, IdlGenericArg, IdlRepr,
+        IdlReprModifier, IdlSerialization, IdlType, IdlTypeDef, IdlTypeDefGeneric, IdlTypeDefTy,
+    },
+    proc_macro2::{Group, TokenStream, TokenTree},
+    quote::ToTokens,
+    std::collections::HashMap,
+    syn::parse::{Parse, ParseStream},
+};
+
+/// Type aliases from the Solana crates that are commonly used in programs.
+const BUILTIN_ALIASES: &[(&str, IdlType)] = &[
+    ("Epoch", IdlType::U64),
+    ("Slot", IdlType::U64),
+    ("UnixTimestamp", IdlType::I64),
+];
+
+impl<'a> Parser<'a> {
+    /// Get the IDL type of the given type, generating the definitions of the types it references.
+    pub(super) fn idl_type(
+        &mut self,
+        ty: &syn::Type,
+        scope: &Scope<'a>,
+    ) -> Result<IdlType, Diagnostic> {
+        match ty {
+            syn::Type::Path(path) if path.qself.is_none() => self.idl_type_path(&path.path, scope),
+            syn::Type::Array(arr) => Ok(IdlType::Array(
+                Box::new(self.idl_type(&arr.elem, scope)?),
+                self.array_len(&arr.len, scope)?,
+            )),
+            syn::Type::Reference(reference) => match reference.elem.as_ref() {
+                syn::Type::Slice(slice) if is_u8(&slice.elem) => Ok(IdlType::Bytes),
+                elem => self.idl_type(elem, scope),
+            },
+            syn::Type::Paren(ty) => self.idl_type(&ty.elem, scope),
+            syn::Type::Group(ty) => self.idl_type(&ty.elem, scope),
+            _ => Err(scope.error(
+                ty,
+                format!("Unsupported type `{}`", parser::tts_to_string(ty)),
+            )),
+        }
+    }
+
+    fn idl_type_path(
+        &mut self,
+        path: &syn::Path,
+        scope: &Scope<'a>,
+    ) -> Result<IdlType, Diagnostic> {
+        let segment = path
+            .segments
+            .last()
+            .ok_or_else(|| scope.error(path, "Expected a non-empty type path"))?;
+        let name = segment.ident.to_string();
+        if path.segments.len() == 1 && scope.generics.contains(&name) {
+            return Ok(IdlType::Generic(name));
+        }
+
+        let ty = match name.as_str() {
+            "bool" => IdlType::Bool,
+            "u8" => IdlType::U8,
+            "i8" => IdlType::I8,
+            "u16" => IdlType::U16,
+            "i16" => IdlType::I16,
+            "u32" => IdlType::U32,
+            "i32" => IdlType::I32,
+            "f32" => IdlType::F32,
+            "u64" => IdlType::U64,
+            "i64" => IdlType::I64,
+            "f64" => IdlType::F64,
+            "u128" => IdlType::U128,
+            "i128" => IdlType::I128,
+            "String" | "str" => IdlType::String,
+            "Pubkey" => IdlType::Pubkey,
+            "Option" | "Vec" | "Box" => {
+                let inner = get_first_type_arg(segment).ok_or_else(|| {
+                    scope.error(segment, format!("Expected a type argument for `{name}`"))
+                })?;
+                match name.as_str() {
+                    "Option" => IdlType::Option(Box::new(self.idl_type(inner, scope)?)),
+                    "Vec" if is_u8(inner) => IdlType::Bytes,
+                    "Vec" => IdlType::Vec(Box::new(self.idl_type(inner, scope)?)),
+                    _ => return self.idl_type(inner, scope),
+                }
+            }
+            _ => return self.idl_type_defined(path, segment, scope),
+        };
+
+        Ok(ty)
+    }
+
+    fn idl_type_defined(
+        &mut self,
+        path: &syn::Path,
+        segment: &syn::PathSegment,
+        scope: &Scope<'a>,
+    ) -> Result<IdlType, Diagnostic> {
+        let def = self.resolve(path, scope, |item| {
+            matches!(
+                item,
+                DefItem::Struct(_) | DefItem::Enum(_) | DefItem::Alias(_)
+            )
+        })?;
+        let Some(def) = def else {
+            if let Some(name) = self.resolve_external(path, scope) {
+                return Ok(IdlType::Defined {
+                    name,
+                    generics: vec![],
+                });
+            }
+
+            let name = segment.ident.to_string();
+            return BUILTIN_ALIASES
+                .iter()
+                .find(|(alias, _)| *alias == name)
+                .map(|(_, ty)| ty.clone())
+                .ok_or_else(|| {
+                    scope.error(
+                        path,
+                        format!(
+                            "Cannot resolve type `{}` statically because it's not defined in the \
+                             program crate; its IDL definition can be declared in the \
+                             `[idl.types]` section of `Anchor.toml`",
+                            path_to_string(path)
+                        ),
+                    )
+                });
+        };
+
+        match def.item {
+            DefItem::Alias(alias) => {
+                let ty = substitute_alias(alias, segment);
+                let alias_scope = Scope {
+                    generics: scope.generics.clone(),
+                    ..Scope::of(def)
+                };
+                self.idl_type(&ty, &alias_scope)
+            }
+            _ => {
+                self.type_def(def)?;
+                Ok(IdlType::Defined {
+                    name: self.full_name(def),
+                    generics: self.generic_args(segment, scope)?,
+                })
+            }
+        }
+    }
+
+    fn generic_args(
+        &mut self,
+        segment: &syn::PathSegment,
+        scope: &Scope<'a>,
+    ) -> Result<Vec<IdlGenericArg>, Diagnostic> {
+        let syn::PathArguments::AngleBracketed(args) = &segment.arguments else {
+            return Ok(vec![]);
+        };
+
+        let mut generics = vec![];
+        for arg in &args.args {
+            let generic = match arg {
+                syn::GenericArgument::Lifetime(_) => continue,
+                syn::GenericArgument::Const(expr) => IdlGenericArg::Const {
+                    value: self.eval_generic_const(expr, scope)?,
+                },
+                // `MY_CONST` in `Foo<MY_CONST>` is parsed as a type because they're
+                // indistinguishable syntactically, same workaround as the `idl-build` feature
+                syn::GenericArgument::Type(syn::Type::Path(path))
+                    if path
+                        .path
+                        .segments
+                        .last()
+                        .map(|seg| seg.ident.to_string())
+                        .is_some_and(|ident| ident.len() > 1 && ident == ident.to_uppercase()) =>
+                {
+                    IdlGenericArg::Const {
+                        value: self.eval_generic_const(
+                            &syn::Expr::Path(syn::ExprPath {
+                                attrs: vec![],
+                                qself: None,
+                                path: path.path.clone(),
+                            }),
+                            scope,
+                        )?,
+                    }
+                }
+                syn::GenericArgument::Type(ty) => IdlGenericArg::Type {
+                    ty: self.idl_type(ty, scope)?,
+                },
+                _ => return Err(scope.error(arg, "Unsupported generic argument")),
+            };
+            generics.push(generic);
+        }
+
+        Ok(generics)
+    }
+
+    fn eval_generic_const(
+        &self,
+        expr: &syn::Expr,
+        scope: &Scope<'a>,
+    ) -> Result<String, Diagnostic> {
+        self.eval_int(expr, scope)
+            .map(|value| value.to_string())
+            .ok_or_else(|| {
+                scope.error(
+                    expr,
+                    format!(
+                        "Generic argument `{}` cannot be evaluated statically",
+                        parser::tts_to_string(expr)
+                    ),
+                )
+            })
+    }
+
+    fn array_len(&self, len: &syn::Expr, scope: &Scope<'a>) -> Result<IdlArrayLen, Diagnostic> {
+        if let syn::Expr::Path(path) = len {
+            if let Some(ident) = path.path.get_ident() {
+                let ident = ident.to_string();
+                if scope.generics.contains(&ident) {
+                    return Ok(IdlArrayLen::Generic(ident));
+                }
+            }
+        }
+
+        self.eval_int(len, scope)
+            .and_then(|len| usize::try_from(len).ok())
+            .map(IdlArrayLen::Value)
+            .ok_or_else(|| {
+                scope.error(
+                    len,
+                    format!(
+                        "Array length `{}` cannot be evaluated statically",
+                        parser::tts_to_string(len)
+                    ),
+                )
+            })
+    }
+
+    /// Generate the definition of a struct or an enum, including the types it references.
+    pub(super) fn type_def(&mut self, def: &'a Def<'a>) -> Result<(), Diagnostic> {
+        let (attrs, generics) = match def.item {
+            DefItem::Struct(item) => (&item.attrs, &item.generics),
+            DefItem::Enum(item) => (&item.attrs, &item.generics),
+            _ => return Ok(()),
+        };
+        let name = self.full_name(def);
+        if !self.visited.insert(name.clone()) {
+            return Ok(());
+        }
+
+        let scope = Scope {
+            generics: generics
+                .params
+                .iter()
+                .filter_map(|param| match param {
+                    syn::GenericParam::Type(param) => Some(param.ident.to_string()),
+                    syn::GenericParam::Const(param) => Some(param.ident.to_string()),
+                    syn::GenericParam::Lifetime(_) => None,
+                })
+                .collect(),
+            ..Scope::of(def)
+        };
+
+        let ty = match def.item {
+            DefItem::Enum(item) => {
+                let mut variants = vec![];
+                for variant in &item.variants {
+                    variants.push(IdlEnumVariant {
+                        name: variant.ident.to_string(),
+                        fields: self.defined_fields(&variant.fields, &scope)?,
+                    });
+                }
+                IdlTypeDefTy::Enum { variants }
+            }
+            DefItem::Struct(item) => IdlTypeDefTy::Struct {
+                fields: self.defined_fields(&item.fields, &scope)?,
+            },
+            _ => return Ok(()),
+        };
+
+        let generics = generics
+            .params
+            .iter()
+            .filter_map(|param| match param {
+                syn::GenericParam::Type(param) => Some(Ok(IdlTypeDefGeneric::Type {
+                    name: param.ident.to_string(),
+                })),
+                syn::GenericParam::Const(param) => Some(match &param.ty {
+                    syn::Type::Path(path) => Ok(IdlTypeDefGeneric::Const {
+                        name: param.ident.to_string(),
+                        ty: path_to_string(&path.path),
+                    }),
+                    ty => Err(scope.error(ty, "Const generic type must be a path")),
+                }),
+                syn::GenericParam::Lifetime(_) => None,
+            })
+            .collect::<Result<_, _>>()?;
+
+        let zero_copy = get_zero_copy(attrs);
+        let serialization = match zero_copy {
+            Some(true) => IdlSerialization::BytemuckUnsafe,
+            Some(false) => IdlSerialization::Bytemuck,
+            None => get_serialization(attrs),
+        };
+        let repr = get_repr(attrs).or_else(|| {
+            zero_copy.map(|is_unsafe| {
+                // Same as the default `repr` of the `#[zero_copy]` attribute
+                if is_unsafe {
+                    IdlRepr::Rust(IdlReprModifier {
+                        packed: true,
+                        align: None,
+                    })
+                } else {
+                    IdlRepr::C(IdlReprModifier {
+                        packed: false,
+                        align: None,
+                    })
+                }
+            })
+        });
+
+        let type_def = IdlTypeDef {
+            name: name.clone(),
+            docs: self.docs(attrs),
+            serialization,
+            repr,
+            generics,
+            ty,
+        };
+        self.types.insert(name, type_def);
+
+        Ok(())
+    }
+
+    fn defined_fields(
+        &mut self,
+        fields: &syn::Fields,
+        scope: &Scope<'a>,
+    ) -> Result<Option<IdlDefinedFields>, Diagnostic> {
+        let fields = match fields {
+            syn::Fields::Unit => return Ok(None),
+            syn::Fields::Named(fields) => {
+                let mut named = vec![];
+                for field in &fields.named {
+                    named.push(IdlField {
+                        name: field
+                            .ident
+                            .as_ref()
+                            .map(ToString::to_string)
+                            .unwrap_or_default(),
+                        docs: self.docs(&field.attrs),
+                        ty: self.idl_type(&field.ty, scope)?,
+                    });
+                }
+                IdlDefinedFields::Named(named)
+            }
+            syn::Fields::Unnamed(fields) => {
+                let mut types = vec![];
+                for field in &fields.unnamed {
+                    types.push(self.idl_type(&field.ty, scope)?);
+                }
+                IdlDefinedFields::Tuple(types)
+            }
+        };
+
+        Ok(Some(fields))
+    }
+}
+
+/// Arguments of the `#[account]` attribute.
+#[derive(Default)]
+pub(super) struct AccountArgs {
+    /// `Some(true)` for `zero_copy(unsafe)`
+    pub zero_copy: Option<bool>,
+    pub namespace: Option<String>,
+    pub discriminator: Option<Box<syn::Expr>>,
+}
+
+impl Parse for AccountArgs {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut args = Self::default();
+        while !input.is_empty() {
+            if input.peek(syn::LitStr) {
+                args.namespace = Some(input.parse::<syn::LitStr>()?.value());
+            } else if input
+                .fork()
+                .parse::<syn::Ident>()
+                .is_ok_and(|ident| ident == "zero_copy")
+            {
+                input.parse::<syn::Ident>()?;
+                let is_unsafe = input.peek(syn::token::Paren);
+                if is_unsafe {
+                    let content;
+                    syn::parenthesized!(content in input);
+                    content.parse::<TokenStream>()?;
+                }
+                args.zero_copy = Some(is_unsafe);
+            } else {
+                args.discriminator = input.parse::<Overrides>()?.discriminator;
+            }
+
+            if !input.is_empty() {
+                input.parse::<syn::Token![,]>()?;
+            }
+        }
+
+        Ok(args)
+    }
+}
+
+/// Get whether the type uses one of Anchor's zero-copy attributes, `Some(true)` if it's unsafe
+/// e.g. `#[account(zero_copy(unsafe))]`.
+fn get_zero_copy(attrs: &[syn::Attribute]) -> Option<bool> {
+    if let Some(attr) = find_attr(attrs, "zero_copy") {
+        return Some(matches!(attr.meta, syn::Meta::List(_)));
+    }
+
+    find_attr(attrs, "account")
+        .and_then(|attr| super::parse_optional_args::<AccountArgs>(attr).ok())
+        .flatten()
+        .and_then(|args| args.zero_copy)
+}
+
+fn get_serialization(attrs: &[syn::Attribute]) -> IdlSerialization {
+    let mut serialization = IdlSerialization::default();
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("derive")) {
+        let _ = attr.parse_nested_meta(|meta| {
+            let segments = &meta.path.segments;
+            if segments.iter().any(|seg| seg.ident == "bytemuck") {
+                if segments.iter().any(|seg| {
+                    seg.ident
+                        .to_string()
+                        .to_ascii_lowercase()
+                        .contains("unsafe")
+                }) {
+                    serialization = IdlSerialization::BytemuckUnsafe;
+                } else if segments.last().is_some_and(|seg| seg.ident == "Pod")
+                    && serialization != IdlSerialization::BytemuckUnsafe
+                {
+                    serialization = IdlSerialization::Bytemuck;
+                }
+            }
+            Ok(())
+        });
+    }
+
+    serialization
+}
+
+fn get_repr(attrs: &[syn::Attribute]) -> Option<IdlRepr> {
+    let mut found = false;
+    let mut is_c = false;
+    let mut is_transparent = false;
+    let mut modifier = IdlReprModifier {
+        packed: false,
+        align: None,
+    };
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("repr")) {
+        found = true;
+        let _ = attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("C") {
+                is_c = true;
+            } else if meta.path.is_ident("transparent") {
+                is_transparent = true;
+            } else if meta.path.is_ident("packed") {
+                modifier.packed = true;
+            } else if meta.path.is_ident("align") {
+                let content;
+                syn::parenthesized!(content in meta.input);
+                modifier.align = content.parse::<syn::LitInt>()?.base10_parse().ok();
+            }
+            if meta.input.peek(syn::token::Paren) {
+                let content;
+                syn::parenthesized!(content in meta.input);
+                content.parse::<TokenStream>()?;
+            }
+            Ok(())
+        });
+    }
+
+    let repr = if is_transparent {
+        IdlRepr::Transparent
+    } else if is_c {
+        IdlRepr::C(modifier)
+    } else {
+        IdlRepr::Rust(modifier)
+    };
+    found.then_some(repr)
+}
+
+/// Replace the generic parameters of a type alias with the arguments of the given segment, e.g.
+/// `[T; N]` with `[u8; 4]` for `type Arr<T, const N: usize> = [T; N]` and `Arr<u8, 4>`.
+fn substitute_alias(alias: &syn::ItemType, segment: &syn::PathSegment) -> syn::Type {
+    let args = match &segment.arguments {
+        syn::PathArguments::AngleBracketed(args) => args
+            .args
+            .iter()
+            .filter(|arg| !matches!(arg, syn::GenericArgument::Lifetime(_)))
+            .map(ToTokens::to_token_stream)
+            .collect(),
+        _ => vec![],
+    };
+    let substitutions = alias
+        .generics
+        .params
+        .iter()
+        .filter_map(|param| match param {
+            syn::GenericParam::Type(param) => Some(param.ident.to_string()),
+            syn::GenericParam::Const(param) => Some(param.ident.to_string()),
+            syn::GenericParam::Lifetime(_) => None,
+        })
+        .zip(args)
+        .collect::<HashMap<_, _>>();
+    if substitutions.is_empty() {
+        return (*alias.ty).clone();
+    }
+
+    fn substitute(
+        tokens: TokenStream,
+        substitutions: &HashMap<String, TokenStream>,
+    ) -> TokenStream {
+        tokens
+            .into_iter()
+            .map(|tt| match tt {
+                TokenTree::Ident(ident) => substitutions
+                    .get(&ident.to_string())
+                    .cloned()
+                    .unwrap_or_else(|| TokenTree::Ident(ident).into()),
+                TokenTree::Group(group) => {
+                    let mut substituted =
+                        Group::new(group.delimiter(), substitute(group.stream(), substitutions));
+                    substituted.set_span(group.span());
+                    TokenTree::Group(substituted).into()
+                }
+                tt => tt.into(),
+            })
+            .collect()
+    }
+
+    syn::parse2(substitute(alias.ty.to_token_stream(), &substitutions))
+        .unwrap_or_else(|_| (*alias.ty).clone())
+}
+
+fn get_first_type_arg(segment: &syn::PathSegment) -> Option<&syn::Type> {
+    match &segment.arguments {
+        syn::PathArguments::AngleBracketed(args) => args.args.iter().find_map(|arg| match arg {
+            syn::GenericArgument::Type(ty) => Some(ty),
+            _ => None,
+        }),
+        _ => None,
+    }
+}
+
+fn is_u8(ty: &syn::Type) -> bool {
+    matches!(ty, syn::Type::Path(path) if path.path.is_ident("u8"))
+}
@@ -0,0 +1,956 @@
+//! IDL generation from the program's source code.
+//!
+//! The `idl-build` feature generates the IDL by compiling the program and running the generated
+//! tests. This module instead parses the program crate and builds the IDL from the syntax tree,
+//! without compiling or executing any code.
+//!
+//! The trade-off is that only what can be resolved statically ends up in the IDL:
+//!
+//! - Types must either be defined in the program crate or be one of the builtin types
+//! - Constant expressions are only evaluated when they consist of literals, arithmetic and
+//!   references to other constants
+//! - Account types from other crates (e.g. `TokenAccount`) are not included
+
+mod accounts;
+mod defined;
+
+use {
+    crate::{
+        codegen::program::common::{sighash, SIGHASH_GLOBAL_NAMESPACE},
+        parser::{self, context::CrateContext, docs},
+        ErrorArgs, Ix, Overrides,
+    },
+    anchor_lang_idl_spec::{
+        Idl, IdlAccount, IdlConst, IdlErrorCode, IdlEvent, IdlField, IdlInstruction, IdlMetadata,
+        IdlTypeDef, IDL_SPEC,
+    },
+    cargo_toml::Manifest,
+    proc_macro2::Span,
+    std::{
+        collections::{BTreeMap, HashMap, HashSet},
+        fmt,
+        path::{Path, PathBuf},
+    },
+    syn::spanned::Spanned,
+};
+
+/// Error code offset of the user defined errors, same as `anchor_lang::error::ERROR_CODE_OFFSET`.
+const ERROR_CODE_OFFSET: u32 = 6000;
+
+/// Programs with well-known addresses as `(type, module, address)`, e.g. `Program<'info, System>`
+/// or `system_program::ID`.
+const KNOWN_PROGRAMS: &[(&str, &str, &str)] = &[
+    (
+        "System",
+        "system_program",
+        "11111111111111111111111111111111",
+    ),
+    (
+        "Token",
+        "token",
+        "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA",
+    ),
+    (
+        "Token2022",
+        "token_2022",
+        "TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb",
+    ),
+    (
+        "AssociatedToken",
+        "associated_token",
+        "ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL",
+    ),
+    (
+        "Memo",
+        "memo",
+        "MemoSq4gqABAXKb96qnH8TysNcWxMyWCqXgDLGmfcHr",
+    ),
+    (
+        "BpfLoaderUpgradeable",
+        "bpf_loader_upgradeable",
+        "BPFLoaderUpgradeab1e11111111111111111111111",
+    ),
+];
+
+/// IDL generated from the program's source code.
+#[derive(Debug)]
+pub struct ParsedIdl {
+    /// The generated IDL
+    pub idl: Idl,
+    /// Non-fatal diagnostics, e.g. seeds that couldn't be resolved and were left out of the IDL
+    pub warnings: Vec<Diagnostic>,
+}
+
+/// A message about a location in the program's source code.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// File the diagnostic is about
+    pub file: PathBuf,
+    /// 1-based line number, `0` if the diagnostic is about the whole file
+    pub line: usize,
+    /// 1-based column number, `0` if the diagnostic is about the whole file
+    pub column: usize,
+    /// Description of the problem
+    pub message: String,
+}
+
+impl Diagnostic {
+    fn new(file: &Path, span: Span, message: impl Into<String>) -> Self {
+        let start = span.start();
+        Self {
+            file: file.to_owned(),
+            line: start.line,
+            column: start.column + 1,
+            message: message.into(),
+        }
+    }
+
+    fn file(file: &Path, message: impl Into<String>) -> Self {
+        Self {
+            file: file.to_owned(),
+            line: 0,
+            column: 0,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.file.display())?;
+        if self.line != 0 {
+            write!(f, ":{}:{}", self.line, self.column)?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+/// Error returned when the IDL cannot be generated from the program's source code.
+#[derive(Debug)]
+pub struct ParseIdlError {
+    /// All problems that were found
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl From<Diagnostic> for ParseIdlError {
+    fn from(diagnostic: Diagnostic) -> Self {
+        Self {
+            diagnostics: vec![diagnostic],
+        }
+    }
+}
+
+impl fmt::Display for ParseIdlError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let diagnostics = self
+            .diagnostics
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        write!(f, "{}", diagnostics.join("\n"))
+    }
+}
+
+impl std::error::Error for ParseIdlError {}
+
+/// Generate the IDL of the program at the given path by parsing its source code.
+///
+/// Type names are the full paths of the types (e.g. `my_program::state::Vault`), same as the
+/// ones created by the `idl-build` feature.
+///
+/// Types from other crates can only be used if their paths are in `external_types`, in which
+/// case they're referenced by these paths and their definitions are expected to be added
+/// afterwards.
+pub fn parse_idl(
+    program_path: &Path,
+    resolution: bool,
+    no_docs: bool,
+    skip_lint: bool,
+    external_types: &[String],
+) -> Result<ParsedIdl, ParseIdlError> {
+    let manifest_path = program_path.join("Cargo.toml");
+    let manifest = Manifest::from_path(&manifest_path).map_err(|e| {
+        Diagnostic::file(&manifest_path, format!("Failed to read the manifest: {e}"))
+    })?;
+    let package = manifest
+        .package
+        .as_ref()
+        .ok_or_else(|| Diagnostic::file(&manifest_path, "`[package]` section not found"))?;
+    let lib = manifest.lib.as_ref();
+    let crate_name = lib
+        .and_then(|lib| lib.name.clone())
+        .unwrap_or_else(|| package.name.replace('-', "_"));
+    let lib_path = program_path.join(
+        lib.and_then(|lib| lib.path.as_deref())
+            .unwrap_or("src/lib.rs"),
+    );
+
+    let ctx = CrateContext::parse(&lib_path)
+        .map_err(|e| Diagnostic::new(&lib_path, e.span(), format!("Failed to parse crate: {e}")))?;
+    if !skip_lint {
+        ctx.safety_checks().map_err(|e| {
+            Diagnostic::file(
+                &lib_path,
+                format!("Safety checks failed: {}", e.to_string().trim()),
+            )
+        })?;
+    }
+
+    let modules = ctx.modules().collect::<Vec<_>>();
+    let defs = modules
+        .iter()
+        .flat_map(|module| {
+            module.items().filter_map(move |item| {
+                let item = match item {
+                    syn::Item::Struct(item) => DefItem::Struct(item),
+                    syn::Item::Enum(item) => DefItem::Enum(item),
+                    syn::Item::Type(item) => DefItem::Alias(item),
+                    syn::Item::Const(item) => DefItem::Const(item),
+                    _ => return None,
+                };
+                Some(Def {
+                    module: module.path(),
+                    file: module.file(),
+                    item,
+                })
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let uses = modules
+        .iter()
+        .map(|module| {
+            let uses = module
+                .items()
+                .filter_map(|item| match item {
+                    syn::Item::Use(item) => Some(flatten_use(&item.tree)),
+                    _ => None,
+                })
+                .flatten()
+                .collect();
+            (module.path(), uses)
+        })
+        .collect();
+
+    let address = ctx.root_module().items().find_map(|item| match item {
+        syn::Item::Macro(item)
+            if item
+                .mac
+                .path
+                .segments
+                .last()
+                .is_some_and(|seg| seg.ident == "declare_id") =>
+        {
+            item.mac
+                .parse_body::<syn::LitStr>()
+                .ok()
+                .map(|address| address.value())
+        }
+        _ => None,
+    });
+
+    let (program_module, program_mod) = modules
+        .iter()
+        .find_map(|module| {
+            module.items().find_map(|item| match item {
+                syn::Item::Mod(item) if find_attr(&item.attrs, "program").is_some() => {
+                    Some((*module, item))
+                }
+                _ => None,
+            })
+        })
+        .ok_or_else(|| Diagnostic::file(&lib_path, "`#[program]` module not found"))?;
+    let program = parser::program::parse(program_mod.clone())
+        .map_err(|e| Diagnostic::new(program_module.file(), e.span(), e.to_string()))?;
+
+    let mut parser = Parser {
+        crate_name,
+        defs: &defs,
+        uses,
+        external_types,
+        address: address.clone(),
+        resolution,
+        no_docs,
+        accounts: BTreeMap::new(),
+        types: BTreeMap::new(),
+        visited: HashSet::new(),
+        errors: vec![],
+        warnings: vec![],
+    };
+    if address.is_none() {
+        parser.warn(Diagnostic::file(
+            &lib_path,
+            "`declare_id!` not found, the IDL address will be empty",
+        ));
+    }
+
+    let scope = Scope {
+        module: program_module.path(),
+        file: program_module.file(),
+        generics: vec![],
+    };
+    let mut instructions = vec![];
+    for ix in &program.ixs {
+        match parser.instruction(ix, &scope) {
+            Ok(ix) => instructions.push(ix),
+            Err(e) => parser.errors.push(e),
+        }
+    }
+    let events = parser.events();
+    let errors = parser.errors_codes();
+    let constants = parser.constants();
+
+    if !parser.errors.is_empty() {
+        return Err(ParseIdlError {
+            diagnostics: parser.errors,
+        });
+    }
+
+    let docs = match program.docs {
+        Some(docs) if !no_docs => docs,
+        _ => vec![],
+    };
+    let idl = Idl {
+        address: address.unwrap_or_default(),
+        metadata: IdlMetadata {
+            name: program.name.to_string(),
+            version: package.version.get().cloned().unwrap_or_default(),
+            spec: IDL_SPEC.into(),
+            description: package
+                .description
+                .as_ref()
+                .and_then(|description| description.get().ok())
+                .filter(|description| !description.is_empty())
+                .cloned(),
+            repository: package
+                .repository
+                .as_ref()
+                .and_then(|repository| repository.get().ok())
+                .filter(|repository| !repository.is_empty())
+                .cloned(),
+            dependencies: Default::default(),
+            contact: Default::default(),
+            deployments: Default::default(),
+        },
+        docs,
+        instructions,
+        accounts: parser.accounts.into_values().collect(),
+        events,
+        errors,
+        types: parser.types.into_values().collect(),
+        constants,
+    };
+
+    Ok(ParsedIdl {
+        idl,
+        warnings: parser.warnings,
+    })
+}
+
+/// An item of the program crate that can be referenced by path.
+struct Def<'a> {
+    /// Path of the module the item is defined in, relative to the crate root
+    module: &'a str,
+    /// File the item is defined in
+    file: &'a Path,
+    item: DefItem<'a>,
+}
+
+#[derive(Clone, Copy)]
+enum DefItem<'a> {
+    Struct(&'a syn::ItemStruct),
+    Enum(&'a syn::ItemEnum),
+    Alias(&'a syn::ItemType),
+    Const(&'a syn::ItemConst),
+}
+
+impl DefItem<'_> {
+    fn ident(&self) -> &syn::Ident {
+        match self {
+            Self::Struct(item) => &item.ident,
+            Self::Enum(item) => &item.ident,
+            Self::Alias(item) => &item.ident,
+            Self::Const(item) => &item.ident,
+        }
+    }
+
+    fn attrs(&self) -> &[syn::Attribute] {
+        match self {
+            Self::Struct(item) => &item.attrs,
+            Self::Enum(item) => &item.attrs,
+            Self::Alias(item) => &item.attrs,
+            Self::Const(item) => &item.attrs,
+        }
+    }
+}
+
+/// The location paths are resolved from.
+#[derive(Clone)]
+struct Scope<'a> {
+    module: &'a str,
+    file: &'a Path,
+    /// Generic parameters of the type definition that is being parsed
+    generics: Vec<String>,
+}
+
+impl<'a> Scope<'a> {
+    fn of(def: &Def<'a>) -> Self {
+        Self {
+            module: def.module,
+            file: def.file,
+            generics: vec![],
+        }
+    }
+
+    fn error(&self, node: &impl Spanned, message: impl Into<String>) -> Diagnostic {
+        Diagnostic::new(self.file, node.span(), message)
+    }
+}
+
+struct Parser<'a> {
+    crate_name: String,
+    defs: &'a [Def<'a>],
+    /// Flattened `use` paths of each module, e.g. `pyth_sdk::Price`
+    uses: HashMap<&'a str, Vec<String>>,
+    /// Paths of the types from other crates whose definitions are provided externally
+    external_types: &'a [String],
+    address: Option<String>,
+    resolution: bool,
+    no_docs: bool,
+    accounts: BTreeMap<String, IdlAccount>,
+    types: BTreeMap<String, IdlTypeDef>,
+    /// Names of the type definitions that have been (or are being) generated
+    visited: HashSet<String>,
+    errors: Vec<Diagnostic>,
+    warnings: Vec<Diagnostic>,
+}
+
+impl<'a> Parser<'a> {
+    fn warn(&mut self, diagnostic: Diagnostic) {
+        self.warnings.push(diagnostic);
+    }
+
+    fn docs(&self, attrs: &[syn::Attribute]) -> Vec<String> {
+        match docs::parse(attrs) {
+            Some(docs) if !self.no_docs => docs,
+            _ => vec![],
+        }
+    }
+
+    fn full_name(&self, def: &Def) -> String {
+        match def.module {
+            "" => format!("{}::{}", self.crate_name, def.item.ident()),
+            module => format!("{}::{module}::{}", self.crate_name, def.item.ident()),
+        }
+    }
+
+    fn instruction(&mut self, ix: &Ix, scope: &Scope<'a>) -> Result<IdlInstruction, Diagnostic> {
+        let name = ix.ident.to_string();
+        let discriminator = match ix
+            .overrides
+            .as_ref()
+            .and_then(|ov| ov.discriminator.as_ref())
+        {
+            Some(discriminator) => self.eval_discriminator(discriminator, scope)?,
+            None => sighash(SIGHASH_GLOBAL_NAMESPACE, &name).into(),
+        };
+        let accounts = self.instruction_accounts(&ix.anchor_ident.clone().into(), scope)?;
+
+        let mut args = vec![];
+        for arg in &ix.args {
+            args.push(IdlField {
+                name: arg.name.to_string(),
+                docs: self.docs(&arg.raw_arg.attrs),
+                ty: self.idl_type(&arg.raw_arg.ty, scope)?,
+            });
+        }
+
+        let returns = match &ix.returns.ty {
+            syn::Type::Tuple(tuple) if tuple.elems.is_empty() => None,
+            ty => Some(self.idl_type(ty, scope)?),
+        };
+
+        Ok(IdlInstruction {
+            name,
+            docs: match &ix.docs {
+                Some(docs) if !self.no_docs => docs.clone(),
+                _ => vec![],
+            },
+            discriminator,
+            accounts,
+            args,
+            returns,
+        })
+    }
+
+    /// Get the events, i.e. the structs with the `#[event]` attribute.
+    fn events(&mut self) -> Vec<IdlEvent> {
+        let mut events = vec![];
+        for def in self.defs {
+            let Some(attr) = find_attr(def.item.attrs(), "event") else {
+                continue;
+            };
+            let scope = Scope::of(def);
+            let result = parse_optional_args::<Overrides>(attr)
+                .map_err(|e| scope.error(&e.span(), e.to_string()))
+                .and_then(|overrides| {
+                    self.type_def(def)?;
+                    let discriminator = match overrides.and_then(|ov| ov.discriminator) {
+                        Some(discriminator) => self.eval_discriminator(&discriminator, &scope)?,
+                        None => sighash("event", &def.item.ident().to_string()).into(),
+                    };
+                    Ok(IdlEvent {
+                        name: self.full_name(def),
+                        discriminator,
+                    })
+                });
+            match result {
+                Ok(event) => events.push(event),
+                Err(e) => self.errors.push(e),
+            }
+        }
+
+        events
+    }
+
+    /// Get the error codes of the `#[error_code]` enum.
+    fn errors_codes(&mut self) -> Vec<IdlErrorCode> {
+        let mut error_enums = self.defs.iter().filter_map(|def| match def.item {
+            DefItem::Enum(item) => {
+                find_attr(&item.attrs, "error_code").map(|attr| (def, item, attr))
+            }
+            _ => None,
+        });
+        let Some((def, item, attr)) = error_enums.next() else {
+            return vec![];
+        };
+        if let Some((def, item, _)) = error_enums.next() {
+            self.errors.push(
+                Scope::of(def).error(&item.ident, "Multiple error definitions are not allowed"),
+            );
+            return vec![];
+        }
+
+        let scope = Scope::of(def);
+        let result = parse_optional_args::<ErrorArgs>(attr)
+            .and_then(|args| parser::error::parse(&mut item.clone(), args))
+            .map_err(|e| scope.error(&e.span(), e.to_string()))
+            .and_then(|error| {
+                let offset = match &error.args {
+                    Some(args) => args
+                        .offset
+                        .base10_parse::<u32>()
+                        .map_err(|e| scope.error(&args.offset, e.to_string()))?,
+                    None => ERROR_CODE_OFFSET,
+                };
+                Ok(error
+                    .codes
+                    .into_iter()
+                    .map(|code| IdlErrorCode {
+                        code: offset + code.id,
+                        name: code.ident.to_string(),
+                        msg: code.msg,
+                    })
+                    .collect())
+            });
+        result.unwrap_or_else(|e| {
+            self.errors.push(e);
+            vec![]
+        })
+    }
+
+    /// Get the constants with the `#[constant]` attribute.
+    fn constants(&mut self) -> Vec<IdlConst> {
+        let mut constants = vec![];
+        for def in self.defs {
+            let DefItem::Const(item) = def.item else {
+                continue;
+            };
+            if find_attr(&item.attrs, "constant").is_none() {
+                continue;
+            }
+
+            let scope = Scope::of(def);
+            let name = item.ident.to_string();
+            let ty = match self.idl_type(&item.ty, &scope) {
+                Ok(ty) => ty,
+                Err(e) => {
+                    self.warn(Diagnostic {
+                        message: format!(
+                            "Constant `{name}` is not included in the IDL: {}",
+                            e.message
+                        ),
+                        ..e
+                    });
+                    continue;
+                }
+            };
+            let value = self.const_value(&item.expr, &scope).unwrap_or_else(|| {
+                self.warn(scope.error(
+                    &item.expr,
+                    format!(
+                        "Value of constant `{name}` cannot be evaluated statically, using its \
+                         expression instead"
+                    ),
+                ));
+                parser::tts_to_string(&item.expr)
+            });
+            constants.push(IdlConst {
+                name,
+                docs: self.docs(&item.attrs),
+                ty,
+                value,
+            });
+        }
+
+        constants
+    }
+
+    /// Resolve a path to an item defined in the program crate.
+    ///
+    /// Paths are matched by their trailing segments, e.g. `state::Vault` matches
+    /// `crate::state::Vault` but not `crate::Vault`. If multiple items match, the one in the current
+    /// module is picked.
+    fn resolve(
+        &self,
+        path: &syn::Path,
+        scope: &Scope<'a>,
+        kind: impl Fn(&DefItem) -> bool,
+    ) -> Result<Option<&'a Def<'a>>, Diagnostic> {
+        let segments = path
+            .segments
+            .iter()
+            .map(|seg| seg.ident.to_string())
+            .skip_while(|seg| matches!(seg.as_str(), "crate" | "self" | "super"))
+            .collect::<Vec<_>>();
+        let Some(name) = segments.last() else {
+            return Ok(None);
+        };
+
+        let defs = self.defs;
+        let candidates = defs
+            .iter()
+            .filter(|def| kind(&def.item) && def.item.ident() == name)
+            .filter(|def| {
+                def.module
+                    .split("::")
+                    .filter(|module| !module.is_empty())
+                    .map(ToOwned::to_owned)
+                    .chain([name.to_owned()])
+                    .collect::<Vec<_>>()
+                    .ends_with(&segments)
+            })
+            .collect::<Vec<_>>();
+        match candidates.as_slice() {
+            [] => Ok(None),
+            [def] => Ok(Some(def)),
+            _ => candidates
+                .iter()
+                .find(|def| def.module == scope.module)
+                .map(|def| Some(*def))
+                .ok_or_else(|| {
+                    let names = candidates
+                        .iter()
+                        .map(|def| format!("`{}`", self.full_name(def)))
+                        .collect::<Vec<_>>();
+                    scope.error(
+                        path,
+                        format!(
+                            "`{}` is ambiguous, it could refer to {}",
+                            path_to_string(path),
+                            names.join(", ")
+                        ),
+                    )
+                }),
+        }
+    }
+
+    /// Get the path of the external type the given path refers to, if it's one of the types whose
+    /// definitions are provided externally.
+    fn resolve_external(&self, path: &syn::Path, scope: &Scope<'a>) -> Option<String> {
+        let written = path_to_string(path);
+        let full_path = path
+            .get_ident()
+            .and_then(|ident| {
+                self.uses
+                    .get(scope.module)?
+                    .iter()
+                    .find(|u| u.rsplit("::").next() == Some(&ident.to_string()))
+                    .cloned()
+            })
+            .unwrap_or(written);
+
+        self.external_types
+            .iter()
+            .find(|ty| **ty == full_path || ty.ends_with(&format!("::{full_path}")))
+            .cloned()
+    }
+
+    fn resolve_const(
+        &self,
+        path: &syn::Path,
+        scope: &Scope<'a>,
+    ) -> Option<(&'a syn::ItemConst, Scope<'a>)> {
+        match self.resolve(path, scope, |item| matches!(item, DefItem::Const(_))) {
+            Ok(Some(def)) => match def.item {
+                DefItem::Const(item) => Some((item, Scope::of(def))),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    fn eval_discriminator(
+        &self,
+        expr: &syn::Expr,
+        scope: &Scope<'a>,
+    ) -> Result<Vec<u8>, Diagnostic> {
+        self.eval_bytes(expr, scope).ok_or_else(|| {
+            scope.error(
+                expr,
+                format!(
+                    "Discriminator `{}` cannot be evaluated statically",
+                    parser::tts_to_string(expr)
+                ),
+            )
+        })
+    }
+
+    /// Evaluate an expression that results in bytes, e.g. a seed or a discriminator.
+    fn eval_bytes(&self, expr: &syn::Expr, scope: &Scope<'a>) -> Option<Vec<u8>> {
+        match expr {
+            syn::Expr::Reference(expr) => self.eval_bytes(&expr.expr, scope),
+            syn::Expr::Paren(expr) => self.eval_bytes(&expr.expr, scope),
+            syn::Expr::Group(expr) => self.eval_bytes(&expr.expr, scope),
+            syn::Expr::Lit(syn::ExprLit { lit, .. }) => match lit {
+                syn::Lit::ByteStr(lit) => Some(lit.value()),
+                syn::Lit::Str(lit) => Some(lit.value().into_bytes()),
+                _ => self
+                    .eval_int(expr, scope)
+                    .and_then(|value| u8::try_from(value).ok())
+                    .map(|value| vec![value]),
+            },
+            syn::Expr::Array(expr) => expr
+                .elems
+                .iter()
+                .map(|elem| {
+                    self.eval_int(elem, scope)
+                        .and_then(|value| u8::try_from(value).ok())
+                })
+                .collect(),
+            syn::Expr::MethodCall(call)
+                if call.args.is_empty()
+                    && matches!(
+                        call.method.to_string().as_str(),
+                        "as_ref" | "as_bytes" | "as_slice" | "to_bytes" | "to_vec"
+                    ) =>
+            {
+                self.eval_bytes(&call.receiver, scope)
+            }
+            syn::Expr::Path(path) => match self.resolve_const(&path.path, scope) {
+                Some((item, scope)) => self.eval_bytes(&item.expr, &scope),
+                None => self.eval_pubkey_bytes(expr, scope),
+            },
+            _ => self.eval_pubkey_bytes(expr, scope),
+        }
+    }
+
+    fn eval_pubkey_bytes(&self, expr: &syn::Expr, scope: &Scope<'a>) -> Option<Vec<u8>> {
+        self.eval_pubkey(expr, scope)
+            .and_then(|pubkey| bs58::decode(pubkey).into_vec().ok())
+    }
+
+    /// Evaluate an integer expression.
+    fn eval_int(&self, expr: &syn::Expr, scope: &Scope<'a>) -> Option<i128> {
+        match expr {
+            syn::Expr::Lit(expr) => match &expr.lit {
+                syn::Lit::Int(lit) => lit.base10_parse().ok(),
+                syn::Lit::Byte(lit) => Some(lit.value().into()),
+                _ => None,
+            },
+            syn::Expr::Paren(expr) => self.eval_int(&expr.expr, scope),
+            syn::Expr::Group(expr) => self.eval_int(&expr.expr, scope),
+            syn::Expr::Cast(expr) => self.eval_int(&expr.expr, scope),
+            syn::Expr::Block(expr) if expr.block.stmts.len() == 1 => match expr.block.stmts.first()
+            {
+                Some(syn::Stmt::Expr(expr, None)) => self.eval_int(expr, scope),
+                _ => None,
+            },
+            syn::Expr::Unary(syn::ExprUnary {
+                op: syn::UnOp::Neg(_),
+                expr,
+                ..
+            }) => self.eval_int(expr, scope).and_then(i128::checked_neg),
+            syn::Expr::Binary(expr) => {
+                let left = self.eval_int(&expr.left, scope)?;
+                let right = self.eval_int(&expr.right, scope)?;
+                match expr.op {
+                    syn::BinOp::Add(_) => left.checked_add(right),
+                    syn::BinOp::Sub(_) => left.checked_sub(right),
+                    syn::BinOp::Mul(_) => left.checked_mul(right),
+                    syn::BinOp::Div(_) => left.checked_div(right),
+                    syn::BinOp::Rem(_) => left.checked_rem(right),
+                    syn::BinOp::Shl(_) => u32::try_from(right)
+                        .ok()
+                        .and_then(|right| left.checked_shl(right)),
+                    syn::BinOp::Shr(_) => u32::try_from(right)
+                        .ok()
+                        .and_then(|right| left.checked_shr(right)),
+                    _ => None,
+                }
+            }
+            syn::Expr::Path(path) => self
+                .resolve_const(&path.path, scope)
+                .and_then(|(item, scope)| self.eval_int(&item.expr, &scope)),
+            _ => None,
+        }
+    }
+
+    /// Evaluate an expression that results in a public key, returning its base58 representation.
+    ///
+    /// Supported forms are the program's own ID (e.g. `crate::ID` or `id()`), IDs of well-known
+    /// programs (e.g. `system_program::ID` or `System::id()`), `pubkey!` and constants that
+    /// consist of one of these.
+    fn eval_pubkey(&self, expr: &syn::Expr, scope: &Scope<'a>) -> Option<String> {
+        match expr {
+            syn::Expr::Reference(expr) => self.eval_pubkey(&expr.expr, scope),
+            syn::Expr::Paren(expr) => self.eval_pubkey(&expr.expr, scope),
+            syn::Expr::Group(expr) => self.eval_pubkey(&expr.expr, scope),
+            syn::Expr::Macro(expr)
+                if expr
+                    .mac
+                    .path
+                    .segments
+                    .last()
+                    .is_some_and(|seg| seg.ident == "pubkey") =>
+            {
+                expr.mac
+                    .parse_body::<syn::LitStr>()
+                    .ok()
+                    .map(|lit| lit.value())
+            }
+            syn::Expr::Call(call) => match (call.func.as_ref(), call.args.first()) {
+                (syn::Expr::Path(path), None) => self.eval_program_id(&path.path, "id"),
+                (
+                    syn::Expr::Path(path),
+                    Some(syn::Expr::Lit(syn::ExprLit {
+                        lit: syn::Lit::Str(lit),
+                        ..
+                    })),
+                ) if call.args.len() == 1
+                    && path
+                        .path
+                        .segments
+                        .last()
+                        .is_some_and(|seg| seg.ident == "from_str_const") =>
+                {
+                    Some(lit.value())
+                }
+                _ => None,
+            },
+            syn::Expr::Path(path) => match self.resolve_const(&path.path, scope) {
+                Some((item, scope)) => self.eval_pubkey(&item.expr, &scope),
+                None => self.eval_program_id(&path.path, "ID"),
+            },
+            _ => None,
+        }
+    }
+
+    /// Get the program ID from paths such as `crate::ID` or `system_program::ID`, where `name` is
+    /// the last segment of the path (`ID` or `id`).
+    fn eval_program_id(&self, path: &syn::Path, name: &str) -> Option<String> {
+        let segments = path
+            .segments
+            .iter()
+            .map(|seg| seg.ident.to_string())
+            .collect::<Vec<_>>();
+        match segments
+            .iter()
+            .map(String::as_str)
+            .collect::<Vec<_>>()
+            .as_slice()
+        {
+            [.., last] if *last != name => None,
+            [_] | ["crate" | "self" | "super", _] => self.address.clone(),
+            [.., program, _] => KNOWN_PROGRAMS
+                .iter()
+                .find(|(ty, module, _)| ty == program || module == program)
+                .map(|(_, _, address)| (*address).to_owned()),
+            [] => None,
+        }
+    }
+
+    /// Get the `Debug` representation of a constant's value, same as the `idl-build` feature.
+    fn const_value(&self, expr: &syn::Expr, scope: &Scope<'a>) -> Option<String> {
+        match expr {
+            syn::Expr::Reference(expr) => self.const_value(&expr.expr, scope),
+            syn::Expr::Paren(expr) => self.const_value(&expr.expr, scope),
+            syn::Expr::Group(expr) => self.const_value(&expr.expr, scope),
+            syn::Expr::Lit(syn::ExprLit { lit, .. }) => match lit {
+                syn::Lit::Str(lit) => Some(format!("{:?}", lit.value())),
+                syn::Lit::ByteStr(lit) => Some(format!("{:?}", lit.value())),
+                syn::Lit::Char(lit) => Some(format!("{:?}", lit.value())),
+                syn::Lit::Bool(lit) => Some(lit.value.to_string()),
+                syn::Lit::Float(lit) => Some(lit.base10_digits().to_owned()),
+                _ => self.eval_int(expr, scope).map(|value| value.to_string()),
+            },
+            syn::Expr::Array(array) => array
+                .elems
+                .iter()
+                .map(|elem| self.const_value(elem, scope))
+                .collect::<Option<Vec<_>>>()
+                .map(|elems| format!("[{}]", elems.join(", "))),
+            syn::Expr::Path(path) => match self.resolve_const(&path.path, scope) {
+                Some((item, scope)) => self.const_value(&item.expr, &scope),
+                None => self.eval_pubkey(expr, scope),
+            },
+            _ => self
+                .eval_int(expr, scope)
+                .map(|value| value.to_string())
+                .or_else(|| self.eval_pubkey(expr, scope)),
+        }
+    }
+}
+
+/// Find the attribute with the given name, ignoring its path prefix, e.g. both `#[account]` and
+/// `#[anchor_lang::account]` are found with `account`.
+fn find_attr<'b>(attrs: &'b [syn::Attribute], name: &str) -> Option<&'b syn::Attribute> {
+    attrs.iter().find(|attr| {
+        attr.path()
+            .segments
+            .last()
+            .is_some_and(|seg| seg.ident == name)
+    })
+}
+
+/// Parse the arguments of an attribute that may or may not have arguments, e.g. `#[event]` and
+/// `#[event(discriminator = 1)]`.
+fn parse_optional_args<T: syn::parse::Parse>(attr: &syn::Attribute) -> syn::Result<Option<T>> {
+    match &attr.meta {
+        syn::Meta::List(_) => attr.parse_args().map(Some),
+        _ => Ok(None),
+    }
+}
+
+/// Flatten a `use` tree, e.g. `a::{b, c::D}` becomes `a::b` and `a::c::D`.
+///
+/// Renamed items are ignored because they can't be matched by name.
+fn flatten_use(tree: &syn::UseTree) -> Vec<String> {
+    match tree {
+        syn::UseTree::Path(path) => flatten_use(&path.tree)
+            .into_iter()
+            .map(|item| format!("{}::{item}", path.ident))
+            .collect(),
+        syn::UseTree::Name(name) => vec![name.ident.to_string()],
+        syn::UseTree::Group(group) => group.items.iter().flat_map(flatten_use).collect(),
+        syn::UseTree::Rename(_) | syn::UseTree::Glob(_) => vec![],
+    }
+}
+
+fn path_to_string(path: &syn::Path) -> String {
+    path.segments
+        .iter()
+        .map(|seg| seg.ident.to_string())
+        .collect::<Vec<_>>()
+        .join("::")
+}
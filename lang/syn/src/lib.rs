@@ -3,7 +3,7 @@
 pub mod codegen;
 pub mod parser;
 
-#[cfg(feature = "idl-build")]
+#[cfg(any(feature = "idl-build", feature = "idl-parse"))]
 pub mod idl;
 
 #[cfg(feature = "hash")]
@@ -123,10 +123,20 @@ pub struct ModuleContext<'krate> {
     detail: &'krate ParsedModule,
 }
 
-impl ModuleContext<'_> {
-    pub fn items(&self) -> impl Iterator<Item = &syn::Item> {
+impl<'krate> ModuleContext<'krate> {
+    pub fn items(&self) -> impl Iterator<Item = &'krate syn::Item> {
         self.detail.items.iter()
     }
+
+    /// Path of the module relative to the crate root, e.g. `state::vault` (empty for the root).
+    pub fn path(&self) -> &'krate str {
+        self.detail.path.trim_start_matches("::")
+    }
+
+    /// File the module is defined in.
+    pub fn file(&self) -> &'krate Path {
+        &self.detail.file
+    }
 }
 struct ParsedModule {
     name: String,
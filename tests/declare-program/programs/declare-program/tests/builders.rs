@@ -0,0 +1,54 @@
+use anchor_lang::{prelude::*, InstructionData};
+
+declare_program!(external);
+
+use external::client::{builders, BuilderError};
+
+#[test]
+fn builder_derives_accounts() {
+    let authority = Pubkey::new_unique();
+    let my_account = Pubkey::find_program_address(&[authority.as_ref()], &external::ID).0;
+
+    let accounts = builders::Init::new()
+        .authority(authority)
+        .accounts()
+        .unwrap();
+    assert_eq!(accounts.authority, authority);
+    assert_eq!(accounts.my_account, my_account);
+    assert_eq!(accounts.system_program, system_program::ID);
+
+    let ix = builders::Update::new()
+        .authority(authority)
+        .args(external::client::args::Update { value: 1 })
+        .instruction()
+        .unwrap();
+    assert_eq!(ix.program_id, external::ID);
+    assert_eq!(ix.accounts[1].pubkey, my_account);
+    assert_eq!(ix.data, external::client::args::Update { value: 1 }.data());
+}
+
+#[test]
+fn builder_overrides_derived_accounts() {
+    let my_account = Pubkey::new_unique();
+    let accounts = builders::Init::new()
+        .authority(Pubkey::new_unique())
+        .my_account(my_account)
+        .accounts()
+        .unwrap();
+    assert_eq!(accounts.my_account, my_account);
+}
+
+#[test]
+fn builder_errors() {
+    assert_eq!(
+        builders::Init::new().accounts().unwrap_err(),
+        BuilderError::MissingAccount("authority")
+    );
+    assert_eq!(
+        builders::Update::new()
+            .authority(Pubkey::new_unique())
+            .instruction()
+            .unwrap_err(),
+        BuilderError::MissingArgs
+    );
+}
@@ -0,0 +1,15 @@
+use anchor_lang::prelude::*;
+
+declare_program!(external);
+declare_program!(external_from_path, path = "../../idls/external.json");
+declare_program!(external_from_crate, crate = "external-crate");
+
+#[test]
+fn same_program() {
+    assert_eq!(external_from_path::ID, external::ID);
+    assert_eq!(external_from_crate::ID, external::ID);
+    assert_eq!(
+        external_from_crate::accounts::MyAccount::DISCRIMINATOR,
+        external::accounts::MyAccount::DISCRIMINATOR
+    );
+}
@@ -0,0 +1,74 @@
+use anchor_lang::{prelude::*, AccountSerialize};
+
+declare_program!(relations);
+
+use relations::{
+    accounts::Vault,
+    client::{args, builders, BuilderError},
+    types::Config,
+};
+
+fn vault_data(vault: &Vault) -> Vec<u8> {
+    let mut data = vec![];
+    vault.try_serialize(&mut data).unwrap();
+    data
+}
+
+#[test]
+fn resolves_accounts_from_account_data() {
+    let mint = Pubkey::new_unique();
+    let vault = Vault {
+        authority: Pubkey::new_unique(),
+        mint,
+        index: 3,
+        config: Config {
+            owner: Pubkey::new_unique(),
+        },
+    };
+    let vault_address = Pubkey::find_program_address(&[b"vault", mint.as_ref()], &relations::ID).0;
+
+    let builder = builders::Withdraw::new()
+        .mint(mint)
+        .args(args::Withdraw { amount: 1 });
+    assert_eq!(builder.missing_account_data(), [vault_address]);
+    assert_eq!(
+        builder.accounts().unwrap_err(),
+        BuilderError::MissingAccount("authority")
+    );
+
+    let builder = builder.account_data(vault_address, vault_data(&vault));
+    assert!(builder.missing_account_data().is_empty());
+
+    let accounts = builder.accounts().unwrap();
+    assert_eq!(accounts.vault, vault_address);
+    assert_eq!(accounts.authority, vault.authority);
+    assert_eq!(
+        accounts.receipt,
+        Pubkey::find_program_address(
+            &[b"receipt", vault_address.as_ref(), &3u16.to_le_bytes()],
+            &relations::ID
+        )
+        .0
+    );
+    assert_eq!(
+        accounts.destination,
+        Pubkey::find_program_address(&[vault.config.owner.as_ref()], &relations::ID).0
+    );
+}
+
+#[test]
+fn set_accounts_are_not_resolved() {
+    let vault_address = Pubkey::new_unique();
+    let builder = builders::Withdraw::new()
+        .authority(Pubkey::new_unique())
+        .vault(vault_address)
+        .receipt(Pubkey::new_unique())
+        .destination(Pubkey::new_unique());
+    assert!(builder.missing_account_data().is_empty());
+
+    // Only the missing accounts need the data of the vault
+    let builder = builders::Withdraw::new()
+        .authority(Pubkey::new_unique())
+        .vault(vault_address);
+    assert_eq!(builder.missing_account_data(), [vault_address]);
+}
@@ -0,0 +1,79 @@
+use anchor_client::{
+    anchor_lang::{prelude::*, InstructionData, ToAccountMetas},
+    Instruction, TransactionParser,
+};
+
+anchor_client::declare_program!(external);
+anchor_client::declare_program!(relations);
+
+enum Parsed {
+    External(external::parsers::Instruction),
+    Relations(relations::parsers::Instruction),
+}
+
+#[test]
+fn parses_instructions_of_multiple_programs() {
+    let authority = Pubkey::new_unique();
+    let my_account = Pubkey::new_unique();
+    let update_composite = Instruction {
+        program_id: external::ID,
+        // The signer attribute is not checked
+        accounts: external::client::accounts::UpdateComposite {
+            update: external::client::accounts::Update {
+                authority,
+                my_account,
+            },
+        }
+        .to_account_metas(Some(false)),
+        data: external::client::args::UpdateComposite { value: 1 }.data(),
+    };
+    let withdraw = Instruction {
+        program_id: relations::ID,
+        accounts: (0..5)
+            .map(|_| AccountMeta::new_readonly(Pubkey::new_unique(), false))
+            .collect(),
+        data: relations::client::args::Withdraw { amount: 1 }.data(),
+    };
+
+    let parser = TransactionParser::new()
+        .program(Parsed::External)
+        .program(Parsed::Relations);
+
+    let parsed = parser.parse_instruction(&update_composite);
+    assert_eq!(parsed.name, Some("update_composite"));
+    assert!(matches!(
+        parsed.instruction,
+        Some(Parsed::External(
+            external::parsers::Instruction::UpdateComposite { args, .. }
+        )) if args.value == 1
+    ));
+    assert_eq!(
+        parsed
+            .accounts
+            .iter()
+            .map(|acc| (acc.name, acc.pubkey))
+            .collect::<Vec<_>>(),
+        [
+            (Some("update.authority"), authority),
+            (Some("update.my_account"), my_account),
+        ]
+    );
+
+    let parsed = parser.parse_instruction(&withdraw);
+    assert_eq!(parsed.name, Some("withdraw"));
+    assert!(matches!(
+        parsed.instruction,
+        Some(Parsed::Relations(
+            relations::parsers::Instruction::Withdraw { args, .. }
+        )) if args.amount == 1
+    ));
+
+    // Not enough accounts
+    let parsed = parser.parse_instruction(&Instruction {
+        accounts: vec![],
+        ..withdraw
+    });
+    assert_eq!(parsed.program_id, relations::ID);
+    assert!(parsed.name.is_none());
+    assert!(parsed.instruction.is_none());
+}